@@ -0,0 +1,147 @@
+use criterion::{
+    BatchSize, BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main,
+};
+use toy_mysql_client::{
+    auth::{MYSQL_NATIVE_PASSWORD, scramble_native_password},
+    command::ResultsetRow,
+    handshake::{DEFAULT_CLIENT_FLAG, HandshakeResponse41},
+    packet_writer::PacketWriter,
+    statement::ComStmtExecute,
+    utils::{
+        decode_lenenc_integer, decode_lenenc_string, encode_lenenc_integer, encode_lenenc_string,
+    },
+    value::Value,
+};
+
+const ROWS: usize = 1_000_000;
+
+fn text_row(columns: usize, width: usize) -> Vec<u8> {
+    let mut pkt = vec![];
+    for i in 0..columns {
+        let cell = format!("{:0>width$}", i, width = width);
+        encode_lenenc_string(&mut pkt, cell.as_bytes());
+    }
+    pkt
+}
+
+fn decode_rows(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_text_rows");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(ROWS as u64));
+    for (name, columns, width) in [("narrow", 3, 8), ("wide", 50, 32)] {
+        let pkt = text_row(columns, width);
+        group.bench_function(BenchmarkId::new(name, ROWS), |b| {
+            b.iter_batched(
+                || vec![pkt.clone(); ROWS],
+                |pkts| {
+                    for pkt in pkts {
+                        black_box(ResultsetRow::decode(&pkt).unwrap());
+                    }
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+// One value per size class of Protocol::LengthEncodedInteger.
+const LENENC_SIZES: [(&str, u64); 4] = [
+    ("1_byte", 0xfa),
+    ("3_bytes", 0xffff),
+    ("4_bytes", 0xff_ffff),
+    ("9_bytes", u64::MAX),
+];
+
+fn lenenc_integer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lenenc_integer");
+    for (name, val) in LENENC_SIZES {
+        group.bench_function(BenchmarkId::new("encode", name), |b| {
+            let mut buf = Vec::with_capacity(9);
+            b.iter(|| {
+                buf.clear();
+                encode_lenenc_integer(&mut buf, black_box(val));
+            })
+        });
+        let mut buf = vec![];
+        encode_lenenc_integer(&mut buf, val);
+        group.bench_function(BenchmarkId::new("decode", name), |b| {
+            b.iter(|| decode_lenenc_integer(black_box(&buf), 0).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn lenenc_string(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lenenc_string");
+    for (name, len) in [("short", 16), ("medium", 1024), ("long", 70_000)] {
+        let val = "x".repeat(len);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_function(BenchmarkId::new("encode", name), |b| {
+            let mut buf = Vec::with_capacity(len + 9);
+            b.iter(|| {
+                buf.clear();
+                encode_lenenc_string(&mut buf, black_box(val.as_bytes()));
+            })
+        });
+        let mut buf = vec![];
+        encode_lenenc_string(&mut buf, val.as_bytes());
+        group.bench_function(BenchmarkId::new("decode", name), |b| {
+            b.iter(|| decode_lenenc_string(black_box(&buf), 0).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn handshake_response(c: &mut Criterion) {
+    let auth_plugin_data = (0..20).collect::<Vec<u8>>();
+    c.bench_function("handshake_response41_encode", |b| {
+        b.iter(|| {
+            let response = HandshakeResponse41::new(
+                DEFAULT_CLIENT_FLAG,
+                255,
+                "root",
+                scramble_native_password("root", black_box(&auth_plugin_data)),
+                "test",
+                MYSQL_NATIVE_PASSWORD,
+            );
+            black_box(response.encode())
+        })
+    });
+}
+
+const EXECUTE_PARAMS: usize = 10_000;
+
+// A bulk INSERT's worth of parameters, into a fresh Vec and into a reused writer.
+fn stmt_execute(c: &mut Criterion) {
+    let params = (0..EXECUTE_PARAMS)
+        .map(|i| match i % 3 {
+            0 => Value::Int(i as i64),
+            1 => Value::Text(format!("value {}", i)),
+            _ => Value::Null,
+        })
+        .collect::<Vec<_>>();
+    let execute = ComStmtExecute::new(1, &params, true);
+    let mut group = c.benchmark_group("com_stmt_execute_encode");
+    group.throughput(Throughput::Elements(EXECUTE_PARAMS as u64));
+    group.bench_function("fresh", |b| b.iter(|| black_box(execute.encode())));
+    group.bench_function("reused_writer", |b| {
+        let mut w = PacketWriter::new();
+        b.iter(|| {
+            w.clear();
+            execute.write_to(&mut w);
+            black_box(w.len())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    decode_rows,
+    lenenc_integer,
+    lenenc_string,
+    handshake_response,
+    stmt_execute
+);
+criterion_main!(benches);
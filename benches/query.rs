@@ -0,0 +1,106 @@
+// End-to-end benchmarks against a real server. They only run when TOY_MYSQL_BENCH is set, e.g.
+//
+//   docker compose up -d db
+//   TOY_MYSQL_BENCH=1 cargo bench --bench query
+//
+// The connection can be pointed elsewhere with TOY_MYSQL_HOST, TOY_MYSQL_PORT, TOY_MYSQL_USER,
+// TOY_MYSQL_PASSWORD and TOY_MYSQL_DATABASE (defaults match compose.yml).
+
+use std::env;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use toy_mysql_client::connection::{Connection, ConnectionOptions};
+
+const SCAN_ROWS: u64 = 100_000;
+const WIDE_COLUMNS: usize = 50;
+const WIDE_EXECUTIONS: u64 = 10_000;
+
+fn connect() -> Connection {
+    let var = |name: &str, default: &str| env::var(name).unwrap_or_else(|_| String::from(default));
+    Connection::new(ConnectionOptions {
+        username: var("TOY_MYSQL_USER", "root"),
+        password: var("TOY_MYSQL_PASSWORD", "root"),
+        database: var("TOY_MYSQL_DATABASE", "test"),
+        host: var("TOY_MYSQL_HOST", "127.0.0.1"),
+        port: var("TOY_MYSQL_PORT", "3306").parse().unwrap(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn select_one(c: &mut Criterion) {
+    if env::var_os("TOY_MYSQL_BENCH").is_none() {
+        return;
+    }
+    let mut conn = connect();
+    let mut group = c.benchmark_group("query");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("select_1", |b| b.iter(|| conn.query("SELECT 1").unwrap()));
+    group.finish();
+}
+
+fn table_scan(c: &mut Criterion) {
+    if env::var_os("TOY_MYSQL_BENCH").is_none() {
+        return;
+    }
+    let mut conn = connect();
+    conn.query("SET SESSION cte_max_recursion_depth = 1000000")
+        .unwrap();
+    conn.query("DROP TABLE IF EXISTS bench_scan").unwrap();
+    conn.query(&format!(
+        "CREATE TABLE bench_scan AS \
+         WITH RECURSIVE seq (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < {}) \
+         SELECT n AS id, REPEAT('x', 32) AS payload FROM seq",
+        SCAN_ROWS
+    ))
+    .unwrap();
+
+    let mut group = c.benchmark_group("query");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(SCAN_ROWS));
+    group.bench_function("table_scan", |b| {
+        b.iter(|| conn.query_iter("SELECT * FROM bench_scan").unwrap().count())
+    });
+    group.finish();
+
+    conn.query("DROP TABLE bench_scan").unwrap();
+}
+
+// A 50-column statement executed over and over: the column definitions are decoded once and served
+// from the statement's metadata cache afterwards. The text protocol query is there for comparison.
+fn prepared_wide(c: &mut Criterion) {
+    if env::var_os("TOY_MYSQL_BENCH").is_none() {
+        return;
+    }
+    let mut conn = connect();
+    let columns = (0..WIDE_COLUMNS)
+        .map(|i| format!("{} AS c{}", i, i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!("SELECT {}", columns);
+    let mut stmt = conn.prepare(&sql).unwrap();
+
+    let mut group = c.benchmark_group("query");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(WIDE_EXECUTIONS));
+    group.bench_function("prepared_wide", |b| {
+        b.iter(|| {
+            for _ in 0..WIDE_EXECUTIONS {
+                conn.execute(&mut stmt, &[]).unwrap();
+            }
+        })
+    });
+    group.bench_function("query_wide", |b| {
+        b.iter(|| {
+            for _ in 0..WIDE_EXECUTIONS {
+                conn.query(&sql).unwrap();
+            }
+        })
+    });
+    group.finish();
+
+    conn.close_statement(stmt).unwrap();
+}
+
+criterion_group!(benches, select_one, table_scan, prepared_wide);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+// An HTTP endpoint backed by a deadpool of async connections.
+//
+//   docker compose up -d db
+//   cargo run --example axum_pool --features deadpool
+//   curl localhost:3000/users
+
+use axum::{Router, extract::State, http::StatusCode, routing::get};
+use deadpool::managed::Pool;
+use toy_mysql_client::{connection::ConnectionOptions, deadpool::Manager};
+
+type MysqlPool = Pool<Manager>;
+
+async fn users(State(pool): State<MysqlPool>) -> Result<String, (StatusCode, String)> {
+    let internal_error = |err: String| (StatusCode::INTERNAL_SERVER_ERROR, err);
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|err| internal_error(err.to_string()))?;
+    let result = conn
+        .query("SELECT * FROM users")
+        .await
+        .map_err(|err| internal_error(format!("{:#}", err)))?;
+    Ok(format!("{:?}\n", result))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let manager = Manager::new(ConnectionOptions {
+        username: String::from("root"),
+        password: String::from("root"),
+        database: String::from("test"),
+        ..Default::default()
+    });
+    let pool = Pool::builder(manager).max_size(4).build()?;
+
+    let app = Router::new().route("/users", get(users)).with_state(pool);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
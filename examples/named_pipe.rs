@@ -0,0 +1,23 @@
+// A query over the named pipe of a Windows server started with --named-pipe (enable_named_pipe=ON).
+//
+//   cargo run --example named_pipe
+
+#[cfg(windows)]
+fn main() -> anyhow::Result<()> {
+    use toy_mysql_client::connection::{Connection, ConnectionOptions};
+
+    let mut conn = Connection::new(ConnectionOptions {
+        username: String::from("root"),
+        password: String::from("root"),
+        named_pipe: Some(String::from("MySQL")),
+        ..Default::default()
+    })?;
+    let result = conn.query("SELECT @@named_pipe, CURRENT_USER()")?;
+    println!("{:?}", result.result_set().map(|rs| &rs.rows));
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("named pipes are only supported on Windows");
+}
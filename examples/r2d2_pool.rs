@@ -0,0 +1,45 @@
+// A pool of 2 connections shared by 4 threads.
+//
+//   docker compose up -d db
+//   cargo run --example r2d2_pool --features r2d2
+
+use std::thread;
+
+use anyhow::Result;
+use toy_mysql_client::{
+    connection::ConnectionOptions,
+    r2d2::{MysqlConnectionManager, ResetStrategy},
+};
+
+fn main() -> Result<()> {
+    let manager = MysqlConnectionManager::new(ConnectionOptions {
+        username: String::from("root"),
+        password: String::from("root"),
+        database: String::from("test"),
+        ..Default::default()
+    })
+    .with_reset(ResetStrategy::ResetConnection);
+    let pool = r2d2::Pool::builder().max_size(2).build(manager)?;
+
+    let handles = (0..4)
+        .map(|i| {
+            let pool = pool.clone();
+            thread::spawn(move || -> Result<()> {
+                for _ in 0..10 {
+                    let mut conn = pool.get()?;
+                    let result = conn.query("SELECT * FROM users")?;
+                    println!(
+                        "thread {}: {:?}",
+                        i,
+                        result.result_set().map(|rs| rs.rows.len())
+                    );
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// A server answering `SELECT 1`, built from the crate's packet types, queried by the crate's own
+// client. It runs without a database, so it doubles as an integration test.
+//
+//   cargo run --example toy_server
+
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use anyhow::{Result, bail};
+use toy_mysql_client::{
+    command::{
+        ColumnDefinition41, ErrPacket, MYSQL_TYPE_LONGLONG, OkPacket, ResultsetRow, StatusFlags,
+    },
+    connection::{Connection, ConnectionOptions},
+    handshake::{
+        CLIENT_OPTIONAL_RESULTSET_METADATA, CLIENT_QUERY_ATTRIBUTES, DEFAULT_CLIENT_FLAG,
+        HandshakeResponse41, HandshakeV10,
+    },
+    packets::{deframe, frame},
+    utils::encode_lenenc_integer,
+};
+
+// The server leaves out the features that change the shape of COM_QUERY and of result sets.
+const CAPABILITIES: u32 =
+    DEFAULT_CLIENT_FLAG & !(CLIENT_QUERY_ATTRIBUTES | CLIENT_OPTIONAL_RESULTSET_METADATA);
+
+fn serve(mut stream: TcpStream) -> Result<()> {
+    let status = StatusFlags(StatusFlags::AUTOCOMMIT);
+    let handshake = HandshakeV10::new(
+        "8.0.36-toy",
+        1,
+        CAPABILITIES,
+        255,
+        status,
+        b"01234567890123456789",
+        "mysql_native_password",
+    );
+    stream.write_all(&frame(&handshake.encode(), 0))?;
+
+    // Anyone may log in.
+    let (pkt, seq) = deframe(&mut stream)?;
+    let response = HandshakeResponse41::decode(&pkt)?;
+    println!("server: {} logged in", response.username);
+    let capabilities = response.client_flag;
+    stream.write_all(&frame(
+        &OkPacket::new(0, 0, status).encode(capabilities),
+        seq,
+    ))?;
+
+    loop {
+        // The client may just close the socket when it is done.
+        let Ok((pkt, mut seq)) = deframe(&mut stream) else {
+            return Ok(());
+        };
+        let mut reply = |payload: Vec<u8>| {
+            let framed = frame(&payload, seq);
+            seq = seq.wrapping_add(1);
+            stream.write_all(&framed)
+        };
+        match pkt.first() {
+            // COM_QUIT
+            Some(0x01) => return Ok(()),
+            // COM_QUERY
+            Some(0x03) => {
+                let sql = String::from_utf8_lossy(&pkt[1..]);
+                println!("server: {}", sql);
+                // The client sets up session tracking when it connects.
+                if sql.starts_with("SET ") {
+                    reply(OkPacket::new(0, 0, status).encode(capabilities))?;
+                    continue;
+                }
+                if !sql.eq_ignore_ascii_case("SELECT 1") {
+                    let err = ErrPacket::new(1064, "42000", "only SELECT 1 is supported");
+                    reply(err.encode())?;
+                    continue;
+                }
+                let mut column_count = vec![];
+                encode_lenenc_integer(&mut column_count, 1);
+                reply(column_count)?;
+                let column = ColumnDefinition41 {
+                    catalog: String::from("def"),
+                    schema: String::new(),
+                    table: String::new(),
+                    org_table: String::new(),
+                    name: String::from("1"),
+                    org_name: String::new(),
+                    length_of_fixed_length_fields: 0x0c,
+                    character_set: 63,
+                    column_length: 1,
+                    type_: MYSQL_TYPE_LONGLONG,
+                    flags: 0,
+                    decimals: 0,
+                };
+                reply(column.encode())?;
+                reply(ResultsetRow(vec![Some(b"1".to_vec())]).encode())?;
+                let mut terminator = OkPacket::new(0, 0, status);
+                terminator.header = 0xfe;
+                reply(terminator.encode(capabilities))?;
+            }
+            Some(command) => {
+                let err = ErrPacket::new(1047, "08S01", &format!("unknown command {}", command));
+                reply(err.encode())?;
+            }
+            None => bail!("empty packet"),
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    let server = thread::spawn(move || -> Result<()> {
+        let (stream, _) = listener.accept()?;
+        serve(stream)
+    });
+
+    let mut conn = Connection::new(ConnectionOptions {
+        port,
+        username: String::from("toy"),
+        ..Default::default()
+    })?;
+    let result = conn.query("SELECT 1")?;
+    let rows = &result.result_set().expect("a result set").rows;
+    println!("client: {:?}", rows);
+    assert_eq!(rows[0].0[0].as_deref(), Some(&b"1"[..]));
+    drop(conn);
+
+    server.join().unwrap()
+}
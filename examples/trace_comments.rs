@@ -0,0 +1,53 @@
+// A query rewriter tagging every statement with the code path it comes from, so it can be found
+// in the slow query log, SHOW PROCESSLIST and performance_schema.
+//
+//   docker compose up -d db
+//   cargo run --example trace_comments
+
+use std::{
+    borrow::Cow,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use toy_mysql_client::{
+    connection::{Connection, ConnectionOptions},
+    slow_query::SlowQueryHandler,
+};
+
+fn main() -> anyhow::Result<()> {
+    let mut conn = Connection::new(ConnectionOptions {
+        username: String::from("root"),
+        password: String::from("root"),
+        // Report every statement, to show what was actually sent.
+        slow_query_threshold: Some(Duration::ZERO),
+        slow_query_handler: Some(SlowQueryHandler::new(|query| {
+            println!("sent: {}", query.sql)
+        })),
+        ..Default::default()
+    })?;
+
+    // Set per request by the application; a request counter stands in for a trace id here.
+    let route = Arc::new(Mutex::new(String::from("checkout")));
+    let request_id = Arc::new(AtomicU64::new(1));
+    let (current_route, current_request) = (route.clone(), request_id.clone());
+    conn.set_query_rewriter(move |sql| {
+        Cow::Owned(format!(
+            "/* app:{} request:{} */ {}",
+            current_route.lock().unwrap(),
+            current_request.load(Ordering::Relaxed),
+            sql
+        ))
+    });
+
+    conn.query("SELECT 1")?;
+    *route.lock().unwrap() = String::from("cart");
+    request_id.fetch_add(1, Ordering::Relaxed);
+    conn.query("SELECT @@version")?;
+    // Issued by the crate, so not tagged.
+    conn.show_warnings()?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    command::ErrPacket,
+    connection::Connection,
+    error::Error,
+    raw::{RawResponse, ResponseKind},
+};
+
+// ER_SPECIFIC_ACCESS_DENIED_ERROR: the account lacks the privilege (SUPER, SHUTDOWN) a command needs.
+const ER_SPECIFIC_ACCESS_DENIED_ERROR: u16 = 1227;
+
+// COM_DEBUG
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_debug.html
+const COM_DEBUG: u8 = 0x0d;
+// COM_SHUTDOWN, deprecated since MySQL 5.7.9 and gone in 8.0 in favor of the SHUTDOWN statement.
+// https://dev.mysql.com/doc/dev/mysql-server/8.0.40/page_protocol_com_shutdown.html
+const COM_SHUTDOWN: u8 = 0x08;
+const SHUTDOWN_DEFAULT: u8 = 0x00;
+
+#[derive(Debug, Default, Clone)]
+pub struct ShutdownOptions {
+    // Sends COM_SHUTDOWN instead of running SHUTDOWN, for servers older than 5.7.9.
+    pub legacy_command: bool,
+}
+
+// Server administration commands, meant for test harnesses managing a throwaway mysqld. They live
+// behind this trait so they have to be imported on purpose:
+//
+//   use toy_mysql_client::admin::Admin;
+//
+// Without the needed privilege they fail with `Error::PermissionDenied`; the connection stays usable.
+pub trait Admin {
+    // Makes the server write debug information to its error log. Requires SUPER.
+    fn debug_dump(&mut self) -> Result<()>;
+
+    // Stops the server. Requires SHUTDOWN; the server closes the connection afterwards.
+    fn shutdown(&mut self) -> Result<()> {
+        self.shutdown_with(&ShutdownOptions::default())
+    }
+
+    fn shutdown_with(&mut self, options: &ShutdownOptions) -> Result<()>;
+}
+
+impl Admin for Connection {
+    fn debug_dump(&mut self) -> Result<()> {
+        let response = self.send_command(COM_DEBUG, &[], ResponseKind::Packet)?;
+        admin_response(response)
+    }
+
+    // https://dev.mysql.com/doc/refman/8.4/en/shutdown.html
+    fn shutdown_with(&mut self, options: &ShutdownOptions) -> Result<()> {
+        if !options.legacy_command {
+            return self
+                .query("SHUTDOWN")
+                .map(|_| ())
+                .map_err(permission_denied);
+        }
+        let response =
+            self.send_command(COM_SHUTDOWN, &[SHUTDOWN_DEFAULT], ResponseKind::Packet)?;
+        admin_response(response)
+    }
+}
+
+// Old servers answer with an EOF packet instead of an OK.
+fn admin_response(response: RawResponse) -> Result<()> {
+    match response {
+        RawResponse::Ok(_) | RawResponse::Eof(_) => Ok(()),
+        RawResponse::Err(err) => Err(permission_denied(err.into())),
+        RawResponse::Packets(packets) => bail!("unexpected response: {:02x?}", packets),
+    }
+}
+
+fn permission_denied(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast::<ErrPacket>() {
+        Ok(err) if err.error_code == ER_SPECIFIC_ACCESS_DENIED_ERROR => {
+            Error::PermissionDenied(err.error_message).into()
+        }
+        Ok(err) => err.into(),
+        Err(err) => err,
+    }
+}
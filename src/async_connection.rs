@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::task;
+
+use crate::{
+    connection::{Connection, ConnectionOptions},
+    result::QueryResult,
+    server_version::ServerVersion,
+};
+
+// Async facade over `Connection` for tokio applications.
+//
+// The protocol code is blocking, so every call moves the connection onto tokio's blocking thread pool
+// with `spawn_blocking` and moves it back when the call finishes. This keeps the async runtime
+// responsive, but each in-flight call still occupies one blocking thread.
+//
+// If a call's future is dropped before it completes (e.g. by `tokio::time::timeout` or a cancelled
+// request handler) the connection is lost with it: the command keeps running on the blocking thread
+// and the connection is closed afterwards. `is_broken()` is true from then on, so pools discard it.
+#[derive(Debug)]
+pub struct AsyncConnection {
+    inner: Option<Connection>,
+    server_version: ServerVersion,
+}
+
+impl AsyncConnection {
+    pub async fn new(options: ConnectionOptions) -> Result<Self> {
+        let conn = task::spawn_blocking(move || Connection::new(options)).await??;
+        Ok(Self {
+            server_version: conn.server_version(),
+            inner: Some(conn),
+        })
+    }
+
+    pub fn server_version(&self) -> ServerVersion {
+        self.server_version
+    }
+
+    pub async fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        let sql = String::from(sql);
+        self.run(move |conn| conn.query(&sql)).await
+    }
+
+    pub async fn query_all(&mut self, sql: &str) -> Result<Vec<QueryResult>> {
+        let sql = String::from(sql);
+        self.run(move |conn| conn.query_all(&sql)).await
+    }
+
+    pub async fn reset_connection(&mut self) -> Result<()> {
+        self.run(|conn| conn.reset_connection()).await
+    }
+
+    pub async fn ping(&mut self) -> Result<()> {
+        self.run(|conn| conn.ping()).await
+    }
+
+    pub async fn ping_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.run(move |conn| conn.ping_with_timeout(timeout)).await
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.inner.as_ref().is_none_or(|conn| conn.is_broken())
+    }
+
+    // Runs `f` with the underlying connection on the blocking thread pool, for anything the facade
+    // doesn't wrap.
+    pub async fn run<T, F>(&mut self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let mut conn = self
+            .inner
+            .take()
+            .context("connection was lost by an earlier cancelled call")?;
+        let (conn, result) = task::spawn_blocking(move || {
+            let result = f(&mut conn);
+            (conn, result)
+        })
+        .await?;
+        self.inner = Some(conn);
+        result
+    }
+}
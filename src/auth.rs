@@ -0,0 +1,205 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use anyhow::{Result, bail};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+pub const MYSQL_NATIVE_PASSWORD: &str = "mysql_native_password";
+pub const MYSQL_CLEAR_PASSWORD: &str = "mysql_clear_password";
+pub const CACHING_SHA2_PASSWORD: &str = "caching_sha2_password";
+
+// What an authenticator gets to compute its responses from.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthContext<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+    // Whether the transport protects the password on the wire (TLS or a Unix socket).
+    pub secure_transport: bool,
+    // ConnectionOptions::allow_cleartext_on_insecure
+    pub allow_cleartext_on_insecure: bool,
+}
+
+// The client side of an authentication method.
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_authentication_methods.html
+pub trait Authenticator: Send + Sync {
+    // The response to the server's challenge (the scramble), sent in HandshakeResponse41 or in
+    // reply to an AuthSwitchRequest.
+    fn auth_response(&self, ctx: &AuthContext, challenge: &[u8]) -> Result<Vec<u8>>;
+
+    // Answers a Protocol::AuthMoreData packet; `data` is its payload without the 0x01 status tag.
+    // `None` sends nothing and waits for the next packet from the server.
+    fn more_data(
+        &self,
+        _ctx: &AuthContext,
+        _challenge: &[u8],
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        bail!("unexpected AuthMoreData packet: {:02x?}", data)
+    }
+}
+
+// The authenticators a connection can use, keyed by plugin name. `default()` has the built-in ones.
+#[derive(Clone)]
+pub struct AuthPlugins(HashMap<String, Arc<dyn Authenticator>>);
+
+impl AuthPlugins {
+    // Adds an authenticator for `name`, replacing any previous one (built-in ones included).
+    pub fn register(&mut self, name: &str, authenticator: impl Authenticator + 'static) {
+        self.0.insert(String::from(name), Arc::new(authenticator));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Authenticator>> {
+        self.0.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    // Sorted, for error messages.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.0.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl Default for AuthPlugins {
+    fn default() -> Self {
+        let mut plugins = Self(HashMap::new());
+        plugins.register(MYSQL_NATIVE_PASSWORD, NativePassword);
+        plugins.register(MYSQL_CLEAR_PASSWORD, ClearPassword);
+        plugins.register(CACHING_SHA2_PASSWORD, CachingSha2Password);
+        plugins
+    }
+}
+
+impl fmt::Debug for AuthPlugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct NativePassword;
+
+impl Authenticator for NativePassword {
+    fn auth_response(&self, ctx: &AuthContext, challenge: &[u8]) -> Result<Vec<u8>> {
+        Ok(scramble_native_password(ctx.password, challenge))
+    }
+}
+
+// Refuses to send the password over a transport that would expose it, unless allowed.
+#[derive(Debug)]
+pub struct ClearPassword;
+
+impl Authenticator for ClearPassword {
+    fn auth_response(&self, ctx: &AuthContext, _challenge: &[u8]) -> Result<Vec<u8>> {
+        if !ctx.secure_transport && !ctx.allow_cleartext_on_insecure {
+            bail!(
+                "refusing to authenticate with {} because the password would be sent in cleartext over an \
+                 unencrypted TCP connection; connect over TLS or a Unix socket, or set \
+                 ConnectionOptions::allow_cleartext_on_insecure to send it anyway",
+                MYSQL_CLEAR_PASSWORD
+            );
+        }
+        Ok(clear_password(ctx.password))
+    }
+}
+
+// Only the fast path (the server has the account in its cache) works over plain TCP; the full
+// authentication needs TLS, or an RSA key exchange that isn't implemented.
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_caching_sha2_authentication_exchanges.html
+#[derive(Debug)]
+pub struct CachingSha2Password;
+
+impl Authenticator for CachingSha2Password {
+    fn auth_response(&self, ctx: &AuthContext, challenge: &[u8]) -> Result<Vec<u8>> {
+        Ok(scramble_caching_sha2_password(ctx.password, challenge))
+    }
+
+    fn more_data(
+        &self,
+        ctx: &AuthContext,
+        _challenge: &[u8],
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        match data {
+            // fast_auth_success; the OK packet follows.
+            [0x03] => Ok(None),
+            // perform_full_authentication
+            [0x04] if ctx.secure_transport => Ok(Some(clear_password(ctx.password))),
+            [0x04] => bail!(
+                "{} needs a full authentication, which is only supported over TLS or a Unix socket \
+                 (the password isn't in the server's cache yet; logging in once with the mysql \
+                 client fills it)",
+                CACHING_SHA2_PASSWORD
+            ),
+            _ => bail!("unexpected {} data: {:02x?}", CACHING_SHA2_PASSWORD, data),
+        }
+    }
+}
+
+// Native Authentication
+// SHA1( password ) XOR SHA1( "20-bytes random data from server" <concat> SHA1( SHA1( password ) ) )
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_authentication_methods_native_password_authentication.html
+pub fn scramble_native_password(password: &str, auth_plugin_data: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return vec![];
+    }
+
+    let mut sha1 = Sha1::new();
+
+    let hash1 = {
+        sha1.update(password);
+        sha1.finalize_reset()
+    };
+    let hash2 = {
+        sha1.update(hash1);
+        sha1.finalize_reset()
+    };
+    let hash3 = {
+        sha1.update(auth_plugin_data);
+        sha1.update(hash2);
+        sha1.finalize_reset()
+    };
+
+    hash1
+        .iter()
+        .zip(hash3)
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>()
+}
+
+// Clear text client plugin
+// The password is sent as is, followed by a NUL. Only safe on an encrypted or local connection.
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_authentication_methods_clear_text_password.html
+pub fn clear_password(password: &str) -> Vec<u8> {
+    let mut buf = password.as_bytes().to_vec();
+    buf.push(0);
+    buf
+}
+
+// caching_sha2_password scramble
+// XOR( SHA256( password ), SHA256( SHA256( SHA256( password ) ), scramble ) )
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_caching_sha2_authentication_exchanges.html
+pub fn scramble_caching_sha2_password(password: &str, auth_plugin_data: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return vec![];
+    }
+
+    let hash1 = Sha256::digest(password);
+    let hash2 = Sha256::digest(hash1);
+    let hash3 = {
+        let mut sha256 = Sha256::new();
+        sha256.update(hash2);
+        sha256.update(auth_plugin_data);
+        sha256.finalize()
+    };
+
+    hash1
+        .iter()
+        .zip(hash3)
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>()
+}
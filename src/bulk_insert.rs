@@ -0,0 +1,359 @@
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{
+    connection::Connection, result::QueryResult, statement::Statement, utils::quote_identifier,
+    value::Value,
+};
+
+// Room left in a packet for the command byte and the query attribute fields in front of the SQL.
+const PACKET_OVERHEAD: u64 = 64;
+
+// Rows for one table, sent as multi-row `INSERT INTO t (a, b) VALUES (...), (...)` statements with
+// the values written in as literals. As many rows go into a statement as fit in the server's
+// max_allowed_packet, so a large batch becomes a few statements rather than one per row.
+//
+//     let inserted = BulkInsert::new("users", ["id", "name"])
+//         .row([Value::from(1), Value::from("alice")])
+//         .row([Value::from(2), Value::from("bob")])
+//         .execute(&mut conn)?;
+//
+// The statements run one after another, outside any transaction the caller doesn't start, so a
+// failure part way through leaves the earlier statements' rows inserted.
+#[derive(Debug, Clone, Default)]
+pub struct BulkInsert {
+    table: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl BulkInsert {
+    pub fn new<T: Into<String>>(
+        table: impl Into<String>,
+        columns: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self {
+            table: table.into(),
+            columns: columns.into_iter().map(Into::into).collect(),
+            rows: vec![],
+        }
+    }
+
+    // Values in column order.
+    pub fn row<T: Into<Value>>(mut self, values: impl IntoIterator<Item = T>) -> Self {
+        self.rows.push(values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn rows<R, T>(mut self, rows: impl IntoIterator<Item = R>) -> Self
+    where
+        R: IntoIterator<Item = T>,
+        T: Into<Value>,
+    {
+        for row in rows {
+            self = self.row(row);
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    // The INSERT statements for the rows, none longer than `max_len` bytes. A row that doesn't fit
+    // in a statement on its own is an error, as is a row with the wrong number of values.
+    pub fn statements(&self, max_len: usize, no_backslash_escapes: bool) -> Result<Vec<String>> {
+        if self.columns.is_empty() {
+            bail!("no columns to insert into");
+        }
+        let prefix = insert_prefix(&self.table, &self.columns);
+
+        let mut statements = vec![];
+        let mut sql = String::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.len() != self.columns.len() {
+                bail!(
+                    "row {} has {} values, but {} columns were given",
+                    i,
+                    row.len(),
+                    self.columns.len()
+                );
+            }
+            let values = row
+                .iter()
+                .map(|val| val.to_sql_literal(no_backslash_escapes))
+                .collect::<Result<Vec<_>>>()?;
+            let tuple = format!("({})", values.join(", "));
+
+            if !sql.is_empty() && sql.len() + 2 + tuple.len() > max_len {
+                statements.push(std::mem::take(&mut sql));
+            }
+            if sql.is_empty() {
+                if prefix.len() + tuple.len() > max_len {
+                    bail!(
+                        "row {} takes {} bytes as an INSERT statement, more than the limit of {}",
+                        i,
+                        prefix.len() + tuple.len(),
+                        max_len
+                    );
+                }
+                sql.push_str(&prefix);
+            } else {
+                sql.push_str(", ");
+            }
+            sql.push_str(&tuple);
+        }
+        if !sql.is_empty() {
+            statements.push(sql);
+        }
+        Ok(statements)
+    }
+
+    // Inserts the rows and returns the number of affected rows over all statements. The statements
+    // are sized for the server's max_allowed_packet and escaped for the session's sql_mode.
+    pub fn execute(&self, conn: &mut Connection) -> Result<u64> {
+        self.run(conn, |_| Ok(()))
+    }
+
+    // Inserts the rows and returns the AUTO_INCREMENT value generated for each, in row order. The
+    // server reports only the first value of each statement, so the others are derived from it and
+    // the session's auto_increment_increment; that holds for rows that leave the column to the
+    // server, which gives the rows of a multi-row INSERT consecutive values (see `generated_ids`).
+    //
+    // Values used by a statement that fails are lost, and the earlier statements' rows stay
+    // inserted without their values being returned: the table's next rows skip them, leaving a gap.
+    pub fn execute_returning_ids(&self, conn: &mut Connection) -> Result<Vec<u64>> {
+        let increment = conn.auto_increment_increment()?;
+        let mut ids = Vec::with_capacity(self.rows.len());
+        self.run(conn, |result| {
+            if result.last_insert_id() == 0 && result.affected_rows() > 0 {
+                bail!("no AUTO_INCREMENT value was generated for the rows");
+            }
+            ids.extend(generated_ids(
+                result.last_insert_id(),
+                result.affected_rows(),
+                increment,
+            ));
+            Ok(())
+        })?;
+        Ok(ids)
+    }
+
+    fn run(
+        &self,
+        conn: &mut Connection,
+        mut on_result: impl FnMut(&QueryResult) -> Result<()>,
+    ) -> Result<u64> {
+        debug!(
+            "bulk_insert start: {} rows into {}",
+            self.rows.len(),
+            self.table
+        );
+        let max_len = conn.max_allowed_packet()?.saturating_sub(PACKET_OVERHEAD);
+        let statements = self.statements(max_len as usize, conn.no_backslash_escapes())?;
+        let mut affected_rows = 0;
+        for sql in &statements {
+            let result = conn.query(sql)?;
+            on_result(&result)?;
+            affected_rows += result.affected_rows();
+        }
+        debug!(
+            "bulk_insert done: {} rows affected by {} statements",
+            affected_rows,
+            statements.len()
+        );
+        Ok(affected_rows)
+    }
+}
+
+// The AUTO_INCREMENT values of the rows of one INSERT, from its LAST_INSERT_ID() (the first row's
+// value) and affected rows, with `increment` being @@auto_increment_increment. InnoDB reserves the
+// values of an INSERT ... VALUES in one go, as consecutive steps of the increment. The result is
+// wrong when some rows set the column themselves, and for INSERT IGNORE and ON DUPLICATE KEY
+// UPDATE, where affected rows don't count inserted rows.
+pub fn generated_ids(first_id: u64, affected_rows: u64, increment: u64) -> Vec<u64> {
+    let increment = increment.max(1);
+    (0..affected_rows)
+        .map(|i| first_id + i * increment)
+        .collect()
+}
+
+// An `INSERT INTO t (a, b) VALUES (?, ?)` prepared once and executed for each row, for loops that
+// insert rows one at a time (e.g. as they are read). Only the values go to the server on each
+// execution, and the parameter types only when they change (see `Connection::execute`).
+//
+//     let mut insert = conn.prepare_insert("users", ["name", "email"])?;
+//     for (name, email) in users {
+//         let id = insert.insert_row(&[Value::from(name), Value::from(email)])?;
+//     }
+//     insert.close()?;
+//
+// Dropping it closes the statement too, ignoring any error.
+#[derive(Debug)]
+pub struct PreparedInsert<'a> {
+    conn: &'a mut Connection,
+    // Taken by `close`.
+    stmt: Option<Statement>,
+    last_insert_id: u64,
+    rows_inserted: u64,
+}
+
+impl Connection {
+    pub fn prepare_insert<T: AsRef<str>>(
+        &mut self,
+        table: &str,
+        columns: impl IntoIterator<Item = T>,
+    ) -> Result<PreparedInsert<'_>> {
+        let columns = columns
+            .into_iter()
+            .map(|col| String::from(col.as_ref()))
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            bail!("no columns to insert into");
+        }
+        let sql = format!(
+            "{}({})",
+            insert_prefix(table, &columns),
+            vec!["?"; columns.len()].join(", ")
+        );
+        let stmt = self.prepare(&sql)?;
+        Ok(PreparedInsert {
+            conn: self,
+            stmt: Some(stmt),
+            last_insert_id: 0,
+            rows_inserted: 0,
+        })
+    }
+}
+
+impl PreparedInsert<'_> {
+    // Inserts one row, values in column order, and returns its LAST_INSERT_ID(): the generated
+    // AUTO_INCREMENT value, or 0 when the table has none and the row set none.
+    pub fn insert_row(&mut self, values: &[Value]) -> Result<u64> {
+        let stmt = self
+            .stmt
+            .as_mut()
+            .expect("the statement is only taken by close");
+        let result = self.conn.execute(stmt, values)?;
+        self.last_insert_id = result.last_insert_id();
+        self.rows_inserted += result.affected_rows();
+        Ok(self.last_insert_id)
+    }
+
+    // The LAST_INSERT_ID() of the latest row inserted, 0 before the first.
+    pub fn last_insert_id(&self) -> u64 {
+        self.last_insert_id
+    }
+
+    // The affected rows of all `insert_row` calls so far.
+    pub fn rows_inserted(&self) -> u64 {
+        self.rows_inserted
+    }
+
+    pub fn statement(&self) -> &Statement {
+        self.stmt
+            .as_ref()
+            .expect("the statement is only taken by close")
+    }
+
+    pub fn close(mut self) -> Result<()> {
+        let stmt = self
+            .stmt
+            .take()
+            .expect("the statement is only taken by close");
+        self.conn.close_statement(stmt)
+    }
+}
+
+impl Drop for PreparedInsert<'_> {
+    fn drop(&mut self) {
+        if let Some(stmt) = self.stmt.take() {
+            let _ = self.conn.close_statement(stmt);
+        }
+    }
+}
+
+// INSERT INTO `t` (`a`, `b`) VALUES
+fn insert_prefix(table: &str, columns: &[String]) -> String {
+    format!(
+        "INSERT INTO {} ({}) VALUES ",
+        quote_identifier(table),
+        columns
+            .iter()
+            .map(|col| quote_identifier(col))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(values: &[i32]) -> BulkInsert {
+        BulkInsert::new("t", ["a"]).rows(values.iter().map(|val| [*val]))
+    }
+
+    #[test]
+    fn statements_are_split_where_the_next_row_would_pass_max_len() {
+        let prefix = "INSERT INTO `t` (`a`) VALUES ";
+        // Room for two rows: "(1), (2)".
+        let statements = insert(&[1, 2, 3])
+            .statements(prefix.len() + 8, false)
+            .unwrap();
+        assert_eq!(
+            statements,
+            [format!("{}(1), (2)", prefix), format!("{}(3)", prefix)]
+        );
+        // One byte short of two rows.
+        let statements = insert(&[1, 2, 3])
+            .statements(prefix.len() + 7, false)
+            .unwrap();
+        assert_eq!(statements.len(), 3);
+        let statements = insert(&[1, 2, 3]).statements(1 << 20, false).unwrap();
+        assert_eq!(statements, [format!("{}(1), (2), (3)", prefix)]);
+        assert!(insert(&[]).statements(1 << 20, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn statements_reject_a_row_longer_than_max_len() {
+        let prefix = "INSERT INTO `t` (`a`) VALUES ";
+        let err = insert(&[1, 1000])
+            .statements(prefix.len() + 3, false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "row 1 takes {} bytes as an INSERT statement, more than the limit of {}",
+                prefix.len() + 6,
+                prefix.len() + 3
+            )
+        );
+    }
+
+    #[test]
+    fn statements_reject_a_row_with_the_wrong_number_of_values() {
+        let err = BulkInsert::new("t", ["a", "b"])
+            .row([1, 2])
+            .row([3])
+            .statements(1 << 20, false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 1 has 1 values, but 2 columns were given"
+        );
+    }
+
+    #[test]
+    fn generated_ids_step_by_the_increment() {
+        assert_eq!(generated_ids(10, 3, 1), [10, 11, 12]);
+        assert_eq!(generated_ids(11, 4, 5), [11, 16, 21, 26]);
+        // The server clamps auto_increment_increment to at least 1, and so does generated_ids.
+        assert_eq!(generated_ids(10, 2, 0), [10, 11]);
+        assert!(generated_ids(10, 0, 1).is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::{Result, bail};
+
+use crate::connection::Connection;
+
+// A collation the client knows by name, from SHOW COLLATION.
+// https://dev.mysql.com/doc/refman/8.4/en/charset-mysql.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Collation {
+    pub id: u16,
+    pub name: &'static str,
+    pub charset: &'static str,
+    pub is_default: bool,
+}
+
+const fn collation(
+    id: u16,
+    name: &'static str,
+    charset: &'static str,
+    is_default: bool,
+) -> Collation {
+    Collation {
+        id,
+        name,
+        charset,
+        is_default,
+    }
+}
+
+pub const COLLATIONS: &[Collation] = &[
+    collation(1, "big5_chinese_ci", "big5", true),
+    collation(3, "dec8_swedish_ci", "dec8", true),
+    collation(4, "cp850_general_ci", "cp850", true),
+    collation(5, "latin1_german1_ci", "latin1", false),
+    collation(6, "hp8_english_ci", "hp8", true),
+    collation(7, "koi8r_general_ci", "koi8r", true),
+    collation(8, "latin1_swedish_ci", "latin1", true),
+    collation(9, "latin2_general_ci", "latin2", true),
+    collation(10, "swe7_swedish_ci", "swe7", true),
+    collation(11, "ascii_general_ci", "ascii", true),
+    collation(12, "ujis_japanese_ci", "ujis", true),
+    collation(13, "sjis_japanese_ci", "sjis", true),
+    collation(15, "latin1_danish_ci", "latin1", false),
+    collation(16, "hebrew_general_ci", "hebrew", true),
+    collation(18, "tis620_thai_ci", "tis620", true),
+    collation(19, "euckr_korean_ci", "euckr", true),
+    collation(22, "koi8u_general_ci", "koi8u", true),
+    collation(24, "gb2312_chinese_ci", "gb2312", true),
+    collation(25, "greek_general_ci", "greek", true),
+    collation(26, "cp1250_general_ci", "cp1250", true),
+    collation(28, "gbk_chinese_ci", "gbk", true),
+    collation(30, "latin5_turkish_ci", "latin5", true),
+    collation(31, "latin1_german2_ci", "latin1", false),
+    collation(32, "armscii8_general_ci", "armscii8", true),
+    collation(33, "utf8mb3_general_ci", "utf8mb3", true),
+    collation(35, "ucs2_general_ci", "ucs2", true),
+    collation(36, "cp866_general_ci", "cp866", true),
+    collation(37, "keybcs2_general_ci", "keybcs2", true),
+    collation(38, "macce_general_ci", "macce", true),
+    collation(39, "macroman_general_ci", "macroman", true),
+    collation(40, "cp852_general_ci", "cp852", true),
+    collation(41, "latin7_general_ci", "latin7", true),
+    collation(45, "utf8mb4_general_ci", "utf8mb4", false),
+    collation(46, "utf8mb4_bin", "utf8mb4", false),
+    collation(47, "latin1_bin", "latin1", false),
+    collation(48, "latin1_general_ci", "latin1", false),
+    collation(49, "latin1_general_cs", "latin1", false),
+    collation(51, "cp1251_general_ci", "cp1251", true),
+    collation(54, "utf16_general_ci", "utf16", true),
+    collation(56, "utf16le_general_ci", "utf16le", true),
+    collation(57, "cp1256_general_ci", "cp1256", true),
+    collation(59, "cp1257_general_ci", "cp1257", true),
+    collation(60, "utf32_general_ci", "utf32", true),
+    collation(63, "binary", "binary", true),
+    collation(65, "ascii_bin", "ascii", false),
+    collation(83, "utf8mb3_bin", "utf8mb3", false),
+    collation(92, "geostd8_general_ci", "geostd8", true),
+    collation(94, "latin1_spanish_ci", "latin1", false),
+    collation(95, "cp932_japanese_ci", "cp932", true),
+    collation(97, "eucjpms_japanese_ci", "eucjpms", true),
+    collation(192, "utf8mb3_unicode_ci", "utf8mb3", false),
+    collation(224, "utf8mb4_unicode_ci", "utf8mb4", false),
+    collation(246, "utf8mb4_unicode_520_ci", "utf8mb4", false),
+    collation(248, "gb18030_chinese_ci", "gb18030", true),
+    collation(255, "utf8mb4_0900_ai_ci", "utf8mb4", true),
+    collation(278, "utf8mb4_0900_as_cs", "utf8mb4", false),
+    collation(303, "utf8mb4_ja_0900_as_cs", "utf8mb4", false),
+    collation(309, "utf8mb4_0900_bin", "utf8mb4", false),
+];
+
+pub fn collation_by_id(id: u16) -> Option<&'static Collation> {
+    COLLATIONS.iter().find(|c| c.id == id)
+}
+
+pub fn collation_by_name(name: &str) -> Option<&'static Collation> {
+    COLLATIONS
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+// "utf8" has been an alias of utf8mb3 since MySQL 8.0.
+pub fn normalize_charset(charset: &str) -> String {
+    let charset = charset.to_ascii_lowercase();
+    if charset == "utf8" {
+        String::from("utf8mb3")
+    } else {
+        charset
+    }
+}
+
+// Checks `charset` (and `collation`, which must belong to it) against the collation table and
+// returns the collation the session will use. `None` means the server's default for the charset.
+pub fn resolve_collation(
+    charset: &str,
+    collation: Option<&str>,
+) -> Result<Option<&'static Collation>> {
+    let charset = normalize_charset(charset);
+    if !COLLATIONS.iter().any(|c| c.charset == charset) {
+        bail!("unknown character set: {}", charset);
+    }
+    let Some(name) = collation else {
+        return Ok(None);
+    };
+    match collation_by_name(name) {
+        Some(c) if c.charset == charset => Ok(Some(c)),
+        Some(c) => bail!(
+            "collation {} belongs to {}, not {}",
+            c.name,
+            c.charset,
+            charset
+        ),
+        None => bail!("unknown collation: {}", name),
+    }
+}
+
+impl Connection {
+    // Character set of the session (character_set_client / _connection / _results).
+    pub fn charset(&self) -> &str {
+        &self.options.charset
+    }
+
+    pub fn collation(&self) -> Option<&str> {
+        self.options.collation.as_deref()
+    }
+
+    // SET NAMES 'charset' [COLLATE 'collation']
+    // https://dev.mysql.com/doc/refman/8.4/en/set-names.html
+    //
+    // The choice is stored in the connection options, so it is also what the handshake asks for when
+    // the connection is established again.
+    pub fn set_charset(&mut self, charset: &str, collation: Option<&str>) -> Result<()> {
+        let resolved = resolve_collation(charset, collation)?;
+        let charset = normalize_charset(charset);
+        let mut sql = format!("SET NAMES '{}'", self.escape_string(&charset));
+        if let Some(c) = resolved {
+            sql.push_str(&format!(" COLLATE '{}'", self.escape_string(c.name)));
+        }
+        self.internal(|conn| conn.query_drop(&sql))?;
+        self.options.charset = charset;
+        self.options.collation = resolved.map(|c| String::from(c.name));
+        Ok(())
+    }
+
+    pub(crate) fn track_charset(&mut self, charset: &str) {
+        let charset = normalize_charset(charset);
+        if charset == self.options.charset {
+            return;
+        }
+        let collation = self
+            .options
+            .collation
+            .as_deref()
+            .and_then(collation_by_name);
+        if collation.is_some_and(|c| c.charset != charset) {
+            self.options.collation = None;
+        }
+        self.options.charset = charset;
+    }
+
+    // The collation id sent in the handshake, and whether it stands in for a collation that only
+    // SET NAMES can select (ids above 255 don't fit in the handshake).
+    pub(crate) fn handshake_collation_id(&self) -> Result<(u8, bool)> {
+        let fallback = self.server_version().utf8mb4_collation_id();
+        let collation =
+            match resolve_collation(&self.options.charset, self.options.collation.as_deref())? {
+                Some(c) => c,
+                None if self.options.charset == "utf8mb4" => return Ok((fallback, false)),
+                None => COLLATIONS
+                    .iter()
+                    .find(|c| c.charset == self.options.charset && c.is_default)
+                    .expect("every known charset has a default collation"),
+            };
+        match u8::try_from(collation.id) {
+            Ok(id) => Ok((id, false)),
+            Err(_) => Ok((fallback, true)),
+        }
+    }
+}
@@ -1,42 +1,95 @@
+use std::{fmt, ops::Index};
+
 use anyhow::{Result, bail};
 
-use crate::utils::{decode_lenenc_integer, decode_lenenc_string};
+use crate::{
+    consts::{COM_QUERY, EOF_HEADER, ERR_HEADER, LENENC_NULL, OK_HEADER},
+    decode::check_len,
+    handshake::CLIENT_SESSION_TRACK,
+    packet_writer::PacketWriter,
+    utils::{decode_lenenc_integer, decode_lenenc_string},
+    value::Value,
+};
 
 // COM_QUERY
 // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query.html
 #[derive(Debug)]
 pub struct ComQuery {
     pub command: u8,
+    pub query_attributes: bool,
     pub parameter_count: u64,
     pub parameter_set_count: u64,
-    pub query: String,
+    // The statement in the session character set.
+    pub query: Vec<u8>,
 }
 
 impl ComQuery {
-    pub fn new(query: &str) -> Self {
+    pub fn new(query: impl AsRef<[u8]>, query_attributes: bool) -> Self {
         Self {
-            command: 0x03,
+            command: COM_QUERY,
+            query_attributes,
             parameter_count: 0,
             parameter_set_count: 1,
-            query: String::from(query),
+            query: query.as_ref().to_vec(),
         }
     }
 
     pub fn encode(&self) -> Vec<u8> {
-        let mut pkt = vec![];
-
-        pkt.push(self.command);
-        pkt.push(self.parameter_count as u8);
-        pkt.push(self.parameter_set_count as u8);
-        pkt.append(&mut self.query.as_bytes().to_vec());
+        PacketWriter::encode(|w| self.write_to(w))
+    }
 
-        pkt
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.command);
+        // The parameter fields are only present when CLIENT_QUERY_ATTRIBUTES is negotiated.
+        if self.query_attributes {
+            w.lenenc_int(self.parameter_count);
+            w.lenenc_int(self.parameter_set_count);
+        }
+        w.bytes(&self.query);
     }
 }
 
+// Column types
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/field__types_8h.html
+pub const MYSQL_TYPE_DECIMAL: u8 = 0x00;
+pub const MYSQL_TYPE_TINY: u8 = 0x01;
+pub const MYSQL_TYPE_SHORT: u8 = 0x02;
+pub const MYSQL_TYPE_LONG: u8 = 0x03;
+pub const MYSQL_TYPE_FLOAT: u8 = 0x04;
+pub const MYSQL_TYPE_DOUBLE: u8 = 0x05;
+pub const MYSQL_TYPE_NULL: u8 = 0x06;
+pub const MYSQL_TYPE_TIMESTAMP: u8 = 0x07;
+pub const MYSQL_TYPE_LONGLONG: u8 = 0x08;
+pub const MYSQL_TYPE_INT24: u8 = 0x09;
+pub const MYSQL_TYPE_DATE: u8 = 0x0a;
+pub const MYSQL_TYPE_TIME: u8 = 0x0b;
+pub const MYSQL_TYPE_DATETIME: u8 = 0x0c;
+pub const MYSQL_TYPE_YEAR: u8 = 0x0d;
+pub const MYSQL_TYPE_NEWDATE: u8 = 0x0e;
+pub const MYSQL_TYPE_VARCHAR: u8 = 0x0f;
+pub const MYSQL_TYPE_BIT: u8 = 0x10;
+pub const MYSQL_TYPE_TIMESTAMP2: u8 = 0x11;
+pub const MYSQL_TYPE_DATETIME2: u8 = 0x12;
+pub const MYSQL_TYPE_TIME2: u8 = 0x13;
+pub const MYSQL_TYPE_VECTOR: u8 = 0xf2;
+pub const MYSQL_TYPE_JSON: u8 = 0xf5;
+pub const MYSQL_TYPE_NEWDECIMAL: u8 = 0xf6;
+pub const MYSQL_TYPE_ENUM: u8 = 0xf7;
+pub const MYSQL_TYPE_SET: u8 = 0xf8;
+pub const MYSQL_TYPE_TINY_BLOB: u8 = 0xf9;
+pub const MYSQL_TYPE_MEDIUM_BLOB: u8 = 0xfa;
+pub const MYSQL_TYPE_LONG_BLOB: u8 = 0xfb;
+pub const MYSQL_TYPE_BLOB: u8 = 0xfc;
+pub const MYSQL_TYPE_VAR_STRING: u8 = 0xfd;
+pub const MYSQL_TYPE_STRING: u8 = 0xfe;
+pub const MYSQL_TYPE_GEOMETRY: u8 = 0xff;
+
+// Column definition flags
+pub const UNSIGNED_FLAG: u16 = 32;
+
 // Protocol::ColumnDefinition41
 // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query_response_text_resultset_column_definition.html
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct ColumnDefinition41 {
     pub catalog: String,
@@ -54,30 +107,52 @@ pub struct ColumnDefinition41 {
 }
 
 impl ColumnDefinition41 {
-    pub fn decode(pkt: Vec<u8>) -> Result<Self> {
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.lenenc_str(self.catalog.as_bytes());
+        w.lenenc_str(self.schema.as_bytes());
+        w.lenenc_str(self.table.as_bytes());
+        w.lenenc_str(self.org_table.as_bytes());
+        w.lenenc_str(self.name.as_bytes());
+        w.lenenc_str(self.org_name.as_bytes());
+        w.lenenc_int(self.length_of_fixed_length_fields);
+        w.u16_le(self.character_set);
+        w.u32_le(self.column_length);
+        w.u8(self.type_);
+        w.u16_le(self.flags);
+        w.u8(self.decimals);
+        // The 2 bytes making up the 0x0c fixed-length fields.
+        w.bytes(&[0, 0]);
+    }
+
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
         let mut pos = 0;
 
-        let (catalog, consumed) = decode_lenenc_string(&pkt, pos)?;
+        let (catalog, consumed) = decode_lenenc_string(pkt, pos)?;
         pos += consumed;
 
-        let (schema, consumed) = decode_lenenc_string(&pkt, pos)?;
+        let (schema, consumed) = decode_lenenc_string(pkt, pos)?;
         pos += consumed;
 
-        let (table, consumed) = decode_lenenc_string(&pkt, pos)?;
+        let (table, consumed) = decode_lenenc_string(pkt, pos)?;
         pos += consumed;
 
-        let (org_table, consumed) = decode_lenenc_string(&pkt, pos)?;
+        let (org_table, consumed) = decode_lenenc_string(pkt, pos)?;
         pos += consumed;
 
-        let (name, consumed) = decode_lenenc_string(&pkt, pos)?;
+        let (name, consumed) = decode_lenenc_string(pkt, pos)?;
         pos += consumed;
 
-        let (org_name, consumed) = decode_lenenc_string(&pkt, pos)?;
+        let (org_name, consumed) = decode_lenenc_string(pkt, pos)?;
         pos += consumed;
 
-        let (length_of_fixed_length_fields, consumed) = decode_lenenc_integer(&pkt, pos)?;
+        let (length_of_fixed_length_fields, consumed) = decode_lenenc_integer(pkt, pos)?;
         pos += consumed;
 
+        check_len(pkt, pos, 10)?;
         let character_set = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
         pos += 2;
 
@@ -109,25 +184,142 @@ impl ColumnDefinition41 {
             decimals,
         })
     }
+
+    // Just the name and type of a column definition packet, without decoding (or allocating) the
+    // rest; enough for `schema_fingerprint`.
+    pub(crate) fn decode_name_and_type(pkt: &[u8]) -> Result<(&[u8], u8)> {
+        let mut pos = 0;
+        let mut name = &pkt[..0];
+        // catalog, schema, table, org_table, name, org_name
+        for i in 0..6 {
+            let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+            pos += consumed;
+            check_len(pkt, pos, len as usize)?;
+            if i == 4 {
+                name = &pkt[pos..pos + len as usize];
+            }
+            pos += len as usize;
+        }
+        let (_, consumed) = decode_lenenc_integer(pkt, pos)?;
+        pos += consumed;
+        // character_set and column_length come before the type.
+        check_len(pkt, pos, 7)?;
+        Ok((name, pkt[pos + 6]))
+    }
 }
 
 // ProtocolText::ResultsetRow
 // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query_response_text_resultset_row.html
-#[derive(Debug)]
-#[allow(dead_code)]
-pub struct ResultsetRow(pub Vec<String>);
+// Cells are kept as sent: `None` is NULL (0xfb), anything else the raw text (or binary) value.
+pub struct ResultsetRow(pub Vec<Option<Vec<u8>>>);
 
 impl ResultsetRow {
-    pub fn decode(pkt: Vec<u8>) -> Result<Self> {
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        for cell in &self.0 {
+            match cell {
+                Some(val) => w.lenenc_str(val),
+                None => w.u8(LENENC_NULL),
+            }
+        }
+    }
+
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
         let mut buf = vec![];
         let mut pos = 0;
         while pos < pkt.len() {
-            let (s, consumed) = decode_lenenc_string(&pkt, pos)?;
+            if pkt[pos] == LENENC_NULL {
+                pos += 1;
+                buf.push(None);
+                continue;
+            }
+            let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
             pos += consumed;
-            buf.push(s);
+            check_len(pkt, pos, len as usize)?;
+            buf.push(Some(pkt[pos..(pos + len as usize)].to_vec()));
+            pos += len as usize;
         }
         Ok(Self(buf))
     }
+
+    // The cells as typed values, given the result set's column definitions.
+    pub fn values(&self, columns: &[ColumnDefinition41]) -> Result<Vec<Value>> {
+        if self.0.len() != columns.len() {
+            bail!(
+                "the row has {} cells, but {} columns were given",
+                self.0.len(),
+                columns.len()
+            );
+        }
+        self.0
+            .iter()
+            .zip(columns)
+            .map(|(cell, column)| match cell {
+                Some(bytes) => Value::parse_text(bytes, column),
+                None => Ok(Value::Null),
+            })
+            .collect()
+    }
+
+    // Number of cells, i.e. of columns.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // The cell at `idx` as sent: `None` past the end of the row, `Some(None)` for NULL. `row[idx]`
+    // panics instead.
+    pub fn get(&self, idx: usize) -> Option<Option<&[u8]>> {
+        self.0.get(idx).map(Option::as_deref)
+    }
+
+    // The cells as UTF-8 strings, for result sets known to be text (SHOW ..., SELECT @@...).
+    pub fn into_strings(self) -> Result<Vec<Option<String>>> {
+        self.0
+            .into_iter()
+            .map(|cell| cell.map(String::from_utf8).transpose().map_err(Into::into))
+            .collect()
+    }
+}
+
+impl Index<usize> for ResultsetRow {
+    type Output = Option<Vec<u8>>;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        match self.0.get(idx) {
+            Some(cell) => cell,
+            None => panic!(
+                "index out of bounds: the row has {} cells but the index is {}",
+                self.0.len(),
+                idx
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for ResultsetRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Cell<'a>(&'a Option<Vec<u8>>);
+
+        impl fmt::Debug for Cell<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.0 {
+                    Some(val) => write!(f, "{:?}", String::from_utf8_lossy(val)),
+                    None => f.write_str("NULL"),
+                }
+            }
+        }
+
+        f.debug_tuple("ResultsetRow")
+            .field(&self.0.iter().map(Cell).collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 // ERR_Packet
@@ -143,11 +335,36 @@ pub struct ErrPacket {
 }
 
 impl ErrPacket {
-    pub fn decode(pkt: Vec<u8>) -> Result<Self> {
+    pub fn new(error_code: u16, sql_state: &str, error_message: &str) -> Self {
+        Self {
+            header: ERR_HEADER,
+            error_code,
+            sql_state_marker: String::from("#"),
+            sql_state: String::from(sql_state),
+            error_message: String::from(error_message),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.header);
+        w.u16_le(self.error_code);
+        w.bytes(self.sql_state_marker.as_bytes());
+        if !self.sql_state_marker.is_empty() {
+            w.bytes(self.sql_state.as_bytes());
+        }
+        w.bytes(self.error_message.as_bytes());
+    }
+
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
         let mut pos = 0;
 
+        check_len(pkt, pos, 3)?;
         let header = pkt[pos];
-        if header != 0xff {
+        if header != ERR_HEADER {
             bail!("not err packet");
         }
         pos += 1;
@@ -155,11 +372,16 @@ impl ErrPacket {
         let error_code = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
         pos += 2;
 
-        let sql_state_marker = String::from_utf8(pkt[pos..(pos + 1)].to_vec())?;
-        pos += 1;
-
-        let sql_state = String::from_utf8(pkt[pos..(pos + 5)].to_vec())?;
-        pos += 5;
+        // An ERR sent before the capabilities are negotiated (e.g. instead of the handshake) has no
+        // SQL state; report the generic HY000 for it, as libmysqlclient does.
+        let (sql_state_marker, sql_state) = if pkt.get(pos) == Some(&b'#') {
+            check_len(pkt, pos, 6)?;
+            let sql_state = String::from_utf8(pkt[(pos + 1)..(pos + 6)].to_vec())?;
+            pos += 6;
+            (String::from("#"), sql_state)
+        } else {
+            (String::new(), String::from("HY000"))
+        };
 
         let error_message = String::from_utf8(pkt[pos..].to_vec())?;
 
@@ -179,3 +401,449 @@ impl ErrPacket {
         )
     }
 }
+
+impl fmt::Display for ErrPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.human_readable_text())
+    }
+}
+
+impl std::error::Error for ErrPacket {}
+
+// SERVER_STATUS_flags_enum
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/mysql__com_8h.html
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatusFlags(pub u16);
+
+impl StatusFlags {
+    pub const IN_TRANS: u16 = 0x0001;
+    pub const AUTOCOMMIT: u16 = 0x0002;
+    pub const MORE_RESULTS_EXISTS: u16 = 0x0008;
+    pub const NO_GOOD_INDEX_USED: u16 = 0x0010;
+    pub const NO_INDEX_USED: u16 = 0x0020;
+    pub const CURSOR_EXISTS: u16 = 0x0040;
+    pub const LAST_ROW_SENT: u16 = 0x0080;
+    pub const DB_DROPPED: u16 = 0x0100;
+    pub const NO_BACKSLASH_ESCAPES: u16 = 0x0200;
+    pub const METADATA_CHANGED: u16 = 0x0400;
+    pub const QUERY_WAS_SLOW: u16 = 0x0800;
+    pub const PS_OUT_PARAMS: u16 = 0x1000;
+    pub const IN_TRANS_READONLY: u16 = 0x2000;
+    pub const SESSION_STATE_CHANGED: u16 = 0x4000;
+
+    const NAMES: [(u16, &str); 14] = [
+        (Self::IN_TRANS, "IN_TRANS"),
+        (Self::AUTOCOMMIT, "AUTOCOMMIT"),
+        (Self::MORE_RESULTS_EXISTS, "MORE_RESULTS_EXISTS"),
+        (Self::NO_GOOD_INDEX_USED, "NO_GOOD_INDEX_USED"),
+        (Self::NO_INDEX_USED, "NO_INDEX_USED"),
+        (Self::CURSOR_EXISTS, "CURSOR_EXISTS"),
+        (Self::LAST_ROW_SENT, "LAST_ROW_SENT"),
+        (Self::DB_DROPPED, "DB_DROPPED"),
+        (Self::NO_BACKSLASH_ESCAPES, "NO_BACKSLASH_ESCAPES"),
+        (Self::METADATA_CHANGED, "METADATA_CHANGED"),
+        (Self::QUERY_WAS_SLOW, "QUERY_WAS_SLOW"),
+        (Self::PS_OUT_PARAMS, "PS_OUT_PARAMS"),
+        (Self::IN_TRANS_READONLY, "IN_TRANS_READONLY"),
+        (Self::SESSION_STATE_CHANGED, "SESSION_STATE_CHANGED"),
+    ];
+
+    pub fn contains(&self, flag: u16) -> bool {
+        self.0 & flag != 0
+    }
+
+    pub fn in_trans(&self) -> bool {
+        self.contains(Self::IN_TRANS)
+    }
+
+    pub fn autocommit(&self) -> bool {
+        self.contains(Self::AUTOCOMMIT)
+    }
+
+    pub fn more_results_exists(&self) -> bool {
+        self.contains(Self::MORE_RESULTS_EXISTS)
+    }
+
+    pub fn no_good_index_used(&self) -> bool {
+        self.contains(Self::NO_GOOD_INDEX_USED)
+    }
+
+    pub fn no_index_used(&self) -> bool {
+        self.contains(Self::NO_INDEX_USED)
+    }
+
+    pub fn cursor_exists(&self) -> bool {
+        self.contains(Self::CURSOR_EXISTS)
+    }
+
+    pub fn last_row_sent(&self) -> bool {
+        self.contains(Self::LAST_ROW_SENT)
+    }
+
+    pub fn db_dropped(&self) -> bool {
+        self.contains(Self::DB_DROPPED)
+    }
+
+    pub fn no_backslash_escapes(&self) -> bool {
+        self.contains(Self::NO_BACKSLASH_ESCAPES)
+    }
+
+    pub fn metadata_changed(&self) -> bool {
+        self.contains(Self::METADATA_CHANGED)
+    }
+
+    pub fn query_was_slow(&self) -> bool {
+        self.contains(Self::QUERY_WAS_SLOW)
+    }
+
+    pub fn ps_out_params(&self) -> bool {
+        self.contains(Self::PS_OUT_PARAMS)
+    }
+
+    pub fn in_trans_readonly(&self) -> bool {
+        self.contains(Self::IN_TRANS_READONLY)
+    }
+
+    pub fn session_state_changed(&self) -> bool {
+        self.contains(Self::SESSION_STATE_CHANGED)
+    }
+}
+
+// IN_TRANS | AUTOCOMMIT
+impl fmt::Display for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        f.write_str(&names.join(" | "))
+    }
+}
+
+// StatusFlags(IN_TRANS | AUTOCOMMIT)
+impl fmt::Debug for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StatusFlags({})", self)
+    }
+}
+
+// A block of the session state information in an OK_Packet. `data` is the type-specific payload.
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_ok_packet.html
+#[derive(Debug, Clone)]
+pub struct SessionStateChange {
+    pub type_: u8,
+    pub data: Vec<u8>,
+}
+
+// OK_Packet
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_ok_packet.html
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct OkPacket {
+    pub header: u8,
+    pub affected_rows: u64,
+    pub last_insert_id: u64,
+    pub status_flags: StatusFlags,
+    pub warnings: u16,
+    pub info: String,
+    pub session_state_changes: Vec<SessionStateChange>,
+}
+
+impl OkPacket {
+    // Header 0x00; a result set is ended by one with 0xfe (CLIENT_DEPRECATE_EOF).
+    pub fn new(affected_rows: u64, last_insert_id: u64, status_flags: StatusFlags) -> Self {
+        Self {
+            header: OK_HEADER,
+            affected_rows,
+            last_insert_id,
+            status_flags,
+            warnings: 0,
+            info: String::new(),
+            session_state_changes: vec![],
+        }
+    }
+
+    pub fn encode(&self, capabilities: u32) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w, capabilities))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter, capabilities: u32) {
+        w.u8(self.header);
+        w.lenenc_int(self.affected_rows);
+        w.lenenc_int(self.last_insert_id);
+        w.u16_le(self.status_flags.0);
+        w.u16_le(self.warnings);
+        if capabilities & CLIENT_SESSION_TRACK == 0 {
+            w.bytes(self.info.as_bytes());
+            return;
+        }
+        if !self.info.is_empty() || self.status_flags.session_state_changed() {
+            w.lenenc_str(self.info.as_bytes());
+        }
+        if self.status_flags.session_state_changed() {
+            let changes = PacketWriter::encode(|changes| {
+                for change in &self.session_state_changes {
+                    changes.u8(change.type_);
+                    changes.lenenc_str(&change.data);
+                }
+            });
+            w.lenenc_str(&changes);
+        }
+    }
+
+    pub fn decode(pkt: &[u8], capabilities: u32) -> Result<Self> {
+        let mut pos = 0;
+
+        check_len(pkt, pos, 1)?;
+        let header = pkt[pos];
+        if header != OK_HEADER && header != EOF_HEADER {
+            bail!("not ok packet");
+        }
+        pos += 1;
+
+        let (affected_rows, consumed) = decode_lenenc_integer(pkt, pos)?;
+        pos += consumed;
+
+        let (last_insert_id, consumed) = decode_lenenc_integer(pkt, pos)?;
+        pos += consumed;
+
+        check_len(pkt, pos, 4)?;
+        let status_flags = StatusFlags(u16::from_le_bytes([pkt[pos], pkt[pos + 1]]));
+        pos += 2;
+
+        let warnings = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
+        pos += 2;
+
+        let mut session_state_changes = vec![];
+        let info = if capabilities & CLIENT_SESSION_TRACK != 0 {
+            let info = if pos < pkt.len() {
+                let (info, consumed) = decode_lenenc_string(pkt, pos)?;
+                pos += consumed;
+                info
+            } else {
+                String::new()
+            };
+            if status_flags.session_state_changed() {
+                let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+                pos += consumed;
+                let end = pos + len as usize;
+                check_len(pkt, pos, len as usize)?;
+                while pos < end {
+                    let type_ = pkt[pos];
+                    pos += 1;
+                    let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+                    pos += consumed;
+                    check_len(pkt, pos, len as usize)?;
+                    let data = pkt[pos..(pos + len as usize)].to_vec();
+                    pos += len as usize;
+                    session_state_changes.push(SessionStateChange { type_, data });
+                }
+            }
+            info
+        } else {
+            String::from_utf8(pkt[pos..].to_vec())?
+        };
+
+        Ok(Self {
+            header,
+            affected_rows,
+            last_insert_id,
+            status_flags,
+            warnings,
+            info,
+            session_state_changes,
+        })
+    }
+}
+
+// EOF_Packet
+// Ends the column definitions and the rows of a result set when CLIENT_DEPRECATE_EOF isn't
+// negotiated (servers before MySQL 5.7.5).
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_eof_packet.html
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct EofPacket {
+    pub header: u8,
+    pub warnings: u16,
+    pub status_flags: StatusFlags,
+}
+
+impl EofPacket {
+    pub fn new(warnings: u16, status_flags: StatusFlags) -> Self {
+        Self {
+            header: EOF_HEADER,
+            warnings,
+            status_flags,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.header);
+        w.u16_le(self.warnings);
+        w.u16_le(self.status_flags.0);
+    }
+
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+
+        check_len(pkt, pos, 5)?;
+        let header = pkt[pos];
+        if header != EOF_HEADER {
+            bail!("not eof packet");
+        }
+        pos += 1;
+
+        let warnings = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
+        pos += 2;
+
+        let status_flags = StatusFlags(u16::from_le_bytes([pkt[pos], pkt[pos + 1]]));
+        // pos += 2;
+
+        Ok(Self {
+            header,
+            warnings,
+            status_flags,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handshake::CLIENT_DEPRECATE_EOF;
+
+    // An UPDATE's OK packet: 1 row affected, AUTOCOMMIT, no warnings.
+    const UPDATE_OK: &[u8] = b"\x00\x01\x00\x02\x00\x00\x00Rows matched: 1";
+
+    #[test]
+    fn ok_packet_without_session_track_takes_the_rest_as_info() {
+        let ok = OkPacket::decode(UPDATE_OK, CLIENT_DEPRECATE_EOF).unwrap();
+        assert_eq!(ok.header, OK_HEADER);
+        assert_eq!(ok.affected_rows, 1);
+        assert_eq!(ok.status_flags.0, StatusFlags::AUTOCOMMIT);
+        assert_eq!(ok.info, "Rows matched: 1");
+        assert!(ok.session_state_changes.is_empty());
+    }
+
+    #[test]
+    fn ok_packet_with_session_track_reads_state_changes() {
+        // SESSION_STATE_CHANGED | AUTOCOMMIT, an empty info and a SESSION_TRACK_SCHEMA change to
+        // "test".
+        let pkt = b"\x00\x00\x00\x02\x40\x00\x00\x00\x07\x01\x05\x04test";
+        let ok = OkPacket::decode(pkt, CLIENT_SESSION_TRACK | CLIENT_DEPRECATE_EOF).unwrap();
+        assert!(ok.status_flags.session_state_changed());
+        assert_eq!(ok.info, "");
+        assert_eq!(ok.session_state_changes.len(), 1);
+        assert_eq!(ok.session_state_changes[0].type_, 1);
+        assert_eq!(ok.session_state_changes[0].data, b"\x04test");
+    }
+
+    #[test]
+    fn ok_packet_with_several_session_state_changes() {
+        // autocommit = OFF, schema "other", then an info string before the blocks.
+        let mut pkt = b"\x00\x00\x00\x00\x40\x00\x00\x04info".to_vec();
+        let changes = b"\x00\x0f\x0aautocommit\x03OFF\x01\x06\x05other";
+        pkt.push(changes.len() as u8);
+        pkt.extend_from_slice(changes);
+        let ok = OkPacket::decode(&pkt, CLIENT_SESSION_TRACK).unwrap();
+        assert_eq!(ok.info, "info");
+        let changes = ok
+            .session_state_changes
+            .iter()
+            .map(|change| (change.type_, change.data.as_slice()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [(0, &b"\x0aautocommit\x03OFF"[..]), (1, &b"\x05other"[..])]
+        );
+    }
+
+    #[test]
+    fn ok_packet_with_truncated_session_state_is_an_error() {
+        // The block says 7 bytes, but only 3 follow.
+        let pkt = b"\x00\x00\x00\x02\x40\x00\x00\x00\x07\x01\x05\x04";
+        assert!(OkPacket::decode(pkt, CLIENT_SESSION_TRACK).is_err());
+        // The change says 5 bytes, but the block ends after 2.
+        let pkt = b"\x00\x00\x00\x02\x40\x00\x00\x00\x03\x01\x05\x04";
+        assert!(OkPacket::decode(pkt, CLIENT_SESSION_TRACK).is_err());
+    }
+
+    #[test]
+    fn ok_packet_ending_rows_with_deprecate_eof() {
+        // 0xfe header, no info, with and without session tracking.
+        let pkt = b"\xfe\x00\x00\x22\x00\x01\x00";
+        for capabilities in [
+            CLIENT_DEPRECATE_EOF,
+            CLIENT_DEPRECATE_EOF | CLIENT_SESSION_TRACK,
+        ] {
+            let ok = OkPacket::decode(pkt, capabilities).unwrap();
+            assert_eq!(ok.header, EOF_HEADER);
+            assert_eq!(
+                ok.status_flags.0,
+                StatusFlags::AUTOCOMMIT | StatusFlags::NO_INDEX_USED
+            );
+            assert_eq!(ok.warnings, 1);
+            assert_eq!(ok.info, "");
+        }
+    }
+
+    #[test]
+    fn eof_packet_without_deprecate_eof() {
+        let eof = EofPacket::decode(b"\xfe\x01\x00\x0a\x00").unwrap();
+        assert_eq!(eof.warnings, 1);
+        assert_eq!(
+            eof.status_flags.0,
+            StatusFlags::AUTOCOMMIT | StatusFlags::MORE_RESULTS_EXISTS
+        );
+        assert!(EofPacket::decode(b"\xfe\x01\x00").is_err());
+    }
+
+    #[test]
+    fn ok_packets_round_trip_with_and_without_session_track() {
+        let mut ok = OkPacket::new(3, 7, StatusFlags(StatusFlags::SESSION_STATE_CHANGED));
+        ok.info = String::from("Records: 3  Duplicates: 0  Warnings: 0");
+        ok.session_state_changes.push(SessionStateChange {
+            type_: 1,
+            data: b"\x04test".to_vec(),
+        });
+        let decoded =
+            OkPacket::decode(&ok.encode(CLIENT_SESSION_TRACK), CLIENT_SESSION_TRACK).unwrap();
+        assert_eq!(decoded.info, ok.info);
+        assert_eq!(decoded.session_state_changes[0].data, b"\x04test");
+        let decoded = OkPacket::decode(&ok.encode(0), 0).unwrap();
+        assert_eq!((decoded.affected_rows, decoded.last_insert_id), (3, 7));
+        assert_eq!(decoded.info, ok.info);
+    }
+
+    fn row() -> ResultsetRow {
+        ResultsetRow(vec![Some(b"1".to_vec()), None, Some(b"bob".to_vec())])
+    }
+
+    #[test]
+    fn resultset_row_index_returns_the_cell() {
+        let row = row();
+        assert_eq!(row[0].as_deref(), Some(&b"1"[..]));
+        assert_eq!(row[1], None);
+        assert_eq!(row[2].as_deref(), Some(&b"bob"[..]));
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds: the row has 3 cells but the index is 3")]
+    fn resultset_row_index_out_of_range_panics() {
+        let _ = &row()[3];
+    }
+
+    #[test]
+    fn resultset_row_get_is_none_out_of_range() {
+        let row = row();
+        assert_eq!(row.get(0), Some(Some(&b"1"[..])));
+        assert_eq!(row.get(1), Some(None));
+        assert_eq!(row.get(3), None);
+        assert_eq!(row.get(usize::MAX), None);
+    }
+}
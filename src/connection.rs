@@ -1,15 +1,50 @@
 use std::{
-    io::{BufReader, BufWriter, Read, Write},
-    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
-    str::FromStr,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use anyhow::{Result, bail};
 use log::debug;
 
 use crate::{
-    command::{ColumnDefinition41, ComQuery, ErrPacket, ResultsetRow},
-    handshake::{HandshakeResponse41, HandshakeV10},
+    auth::{AuthContext, AuthPlugins, Authenticator, MYSQL_NATIVE_PASSWORD},
+    charset::{normalize_charset, resolve_collation},
+    command::{
+        ColumnDefinition41, ComQuery, EofPacket, ErrPacket, OkPacket, ResultsetRow, StatusFlags,
+    },
+    consts::{
+        AUTH_MORE_DATA_HEADER, AUTH_NEXT_FACTOR_HEADER, AUTH_SWITCH_REQUEST_HEADER, COM_PING,
+        COM_RESET_CONNECTION, CapabilityFlags, CommandByte, EOF_HEADER, ERR_HEADER, OK_HEADER,
+        describe_response,
+    },
+    deadline::{CancellationToken, ConnectStage, Deadline},
+    decode::{PacketKind, packet_header},
+    encoding::encode_str,
+    error::Error,
+    handshake::{
+        AuthNextFactor, AuthSwitchRequest, CLIENT_CONNECT_ATTRS, CLIENT_DEPRECATE_EOF,
+        CLIENT_FOUND_ROWS, CLIENT_OPTIONAL_RESULTSET_METADATA, CLIENT_QUERY_ATTRIBUTES,
+        DEFAULT_CLIENT_FLAG, DEFAULT_MARIADB_CLIENT_FLAG, HandshakeResponse41, HandshakeV10,
+        MARIADB_CLIENT_PROGRESS,
+    },
+    iter::ResultSetIter,
+    packet_writer::PacketWriter,
+    pipeline::Pipeline,
+    progress_report::ProgressReportHandler,
+    redirect::redirect_target,
+    result::{ProgressTracker, QueryOptions, QueryResult, ResultSet, Terminator},
+    rewrite::QueryRewriter,
+    server_stats::{DEFAULT_SESSION_STATUS_COUNTERS, ServerStats},
+    server_version::ServerVersion,
+    session::TransactionState,
+    slow_query::SlowQueryHandler,
+    sql_mode::SqlMode,
+    state::{ConnectionState, StateListener},
+    transport::Transport,
+    utils::decode_lenenc_integer,
 };
 
 #[derive(Debug, Clone)]
@@ -19,110 +54,1425 @@ pub struct ConnectionOptions {
     pub database: String,
     pub host: String,
     pub port: u16,
+    // Failover candidates tried in order until one connects; `host`/`port` are used when empty.
+    pub hosts: Vec<(String, u16)>,
+    // Try `hosts` in random order to spread connections over them.
+    pub randomize_hosts: bool,
+    // Allows mysql_clear_password on connections that are neither TLS nor a Unix socket.
+    pub allow_cleartext_on_insecure: bool,
+    // Reconnect to the host the server redirects to at the end of the handshake.
+    pub follow_redirect: bool,
+    // Session character set and, optionally, collation. Kept up to date by `set_charset`.
+    pub charset: String,
+    pub collation: Option<String>,
+    // `slow_query_handler` is called for every `query`/`query_all` taking longer than this.
+    pub slow_query_threshold: Option<Duration>,
+    pub slow_query_handler: Option<SlowQueryHandler>,
+    // Reconnect before a query when the server has dropped the connection meanwhile (see
+    // `reconnect_if_closed`).
+    pub reconnect: bool,
+    // Set as the session's net_read_timeout / net_write_timeout after connecting, bounding how long
+    // the server waits on a half-sent packet from us or for us to read its output. Whole seconds,
+    // clamped to the range the server accepts.
+    pub net_read_timeout: Option<Duration>,
+    pub net_write_timeout: Option<Duration>,
+    // Packets that fail to decode are written to this directory, for attaching to bug reports.
+    pub packet_dump_dir: Option<PathBuf>,
+    // The auth plugin to start the handshake with instead of the server's default one (e.g.
+    // mysql_clear_password for an LDAP or PAM account). The server may still switch to another.
+    pub auth_plugin: Option<String>,
+    // Plugins to try, in order, when the server rejects the authentication with `auth_plugin` (or
+    // the default). Each attempt is a new connection, since the server closes it after a failed
+    // authentication. The last server error is returned when all of them fail.
+    pub auth_plugin_fallbacks: Vec<String>,
+    // The authenticators available to the handshake, by plugin name. Register one to support a
+    // plugin this crate doesn't implement.
+    pub auth_plugins: AuthPlugins,
+    // Whether to send connection attributes (_client_name, _pid, ...) in the handshake. Some proxies
+    // fail the handshake when they are present.
+    pub send_connect_attrs: bool,
+    // Lets `send_command` send commands that would desynchronize the connection.
+    pub unsafe_raw: bool,
+    // The local address (and port) to connect from, for servers or firewalls allowing only certain
+    // source addresses and for hosts with several interfaces. TCP only.
+    pub local_address: Option<IpAddr>,
+    pub local_port: Option<u16>,
+    // Limits `new` like `connect_with_deadline` does, counting from the call; the earlier of the
+    // two applies to `connect_with_deadline`.
+    pub connect_timeout: Option<Duration>,
+    // Retry a TCP connect that fails (e.g. refused while the server restarts, or a network blip)
+    // this many times, `connect_retry_delay` apart, before giving up on the host. Failures after
+    // the connect, such as a rejected login, aren't retried; the retries stay within the connect's
+    // deadline, if any.
+    pub connect_retries: u32,
+    pub connect_retry_delay: Duration,
+    // Send the parameter types with every COM_STMT_EXECUTE, instead of only when they differ from
+    // those of the statement's previous execution. For proxies that don't keep them between
+    // executions.
+    pub always_send_param_types: bool,
+    // Fail with `Error::ProtocolViolation` on packets that break the protocol in ways the decoders
+    // otherwise tolerate (see `strict`), e.g. to check a server implementation against MySQL's.
+    pub strict_protocol: bool,
+    // Ask MariaDB servers for progress reports during long statements (ALTER TABLE, LOAD DATA, ...),
+    // passed to `QueryOptions::on_progress_report`. Ignored by MySQL, which has none.
+    pub mariadb_progress_reports: bool,
+    // Negotiate CLIENT_FOUND_ROWS: the affected rows of an UPDATE count the rows it matched rather
+    // than those it changed, so `UPDATE t SET x = 1 WHERE id = 7` reports 1 even when x already
+    // was 1. The default, false, is the server's own behavior (and the mysql client's). It holds
+    // for every statement of the connection: `QueryResult::affected_rows`, ROW_COUNT(), and the
+    // `Connection::upsert` outcome, which can't tell an unchanged row from an inserted one then.
+    pub found_rows: bool,
+    // Connect through this named pipe (e.g. "MySQL", or a full \\.\pipe\... path) instead of TCP;
+    // `host` and `port` are then ignored.
+    #[cfg(windows)]
+    pub named_pipe: Option<String>,
+    // Snapshot `session_status_counters` with SHOW SESSION STATUS before and after every `query`,
+    // `query_all` and `query_with`, and attach the deltas to the results (`server_stats`). Costs two
+    // extra round trips per statement; the snapshots themselves count towards the next delta, and
+    // SHOW WARNINGS afterwards sees the second snapshot rather than the statement.
+    pub collect_session_status: bool,
+    pub session_status_counters: Vec<String>,
+    // Collect inside an explicit transaction too, where the snapshots become part of it.
+    pub collect_session_status_in_transaction: bool,
+}
+
+impl ConnectionOptions {
+    // Registers `authenticator` for `plugin_name` in `auth_plugins`, so the handshake can use it
+    // when the server (or `auth_plugin`) asks for that plugin.
+    pub fn with_auth_plugin(
+        mut self,
+        plugin_name: &str,
+        authenticator: impl Authenticator + 'static,
+    ) -> Self {
+        self.auth_plugins.register(plugin_name, authenticator);
+        self
+    }
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            username: String::new(),
+            password: String::new(),
+            database: String::new(),
+            host: String::from("127.0.0.1"),
+            port: 3306,
+            hosts: vec![],
+            randomize_hosts: false,
+            allow_cleartext_on_insecure: false,
+            follow_redirect: false,
+            charset: String::from("utf8mb4"),
+            collation: None,
+            slow_query_threshold: None,
+            slow_query_handler: None,
+            reconnect: false,
+            net_read_timeout: None,
+            net_write_timeout: None,
+            packet_dump_dir: None,
+            auth_plugin: None,
+            auth_plugin_fallbacks: vec![],
+            auth_plugins: AuthPlugins::default(),
+            send_connect_attrs: true,
+            unsafe_raw: false,
+            local_address: None,
+            local_port: None,
+            connect_timeout: None,
+            connect_retries: 0,
+            connect_retry_delay: Duration::from_secs(1),
+            always_send_param_types: false,
+            strict_protocol: false,
+            mariadb_progress_reports: false,
+            found_rows: false,
+            #[cfg(windows)]
+            named_pipe: None,
+            collect_session_status: false,
+            session_status_counters: DEFAULT_SESSION_STATUS_COUNTERS
+                .iter()
+                .map(|name| String::from(*name))
+                .collect(),
+            collect_session_status_in_transaction: false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Connection {
-    options: ConnectionOptions,
-    reader: BufReader<TcpStream>,
-    writer: BufWriter<TcpStream>,
+    pub(crate) options: ConnectionOptions,
+    reader: BufReader<Transport>,
+    writer: BufWriter<Transport>,
+    // Outgoing commands are encoded here, reusing the buffer from one command to the next.
+    packet_writer: PacketWriter,
     sequence: u8,
+    server_version: ServerVersion,
+    connection_id: u32,
+    pub(crate) capabilities: u32,
+    // The MariaDB extended capabilities both sides support; 0 with MySQL.
+    pub(crate) mariadb_capabilities: u32,
+    pub(crate) max_allowed_packet: Option<u64>,
+    pub(crate) sql_mode: Option<SqlMode>,
+    pub(crate) warning_count: u16,
+    pub(crate) state: ConnectionState,
+    pub(crate) state_listener: Option<StateListener>,
+    pub(crate) query_rewriter: Option<QueryRewriter>,
+    // Set while the crate runs statements of its own, which the query rewriter doesn't see.
+    pub(crate) internal: bool,
+    pub(crate) changed_system_variables: Vec<(String, String)>,
+    pub(crate) last_gtid: Option<String>,
+    pub(crate) status_flags: StatusFlags,
+    pub(crate) transaction_state: Option<TransactionState>,
+    pub(crate) redirect: Option<(String, u16)>,
+    pub(crate) server_stats: Option<ServerStats>,
+    // Bumped whenever cached result set metadata of prepared statements may have gone stale.
+    pub(crate) metadata_generation: u64,
+    // The `QueryOptions::on_progress_report` of the statement running.
+    pub(crate) progress_report_handler: Option<ProgressReportHandler>,
+    // Set while connecting; every packet read/write then observes `deadline`.
+    connect_stage: Option<ConnectStage>,
+    deadline: Deadline,
+}
+
+const ER_SECURE_TRANSPORT_REQUIRED: u16 = 3159;
+
+// Errors an authentication attempt with another plugin may get past: ER_ACCESS_DENIED_ERROR,
+// ER_NOT_SUPPORTED_AUTH_MODE and ER_PLUGIN_IS_NOT_LOADED.
+const AUTH_REJECTED_ERRORS: &[u16] = &[1045, 1251, 1524];
+
+pub(crate) const MAX_FRAME_LEN: usize = 0xffffff;
+
+// The encoding buffer is kept up to this size between commands.
+const MAX_RETAINED_PACKET_CAPACITY: usize = 1 << 20;
+
+// A server switching plugins more often than this is taken to be looping.
+const MAX_AUTH_SWITCHES: usize = 8;
+// MySQL accounts have at most 3 authentication factors.
+const MAX_AUTH_FACTORS: usize = 3;
+
+// Where the authentication exchange stands: the plugin answering the server, the challenge it was
+// given and how many times the server has switched plugins so far.
+struct AuthExchange {
+    plugin_name: String,
+    challenge: Vec<u8>,
+    switches: usize,
+    // Counting from 1.
+    factor: usize,
+}
+
+pub(crate) enum ResponseHeader {
+    Ok(OkPacket),
+    Columns(Vec<ColumnDefinition41>),
+}
+
+pub(crate) enum RowPacket {
+    Row(ResultsetRow),
+    End(Terminator),
 }
 
 impl Connection {
     pub fn new(options: ConnectionOptions) -> Result<Self> {
-        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str(&options.host)?), options.port);
-        let stream = TcpStream::connect(addr)?;
+        Self::connect_with(options, Deadline::default())
+    }
+
+    // Like `new`, but gives up once `deadline` passes or `cancel` is set, whichever comes first. The
+    // deadline covers the whole connect (name resolution, TCP connect, every handshake packet and the
+    // session setup queries after it); the error says which stage was interrupted, as an
+    // `Error::ConnectTimedOut` or `Error::ConnectCancelled`. The socket is closed on failure.
+    pub fn connect_with_deadline(
+        options: ConnectionOptions,
+        deadline: Instant,
+        cancel: &CancellationToken,
+    ) -> Result<Self> {
+        Self::connect_with(options, Deadline::new(deadline, cancel.clone()))
+    }
+
+    fn connect_with(mut options: ConnectionOptions, mut deadline: Deadline) -> Result<Self> {
+        if let Some(timeout) = options.connect_timeout {
+            deadline = deadline.within(Instant::now() + timeout);
+        }
+        resolve_collation(&options.charset, options.collation.as_deref())?;
+        options.charset = normalize_charset(&options.charset);
+        for plugin in options
+            .auth_plugin
+            .iter()
+            .chain(&options.auth_plugin_fallbacks)
+        {
+            if !options.auth_plugins.contains(plugin) {
+                bail!(
+                    "unsupported auth plugin: {} (supported: {})",
+                    plugin,
+                    options.auth_plugins.names().join(", ")
+                );
+            }
+        }
+        let mut conn = Self::connect_any(options, &deadline)?;
+        conn.connect_stage = Some(ConnectStage::SessionSetup);
+        conn.init_session_tracking()?;
+        if conn.handshake_collation_id()?.1 {
+            let (charset, collation) =
+                (conn.options.charset.clone(), conn.options.collation.clone());
+            conn.set_charset(&charset, collation.as_deref())?;
+        }
+        conn.set_net_timeouts()?;
+        conn.connect_stage = None;
+        conn.set_socket_timeout(None)?;
+        Ok(conn)
+    }
+
+    // Tries `auth_plugin` and then each of `auth_plugin_fallbacks` while the server rejects the
+    // authentication.
+    pub(crate) fn connect(options: ConnectionOptions, deadline: &Deadline) -> Result<Self> {
+        let mut fallbacks = options.auth_plugin_fallbacks.clone().into_iter();
+        let mut options = options;
+        loop {
+            let err = match Self::connect_once(options.clone(), deadline) {
+                Ok(conn) => return Ok(conn),
+                Err(err) => err,
+            };
+            let rejected = err
+                .downcast_ref::<ErrPacket>()
+                .is_some_and(|err| AUTH_REJECTED_ERRORS.contains(&err.error_code));
+            let Some(plugin) = fallbacks.next().filter(|_| rejected) else {
+                return Err(err);
+            };
+            debug!("{:#}; retrying with {}", err, plugin);
+            options.auth_plugin = Some(plugin);
+        }
+    }
+
+    fn connect_once(options: ConnectionOptions, deadline: &Deadline) -> Result<Self> {
+        let stream = Self::open_transport(&options, deadline)?;
         let reader = BufReader::new(stream.try_clone()?);
         let writer = BufWriter::new(stream);
         let mut conn = Self {
             options,
             reader,
             writer,
+            packet_writer: PacketWriter::new(),
             sequence: 0,
+            server_version: ServerVersion::parse(""),
+            connection_id: 0,
+            capabilities: 0,
+            mariadb_capabilities: 0,
+            max_allowed_packet: None,
+            sql_mode: None,
+            warning_count: 0,
+            state: ConnectionState::Connecting,
+            state_listener: None,
+            query_rewriter: None,
+            internal: false,
+            changed_system_variables: vec![],
+            last_gtid: None,
+            status_flags: StatusFlags::default(),
+            transaction_state: None,
+            redirect: None,
+            server_stats: None,
+            metadata_generation: 0,
+            progress_report_handler: None,
+            connect_stage: Some(ConnectStage::Handshake),
+            deadline: deadline.clone(),
         };
         conn.handshake()?;
         Ok(conn)
     }
 
-    pub fn query(&mut self, sql: &str) -> Result<String> {
-        debug!("query start");
-        self.sequence = 0;
-        let com_query = ComQuery::new(sql);
-        self.write_packet(&com_query.encode())?;
-        let pkt = self.read_packet()?;
-        if pkt[0] == 0xff {
-            let err = ErrPacket::decode(pkt)?;
-            return Ok(err.human_readable_text());
+    #[cfg(windows)]
+    fn open_transport(options: &ConnectionOptions, deadline: &Deadline) -> Result<Transport> {
+        if let Some(name) = &options.named_pipe {
+            if options.local_address.is_some() || options.local_port.is_some() {
+                bail!("local_address and local_port only apply to TCP, not to named pipes");
+            }
+            return Transport::open_pipe(name);
         }
-        let col_count = pkt[0];
-        let mut cols = vec![];
+        Self::open_tcp(options, deadline)
+    }
+
+    #[cfg(not(windows))]
+    fn open_transport(options: &ConnectionOptions, deadline: &Deadline) -> Result<Transport> {
+        Self::open_tcp(options, deadline)
+    }
 
-        for _ in 0..col_count {
-            cols.push(ColumnDefinition41::decode(self.read_packet()?)?);
+    fn open_tcp(options: &ConnectionOptions, deadline: &Deadline) -> Result<Transport> {
+        let addrs = deadline.resolve(&options.host, options.port)?;
+        let mut retries = options.connect_retries;
+        loop {
+            match deadline.connect(&addrs, options.local_address, options.local_port) {
+                Ok(stream) => return Ok(Transport::Tcp(stream)),
+                // A timed out or cancelled connect has no time left to retry in.
+                Err(err) if retries > 0 && err.downcast_ref::<Error>().is_none() => {
+                    debug!(
+                        "failed to connect to {}:{}: {:#}; retrying in {:?} ({} left)",
+                        options.host, options.port, err, options.connect_retry_delay, retries
+                    );
+                    retries -= 1;
+                    deadline.sleep(ConnectStage::Connect, options.connect_retry_delay)?;
+                }
+                Err(err) => return Err(err),
+            }
         }
-        let mut rows = vec![];
+    }
+
+    // The local end of the TCP connection, e.g. for logging which interface and port it went out
+    // through. `None` for a named pipe.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.reader.get_ref().local_addr()
+    }
+
+    pub fn server_version(&self) -> ServerVersion {
+        self.server_version
+    }
+
+    // The server's id for this session, as in CONNECTION_ID() and SHOW PROCESSLIST.
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    // Whether affected rows count matched rather than changed rows; see
+    // `ConnectionOptions::found_rows`.
+    pub fn found_rows(&self) -> bool {
+        self.capabilities & CLIENT_FOUND_ROWS != 0
+    }
+
+    // Number of warnings reported by the last statement.
+    pub fn warning_count(&self) -> u16 {
+        self.warning_count
+    }
+
+    // Returns the first result. Any further results (multi-statements, CALL) are read and discarded
+    // so the connection is ready for the next command.
+    pub fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        debug!("query start");
+        let mut first = None;
+        self.run_query(sql, |result| {
+            first.get_or_insert(result);
+        })?;
+        debug!("query done");
+        Ok(first.expect("at least one result is read"))
+    }
+
+    // Returns every result of a multi-statement query or a stored procedure call, in the order the
+    // server sent them. The trailing OK of a CALL is included as its own `QueryResult::Ok`.
+    pub fn query_all(&mut self, sql: &str) -> Result<Vec<QueryResult>> {
+        debug!("query_all start");
+        let mut results = vec![];
+        self.run_query(sql, |result| results.push(result))?;
+        debug!("query_all done");
+        Ok(results)
+    }
+
+    // `query` with limits on how much of a result set is kept; see `QueryOptions`.
+    pub fn query_with(&mut self, sql: &str, options: &QueryOptions) -> Result<QueryResult> {
+        debug!("query_with start");
+        let mut first = None;
+        self.run_query_with(sql, options, |result| {
+            first.get_or_insert(result);
+        })?;
+        debug!("query_with done");
+        Ok(first.expect("at least one result is read"))
+    }
+
+    // `query_all` with the limits of `options` applied to each result set.
+    pub fn query_all_with(
+        &mut self,
+        sql: &str,
+        options: &QueryOptions,
+    ) -> Result<Vec<QueryResult>> {
+        debug!("query_all_with start");
+        let mut results = vec![];
+        self.run_query_with(sql, options, |result| results.push(result))?;
+        debug!("query_all_with done");
+        Ok(results)
+    }
+
+    // Runs a statement whose results aren't needed (SET, CREATE TEMPORARY TABLE, ANALYZE, ...).
+    // Every result is read and thrown away; rows are skipped without decoding their cells. The
+    // warning count is kept as for `query`.
+    pub fn query_drop(&mut self, sql: &str) -> Result<()> {
+        debug!("query_drop start");
+        self.reconnect_if_closed()?;
+        let sql = &self.rewrite(sql);
+        let com_query = self.com_query(sql)?;
+        let started = Instant::now();
+        let mut rows = 0;
+        let ran = self.begin_command().and_then(|()| {
+            self.write_packet_with(|w| com_query.write_to(w))?;
+            let read = self.drop_results(&mut rows);
+            self.end_command(read)
+        });
+        self.report_slow_query(sql, started.elapsed(), rows);
+        debug!("query_drop done");
+        ran
+    }
+
+    fn drop_results(&mut self, rows: &mut u64) -> Result<()> {
         loop {
             let pkt = self.read_packet()?;
-            let header = pkt[0];
-            if header == 0xfe {
-                break;
+            let status_flags = match packet_header(&pkt)? {
+                ERR_HEADER => {
+                    bail!(self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?)
+                }
+                OK_HEADER => {
+                    let ok = self.decode_ok(pkt)?;
+                    self.warning_count = ok.warnings;
+                    *rows += ok.affected_rows;
+                    ok.status_flags
+                }
+                _ => {
+                    let (col_count, metadata_follows) = self.decode_column_count(&pkt)?;
+                    if metadata_follows {
+                        for _ in 0..col_count {
+                            let pkt = self.read_packet()?;
+                            self.check_server_error(&pkt)?;
+                        }
+                        self.read_eof()?;
+                    }
+                    loop {
+                        match self.skip_row()? {
+                            Some(terminator) => break terminator.status_flags(),
+                            None => *rows += 1,
+                        }
+                    }
+                }
+            };
+            if !status_flags.more_results_exists() {
+                return Ok(());
             }
-            let row = ResultsetRow::decode(pkt)?;
-            rows.push(row);
         }
-        debug!("query done");
-        Ok(format!("{:?}", rows))
     }
 
+    fn run_query(&mut self, sql: &str, on_result: impl FnMut(QueryResult)) -> Result<()> {
+        self.run_query_with(sql, &QueryOptions::default(), on_result)
+    }
+
+    fn run_query_with(
+        &mut self,
+        sql: &str,
+        options: &QueryOptions,
+        mut on_result: impl FnMut(QueryResult),
+    ) -> Result<()> {
+        self.reconnect_if_closed()?;
+        let sql = &self.rewrite(sql);
+        self.server_stats = None;
+        if !self.collects_session_status() {
+            return self.run_statement(sql, options, on_result);
+        }
+        // The deltas are known only once every result is read.
+        let before = self.session_status_snapshot()?;
+        let mut results = vec![];
+        self.run_statement(sql, options, |result| results.push(result))?;
+        let stats = self.server_stats_since(&before)?;
+        for mut result in results {
+            if let QueryResult::ResultSet(rs) = &mut result {
+                rs.server_stats = Some(stats.clone());
+            }
+            on_result(result);
+        }
+        self.server_stats = Some(stats);
+        Ok(())
+    }
+
+    fn run_statement(
+        &mut self,
+        sql: &str,
+        options: &QueryOptions,
+        mut on_result: impl FnMut(QueryResult),
+    ) -> Result<()> {
+        let com_query = self.com_query(sql)?;
+        let started = Instant::now();
+        let mut rows = 0;
+        self.progress_report_handler = options.on_progress_report.clone();
+        let ran = self.begin_command().and_then(|()| {
+            self.write_packet_with(|w| com_query.write_to(w))?;
+            let read = self.read_results_with(options, |result| {
+                rows += match &result {
+                    QueryResult::ResultSet(rs) => rs.rows.len() as u64,
+                    QueryResult::Ok(ok) => ok.affected_rows,
+                };
+                on_result(result);
+            });
+            self.end_command(read)
+        });
+        self.progress_report_handler = None;
+        self.report_slow_query(sql, started.elapsed(), rows);
+        ran
+    }
+
+    // Streams the rows of the first result set instead of collecting them. For a statement that
+    // doesn't return rows the iterator is simply empty.
+    pub fn query_iter(&mut self, sql: &str) -> Result<ResultSetIter<'_>> {
+        debug!("query_iter start");
+        let sql = &self.rewrite(sql);
+        let com_query = self.com_query(sql)?;
+        self.begin_command()?;
+        self.write_packet_with(|w| com_query.write_to(w))?;
+        let header = match self.read_response_header() {
+            Ok(header) => header,
+            Err(err) => return self.end_command(Err(err)),
+        };
+        match header {
+            ResponseHeader::Ok(ok) => {
+                let more_results = ok.status_flags.more_results_exists();
+                self.warning_count = ok.warnings;
+                let read = if more_results {
+                    self.read_results(|_| {})
+                } else {
+                    Ok(())
+                };
+                self.end_command(read)?;
+                Ok(ResultSetIter::new(self, vec![], Some(Terminator::Ok(ok))))
+            }
+            ResponseHeader::Columns(columns) => {
+                self.set_state(ConnectionState::StreamingResult { rows_read: 0 });
+                Ok(ResultSetIter::new(self, columns, None))
+            }
+        }
+    }
+
+    // COM_RESET_CONNECTION
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_reset_connection.html
+    pub fn reset_connection(&mut self) -> Result<()> {
+        if !self.server_version.supports_reset_connection() {
+            bail!(
+                "COM_RESET_CONNECTION is not supported by server version {:?}",
+                self.server_version
+            );
+        }
+        self.simple_command(&[COM_RESET_CONNECTION])?;
+        // The session variables are back to their global values.
+        self.sql_mode = None;
+        Ok(())
+    }
+
+    // COM_PING
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_ping.html
+    pub fn ping(&mut self) -> Result<()> {
+        self.simple_command(&[COM_PING])?;
+        Ok(())
+    }
+
+    // `ping` that gives up after `timeout` with `Error::Timeout`, so a hung server can't block a health
+    // check. The response may still arrive later, so the connection is broken after a timeout.
+    pub fn ping_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.set_socket_timeout(Some(timeout))?;
+        let pinged = self.ping();
+        self.set_socket_timeout(None)?;
+        pinged.map_err(|err| match is_timeout(&err) {
+            true => Error::Timeout { after: timeout }.into(),
+            false => err,
+        })
+    }
+
+    // Sends a command whose response is a single OK or ERR packet.
+    pub(crate) fn simple_command(&mut self, payload: &[u8]) -> Result<OkPacket> {
+        self.begin_command()?;
+        self.write_packet(payload)?;
+        let response = self.read_packet().and_then(|pkt| {
+            if packet_header(&pkt)? == ERR_HEADER {
+                bail!(self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?);
+            }
+            self.decode_ok(pkt)
+        });
+        self.end_command(response)
+    }
+
+    // Whether the connection must be discarded: it failed at the transport or protocol level, or it
+    // is not `Idle` (e.g. left in the middle of a result set by a leaked `ResultSetIter`).
+    pub fn is_broken(&self) -> bool {
+        self.state != ConnectionState::Idle
+    }
+
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline::new(self)
+    }
+
+    // Reads the results of one command until SERVER_MORE_RESULTS_EXISTS is no longer set. After an
+    // ERR nothing else follows, so returning early on an error doesn't leave packets unread either.
+    pub(crate) fn read_results(&mut self, on_result: impl FnMut(QueryResult)) -> Result<()> {
+        self.read_results_with(&QueryOptions::default(), on_result)
+    }
+
+    fn read_results_with(
+        &mut self,
+        options: &QueryOptions,
+        mut on_result: impl FnMut(QueryResult),
+    ) -> Result<()> {
+        loop {
+            let result = self.read_query_result(options)?;
+            self.warning_count = result.warnings();
+            let more_results = result.status_flags().more_results_exists();
+            on_result(result);
+            if !more_results {
+                return Ok(());
+            }
+        }
+    }
+
+    // Reads one COM_QUERY response. A server error is returned as an `ErrPacket` so callers can tell
+    // it apart from an io or protocol failure (which leaves the connection unusable).
+    fn read_query_result(&mut self, options: &QueryOptions) -> Result<QueryResult> {
+        match self.read_response_header()? {
+            ResponseHeader::Ok(ok) => Ok(QueryResult::Ok(ok)),
+            ResponseHeader::Columns(columns) => {
+                let mut rows = vec![];
+                let mut bytes = 0;
+                let mut truncated = None;
+                let mut progress = ProgressTracker::from_options(options);
+                let terminator = loop {
+                    if truncated.is_some() {
+                        match self.skip_row()? {
+                            Some(terminator) => break terminator,
+                            None => {
+                                if let Some(progress) = &mut progress {
+                                    progress.row(0);
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                    match self.read_row()? {
+                        RowPacket::Row(row) => {
+                            let row_bytes = row.0.iter().flatten().map(|v| v.len() as u64).sum();
+                            if let Some(progress) = &mut progress {
+                                progress.row(row_bytes);
+                            }
+                            truncated = options.exceeded(rows.len() as u64, bytes, row_bytes);
+                            if truncated.is_none() {
+                                bytes += row_bytes;
+                                rows.push(row);
+                            }
+                        }
+                        RowPacket::End(terminator) => break terminator,
+                    }
+                };
+                if let Some(limit) = truncated {
+                    debug!("result set truncated at {} rows ({:?})", rows.len(), limit);
+                }
+                Ok(QueryResult::ResultSet(ResultSet {
+                    columns,
+                    rows,
+                    terminator,
+                    truncated,
+                    server_stats: None,
+                }))
+            }
+        }
+    }
+
+    // Reads the first packet of a response and, for a result set, its column definitions.
+    fn read_response_header(&mut self) -> Result<ResponseHeader> {
+        let pkt = self.read_packet()?;
+        match packet_header(&pkt)? {
+            ERR_HEADER => bail!(self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?),
+            OK_HEADER => Ok(ResponseHeader::Ok(self.decode_ok(pkt)?)),
+            _ => {
+                let (col_count, metadata_follows) = self.decode_column_count(&pkt)?;
+                let mut columns = vec![];
+                // Without metadata (`resultset_metadata = NONE`) the rows still decode; only the
+                // column definitions are missing.
+                for _ in 0..if metadata_follows { col_count } else { 0 } {
+                    let pkt = self.read_packet()?;
+                    self.check_server_error(&pkt)?;
+                    columns.push(self.decode_packet(
+                        PacketKind::ColumnDefinition,
+                        &pkt,
+                        ColumnDefinition41::decode,
+                    )?);
+                }
+                if metadata_follows {
+                    self.read_eof()?;
+                }
+                Ok(ResponseHeader::Columns(columns))
+            }
+        }
+    }
+
+    // The first packet of a result set: the column count, followed by whether the column
+    // definitions are sent when CLIENT_OPTIONAL_RESULTSET_METADATA is negotiated.
+    pub(crate) fn decode_column_count(&mut self, pkt: &[u8]) -> Result<(u64, bool)> {
+        let capabilities = self.capabilities;
+        self.decode_packet(PacketKind::ColumnCount, pkt, |pkt| {
+            let (col_count, consumed) = decode_lenenc_integer(pkt, 0)?;
+            let metadata_follows = capabilities & CLIENT_OPTIONAL_RESULTSET_METADATA == 0
+                || pkt.get(consumed).is_none_or(|&flag| flag != 0);
+            Ok((col_count, metadata_follows))
+        })
+    }
+
+    // Reads a row of a result set, or the OK packet that terminates it (CLIENT_DEPRECATE_EOF).
+    pub(crate) fn read_row(&mut self) -> Result<RowPacket> {
+        let pkt = self.read_packet()?;
+        self.check_server_error(&pkt)?;
+        if packet_header(&pkt)? == EOF_HEADER && pkt.len() < 0xffffff {
+            return Ok(RowPacket::End(self.decode_terminator(pkt)?));
+        }
+        Ok(RowPacket::Row(self.decode_packet(
+            PacketKind::Row,
+            &pkt,
+            ResultsetRow::decode,
+        )?))
+    }
+
+    // Reads a row without decoding it; returns the terminator at the end of the result set.
+    fn skip_row(&mut self) -> Result<Option<Terminator>> {
+        let pkt = self.read_packet()?;
+        self.check_server_error(&pkt)?;
+        if packet_header(&pkt)? == EOF_HEADER && pkt.len() < 0xffffff {
+            return Ok(Some(self.decode_terminator(pkt)?));
+        }
+        Ok(None)
+    }
+
+    // Decodes the packet ending a result set, an OK or (without CLIENT_DEPRECATE_EOF) an EOF packet.
+    pub(crate) fn decode_terminator(&mut self, pkt: Vec<u8>) -> Result<Terminator> {
+        let terminator = if self.capabilities & CLIENT_DEPRECATE_EOF != 0 {
+            Terminator::Ok(self.decode_ok(pkt)?)
+        } else {
+            let eof = self.decode_packet(PacketKind::Eof, &pkt, EofPacket::decode)?;
+            self.status_flags = eof.status_flags;
+            Terminator::Eof {
+                warnings: eof.warnings,
+                status: eof.status_flags,
+            }
+        };
+        self.warning_count = terminator.warnings();
+        Ok(terminator)
+    }
+
+    // Without CLIENT_DEPRECATE_EOF an EOF packet follows the column definitions.
+    pub(crate) fn read_eof(&mut self) -> Result<()> {
+        if self.capabilities & CLIENT_DEPRECATE_EOF != 0 {
+            return Ok(());
+        }
+        let pkt = self.read_packet()?;
+        self.check_server_error(&pkt)?;
+        self.decode_packet(PacketKind::Eof, &pkt, EofPacket::decode)?;
+        Ok(())
+    }
+
+    // The server can abort a response at any packet, e.g. a result set when max_execution_time is
+    // exceeded or a row fails to evaluate, by sending an ERR packet in its place. Nothing follows
+    // the ERR, so the connection is ready for the next command. A row can't be mistaken for one:
+    // 0xff doesn't start a length-encoded value.
+    pub(crate) fn check_server_error(&mut self, pkt: &[u8]) -> Result<()> {
+        if pkt.first() == Some(&ERR_HEADER) {
+            bail!(self.decode_packet(PacketKind::Err, pkt, ErrPacket::decode)?);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn decode_ok(&mut self, pkt: Vec<u8>) -> Result<OkPacket> {
+        let capabilities = self.capabilities;
+        let ok = self.decode_packet(PacketKind::Ok, &pkt, |pkt| {
+            OkPacket::decode(pkt, capabilities)
+        })?;
+        self.track_session_state(&ok)?;
+        Ok(ok)
+    }
+
+    // Every command starts with a fresh sequence, and only when nothing else is in progress; writing
+    // while a result set is still being read would interleave two responses on the wire.
+    pub(crate) fn begin_command(&mut self) -> Result<()> {
+        if self.state == ConnectionState::Broken {
+            bail!(Error::Broken);
+        }
+        if self.state != ConnectionState::Idle {
+            bail!(Error::InvalidState {
+                expected: ConnectionState::Idle,
+                actual: self.state,
+            });
+        }
+        if self.is_peer_closed() {
+            self.set_state(ConnectionState::Broken);
+            return Err(self.read_disconnect());
+        }
+        self.set_state(ConnectionState::QueryInFlight);
+        self.sequence = 0;
+        self.changed_system_variables.clear();
+        Ok(())
+    }
+
+    // Whatever the server sent while the connection was idle, which can only be the reason it closed
+    // the connection: an ERR packet, or nothing at all. The sequence id of such a packet isn't tied to
+    // any command, so it isn't checked.
+    fn read_disconnect(&mut self) -> anyhow::Error {
+        let mut header = [0; 4];
+        if self.fill(&mut header, "header").is_err() {
+            return Error::ServerClosedConnection { error: None }.into();
+        }
+        let mut pkt = vec![0; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+        if let Err(err) = self.fill(&mut pkt, "body") {
+            return err;
+        }
+        match pkt.first() {
+            Some(&ERR_HEADER) => match self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)
+            {
+                Ok(err) => Error::ServerClosedConnection { error: Some(err) }.into(),
+                Err(err) => err,
+            },
+            _ => anyhow::anyhow!(
+                "unexpected packet from the server while the connection was idle: {:02x?}",
+                pkt
+            ),
+        }
+    }
+
+    // Back to `Idle` once the response is read. A server error ends the response too, while any other
+    // failure leaves unread packets behind.
+    pub(crate) fn end_command<T>(&mut self, result: Result<T>) -> Result<T> {
+        match &result {
+            Err(err) if !err.is::<ErrPacket>() => self.set_state(ConnectionState::Broken),
+            _ => self.set_state(ConnectionState::Idle),
+        }
+        result
+    }
+
+    fn authenticator(&self, plugin_name: &str) -> Result<Arc<dyn Authenticator>> {
+        match self.options.auth_plugins.get(plugin_name) {
+            Some(authenticator) => Ok(authenticator),
+            None => bail!(
+                "the server asked for auth plugin {}, which has no authenticator (default plugin of \
+                 server {:?} is {}; registered: {}); implement auth::Authenticator for it and add it \
+                 with ConnectionOptions::with_auth_plugin",
+                plugin_name,
+                self.server_version,
+                self.server_version.default_auth_plugin(),
+                self.options.auth_plugins.names().join(", ")
+            ),
+        }
+    }
+
+    fn auth_context(&self) -> AuthContext<'_> {
+        AuthContext {
+            username: &self.options.username,
+            password: &self.options.password,
+            secure_transport: self.is_secure_transport(),
+            allow_cleartext_on_insecure: self.options.allow_cleartext_on_insecure,
+        }
+    }
+
+    // Whether the transport protects the password on the wire (TLS, a Unix socket or a named pipe).
+    pub fn is_secure_transport(&self) -> bool {
+        self.reader.get_ref().is_secure()
+    }
+
+    // The statement is sent in the session character set; see `encoding::encode_str`.
+    pub(crate) fn com_query(&mut self, sql: &str) -> Result<ComQuery> {
+        self.forget_sql_mode_if_changed_by(sql);
+        let sql = encode_str(sql, &self.options.charset)?;
+        Ok(ComQuery::new(
+            sql,
+            self.capabilities & CLIENT_QUERY_ATTRIBUTES != 0,
+        ))
+    }
+
+    // A server that won't take the connection (host not allowed, too many connections, ...) sends an
+    // ERR packet instead of the handshake, or simply closes the socket.
     fn handshake(&mut self) -> Result<()> {
+        self.handshake_exchange()
+            .map_err(|err| match err.downcast_ref::<Error>() {
+                Some(Error::ServerGone(_) | Error::ServerClosedConnection { .. }) => err.context(
+                    "server closed connection during handshake (check the host allowlist and \
+                     max_connections)",
+                ),
+                _ => err,
+            })
+    }
+
+    fn handshake_exchange(&mut self) -> Result<()> {
         debug!("handshake start");
+        self.set_state(ConnectionState::Connecting);
         self.sequence = 0;
-        let handshake = HandshakeV10::decode(self.read_packet()?)?;
-        let response = HandshakeResponse41::new(
+        self.check_greeting()?;
+        let pkt = self.read_packet()?;
+        if pkt.first() == Some(&ERR_HEADER) {
+            return Err(self.handshake_err(&pkt)?);
+        }
+        let handshake = self.decode_packet(PacketKind::Handshake, &pkt, |pkt| {
+            HandshakeV10::decode(pkt.to_vec())
+        })?;
+        debug!(
+            "server capabilities: {}",
+            CapabilityFlags(handshake.capability_flags())
+        );
+        self.server_version = ServerVersion::parse(handshake.server_version());
+        self.connection_id = handshake.connection_id();
+        let mut client_flag = DEFAULT_CLIENT_FLAG & handshake.capability_flags();
+        if !self.server_version.supports_query_attributes() {
+            client_flag &= !CLIENT_QUERY_ATTRIBUTES;
+        }
+        if !self.options.send_connect_attrs {
+            client_flag &= !CLIENT_CONNECT_ATTRS;
+        }
+        if self.options.found_rows {
+            client_flag |= CLIENT_FOUND_ROWS & handshake.capability_flags();
+        }
+        self.capabilities = client_flag;
+        let mut mariadb_client_flag = DEFAULT_MARIADB_CLIENT_FLAG;
+        if self.options.mariadb_progress_reports {
+            mariadb_client_flag |= MARIADB_CLIENT_PROGRESS;
+        }
+        self.mariadb_capabilities = mariadb_client_flag & handshake.mariadb_capabilities();
+        // Start with the chosen plugin, or else the server's default one when we can; otherwise the
+        // server will ask for another one through an AuthSwitchRequest.
+        let challenge = handshake.auth_plugin_data();
+        let (plugin_name, auth_response) = match self.options.auth_plugin.clone() {
+            Some(plugin_name) => {
+                let auth_response = self
+                    .authenticator(&plugin_name)?
+                    .auth_response(&self.auth_context(), &challenge)?;
+                (plugin_name, auth_response)
+            }
+            None => match self
+                .authenticator(handshake.auth_plugin_name())
+                .and_then(|auth| auth.auth_response(&self.auth_context(), &challenge))
+            {
+                Ok(auth_response) => (String::from(handshake.auth_plugin_name()), auth_response),
+                Err(err) => {
+                    debug!("{}; starting with {}", err, MYSQL_NATIVE_PASSWORD);
+                    let auth_response = self
+                        .authenticator(MYSQL_NATIVE_PASSWORD)?
+                        .auth_response(&self.auth_context(), &challenge)?;
+                    (String::from(MYSQL_NATIVE_PASSWORD), auth_response)
+                }
+            },
+        };
+        let mut response = HandshakeResponse41::new(
+            client_flag,
+            self.handshake_collation_id()?.0,
             &self.options.username,
-            &self.options.password,
+            auth_response,
             &self.options.database,
-            handshake.auth_plugin_data(),
+            &plugin_name,
         );
-        self.write_packet(&response.encode())?;
-        let pkt = self.read_packet()?;
-        if pkt[0] != 0x00 {
-            bail!("not ok packet");
-        }
+        response.set_mariadb_capabilities(self.mariadb_capabilities);
+        self.write_packet_with(|w| response.write_to(w))?;
+        self.set_state(ConnectionState::Authenticating);
+        let ok = self.authenticate(AuthExchange {
+            plugin_name,
+            challenge,
+            switches: 0,
+            factor: 1,
+        })?;
+        self.redirect = redirect_target(&ok.info, &self.changed_system_variables);
+        self.set_state(ConnectionState::Idle);
         debug!("handshake done");
 
         Ok(())
     }
 
-    fn read_packet(&mut self) -> Result<Vec<u8>> {
-        let mut buf = [0; 4];
-        self.reader.read_exact(&mut buf)?;
-        let packet_len = u32::from_le_bytes([buf[0], buf[1], buf[2], 0]);
-        let packet_seq = buf[3];
-        if packet_seq != self.sequence {
-            bail!("invalid sequence: {}", packet_seq);
-        }
-        self.sequence += 1;
-        let mut buf = vec![0; packet_len as usize];
-        self.reader.read_exact(&mut buf)?;
-        debug!("read_packet: {:02?}", &buf);
+    // Answers the server until it ends the authentication exchange with an OK or ERR. It may switch
+    // plugins several times, e.g. to caching_sha2_password and then on to its full authentication
+    // through AuthMoreData, so every packet is answered with whichever plugin is current. For an
+    // account with several authentication factors the server asks for each next one with
+    // AuthNextFactor, answered like an auth switch; a factor needing another credential than the
+    // password takes an authenticator registered with `with_auth_plugin`.
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase.html
+    fn authenticate(&mut self, mut exchange: AuthExchange) -> Result<OkPacket> {
+        loop {
+            let pkt = self.read_packet()?;
+            match packet_header(&pkt)? {
+                OK_HEADER => return self.decode_ok(pkt),
+                ERR_HEADER => return Err(self.handshake_err(&pkt)?),
+                AUTH_SWITCH_REQUEST_HEADER => {
+                    let request =
+                        self.decode_packet(PacketKind::AuthSwitchRequest, &pkt, |pkt| {
+                            AuthSwitchRequest::decode(pkt.to_vec())
+                        })?;
+                    exchange.switches += 1;
+                    if exchange.switches > MAX_AUTH_SWITCHES {
+                        bail!(
+                            "server requested more than {} auth switches (last to {})",
+                            MAX_AUTH_SWITCHES,
+                            request.plugin_name
+                        );
+                    }
+                    debug!(
+                        "auth switch from {} to {}",
+                        exchange.plugin_name, request.plugin_name
+                    );
+                    exchange.plugin_name = request.plugin_name;
+                    exchange.challenge = request.plugin_provided_data;
+                    let auth_response = self
+                        .authenticator(&exchange.plugin_name)?
+                        .auth_response(&self.auth_context(), &exchange.challenge)?;
+                    self.write_packet(&auth_response)?;
+                }
+                AUTH_NEXT_FACTOR_HEADER => {
+                    let request = self.decode_packet(PacketKind::AuthNextFactor, &pkt, |pkt| {
+                        AuthNextFactor::decode(pkt)
+                    })?;
+                    exchange.factor += 1;
+                    if exchange.factor > MAX_AUTH_FACTORS {
+                        bail!(
+                            "server requested more than {} authentication factors (last with {})",
+                            MAX_AUTH_FACTORS,
+                            request.plugin_name
+                        );
+                    }
+                    debug!(
+                        "authentication factor {} with {}",
+                        exchange.factor, request.plugin_name
+                    );
+                    exchange.plugin_name = request.plugin_name;
+                    exchange.challenge = request.plugin_provided_data;
+                    let auth_response = self
+                        .authenticator(&exchange.plugin_name)?
+                        .auth_response(&self.auth_context(), &exchange.challenge)?;
+                    self.write_packet(&auth_response)?;
+                }
+                // Protocol::AuthMoreData
+                AUTH_MORE_DATA_HEADER => {
+                    let reply = self.authenticator(&exchange.plugin_name)?.more_data(
+                        &self.auth_context(),
+                        &exchange.challenge,
+                        &pkt[1..],
+                    )?;
+                    if let Some(reply) = reply {
+                        self.write_packet(&reply)?;
+                    }
+                }
+                header => bail!(
+                    "unexpected packet (header 0x{:02x}) during {} authentication",
+                    header,
+                    exchange.plugin_name
+                ),
+            }
+        }
+    }
+
+    // Looks at the first bytes from the server before parsing them as a packet, to explain the usual
+    // ways of pointing the client at something that isn't a MySQL server (or not a plaintext one).
+    fn check_greeting(&mut self) -> Result<()> {
+        let peeked = self
+            .apply_deadline()
+            .and_then(|()| Ok(self.reader.fill_buf()?.to_vec()))
+            .map_err(|err| self.interrupted(err));
+        let head = match peeked {
+            Ok(head) => head,
+            Err(err)
+                if is_timeout(&err)
+                    || matches!(err.downcast_ref(), Some(Error::ConnectTimedOut { .. })) =>
+            {
+                return Err(err.context(
+                    "the server sent nothing after the connection was established; check that the \
+                     port belongs to a MySQL server",
+                ));
+            }
+            Err(err) => return self.mark_broken_on_err(Err(err)),
+        };
+        match head.as_slice() {
+            // A TLS record (alert or handshake): the protocol version follows, always 3.x.
+            [0x15 | 0x16, 0x03, ..] => bail!(
+                "server appears to require TLS or is not speaking MySQL protocol; try enabling tls"
+            ),
+            head if head.starts_with(b"HTTP/") => {
+                bail!("the port looks like an HTTP server, not MySQL; check the host and port")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // ER_SECURE_TRANSPORT_REQUIRED, sent when require_secure_transport=ON refuses a plaintext
+    // connection, gets a hint on what to do about it.
+    // https://dev.mysql.com/doc/refman/8.4/en/using-encrypted-connections.html#mandatory-encrypted-connections
+    fn handshake_err(&self, pkt: &[u8]) -> Result<anyhow::Error> {
+        let err = self.decode_packet(PacketKind::Err, pkt, ErrPacket::decode)?;
+        if err.error_code != ER_SECURE_TRANSPORT_REQUIRED {
+            return Ok(err.into());
+        }
+        Ok(anyhow::Error::new(err).context(
+            "the server requires TLS or a Unix socket (require_secure_transport=ON), but this \
+             connection is plain TCP; connect with TLS enabled, or set require_secure_transport=OFF \
+             on the server",
+        ))
+    }
+
+    // Any failure below the packet layer leaves the stream in an unknown position, so the connection
+    // is marked broken and can't be used anymore.
+    // MariaDB progress reports are passed on and skipped; see `take_progress_report`.
+    pub(crate) fn read_packet(&mut self) -> Result<Vec<u8>> {
+        loop {
+            let pkt = self
+                .apply_deadline()
+                .and_then(|()| self.read_packet_inner())
+                .map_err(|err| self.interrupted(err));
+            let pkt = self.mark_broken_on_err(pkt)?;
+            if !self.take_progress_report(&pkt)? {
+                return Ok(pkt);
+            }
+        }
+    }
+
+    // The socket closing under us means the server (or something in between) dropped the connection,
+    // e.g. after wait_timeout or a KILL; that is reported as `Error::ServerGone`.
+    fn mark_broken_on_err<T>(&mut self, result: Result<T>) -> Result<T> {
+        let Err(err) = result else {
+            return result;
+        };
+        self.set_state(ConnectionState::Broken);
+        Err(match is_server_gone(&err) {
+            true => Error::ServerGone(err.to_string()).into(),
+            false => err,
+        })
+    }
+
+    // Whether the server has closed the connection (or sent something unsolicited, such as the ERR
+    // packet MySQL 8.0.24+ sends before closing an idle session), checked without blocking.
+    pub(crate) fn is_peer_closed(&mut self) -> bool {
+        if !self.reader.buffer().is_empty() {
+            return true;
+        }
+        self.reader.get_ref().is_readable()
+    }
+
+    // While connecting, limits the next socket operation to the time left until the deadline.
+    fn apply_deadline(&self) -> Result<()> {
+        let Some(stage) = self.connect_stage else {
+            return Ok(());
+        };
+        let timeout = self.deadline.remaining(stage)?;
+        self.set_socket_timeout(timeout)
+    }
+
+    fn set_socket_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.reader.get_ref().set_timeout(timeout)?;
+        Ok(())
+    }
+
+    fn interrupted(&self, err: anyhow::Error) -> anyhow::Error {
+        match self.connect_stage {
+            Some(stage) => self.deadline.interrupted(stage, err),
+            None => err,
+        }
+    }
+
+    // A payload of 0xffffff bytes or more is split into frames of at most 0xffffff bytes; a frame of
+    // exactly that size means another one follows (possibly empty).
+    fn read_packet_inner(&mut self) -> Result<Vec<u8>> {
+        self.check_not_closed()?;
+        let mut buf = vec![];
+        loop {
+            let frame_len = self.read_frame_header()?;
+            let start = buf.len();
+            buf.resize(start + frame_len, 0);
+            self.fill(&mut buf[start..], "body")?;
+            if frame_len < MAX_FRAME_LEN {
+                break;
+            }
+        }
+        debug!("read_packet ({}): {:02?}", describe_response(&buf), &buf);
         Ok(buf)
     }
 
-    fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
-        let packet_len = payload.len();
-        let packet_seq = self.sequence;
-        self.sequence += 1;
+    // The stream ending where a packet would start is the server closing the connection, as opposed
+    // to one ending inside a packet, which loses part of a response.
+    fn check_not_closed(&mut self) -> Result<()> {
+        if self.reader.fill_buf()?.is_empty() {
+            bail!(Error::ServerClosedConnection { error: None });
+        }
+        Ok(())
+    }
+
+    // Reads the 4-byte header of the next frame and returns its payload length.
+    fn read_frame_header(&mut self) -> Result<usize> {
+        let mut buf = [0; 4];
+        self.fill(&mut buf, "header")?;
+        let frame_len = u32::from_le_bytes([buf[0], buf[1], buf[2], 0]);
+        let frame_seq = buf[3];
+        if frame_seq != self.sequence {
+            bail!("invalid sequence: {}", frame_seq);
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(frame_len as usize)
+    }
+
+    // `read_exact`, but saying how much of which part of the packet arrived before the stream ended.
+    fn fill(&mut self, buf: &mut [u8], part: &str) -> Result<()> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) => bail!(Error::ServerGone(format!(
+                    "connection closed after {} of {} bytes of a packet {}",
+                    read,
+                    buf.len(),
+                    part
+                ))),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    // Reads the next packet incrementally instead of collecting it, following it across frames.
+    pub(crate) fn payload_reader(&mut self) -> Result<PayloadReader<'_>> {
+        self.check_not_closed()?;
+        let frame_len = self.read_frame_header()?;
+        Ok(PayloadReader {
+            conn: self,
+            remaining: frame_len,
+            more_frames: frame_len == MAX_FRAME_LEN,
+        })
+    }
+
+    // Encodes a packet into the connection's reusable buffer and sends it.
+    pub(crate) fn write_packet_with(
+        &mut self,
+        write: impl FnOnce(&mut PacketWriter),
+    ) -> Result<()> {
+        self.buffer_packet_with(write)?;
+        self.flush()
+    }
+
+    pub(crate) fn buffer_packet_with(
+        &mut self,
+        write: impl FnOnce(&mut PacketWriter),
+    ) -> Result<()> {
+        let mut packet_writer = std::mem::take(&mut self.packet_writer);
+        packet_writer.clear();
+        write(&mut packet_writer);
+        let buffered = self.buffer_packet(packet_writer.as_bytes());
+        packet_writer.release_if_larger_than(MAX_RETAINED_PACKET_CAPACITY);
+        self.packet_writer = packet_writer;
+        buffered
+    }
+
+    pub(crate) fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
+        self.buffer_packet(payload)?;
+        self.flush()
+    }
+
+    // Queues a packet in the write buffer without flushing, so several commands can go out in one write.
+    pub(crate) fn buffer_packet(&mut self, payload: &[u8]) -> Result<()> {
+        let written = self
+            .apply_deadline()
+            .and_then(|()| self.buffer_packet_inner(payload))
+            .map_err(|err| self.interrupted(err));
+        self.mark_broken_on_err(written)
+    }
+
+    // A payload of 0xffffff bytes or more goes out in frames of that size, ended by a shorter
+    // (possibly empty) one.
+    fn buffer_packet_inner(&mut self, payload: &[u8]) -> Result<()> {
+        let mut chunks = payload.chunks(MAX_FRAME_LEN);
+        loop {
+            let chunk = chunks.next().unwrap_or_default();
+            self.buffer_frame_inner(chunk)?;
+            if chunk.len() < MAX_FRAME_LEN {
+                return Ok(());
+            }
+        }
+    }
+
+    // Queues a single frame of a packet being written piece by piece; a frame shorter than
+    // 0xffffff bytes ends the packet.
+    pub(crate) fn buffer_frame(&mut self, chunk: &[u8]) -> Result<()> {
+        let written = self
+            .apply_deadline()
+            .and_then(|()| self.buffer_frame_inner(chunk))
+            .map_err(|err| self.interrupted(err));
+        self.mark_broken_on_err(written)
+    }
+
+    fn buffer_frame_inner(&mut self, chunk: &[u8]) -> Result<()> {
+        let frame_len = chunk.len();
+        let frame_seq = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
         let mut header = [0; 4];
-        header[0] = packet_len as u8;
-        header[1] = (packet_len >> 8) as u8;
-        header[2] = (packet_len >> 16) as u8;
-        header[3] = packet_seq;
-        let buf = [header.to_vec(), payload.to_vec()].concat();
-        debug!("write_packet: {:02?}", &buf);
-        self.writer.write_all(&buf)?;
-        self.writer.flush()?;
+        header[0] = frame_len as u8;
+        header[1] = (frame_len >> 8) as u8;
+        header[2] = (frame_len >> 16) as u8;
+        header[3] = frame_seq;
+        // The first frame of a command starts with its command byte.
+        match chunk.first().and_then(|&b| CommandByte::from_u8(b)) {
+            Some(cmd) if frame_seq == 0 => {
+                debug!("write_packet ({}): {:02?} {:02?}", cmd, &header, chunk)
+            }
+            _ => debug!("write_packet: {:02?} {:02?}", &header, chunk),
+        }
+        self.writer.write_all(&header)?;
+        self.writer.write_all(chunk)?;
         Ok(())
     }
+
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        let flushed = self
+            .apply_deadline()
+            .and_then(|()| Ok(self.writer.flush()?))
+            .map_err(|err| self.interrupted(err));
+        self.mark_broken_on_err(flushed)
+    }
+
+    pub(crate) fn sequence(&self) -> u8 {
+        self.sequence
+    }
+
+    pub(crate) fn set_sequence(&mut self, sequence: u8) {
+        self.sequence = sequence;
+    }
+}
+
+// A socket read or write that hit its timeout; the error kind depends on the platform.
+fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        )
+    })
+}
+
+fn is_server_gone(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::UnexpectedEof
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+        )
+    })
+}
+
+// The payload of one packet, read straight from the socket as the caller consumes it.
+pub(crate) struct PayloadReader<'a> {
+    conn: &'a mut Connection,
+    remaining: usize,
+    more_frames: bool,
+}
+
+impl PayloadReader<'_> {
+    // Whether the payload is larger than a single frame.
+    pub(crate) fn is_multi_frame(&self) -> bool {
+        self.more_frames
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.remaining == 0 && !self.more_frames
+    }
+}
+
+impl Read for PayloadReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            if !self.more_frames {
+                return Ok(0);
+            }
+            let frame_len = self.conn.read_frame_header().map_err(io::Error::other)?;
+            self.remaining = frame_len;
+            self.more_frames = frame_len == MAX_FRAME_LEN;
+        }
+        let len = buf.len().min(self.remaining);
+        let read = self.conn.reader.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "connection closed with {} bytes of a packet body left",
+                    self.remaining
+                ),
+            ));
+        }
+        self.remaining -= read;
+        Ok(read)
+    }
 }
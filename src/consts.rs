@@ -0,0 +1,219 @@
+use std::fmt;
+
+// The numbers of the client/server protocol in one place, for code reading or writing packets
+// itself (see `raw` and `packets`): command bytes, packet headers, length-encoded integer markers,
+// column types, capability and status flags. `CommandByte` and `ColumnType` name them in logs.
+
+pub use crate::{
+    command::{
+        MYSQL_TYPE_BIT, MYSQL_TYPE_BLOB, MYSQL_TYPE_DATE, MYSQL_TYPE_DATETIME,
+        MYSQL_TYPE_DATETIME2, MYSQL_TYPE_DECIMAL, MYSQL_TYPE_DOUBLE, MYSQL_TYPE_ENUM,
+        MYSQL_TYPE_FLOAT, MYSQL_TYPE_GEOMETRY, MYSQL_TYPE_INT24, MYSQL_TYPE_JSON, MYSQL_TYPE_LONG,
+        MYSQL_TYPE_LONG_BLOB, MYSQL_TYPE_LONGLONG, MYSQL_TYPE_MEDIUM_BLOB, MYSQL_TYPE_NEWDATE,
+        MYSQL_TYPE_NEWDECIMAL, MYSQL_TYPE_NULL, MYSQL_TYPE_SET, MYSQL_TYPE_SHORT,
+        MYSQL_TYPE_STRING, MYSQL_TYPE_TIME, MYSQL_TYPE_TIME2, MYSQL_TYPE_TIMESTAMP,
+        MYSQL_TYPE_TIMESTAMP2, MYSQL_TYPE_TINY, MYSQL_TYPE_TINY_BLOB, MYSQL_TYPE_VAR_STRING,
+        MYSQL_TYPE_VARCHAR, MYSQL_TYPE_VECTOR, MYSQL_TYPE_YEAR, StatusFlags,
+    },
+    handshake::{
+        CLIENT_CAN_HANDLE_EXPIRED_PASSWORDS, CLIENT_CAPABILITY_EXTENSION, CLIENT_COMPRESS,
+        CLIENT_CONNECT_ATTRS, CLIENT_CONNECT_WITH_DB, CLIENT_DEPRECATE_EOF, CLIENT_FOUND_ROWS,
+        CLIENT_IGNORE_SIGPIPE, CLIENT_IGNORE_SPACE, CLIENT_INTERACTIVE, CLIENT_LOCAL_FILES,
+        CLIENT_LONG_FLAG, CLIENT_LONG_PASSWORD, CLIENT_MULTI_RESULTS, CLIENT_MULTI_STATEMENTS,
+        CLIENT_NO_SCHEMA, CLIENT_ODBC, CLIENT_OPTIONAL_RESULTSET_METADATA, CLIENT_PLUGIN_AUTH,
+        CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA, CLIENT_PROTOCOL_41, CLIENT_PS_MULTI_RESULTS,
+        CLIENT_QUERY_ATTRIBUTES, CLIENT_REMEMBER_OPTIONS, CLIENT_RESERVED,
+        CLIENT_SECURE_CONNECTION, CLIENT_SESSION_TRACK, CLIENT_SSL, CLIENT_SSL_VERIFY_SERVER_CERT,
+        CLIENT_TRANSACTIONS, CLIENT_ZSTD_COMPRESSION_ALGORITHM, CapabilityFlags,
+        DEFAULT_CLIENT_FLAG, DEFAULT_MARIADB_CLIENT_FLAG, MARIADB_CLIENT_CACHE_METADATA,
+        MARIADB_CLIENT_COM_MULTI, MARIADB_CLIENT_EXTENDED_METADATA, MARIADB_CLIENT_PROGRESS,
+        MARIADB_CLIENT_STMT_BULK_OPERATIONS, MULTI_FACTOR_AUTHENTICATION,
+    },
+};
+
+// Text Protocol and Prepared Statements commands
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_command_phase.html
+pub const COM_SLEEP: u8 = 0x00;
+pub const COM_QUIT: u8 = 0x01;
+pub const COM_INIT_DB: u8 = 0x02;
+pub const COM_QUERY: u8 = 0x03;
+pub const COM_FIELD_LIST: u8 = 0x04;
+pub const COM_CREATE_DB: u8 = 0x05;
+pub const COM_DROP_DB: u8 = 0x06;
+pub const COM_REFRESH: u8 = 0x07;
+pub const COM_SHUTDOWN: u8 = 0x08;
+pub const COM_STATISTICS: u8 = 0x09;
+pub const COM_PROCESS_INFO: u8 = 0x0a;
+pub const COM_CONNECT: u8 = 0x0b;
+pub const COM_PROCESS_KILL: u8 = 0x0c;
+pub const COM_DEBUG: u8 = 0x0d;
+pub const COM_PING: u8 = 0x0e;
+pub const COM_TIME: u8 = 0x0f;
+pub const COM_DELAYED_INSERT: u8 = 0x10;
+pub const COM_CHANGE_USER: u8 = 0x11;
+pub const COM_BINLOG_DUMP: u8 = 0x12;
+pub const COM_TABLE_DUMP: u8 = 0x13;
+pub const COM_CONNECT_OUT: u8 = 0x14;
+pub const COM_REGISTER_SLAVE: u8 = 0x15;
+pub const COM_STMT_PREPARE: u8 = 0x16;
+pub const COM_STMT_EXECUTE: u8 = 0x17;
+pub const COM_STMT_SEND_LONG_DATA: u8 = 0x18;
+pub const COM_STMT_CLOSE: u8 = 0x19;
+pub const COM_STMT_RESET: u8 = 0x1a;
+pub const COM_SET_OPTION: u8 = 0x1b;
+pub const COM_STMT_FETCH: u8 = 0x1c;
+pub const COM_DAEMON: u8 = 0x1d;
+pub const COM_BINLOG_DUMP_GTID: u8 = 0x1e;
+pub const COM_RESET_CONNECTION: u8 = 0x1f;
+pub const COM_CLONE: u8 = 0x20;
+
+// The first byte of a response packet. 0xfe starts an EOF packet only when the packet is shorter
+// than 9 bytes (with CLIENT_DEPRECATE_EOF, an OK packet in its place shorter than 0xffffff bytes);
+// otherwise it is a length-encoded integer starting a row.
+pub const OK_HEADER: u8 = 0x00;
+pub const EOF_HEADER: u8 = 0xfe;
+pub const ERR_HEADER: u8 = 0xff;
+pub const LOCAL_INFILE_HEADER: u8 = 0xfb;
+// During authentication.
+pub const AUTH_MORE_DATA_HEADER: u8 = 0x01;
+pub const AUTH_SWITCH_REQUEST_HEADER: u8 = 0xfe;
+pub const AUTH_NEXT_FACTOR_HEADER: u8 = 0x02;
+
+// Protocol::LengthEncodedInteger: values below 0xfb are a single byte; otherwise the first byte
+// says how many follow. 0xfb stands for NULL in a text row.
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_dt_integers.html#sect_protocol_basic_dt_int_le
+pub const LENENC_NULL: u8 = 0xfb;
+pub const LENENC_U16: u8 = 0xfc;
+pub const LENENC_U24: u8 = 0xfd;
+pub const LENENC_U64: u8 = 0xfe;
+
+// An enum over a set of the byte constants above, named after them: `from_u8`, `name` and Display
+// give the constant's name, e.g. for logging `COM_QUERY` rather than 0x03.
+macro_rules! byte_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:ident,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u8)]
+        pub enum $name {
+            $($variant = $value,)*
+        }
+
+        impl $name {
+            pub fn from_u8(val: u8) -> Option<Self> {
+                match val {
+                    $($value => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($value),)*
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(val: $name) -> u8 {
+                val as u8
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+byte_enum! {
+    // The first byte of a command packet.
+    CommandByte {
+        Sleep = COM_SLEEP,
+        Quit = COM_QUIT,
+        InitDb = COM_INIT_DB,
+        Query = COM_QUERY,
+        FieldList = COM_FIELD_LIST,
+        CreateDb = COM_CREATE_DB,
+        DropDb = COM_DROP_DB,
+        Refresh = COM_REFRESH,
+        Shutdown = COM_SHUTDOWN,
+        Statistics = COM_STATISTICS,
+        ProcessInfo = COM_PROCESS_INFO,
+        Connect = COM_CONNECT,
+        ProcessKill = COM_PROCESS_KILL,
+        Debug = COM_DEBUG,
+        Ping = COM_PING,
+        Time = COM_TIME,
+        DelayedInsert = COM_DELAYED_INSERT,
+        ChangeUser = COM_CHANGE_USER,
+        BinlogDump = COM_BINLOG_DUMP,
+        TableDump = COM_TABLE_DUMP,
+        ConnectOut = COM_CONNECT_OUT,
+        RegisterSlave = COM_REGISTER_SLAVE,
+        StmtPrepare = COM_STMT_PREPARE,
+        StmtExecute = COM_STMT_EXECUTE,
+        StmtSendLongData = COM_STMT_SEND_LONG_DATA,
+        StmtClose = COM_STMT_CLOSE,
+        StmtReset = COM_STMT_RESET,
+        SetOption = COM_SET_OPTION,
+        StmtFetch = COM_STMT_FETCH,
+        Daemon = COM_DAEMON,
+        BinlogDumpGtid = COM_BINLOG_DUMP_GTID,
+        ResetConnection = COM_RESET_CONNECTION,
+        Clone = COM_CLONE,
+    }
+}
+
+byte_enum! {
+    // The type of a column (`ColumnDefinition41::type_`) or of a statement parameter.
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/field__types_8h.html
+    ColumnType {
+        Decimal = MYSQL_TYPE_DECIMAL,
+        Tiny = MYSQL_TYPE_TINY,
+        Short = MYSQL_TYPE_SHORT,
+        Long = MYSQL_TYPE_LONG,
+        Float = MYSQL_TYPE_FLOAT,
+        Double = MYSQL_TYPE_DOUBLE,
+        Null = MYSQL_TYPE_NULL,
+        Timestamp = MYSQL_TYPE_TIMESTAMP,
+        LongLong = MYSQL_TYPE_LONGLONG,
+        Int24 = MYSQL_TYPE_INT24,
+        Date = MYSQL_TYPE_DATE,
+        Time = MYSQL_TYPE_TIME,
+        DateTime = MYSQL_TYPE_DATETIME,
+        Year = MYSQL_TYPE_YEAR,
+        NewDate = MYSQL_TYPE_NEWDATE,
+        VarChar = MYSQL_TYPE_VARCHAR,
+        Bit = MYSQL_TYPE_BIT,
+        Timestamp2 = MYSQL_TYPE_TIMESTAMP2,
+        DateTime2 = MYSQL_TYPE_DATETIME2,
+        Time2 = MYSQL_TYPE_TIME2,
+        Vector = MYSQL_TYPE_VECTOR,
+        Json = MYSQL_TYPE_JSON,
+        NewDecimal = MYSQL_TYPE_NEWDECIMAL,
+        Enum = MYSQL_TYPE_ENUM,
+        Set = MYSQL_TYPE_SET,
+        TinyBlob = MYSQL_TYPE_TINY_BLOB,
+        MediumBlob = MYSQL_TYPE_MEDIUM_BLOB,
+        LongBlob = MYSQL_TYPE_LONG_BLOB,
+        Blob = MYSQL_TYPE_BLOB,
+        VarString = MYSQL_TYPE_VAR_STRING,
+        String = MYSQL_TYPE_STRING,
+        Geometry = MYSQL_TYPE_GEOMETRY,
+    }
+}
+
+// What a response packet most likely is, from its first byte and length alone, for logs. A packet
+// starting with 0x00 may also be a row of the binary protocol, and one starting with 0xfb a NULL
+// cell of a text row.
+pub fn describe_response(pkt: &[u8]) -> &'static str {
+    match pkt.first() {
+        Some(&OK_HEADER) => "OK or binary row",
+        Some(&ERR_HEADER) => "ERR",
+        Some(&EOF_HEADER) if pkt.len() < 9 => "EOF",
+        Some(&LOCAL_INFILE_HEADER) => "LOCAL INFILE request or row",
+        _ => "data",
+    }
+}
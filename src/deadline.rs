@@ -0,0 +1,226 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::error::Error;
+
+// A flag another thread (e.g. a GUI's cancel button) sets to abort an in-progress connect. It is
+// checked between the steps of a connect, so an abort takes effect at the next step boundary; a step
+// that is already blocking finishes or times out first.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// The part of a connect that was running when it was interrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectStage {
+    Resolve,
+    Connect,
+    Handshake,
+    SessionSetup,
+}
+
+// Deadline and cancellation of one connect, threaded through every stage of it. The default has
+// neither, and connecting blocks for as long as the OS lets it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Deadline {
+    at: Option<Instant>,
+    cancel: Option<CancellationToken>,
+}
+
+impl Deadline {
+    pub(crate) fn new(at: Instant, cancel: CancellationToken) -> Self {
+        Self {
+            at: Some(at),
+            cancel: Some(cancel),
+        }
+    }
+
+    // This deadline, moved up to `at` when that is earlier.
+    pub(crate) fn within(self, at: Instant) -> Self {
+        Self {
+            at: Some(self.at.map_or(at, |own| own.min(at))),
+            cancel: self.cancel,
+        }
+    }
+
+    // The time left for the next step of `stage`, or an error when the connect was cancelled or the
+    // deadline has passed. `None` means no limit.
+    pub(crate) fn remaining(&self, stage: ConnectStage) -> Result<Option<Duration>> {
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            bail!(Error::ConnectCancelled { stage });
+        }
+        match self.at {
+            None => Ok(None),
+            Some(at) => match at.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+                _ => bail!(Error::ConnectTimedOut { stage }),
+            },
+        }
+    }
+
+    // Replaces an io error caused by a socket timeout with the error saying which stage ran out of
+    // time (or was cancelled meanwhile).
+    pub(crate) fn interrupted(&self, stage: ConnectStage, err: anyhow::Error) -> anyhow::Error {
+        match self.remaining(stage) {
+            Err(interrupted) => interrupted,
+            Ok(_) => err,
+        }
+    }
+
+    // Waits for `duration`, or fails as `remaining` does once the deadline passes or the connect is
+    // cancelled meanwhile.
+    pub(crate) fn sleep(&self, stage: ConnectStage, duration: Duration) -> Result<()> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+        let until = Instant::now() + duration;
+        while let Some(left) = until.checked_duration_since(Instant::now()) {
+            if left.is_zero() {
+                break;
+            }
+            let wait = match self.remaining(stage)? {
+                Some(remaining) => left.min(remaining).min(POLL_INTERVAL),
+                None if self.cancel.is_some() => left.min(POLL_INTERVAL),
+                None => left,
+            };
+            thread::sleep(wait);
+        }
+        self.remaining(stage)?;
+        Ok(())
+    }
+
+    // std has no resolver with a timeout, so with a deadline the lookup runs on its own thread and is
+    // abandoned (left to finish in the background) when the deadline passes or the connect is
+    // cancelled.
+    pub(crate) fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+        let stage = ConnectStage::Resolve;
+        if self.at.is_none() && self.cancel.is_none() {
+            return Ok((host, port).to_socket_addrs()?.collect());
+        }
+        self.remaining(stage)?;
+        let (tx, rx) = mpsc::channel();
+        let target = (String::from(host), port);
+        thread::spawn(move || {
+            let addrs = target
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            let _ = tx.send(addrs);
+        });
+        loop {
+            let wait = self
+                .remaining(stage)?
+                .map_or(POLL_INTERVAL, |remaining| remaining.min(POLL_INTERVAL));
+            match rx.recv_timeout(wait) {
+                Ok(addrs) => return Ok(addrs?),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => bail!("resolver thread exited"),
+            }
+        }
+    }
+
+    // Tries each resolved address in turn within the time left, from `local_ip` and `local_port`
+    // when given (only the addresses of `local_ip`'s family are tried then).
+    pub(crate) fn connect(
+        &self,
+        addrs: &[SocketAddr],
+        local_ip: Option<IpAddr>,
+        local_port: Option<u16>,
+    ) -> Result<TcpStream> {
+        let stage = ConnectStage::Connect;
+        let mut last_err = None;
+        for addr in addrs {
+            if local_ip.is_some_and(|local_ip| local_ip.is_ipv4() != addr.is_ipv4()) {
+                continue;
+            }
+            let local = match (local_ip, local_port) {
+                (None, None) => None,
+                (local_ip, local_port) => Some(SocketAddr::new(
+                    local_ip.unwrap_or_else(|| unspecified(addr)),
+                    local_port.unwrap_or(0),
+                )),
+            };
+            let remaining = self.remaining(stage)?;
+            let connected = match local {
+                Some(local) => connect_from(local, addr, remaining)?,
+                None => match remaining {
+                    Some(remaining) => TcpStream::connect_timeout(addr, remaining),
+                    None => TcpStream::connect(addr),
+                },
+            };
+            match connected {
+                Ok(stream) => return Ok(stream),
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                    last_err = Some(self.interrupted(stage, err.into()));
+                }
+                Err(err) => last_err = Some(err.into()),
+            }
+        }
+        if let (None, Some(local_ip)) = (&last_err, local_ip) {
+            bail!(
+                "none of {:?} is of the same address family as the local address {}",
+                addrs,
+                local_ip
+            );
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("{:?} resolved to no addresses", addrs)))
+    }
+}
+
+// The wildcard address of `addr`'s family, for binding only a local port.
+fn unspecified(addr: &SocketAddr) -> IpAddr {
+    match addr {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    }
+}
+
+// Binds the socket to `local` before connecting, for servers allowing only certain source addresses.
+// A failed bind is returned as an error, a failed connect as the inner io result.
+fn connect_from(
+    local: SocketAddr,
+    addr: &SocketAddr,
+    timeout: Option<Duration>,
+) -> Result<io::Result<TcpStream>> {
+    let socket = Socket::new(
+        Domain::for_address(*addr),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.bind(&local.into()).with_context(|| {
+        format!(
+            "failed to bind to local address {} (is it assigned to this host, and the port free?)",
+            local
+        )
+    })?;
+    let connected = match timeout {
+        Some(timeout) => socket.connect_timeout(&(*addr).into(), timeout),
+        None => socket.connect(&(*addr).into()),
+    };
+    Ok(connected.map(|()| socket.into()))
+}
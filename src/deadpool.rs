@@ -0,0 +1,284 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        Arc,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use ::deadpool::managed::{self, Metrics, Object, Pool, RecycleError, RecycleResult, Timeouts};
+use log::{debug, error};
+use tokio::runtime::Handle;
+
+pub use crate::error::ManagerError as Error;
+use crate::{async_connection::AsyncConnection, connection::ConnectionOptions};
+
+// deadpool::managed::Manager for `AsyncConnection`
+//
+//   let manager = Manager::new(options);
+//   let pool = deadpool::managed::Pool::builder(manager).max_size(4).build()?;
+//   let result = pool.get().await?.query("SELECT 1").await?;
+//
+// deadpool recycles an idle connection when it is checked out. A broken connection is always
+// discarded; with the health check enabled (the default) a COM_PING is sent as well, which costs a
+// round trip per checkout but catches connections the server has closed in the meantime.
+//
+// Anything cached per connection (session variables, prepared statements and their metadata) survives
+// recycling, since the same `AsyncConnection` is handed out again. When recycling fails deadpool drops
+// the connection, and such caches go with it; they are never carried over to a replacement.
+#[derive(Debug, Clone)]
+pub struct Manager {
+    options: ConnectionOptions,
+    health_check: bool,
+    health_check_timeout: Option<Duration>,
+}
+
+impl Manager {
+    pub fn new(options: ConnectionOptions) -> Self {
+        Self {
+            options,
+            health_check: true,
+            health_check_timeout: None,
+        }
+    }
+
+    // Ping connections before they are checked out.
+    pub fn health_check(mut self, health_check: bool) -> Self {
+        self.health_check = health_check;
+        self
+    }
+
+    // Give up on the health check ping after `timeout`, discarding the connection.
+    pub fn health_check_timeout(mut self, timeout: Duration) -> Self {
+        self.health_check_timeout = Some(timeout);
+        self
+    }
+}
+
+impl managed::Manager for Manager {
+    type Type = AsyncConnection;
+    type Error = Error;
+
+    async fn create(&self) -> Result<AsyncConnection, Error> {
+        Ok(AsyncConnection::new(self.options.clone()).await?)
+    }
+
+    async fn recycle(&self, conn: &mut AsyncConnection, _: &Metrics) -> RecycleResult<Error> {
+        if conn.is_broken() {
+            return Err(RecycleError::message("connection is broken"));
+        }
+        if !self.health_check {
+            return Ok(());
+        }
+        let pinged = match self.health_check_timeout {
+            Some(timeout) => conn.ping_with_timeout(timeout).await,
+            None => conn.ping().await,
+        };
+        if let Err(err) = pinged {
+            debug!("health check failed: {:#}", err);
+            return Err(RecycleError::Backend(err.into()));
+        }
+        Ok(())
+    }
+}
+
+// What the keepalive did, for metrics.
+#[derive(Debug, Clone)]
+pub enum KeepaliveEvent {
+    // An idle connection failed its ping and was removed from the pool.
+    Evicted { error: String },
+    // A connection was opened to bring the pool back to the minimum size.
+    Replenished,
+}
+
+// Pings the idle connections of a pool in the background, so that one the server closed (after
+// wait_timeout, or a restart) is discarded before a user checks it out, and opens connections when
+// the pool has fewer than `min_size`.
+//
+//   let pool = deadpool::managed::Pool::builder(Manager::new(options).health_check(false))
+//       .max_size(8)
+//       .build()?;
+//   let keepalive = Keepalive::new(Duration::from_secs(60)).min_size(2).spawn(&pool);
+//
+// Every `interval` (plus up to `jitter`, so that the pools of many processes don't ping at the same
+// moment) it checks out each idle connection in turn, without waiting, and pings it. Only the
+// connection being pinged is unavailable to `get`. This relies on the pool's default
+// QueueMode::Fifo; with Lifo the same connection would be pinged each time. Checkouts go through
+// `recycle`, so with the manager's health check on a connection is pinged twice.
+//
+// deadpool has no way to add a connection without checking it out, so to open one the keepalive
+// checks out the idle connections first, holding them until the new ones are open.
+//
+// It runs on its own thread, using the tokio runtime `spawn` was called in. It stops when the pool
+// is dropped or closed, within an interval, or when the handle is stopped or dropped.
+#[derive(Clone)]
+pub struct Keepalive {
+    interval: Duration,
+    jitter: Duration,
+    min_size: usize,
+    ping_timeout: Option<Duration>,
+    on_event: Option<Arc<dyn Fn(KeepaliveEvent) + Send + Sync>>,
+}
+
+impl fmt::Debug for Keepalive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keepalive")
+            .field("interval", &self.interval)
+            .field("jitter", &self.jitter)
+            .field("min_size", &self.min_size)
+            .field("ping_timeout", &self.ping_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keepalive {
+    // `interval` should be well below the server's wait_timeout.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            jitter: interval / 10,
+            min_size: 0,
+            ping_timeout: None,
+            on_event: None,
+        }
+    }
+
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    // Give up on a ping after `timeout`, evicting the connection.
+    pub fn ping_timeout(mut self, timeout: Duration) -> Self {
+        self.ping_timeout = Some(timeout);
+        self
+    }
+
+    // Called on the keepalive thread for every eviction and replenishment; it should return
+    // quickly.
+    pub fn on_event(mut self, on_event: impl Fn(KeepaliveEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Some(Arc::new(on_event));
+        self
+    }
+
+    // Must be called within a tokio runtime.
+    pub fn spawn(self, pool: &Pool<Manager>) -> KeepaliveHandle {
+        let runtime = Handle::current();
+        let pool = pool.weak();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name(String::from("mysql-keepalive"))
+            .spawn(move || {
+                debug!("keepalive start");
+                // Nothing is ever sent: the handle stops the thread by dropping the sender.
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(self.next_wait()) {
+                    let Some(pool) = pool.upgrade().filter(|pool| !pool.is_closed()) else {
+                        break;
+                    };
+                    runtime.block_on(self.run_once(&pool));
+                }
+                debug!("keepalive done");
+            })
+            .expect("failed to spawn the keepalive thread");
+        KeepaliveHandle {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    fn next_wait(&self) -> Duration {
+        let random = RandomState::new().build_hasher().finish();
+        let jitter_nanos = self.jitter.as_nanos() as u64;
+        let jitter = match jitter_nanos {
+            0 => Duration::ZERO,
+            _ => Duration::from_nanos(random % jitter_nanos),
+        };
+        self.interval + jitter
+    }
+
+    async fn run_once(&self, pool: &Pool<Manager>) {
+        // Without waiting: a checkout by the keepalive never queues behind users.
+        let timeouts = Timeouts {
+            wait: Some(Duration::ZERO),
+            ..pool.timeouts()
+        };
+        for _ in 0..pool.status().available {
+            // A checkout with no idle connection would open a new one.
+            if pool.status().available == 0 {
+                break;
+            }
+            let Ok(mut conn) = pool.timeout_get(&timeouts).await else {
+                break;
+            };
+            let pinged = match self.ping_timeout {
+                Some(timeout) => conn.ping_with_timeout(timeout).await,
+                None => conn.ping().await,
+            };
+            if let Err(err) = pinged {
+                debug!("keepalive evicting connection: {:#}", err);
+                drop(Object::take(conn));
+                self.emit(KeepaliveEvent::Evicted {
+                    error: format!("{:#}", err),
+                });
+            }
+        }
+
+        let mut held = vec![];
+        while pool.status().size < self.min_size {
+            match pool.timeout_get(&timeouts).await {
+                Ok(conn) => {
+                    if Object::metrics(&conn).recycle_count == 0 {
+                        self.emit(KeepaliveEvent::Replenished);
+                    }
+                    held.push(conn);
+                }
+                Err(err) => {
+                    debug!("keepalive failed to replenish the pool: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn emit(&self, event: KeepaliveEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+}
+
+// Stops the keepalive when dropped.
+#[derive(Debug)]
+pub struct KeepaliveHandle {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepaliveHandle {
+    // Stops the keepalive and waits for a round in progress to finish. Blocks, so call it outside
+    // async code (or through spawn_blocking).
+    pub fn stop(mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            error!("keepalive thread panicked");
+        }
+    }
+}
+
+impl Drop for KeepaliveHandle {
+    // Only signals the thread, which exits after a round in progress.
+    fn drop(&mut self) {
+        self.stop.take();
+    }
+}
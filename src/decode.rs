@@ -0,0 +1,139 @@
+use std::{fmt, fs};
+
+use anyhow::{Result, bail};
+use log::{debug, error};
+
+use crate::{connection::Connection, error::Error, strict};
+
+// The packet a decode error happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    Handshake,
+    AuthSwitchRequest,
+    AuthNextFactor,
+    Ok,
+    Err,
+    Eof,
+    ColumnCount,
+    ColumnDefinition,
+    Row,
+    StmtPrepareOk,
+    ProgressReport,
+}
+
+impl PacketKind {
+    // Packets of the authentication exchange carry the server's nonce, which together with a
+    // captured auth response allows guessing the password offline. They are never dumped.
+    pub fn is_sensitive(&self) -> bool {
+        matches!(
+            self,
+            Self::Handshake | Self::AuthSwitchRequest | Self::AuthNextFactor
+        )
+    }
+}
+
+// A field that would run past the end of the packet.
+#[derive(Debug)]
+pub(crate) struct OutOfBounds {
+    offset: usize,
+    needed: usize,
+    len: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} bytes at offset {}, but the packet is {} bytes long",
+            self.needed, self.offset, self.len
+        )
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+// Checked before reading `needed` bytes at `pos`, so a truncated packet is an error instead of a
+// panic.
+pub(crate) fn check_len(pkt: &[u8], pos: usize, needed: usize) -> Result<()> {
+    if pos.checked_add(needed).is_none_or(|end| end > pkt.len()) {
+        bail!(OutOfBounds {
+            offset: pos,
+            needed,
+            len: pkt.len(),
+        });
+    }
+    Ok(())
+}
+
+// The first byte of a response packet, which tells what kind of packet it is. An empty payload is
+// valid framing, but no response is empty.
+pub(crate) fn packet_header(pkt: &[u8]) -> Result<u8> {
+    match pkt.first() {
+        Some(&header) => Ok(header),
+        None => bail!("empty packet from the server"),
+    }
+}
+
+// Hex of `bytes`, space-separated.
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const CONTEXT_BYTES: usize = 32;
+
+impl Connection {
+    // Runs `decode` on a packet just read, turning a failure into an `Error::Decode` that says which
+    // packet it was and shows its first and last bytes. With `strict_protocol`, a packet that decodes
+    // is then checked for what the decoders tolerate (see `strict`).
+    pub(crate) fn decode_packet<T>(
+        &self,
+        kind: PacketKind,
+        pkt: &[u8],
+        decode: impl FnOnce(&[u8]) -> Result<T>,
+    ) -> Result<T> {
+        let decoded = decode(pkt).map_err(|err| self.decode_error(kind, pkt, err))?;
+        if self.options.strict_protocol {
+            strict::check(kind, pkt, self.capabilities)?;
+        }
+        Ok(decoded)
+    }
+
+    fn decode_error(&self, kind: PacketKind, pkt: &[u8], err: anyhow::Error) -> anyhow::Error {
+        let sequence = self.sequence().wrapping_sub(1);
+        let (head, tail) = if kind.is_sensitive() {
+            (String::new(), String::new())
+        } else {
+            let head = &pkt[..pkt.len().min(CONTEXT_BYTES)];
+            let tail = &pkt[pkt.len().saturating_sub(CONTEXT_BYTES)..];
+            (hex(head), hex(tail))
+        };
+        if !kind.is_sensitive()
+            && let Some(dir) = &self.options.packet_dump_dir
+        {
+            let path = dir.join(format!(
+                "packet-{}-{}-{:?}.bin",
+                self.connection_id(),
+                sequence,
+                kind
+            ));
+            match fs::write(&path, pkt) {
+                Ok(()) => debug!("wrote undecodable packet to {}", path.display()),
+                Err(err) => error!("failed to write packet dump {}: {}", path.display(), err),
+            }
+        }
+        Error::Decode {
+            kind,
+            sequence,
+            len: pkt.len(),
+            offset: err.downcast_ref::<OutOfBounds>().map(|err| err.offset),
+            head,
+            tail,
+            message: format!("{:#}", err),
+        }
+        .into()
+    }
+}
@@ -0,0 +1,220 @@
+use std::io::Write;
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    command::{ColumnDefinition41, ResultsetRow},
+    connection::Connection,
+    result::{ProgressHandler, ProgressInterval},
+    utils::{escape_string, quote_identifier},
+};
+
+// INSERT statements are split once they grow past this many bytes.
+pub const INSERT_BATCH_BYTES: usize = 1024 * 1024;
+
+// Character set number of binary strings
+const BINARY_CHARSET: u16 = 63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    // RFC 4180 CSV with a header row. NULL is written as an unquoted \N, like LOAD DATA expects.
+    Csv,
+    // Multi-row `INSERT INTO ... VALUES` statements, binary values as hex literals.
+    InsertStatements,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DumpStats {
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+impl Connection {
+    // Streams `SELECT * FROM table [WHERE filter]` into `out`. The rows are read inside a
+    // consistent-snapshot transaction so the export is coherent even while the table is written to.
+    //
+    // `filter` is inserted as is and must not come from untrusted input.
+    pub fn dump_table(
+        &mut self,
+        table: &str,
+        filter: Option<&str>,
+        out: &mut impl Write,
+        format: DumpFormat,
+    ) -> Result<DumpStats> {
+        self.dump_table_inner(table, filter, out, format, None)
+    }
+
+    // `dump_table`, calling `on_progress` every `interval` of rows exported.
+    pub fn dump_table_with_progress(
+        &mut self,
+        table: &str,
+        filter: Option<&str>,
+        out: &mut impl Write,
+        format: DumpFormat,
+        on_progress: ProgressHandler,
+        interval: ProgressInterval,
+    ) -> Result<DumpStats> {
+        let progress = Some((on_progress, interval));
+        self.dump_table_inner(table, filter, out, format, progress)
+    }
+
+    fn dump_table_inner(
+        &mut self,
+        table: &str,
+        filter: Option<&str>,
+        out: &mut impl Write,
+        format: DumpFormat,
+        progress: Option<(ProgressHandler, ProgressInterval)>,
+    ) -> Result<DumpStats> {
+        debug!("dump_table start: {}", table);
+        let mut sql = format!("SELECT * FROM {}", quote_identifier(table));
+        if let Some(filter) = filter {
+            sql.push_str(" WHERE ");
+            sql.push_str(filter);
+        }
+
+        let stats = self.internal(|conn| {
+            conn.query("START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY")?;
+            let stats = conn.dump_rows(table, &sql, out, format, progress);
+            if stats.is_ok() {
+                conn.query("COMMIT")?;
+            } else if let Err(err) = conn.query("ROLLBACK") {
+                debug!("failed to roll back dump transaction: {}", err);
+            }
+            stats
+        });
+        debug!("dump_table done: {:?}", stats);
+        stats
+    }
+
+    fn dump_rows(
+        &mut self,
+        table: &str,
+        sql: &str,
+        out: &mut impl Write,
+        format: DumpFormat,
+        progress: Option<(ProgressHandler, ProgressInterval)>,
+    ) -> Result<DumpStats> {
+        let mut rows = self.query_iter(sql)?;
+        if let Some((handler, interval)) = progress {
+            rows = rows.with_progress(handler, interval);
+        }
+        let columns = rows.columns().to_vec();
+        let mut writer = DumpWriter {
+            out,
+            stats: DumpStats::default(),
+        };
+        let mut statement = String::new();
+
+        if format == DumpFormat::Csv {
+            let header = columns
+                .iter()
+                .map(|col| Some(col.name.as_bytes().to_vec()))
+                .collect();
+            writer.write_csv_row(&ResultsetRow(header))?;
+        }
+        let table = quote_identifier(table);
+        let column_list = columns
+            .iter()
+            .map(|col| quote_identifier(&col.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        for row in rows.by_ref() {
+            let row = row?;
+            match format {
+                DumpFormat::Csv => writer.write_csv_row(&row)?,
+                DumpFormat::InsertStatements => {
+                    let values = insert_values(&columns, &row);
+                    if !statement.is_empty()
+                        && statement.len() + values.len() + 2 > INSERT_BATCH_BYTES
+                    {
+                        statement.push_str(";\n");
+                        writer.write(statement.as_bytes())?;
+                        statement.clear();
+                    }
+                    if statement.is_empty() {
+                        statement = format!("INSERT INTO {} ({}) VALUES ", table, column_list);
+                    } else {
+                        statement.push(',');
+                    }
+                    statement.push_str(&values);
+                }
+            }
+            writer.stats.rows += 1;
+        }
+        if !statement.is_empty() {
+            statement.push_str(";\n");
+            writer.write(statement.as_bytes())?;
+        }
+        writer.out.flush()?;
+        Ok(writer.stats)
+    }
+}
+
+struct DumpWriter<'a, W: Write> {
+    out: &'a mut W,
+    stats: DumpStats,
+}
+
+impl<W: Write> DumpWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<()> {
+        self.out.write_all(buf)?;
+        self.stats.bytes += buf.len() as u64;
+        Ok(())
+    }
+
+    fn write_csv_row(&mut self, row: &ResultsetRow) -> Result<()> {
+        let mut line = vec![];
+        for (i, cell) in row.0.iter().enumerate() {
+            if i > 0 {
+                line.push(b',');
+            }
+            match cell {
+                None => line.extend_from_slice(b"\\N"),
+                Some(val) => {
+                    let needs_quotes = val.as_slice() == b"\\N"
+                        || val.iter().any(|b| matches!(b, b',' | b'"' | b'\n' | b'\r'));
+                    if needs_quotes {
+                        line.push(b'"');
+                        for &b in val {
+                            if b == b'"' {
+                                line.push(b'"');
+                            }
+                            line.push(b);
+                        }
+                        line.push(b'"');
+                    } else {
+                        line.extend_from_slice(val);
+                    }
+                }
+            }
+        }
+        line.extend_from_slice(b"\r\n");
+        self.write(&line)
+    }
+}
+
+// Column Types
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/field__types_8h.html
+fn is_numeric(col: &ColumnDefinition41) -> bool {
+    matches!(col.type_, 0x00..=0x05 | 0x08 | 0x09 | 0x0d | 0xf6)
+}
+
+fn insert_values(columns: &[ColumnDefinition41], row: &ResultsetRow) -> String {
+    let values = columns.iter().zip(&row.0).map(|(col, cell)| match cell {
+        None => String::from("NULL"),
+        Some(val) if col.character_set == BINARY_CHARSET && !is_numeric(col) => {
+            if val.is_empty() {
+                String::from("''")
+            } else {
+                let hex = val.iter().map(|b| format!("{:02X}", b)).collect::<String>();
+                format!("X'{}'", hex)
+            }
+        }
+        Some(val) if is_numeric(col) => String::from_utf8_lossy(val).into_owned(),
+        Some(val) => format!("'{}'", escape_string(&String::from_utf8_lossy(val))),
+    });
+    format!("({})", values.collect::<Vec<_>>().join(","))
+}
@@ -0,0 +1,125 @@
+use std::borrow::Cow;
+
+use anyhow::{Result, bail};
+
+use crate::{charset::collation_by_id, value::Value};
+
+// Character sets whose text is UTF-8 as far as the client is concerned. ASCII is a subset, and
+// binary strings are sent as they are.
+fn is_utf8(charset: &str) -> bool {
+    matches!(charset, "utf8mb4" | "utf8mb3" | "utf8" | "binary")
+}
+
+// The encoding_rs encoding for a MySQL character set. MySQL's latin1 is cp1252, as is the WHATWG
+// "latin1" label.
+#[cfg(feature = "encoding_rs")]
+fn encoding_for(charset: &str) -> Option<&'static encoding_rs::Encoding> {
+    let label = match charset {
+        "latin1" => "windows-1252",
+        "latin2" => "iso-8859-2",
+        "latin5" => "windows-1254",
+        "latin7" => "iso-8859-13",
+        "greek" => "iso-8859-7",
+        "hebrew" => "iso-8859-8",
+        "koi8r" => "koi8-r",
+        "koi8u" => "koi8-u",
+        "macroman" => "macintosh",
+        "tis620" => "windows-874",
+        "sjis" | "cp932" => "shift_jis",
+        "ujis" | "eucjpms" => "euc-jp",
+        "euckr" => "euc-kr",
+        "gb2312" | "gbk" => "gbk",
+        "gb18030" => "gb18030",
+        "big5" => "big5",
+        "cp866" => "ibm866",
+        "cp1250" => "windows-1250",
+        "cp1251" => "windows-1251",
+        "cp1256" => "windows-1256",
+        "cp1257" => "windows-1257",
+        _ => return None,
+    };
+    encoding_rs::Encoding::for_label(label.as_bytes())
+}
+
+// `text` in the session character set. Text in a UTF-8 session, and ASCII in any other (every
+// character set a client can use is ASCII-compatible), is sent as it is.
+pub(crate) fn encode_str<'a>(text: &'a str, charset: &str) -> Result<Cow<'a, [u8]>> {
+    if is_utf8(charset) || text.is_ascii() {
+        return Ok(Cow::Borrowed(text.as_bytes()));
+    }
+    transcode(text, charset)
+}
+
+#[cfg(feature = "encoding_rs")]
+fn transcode<'a>(text: &'a str, charset: &str) -> Result<Cow<'a, [u8]>> {
+    let Some(encoding) = encoding_for(charset) else {
+        bail!("can't encode text in character set {}", charset);
+    };
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        bail!(
+            "the text has characters that character set {} can't represent",
+            charset
+        );
+    }
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn transcode<'a>(_: &'a str, charset: &str) -> Result<Cow<'a, [u8]>> {
+    bail!(
+        "the session character set is {}, and sending non-ASCII text in it needs the encoding_rs \
+         feature (or switch the session to utf8mb4)",
+        charset
+    )
+}
+
+// String parameters in the session character set, as bytes; the rest are left alone.
+pub(crate) fn encode_params<'a>(params: &'a [Value], charset: &str) -> Result<Cow<'a, [Value]>> {
+    let needs_encoding = |param: &Value| match param {
+        Value::Text(text) | Value::Decimal(text) | Value::Json(text) => !text.is_ascii(),
+        _ => false,
+    };
+    if is_utf8(charset) || !params.iter().any(needs_encoding) {
+        return Ok(Cow::Borrowed(params));
+    }
+    params
+        .iter()
+        .map(|param| match param {
+            Value::Text(text) | Value::Decimal(text) | Value::Json(text) => {
+                Ok(Value::Bytes(encode_str(text, charset)?.into_owned()))
+            }
+            param => Ok(param.clone()),
+        })
+        .collect()
+}
+
+// A text cell as UTF-8, from the character set of its column (a collation id). Without the
+// encoding_rs feature, or for a character set it doesn't know, bytes that aren't UTF-8 are given
+// back as they are.
+pub(crate) fn decode_text(
+    bytes: Vec<u8>,
+    collation_id: u16,
+) -> std::result::Result<String, Vec<u8>> {
+    let charset = collation_by_id(collation_id).map_or("utf8mb4", |c| c.charset);
+    if is_utf8(charset) || bytes.is_ascii() {
+        return String::from_utf8(bytes).map_err(|err| err.into_bytes());
+    }
+    decode_non_utf8(bytes, charset)
+}
+
+#[cfg(feature = "encoding_rs")]
+fn decode_non_utf8(bytes: Vec<u8>, charset: &str) -> std::result::Result<String, Vec<u8>> {
+    let Some(encoding) = encoding_for(charset) else {
+        return String::from_utf8(bytes).map_err(|err| err.into_bytes());
+    };
+    match encoding.decode_without_bom_handling_and_without_replacement(&bytes) {
+        Some(text) => Ok(text.into_owned()),
+        None => Err(bytes),
+    }
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn decode_non_utf8(bytes: Vec<u8>, _: &str) -> std::result::Result<String, Vec<u8>> {
+    String::from_utf8(bytes).map_err(|err| err.into_bytes())
+}
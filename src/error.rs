@@ -0,0 +1,132 @@
+use std::{fmt, time::Duration};
+
+use crate::{
+    command::ErrPacket, deadline::ConnectStage, decode::PacketKind, state::ConnectionState,
+};
+
+// Errors that callers may want to match on. They are raised through `anyhow` like every other error
+// in this crate; use `err.downcast_ref::<Error>()` to inspect them.
+#[derive(Debug)]
+pub enum Error {
+    // A command was issued while the connection was in the middle of something else, e.g. a result
+    // set that is still being streamed.
+    InvalidState {
+        expected: ConnectionState,
+        actual: ConnectionState,
+    },
+    // `Connection::connect_with_deadline` ran out of time, or was cancelled, during `stage`.
+    ConnectTimedOut {
+        stage: ConnectStage,
+    },
+    ConnectCancelled {
+        stage: ConnectStage,
+    },
+    // The server didn't respond within the time given to the call.
+    Timeout {
+        after: Duration,
+    },
+    // The server closed the connection (client error 2013, "Lost connection to MySQL server"). Holds
+    // the underlying io error message.
+    ServerGone(String),
+    // The server closed the connection between packets, typically an idle session after
+    // wait_timeout or a KILL. `error` is the ERR packet it sent first, if any (MySQL 8.0.24+ sends
+    // ER_CLIENT_INTERACTION_TIMEOUT, 4031, before closing an idle session).
+    ServerClosedConnection {
+        error: Option<ErrPacket>,
+    },
+    // The account lacks a privilege the command needs (ER_SPECIFIC_ACCESS_DENIED_ERROR). Holds the
+    // server's message, which names the privilege.
+    PermissionDenied(String),
+    // A command was issued on a connection an earlier io or protocol failure left unusable.
+    Broken,
+    // A packet from the server couldn't be decoded. `sequence` is its sequence id, `offset` where
+    // decoding ran past its end (when that was the problem), and `head`/`tail` hex dumps of its
+    // first and last 32 bytes (empty for packets of the authentication exchange).
+    Decode {
+        kind: PacketKind,
+        sequence: u8,
+        len: usize,
+        offset: Option<usize>,
+        head: String,
+        tail: String,
+        message: String,
+    },
+    // A packet broke a rule of the protocol that is only checked with
+    // `ConnectionOptions::strict_protocol`. `rule` is one of the names in `strict`.
+    ProtocolViolation {
+        rule: &'static str,
+        detail: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidState { expected, actual } => write!(
+                f,
+                "invalid connection state: expected {:?}, but was {:?}",
+                expected, actual
+            ),
+            Self::ConnectTimedOut { stage } => write!(f, "connect timed out during {:?}", stage),
+            Self::ConnectCancelled { stage } => write!(f, "connect cancelled during {:?}", stage),
+            Self::Timeout { after } => write!(f, "no response from the server within {:?}", after),
+            Self::ServerGone(reason) => write!(f, "lost connection to MySQL server: {}", reason),
+            Self::ServerClosedConnection { error: None } => {
+                f.write_str("the server closed the connection")
+            }
+            Self::ServerClosedConnection { error: Some(err) } => {
+                write!(f, "the server closed the connection: {}", err)
+            }
+            Self::PermissionDenied(message) => write!(f, "permission denied: {}", message),
+            Self::Broken => {
+                f.write_str("connection is broken by an earlier failure; open a new one")
+            }
+            Self::Decode {
+                kind,
+                sequence,
+                len,
+                offset,
+                head,
+                tail,
+                message,
+            } => {
+                write!(
+                    f,
+                    "failed to decode {:?} packet (sequence {}, {} bytes",
+                    kind, sequence, len
+                )?;
+                if let Some(offset) = offset {
+                    write!(f, ", at offset {}", offset)?;
+                }
+                write!(f, "): {}", message)?;
+                if !head.is_empty() {
+                    write!(f, " [first bytes: {}] [last bytes: {}]", head, tail)?;
+                }
+                Ok(())
+            }
+            Self::ProtocolViolation { rule, detail } => {
+                write!(f, "protocol violation ({}): {}", rule, detail)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Pool crates need an error type implementing std::error::Error, which anyhow::Error doesn't.
+#[derive(Debug)]
+pub struct ManagerError(pub anyhow::Error);
+
+impl From<anyhow::Error> for ManagerError {
+    fn from(err: anyhow::Error) -> Self {
+        Self(err)
+    }
+}
+
+impl fmt::Display for ManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for ManagerError {}
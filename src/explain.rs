@@ -0,0 +1,99 @@
+use anyhow::{Context, Result, bail};
+
+use crate::{connection::Connection, result::QueryResult, routing::skip_comments};
+
+// A row of EXPLAIN's tabular output
+// https://dev.mysql.com/doc/refman/8.4/en/explain-output.html
+//
+// Columns are matched by name, so servers that leave some out (e.g. MariaDB without `partitions`
+// or `filtered`) get `None` for those.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplainRow {
+    pub id: Option<u64>,
+    pub select_type: String,
+    pub table: Option<String>,
+    pub partitions: Option<String>,
+    pub type_: Option<String>,
+    pub possible_keys: Option<String>,
+    pub key: Option<String>,
+    pub key_len: Option<String>,
+    pub ref_: Option<String>,
+    pub rows: Option<u64>,
+    pub filtered: Option<f64>,
+    pub extra: Option<String>,
+}
+
+impl Connection {
+    // EXPLAIN <sql>. Only SELECT, INSERT, UPDATE and DELETE are accepted, so a typo can't turn into
+    // running some other statement.
+    pub fn explain(&mut self, sql: &str) -> Result<Vec<ExplainRow>> {
+        let sql = explainable(sql)?;
+        let QueryResult::ResultSet(rs) = self.query(&format!("EXPLAIN {}", sql))? else {
+            bail!("EXPLAIN did not return a result set");
+        };
+        let names = rs
+            .columns
+            .iter()
+            .map(|column| column.name.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        rs.rows
+            .into_iter()
+            .map(|row| {
+                let cells = row.into_strings()?;
+                let cell = |name: &str| {
+                    names
+                        .iter()
+                        .position(|n| n == name)
+                        .and_then(|i| cells[i].clone())
+                };
+                Ok(ExplainRow {
+                    id: cell("id").map(|id| id.parse()).transpose()?,
+                    select_type: cell("select_type").context("EXPLAIN row without select_type")?,
+                    table: cell("table"),
+                    partitions: cell("partitions"),
+                    type_: cell("type"),
+                    possible_keys: cell("possible_keys"),
+                    key: cell("key"),
+                    key_len: cell("key_len"),
+                    ref_: cell("ref"),
+                    rows: cell("rows").map(|rows| rows.parse()).transpose()?,
+                    filtered: cell("filtered").map(|f| f.parse()).transpose()?,
+                    extra: cell("extra"),
+                })
+            })
+            .collect()
+    }
+
+    // EXPLAIN FORMAT=JSON <sql>
+    #[cfg(feature = "json")]
+    pub fn explain_json(&mut self, sql: &str) -> Result<serde_json::Value> {
+        let sql = explainable(sql)?;
+        let QueryResult::ResultSet(rs) = self.query(&format!("EXPLAIN FORMAT=JSON {}", sql))?
+        else {
+            bail!("EXPLAIN did not return a result set");
+        };
+        let plan = rs
+            .rows
+            .into_iter()
+            .next()
+            .and_then(|row| row.0.into_iter().next().flatten())
+            .context("EXPLAIN FORMAT=JSON returned no plan")?;
+        Ok(serde_json::from_slice(&plan)?)
+    }
+}
+
+fn explainable(sql: &str) -> Result<&str> {
+    let sql = skip_comments(sql);
+    let keyword = sql
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_ascii_uppercase();
+    if !["SELECT", "INSERT", "UPDATE", "DELETE"].contains(&keyword.as_str()) {
+        bail!(
+            "refusing to EXPLAIN a {} statement; only SELECT, INSERT, UPDATE and DELETE are allowed",
+            keyword
+        );
+    }
+    Ok(sql)
+}
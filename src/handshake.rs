@@ -1,7 +1,164 @@
-use std::cmp::max;
+use std::{cmp::max, fmt};
 
 use anyhow::{Result, bail};
-use sha1::{Digest, Sha1};
+
+use crate::{
+    auth::MYSQL_NATIVE_PASSWORD,
+    command::StatusFlags,
+    consts::{AUTH_NEXT_FACTOR_HEADER, AUTH_SWITCH_REQUEST_HEADER},
+    decode::check_len,
+    packet_writer::PacketWriter,
+    utils::{decode_lenenc_integer, decode_lenenc_string},
+};
+
+// Capability Flags
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/group__group__cs__capabilities__flags.html
+pub const CLIENT_LONG_PASSWORD: u32 = 1;
+pub const CLIENT_FOUND_ROWS: u32 = 1 << 1;
+pub const CLIENT_LONG_FLAG: u32 = 1 << 2;
+pub const CLIENT_CONNECT_WITH_DB: u32 = 1 << 3;
+pub const CLIENT_NO_SCHEMA: u32 = 1 << 4;
+pub const CLIENT_COMPRESS: u32 = 1 << 5;
+pub const CLIENT_ODBC: u32 = 1 << 6;
+pub const CLIENT_LOCAL_FILES: u32 = 1 << 7;
+pub const CLIENT_IGNORE_SPACE: u32 = 1 << 8;
+pub const CLIENT_PROTOCOL_41: u32 = 1 << 9;
+pub const CLIENT_INTERACTIVE: u32 = 1 << 10;
+pub const CLIENT_SSL: u32 = 1 << 11;
+pub const CLIENT_IGNORE_SIGPIPE: u32 = 1 << 12;
+pub const CLIENT_TRANSACTIONS: u32 = 1 << 13;
+pub const CLIENT_RESERVED: u32 = 1 << 14;
+pub const CLIENT_SECURE_CONNECTION: u32 = 1 << 15;
+pub const CLIENT_MULTI_STATEMENTS: u32 = 1 << 16;
+pub const CLIENT_MULTI_RESULTS: u32 = 1 << 17;
+pub const CLIENT_PS_MULTI_RESULTS: u32 = 1 << 18;
+pub const CLIENT_PLUGIN_AUTH: u32 = 1 << 19;
+pub const CLIENT_CONNECT_ATTRS: u32 = 1 << 20;
+pub const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 1 << 21;
+pub const CLIENT_CAN_HANDLE_EXPIRED_PASSWORDS: u32 = 1 << 22;
+pub const CLIENT_SESSION_TRACK: u32 = 1 << 23;
+pub const CLIENT_DEPRECATE_EOF: u32 = 1 << 24;
+pub const CLIENT_OPTIONAL_RESULTSET_METADATA: u32 = 1 << 25;
+pub const CLIENT_ZSTD_COMPRESSION_ALGORITHM: u32 = 1 << 26;
+pub const CLIENT_QUERY_ATTRIBUTES: u32 = 1 << 27;
+pub const MULTI_FACTOR_AUTHENTICATION: u32 = 1 << 28;
+pub const CLIENT_CAPABILITY_EXTENSION: u32 = 1 << 29;
+pub const CLIENT_SSL_VERIFY_SERVER_CERT: u32 = 1 << 30;
+pub const CLIENT_REMEMBER_OPTIONS: u32 = 1 << 31;
+
+pub const DEFAULT_CLIENT_FLAG: u32 = CLIENT_LONG_PASSWORD
+    | CLIENT_LONG_FLAG
+    | CLIENT_CONNECT_WITH_DB
+    | CLIENT_LOCAL_FILES
+    | CLIENT_PROTOCOL_41
+    | CLIENT_TRANSACTIONS
+    | CLIENT_SECURE_CONNECTION
+    | CLIENT_MULTI_STATEMENTS
+    | CLIENT_MULTI_RESULTS
+    | CLIENT_PS_MULTI_RESULTS
+    | CLIENT_PLUGIN_AUTH
+    | CLIENT_CONNECT_ATTRS
+    | CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA
+    | CLIENT_SESSION_TRACK
+    | CLIENT_DEPRECATE_EOF
+    | CLIENT_OPTIONAL_RESULTSET_METADATA
+    | CLIENT_QUERY_ATTRIBUTES
+    | MULTI_FACTOR_AUTHENTICATION;
+
+// MariaDB's extended capabilities, the upper half of its 64-bit capability flags. A MariaDB server
+// leaves CLIENT_LONG_PASSWORD unset and sends them in the last 4 reserved bytes of
+// Protocol::HandshakeV10; the client answers in the last 4 filler bytes of HandshakeResponse41.
+// https://mariadb.com/kb/en/connection/#capabilities
+pub const MARIADB_CLIENT_PROGRESS: u32 = 1;
+pub const MARIADB_CLIENT_COM_MULTI: u32 = 1 << 1;
+pub const MARIADB_CLIENT_STMT_BULK_OPERATIONS: u32 = 1 << 2;
+pub const MARIADB_CLIENT_EXTENDED_METADATA: u32 = 1 << 3;
+pub const MARIADB_CLIENT_CACHE_METADATA: u32 = 1 << 4;
+
+pub const DEFAULT_MARIADB_CLIENT_FLAG: u32 = MARIADB_CLIENT_STMT_BULK_OPERATIONS;
+
+// A set of capability flags, as a server advertises them or as both sides agreed on. Its Debug and
+// Display output name the flags.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct CapabilityFlags(pub u32);
+
+impl CapabilityFlags {
+    const NAMES: [(u32, &str); 32] = [
+        (CLIENT_LONG_PASSWORD, "CLIENT_LONG_PASSWORD"),
+        (CLIENT_FOUND_ROWS, "CLIENT_FOUND_ROWS"),
+        (CLIENT_LONG_FLAG, "CLIENT_LONG_FLAG"),
+        (CLIENT_CONNECT_WITH_DB, "CLIENT_CONNECT_WITH_DB"),
+        (CLIENT_NO_SCHEMA, "CLIENT_NO_SCHEMA"),
+        (CLIENT_COMPRESS, "CLIENT_COMPRESS"),
+        (CLIENT_ODBC, "CLIENT_ODBC"),
+        (CLIENT_LOCAL_FILES, "CLIENT_LOCAL_FILES"),
+        (CLIENT_IGNORE_SPACE, "CLIENT_IGNORE_SPACE"),
+        (CLIENT_PROTOCOL_41, "CLIENT_PROTOCOL_41"),
+        (CLIENT_INTERACTIVE, "CLIENT_INTERACTIVE"),
+        (CLIENT_SSL, "CLIENT_SSL"),
+        (CLIENT_IGNORE_SIGPIPE, "CLIENT_IGNORE_SIGPIPE"),
+        (CLIENT_TRANSACTIONS, "CLIENT_TRANSACTIONS"),
+        (CLIENT_RESERVED, "CLIENT_RESERVED"),
+        (CLIENT_SECURE_CONNECTION, "CLIENT_SECURE_CONNECTION"),
+        (CLIENT_MULTI_STATEMENTS, "CLIENT_MULTI_STATEMENTS"),
+        (CLIENT_MULTI_RESULTS, "CLIENT_MULTI_RESULTS"),
+        (CLIENT_PS_MULTI_RESULTS, "CLIENT_PS_MULTI_RESULTS"),
+        (CLIENT_PLUGIN_AUTH, "CLIENT_PLUGIN_AUTH"),
+        (CLIENT_CONNECT_ATTRS, "CLIENT_CONNECT_ATTRS"),
+        (
+            CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA,
+            "CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA",
+        ),
+        (
+            CLIENT_CAN_HANDLE_EXPIRED_PASSWORDS,
+            "CLIENT_CAN_HANDLE_EXPIRED_PASSWORDS",
+        ),
+        (CLIENT_SESSION_TRACK, "CLIENT_SESSION_TRACK"),
+        (CLIENT_DEPRECATE_EOF, "CLIENT_DEPRECATE_EOF"),
+        (
+            CLIENT_OPTIONAL_RESULTSET_METADATA,
+            "CLIENT_OPTIONAL_RESULTSET_METADATA",
+        ),
+        (
+            CLIENT_ZSTD_COMPRESSION_ALGORITHM,
+            "CLIENT_ZSTD_COMPRESSION_ALGORITHM",
+        ),
+        (CLIENT_QUERY_ATTRIBUTES, "CLIENT_QUERY_ATTRIBUTES"),
+        (MULTI_FACTOR_AUTHENTICATION, "MULTI_FACTOR_AUTHENTICATION"),
+        (CLIENT_CAPABILITY_EXTENSION, "CLIENT_CAPABILITY_EXTENSION"),
+        (
+            CLIENT_SSL_VERIFY_SERVER_CERT,
+            "CLIENT_SSL_VERIFY_SERVER_CERT",
+        ),
+        (CLIENT_REMEMBER_OPTIONS, "CLIENT_REMEMBER_OPTIONS"),
+    ];
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag != 0
+    }
+
+    // The names of the flags that are set, lowest bit first.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+    }
+}
+
+// CLIENT_PROTOCOL_41 | CLIENT_PLUGIN_AUTH
+impl fmt::Display for CapabilityFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.names().collect::<Vec<_>>().join(" | "))
+    }
+}
+
+// CapabilityFlags(CLIENT_PROTOCOL_41 | CLIENT_PLUGIN_AUTH)
+impl fmt::Debug for CapabilityFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CapabilityFlags({})", self)
+    }
+}
 
 // Protocol::HandshakeV10
 // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_packets_protocol_handshake_v10.html
@@ -15,7 +172,7 @@ pub struct HandshakeV10 {
     filler: u8,
     capability_flags_1: u16,
     character_set: u8,
-    status_flags: u16,
+    status_flags: StatusFlags,
     capability_flags_2: u16,
     auth_plugin_data_len: u8,
     reserved: [u8; 10],
@@ -24,37 +181,86 @@ pub struct HandshakeV10 {
 }
 
 impl HandshakeV10 {
+    // `auth_plugin_data` is the scramble, usually 20 random bytes; the first 8 go in part 1.
+    pub fn new(
+        server_version: &str,
+        connection_id: u32,
+        capability_flags: u32,
+        character_set: u8,
+        status_flags: StatusFlags,
+        auth_plugin_data: &[u8],
+        auth_plugin_name: &str,
+    ) -> Self {
+        let split = auth_plugin_data.len().min(8);
+        let mut auth_plugin_data_part_1 = auth_plugin_data[..split].to_vec();
+        auth_plugin_data_part_1.resize(8, 0);
+        Self {
+            protocol_version: 10,
+            server_version: String::from(server_version),
+            thread_id: connection_id,
+            auth_plugin_data_part_1,
+            filler: 0,
+            capability_flags_1: capability_flags as u16,
+            character_set,
+            status_flags,
+            capability_flags_2: (capability_flags >> 16) as u16,
+            auth_plugin_data_len: (auth_plugin_data.len() + 1).min(0xff) as u8,
+            reserved: [0; 10],
+            auth_plugin_data_part_2: auth_plugin_data[split..].to_vec(),
+            auth_plugin_name: String::from(auth_plugin_name),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.protocol_version);
+        w.nul_str(self.server_version.as_bytes());
+        w.u32_le(self.thread_id);
+        w.bytes(&self.auth_plugin_data_part_1);
+        w.u8(self.filler);
+        w.u16_le(self.capability_flags_1);
+        w.u8(self.character_set);
+        w.u16_le(self.status_flags.0);
+        w.u16_le(self.capability_flags_2);
+        w.u8(self.auth_plugin_data_len);
+        w.bytes(&self.reserved);
+        // Part 2 ends with a NUL and takes at least 13 bytes.
+        let section_len = max((self.auth_plugin_data_len as usize).saturating_sub(8), 13);
+        let part_2 = &self.auth_plugin_data_part_2;
+        w.bytes(&part_2[..part_2.len().min(section_len)]);
+        for _ in part_2.len()..section_len {
+            w.u8(0);
+        }
+        w.nul_str(self.auth_plugin_name.as_bytes());
+    }
+
+    // Proxies and other MySQL-compatible servers (ProxySQL, Vitess, ReadySet, ...) don't all fill in
+    // the packet the way MySQL does, so the decoder only relies on what authentication needs: the
+    // filler and reserved bytes aren't checked, a scramble shorter than auth_plugin_data_len
+    // announces is taken as is, and a missing or empty plugin name means mysql_native_password.
     pub fn decode(pkt: Vec<u8>) -> Result<Self> {
         let mut pos = 0;
 
+        check_len(&pkt, pos, 1)?;
         let protocol_version = pkt[pos];
         if protocol_version != 10 {
             bail!("invalid protocol version: {}", protocol_version);
         }
         pos += 1;
 
-        let server_version = {
-            let mut buf = vec![];
-            loop {
-                let val = pkt[pos];
-                pos += 1;
-                if val == 0 {
-                    break;
-                }
-                buf.push(val);
-            }
-            String::from_utf8(buf)?
-        };
+        let server_version = decode_nul_string_lossy(&pkt, &mut pos);
 
+        check_len(&pkt, pos, 4 + 8 + 1 + 2 + 1 + 2 + 2 + 1 + 10)?;
         let thread_id = u32::from_le_bytes([pkt[pos], pkt[pos + 1], pkt[pos + 2], pkt[pos + 3]]);
         pos += 4;
 
-        let auth_plugin_data_part_1 = {
-            let buf = &pkt[pos..(pos + 8)];
-            pos += 8;
-            buf.to_vec()
-        };
+        let auth_plugin_data_part_1 = pkt[pos..(pos + 8)].to_vec();
+        pos += 8;
 
+        // 0x00 from MySQL, but not from every proxy.
         let filler = pkt[pos];
         pos += 1;
 
@@ -64,7 +270,7 @@ impl HandshakeV10 {
         let character_set = pkt[pos];
         pos += 1;
 
-        let status_flags = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
+        let status_flags = StatusFlags(u16::from_le_bytes([pkt[pos], pkt[pos + 1]]));
         pos += 2;
 
         let capability_flags_2 = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
@@ -73,27 +279,29 @@ impl HandshakeV10 {
         let auth_plugin_data_len = pkt[pos];
         pos += 1;
 
-        let reserved = [0u8; 10];
+        let mut reserved = [0u8; 10];
+        reserved.copy_from_slice(&pkt[pos..(pos + 10)]);
         pos += 10;
 
+        // The section takes max(13, auth_plugin_data_len - 8) bytes, and the scramble is
+        // auth_plugin_data_len bytes in all with a trailing NUL, so plugins with a challenge longer
+        // than 20 bytes get all of it. The usual 20-byte scramble fills 12 bytes of the section
+        // whatever the length says: 0 without CLIENT_PLUGIN_AUTH, and sometimes less than 21 from
+        // proxies. A section cut short by the end of the packet is taken as far as it goes.
         let auth_plugin_data_part_2 = {
-            let len = max(auth_plugin_data_len - 8, 13) as usize;
-            let buf = &pkt[pos..(pos + len - 1)];
-            pos += len;
+            let section_len = max((auth_plugin_data_len as usize).saturating_sub(8), 13);
+            let data_len = max((auth_plugin_data_len as usize).saturating_sub(9), 12);
+            let rest = &pkt[pos.min(pkt.len())..];
+            let buf = &rest[..data_len.min(rest.len())];
+            pos += section_len;
             buf.to_vec()
         };
 
-        let auth_plugin_name = {
-            let mut buf = vec![];
-            loop {
-                let val = pkt[pos];
-                pos += 1;
-                if val == 0 {
-                    break;
-                }
-                buf.push(val);
-            }
-            String::from_utf8(buf)?
+        // MySQL 5.5.7-5.5.9 and some proxies leave out the terminating NUL, and some leave out the
+        // name altogether.
+        let auth_plugin_name = match decode_nul_string_lossy(&pkt, &mut pos) {
+            name if name.is_empty() => String::from(MYSQL_NATIVE_PASSWORD),
+            name => name,
         };
 
         Ok(Self {
@@ -113,6 +321,45 @@ impl HandshakeV10 {
         })
     }
 
+    pub fn server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    pub fn connection_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    pub fn status_flags(&self) -> StatusFlags {
+        self.status_flags
+    }
+
+    pub fn capability_flags(&self) -> u32 {
+        (self.capability_flags_2 as u32) << 16 | self.capability_flags_1 as u32
+    }
+
+    // 0 unless the server is MariaDB (10.2+).
+    pub fn mariadb_capabilities(&self) -> u32 {
+        if self.capability_flags_1 as u32 & CLIENT_LONG_PASSWORD != 0 {
+            return 0;
+        }
+        u32::from_le_bytes([
+            self.reserved[6],
+            self.reserved[7],
+            self.reserved[8],
+            self.reserved[9],
+        ])
+    }
+
+    // As a MariaDB server sends them, which also clears CLIENT_LONG_PASSWORD.
+    pub fn set_mariadb_capabilities(&mut self, capabilities: u32) {
+        self.capability_flags_1 &= !(CLIENT_LONG_PASSWORD as u16);
+        self.reserved[6..].copy_from_slice(&capabilities.to_le_bytes());
+    }
+
+    pub fn auth_plugin_name(&self) -> &str {
+        &self.auth_plugin_name
+    }
+
     pub fn auth_plugin_data(&self) -> Vec<u8> {
         [
             self.auth_plugin_data_part_1.clone(),
@@ -134,88 +381,459 @@ pub struct HandshakeResponse41 {
     pub auth_response: Vec<u8>,
     pub database: String,
     pub client_plugin_name: String,
+    pub connect_attrs: Vec<(String, String)>,
 }
 
 impl HandshakeResponse41 {
     #[allow(clippy::too_many_arguments)]
-    pub fn new(username: &str, password: &str, database: &str, auth_plugin_data: Vec<u8>) -> Self {
-        // Native Authentication
-        // SHA1( password ) XOR SHA1( "20-bytes random data from server" <concat> SHA1( SHA1( password ) ) )
-        // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_authentication_methods_native_password_authentication.html
-        let auth_response = {
-            let mut sha1 = Sha1::new();
-
-            let hash1 = {
-                sha1.update(password);
-                sha1.finalize_reset()
-            };
-            let hash2 = {
-                sha1.update(hash1);
-                sha1.finalize_reset()
-            };
-            let hash3 = {
-                sha1.update(auth_plugin_data);
-                sha1.update(hash2);
-                sha1.finalize_reset()
-            };
-
-            hash1
-                .iter()
-                .zip(hash3)
-                .map(|(a, b)| a ^ b)
-                .collect::<Vec<_>>()
-        };
-
+    pub fn new(
+        client_flag: u32,
+        character_set: u8,
+        username: &str,
+        auth_response: Vec<u8>,
+        database: &str,
+        client_plugin_name: &str,
+    ) -> Self {
         Self {
-            client_flag: 0x19bfa28d,
+            client_flag,
             max_packet_size: 16777216, // 2 ^ 24
-            character_set: 8,
+            character_set,
             filler: [0; 23],
             username: String::from(username),
             auth_response,
             database: String::from(database),
-            client_plugin_name: String::from("mysql_native_password"),
+            client_plugin_name: String::from(client_plugin_name),
+            connect_attrs: [
+                ("_pid", "246"),
+                ("_platform", "aarch64"),
+                ("_os", "Linux"),
+                ("_client_name", "libmysql"),
+                ("os_user", "root"),
+                ("_client_version", "8.3.0"),
+                ("program_name", "mysql"),
+            ]
+            .iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect(),
+        }
+    }
+
+    // MariaDB reads them only when `client_flag` leaves CLIENT_LONG_PASSWORD unset.
+    pub fn set_mariadb_capabilities(&mut self, capabilities: u32) {
+        self.filler[19..].copy_from_slice(&capabilities.to_le_bytes());
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u32_le(self.client_flag);
+        w.u32_le(self.max_packet_size);
+        w.u8(self.character_set);
+        w.bytes(&self.filler);
+        w.nul_str(self.username.as_bytes());
+        // Without CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA the length is a single byte, which limits the
+        // auth response to 255 bytes.
+        if self.client_flag & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+            w.lenenc_str(&self.auth_response);
+        } else {
+            w.u8(self.auth_response.len() as u8);
+            w.bytes(&self.auth_response);
+        }
+        if self.client_flag & CLIENT_CONNECT_WITH_DB != 0 {
+            w.nul_str(self.database.as_bytes());
+        }
+        if self.client_flag & CLIENT_PLUGIN_AUTH != 0 {
+            w.nul_str(self.client_plugin_name.as_bytes());
+        }
+
+        // The attributes may only be sent when both sides agreed on CLIENT_CONNECT_ATTRS.
+        if self.client_flag & CLIENT_CONNECT_ATTRS == 0 {
+            return;
+        }
+        let attributes = PacketWriter::encode(|attributes| {
+            for (k, v) in &self.connect_attrs {
+                attributes.lenenc_str(k.as_bytes());
+                attributes.lenenc_str(v.as_bytes());
+            }
+        });
+        // Both the block length and every key and value are length-encoded, so neither is limited to
+        // 250 bytes.
+        w.lenenc_str(&attributes);
+    }
+
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+
+        check_len(pkt, pos, 32)?;
+        let client_flag = u32::from_le_bytes([pkt[pos], pkt[pos + 1], pkt[pos + 2], pkt[pos + 3]]);
+        pos += 4;
+
+        let max_packet_size =
+            u32::from_le_bytes([pkt[pos], pkt[pos + 1], pkt[pos + 2], pkt[pos + 3]]);
+        pos += 4;
+
+        let character_set = pkt[pos];
+        pos += 1;
+
+        let mut filler = [0u8; 23];
+        filler.copy_from_slice(&pkt[pos..pos + 23]);
+        pos += 23;
+
+        let username = decode_nul_string(pkt, &mut pos)?;
+
+        let auth_response = {
+            let len = if client_flag & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+                let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+                pos += consumed;
+                len as usize
+            } else {
+                check_len(pkt, pos, 1)?;
+                pos += 1;
+                pkt[pos - 1] as usize
+            };
+            check_len(pkt, pos, len)?;
+            pos += len;
+            pkt[(pos - len)..pos].to_vec()
+        };
+
+        let mut database = String::new();
+        if client_flag & CLIENT_CONNECT_WITH_DB != 0 {
+            database = decode_nul_string(pkt, &mut pos)?;
+        }
+
+        let mut client_plugin_name = String::new();
+        if client_flag & CLIENT_PLUGIN_AUTH != 0 {
+            client_plugin_name = decode_nul_string(pkt, &mut pos)?;
+        }
+
+        let mut connect_attrs = vec![];
+        if client_flag & CLIENT_CONNECT_ATTRS != 0 && pos < pkt.len() {
+            let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+            pos += consumed;
+            check_len(pkt, pos, len as usize)?;
+            let end = pos + len as usize;
+            while pos < end {
+                let (key, consumed) = decode_lenenc_string(pkt, pos)?;
+                pos += consumed;
+                let (value, consumed) = decode_lenenc_string(pkt, pos)?;
+                pos += consumed;
+                connect_attrs.push((key, value));
+            }
+        }
+
+        Ok(Self {
+            client_flag,
+            max_packet_size,
+            character_set,
+            filler,
+            username,
+            auth_response,
+            database,
+            client_plugin_name,
+            connect_attrs,
+        })
+    }
+}
+
+// A NUL-terminated string that may instead run to the end of the packet, with bytes that aren't
+// UTF-8 replaced; for the informational strings of the initial handshake.
+fn decode_nul_string_lossy(pkt: &[u8], pos: &mut usize) -> String {
+    let rest = pkt.get(*pos..).unwrap_or_default();
+    let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+    *pos += (len + 1).min(rest.len());
+    String::from_utf8_lossy(&rest[..len]).into_owned()
+}
+
+// Protocol::NullTerminatedString
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_dt_strings.html#sect_protocol_basic_dt_string_null
+fn decode_nul_string(pkt: &[u8], pos: &mut usize) -> Result<String> {
+    let rest = pkt.get(*pos..).unwrap_or_default();
+    let Some(len) = rest.iter().position(|&b| b == 0) else {
+        bail!("unterminated string at offset {}", pos);
+    };
+    let val = String::from_utf8(rest[..len].to_vec())?;
+    *pos += len + 1;
+    Ok(val)
+}
+
+// Protocol::AuthSwitchRequest
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_packets_protocol_auth_switch_request.html
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct AuthSwitchRequest {
+    pub status_tag: u8,
+    pub plugin_name: String,
+    pub plugin_provided_data: Vec<u8>,
+}
+
+impl AuthSwitchRequest {
+    pub fn new(plugin_name: &str, plugin_provided_data: &[u8]) -> Self {
+        Self {
+            status_tag: AUTH_SWITCH_REQUEST_HEADER,
+            plugin_name: String::from(plugin_name),
+            plugin_provided_data: plugin_provided_data.to_vec(),
         }
     }
 
     pub fn encode(&self) -> Vec<u8> {
-        let mut pkt = vec![];
-
-        pkt.append(&mut self.client_flag.to_le_bytes().to_vec());
-        pkt.append(&mut self.max_packet_size.to_le_bytes().to_vec());
-        pkt.push(self.character_set);
-        pkt.append(&mut self.filler.to_vec());
-        pkt.append(&mut self.username.as_bytes().to_vec());
-        pkt.push(0);
-        pkt.push(self.auth_response.len() as u8);
-        pkt.append(&mut self.auth_response.to_vec());
-        pkt.append(&mut self.database.as_bytes().to_vec());
-        pkt.push(0);
-        pkt.append(&mut self.client_plugin_name.as_bytes().to_vec());
-        pkt.push(0);
-
-        let mut attribute_pkt = {
-            let mut buf = vec![];
-            let attributes = vec![
-                ["_pid", "246"],
-                ["_platform", "aarch64"],
-                ["_os", "Linux"],
-                ["_client_name", "libmysql"],
-                ["os_user", "root"],
-                ["_client_version", "8.3.0"],
-                ["program_name", "mysql"],
-            ];
-            attributes.iter().for_each(|[k, v]| {
-                buf.push(k.len() as u8);
-                buf.append(&mut k.as_bytes().to_vec());
-                buf.push(v.len() as u8);
-                buf.append(&mut v.as_bytes().to_vec());
-            });
-            buf
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.status_tag);
+        w.nul_str(self.plugin_name.as_bytes());
+        w.nul_str(&self.plugin_provided_data);
+    }
+
+    pub fn decode(pkt: Vec<u8>) -> Result<Self> {
+        let mut pos = 0;
+
+        check_len(&pkt, pos, 1)?;
+        let status_tag = pkt[pos];
+        if status_tag != AUTH_SWITCH_REQUEST_HEADER {
+            bail!("not auth switch request packet");
+        }
+        pos += 1;
+
+        let plugin_name = decode_nul_string(&pkt, &mut pos)?;
+
+        // The scramble is sent NUL-terminated.
+        let plugin_provided_data = match pkt[pos..].split_last() {
+            Some((0, data)) => data.to_vec(),
+            _ => pkt[pos..].to_vec(),
         };
-        pkt.push(attribute_pkt.len() as u8);
-        pkt.append(&mut attribute_pkt);
 
-        pkt
+        Ok(Self {
+            status_tag,
+            plugin_name,
+            plugin_provided_data,
+        })
+    }
+}
+
+// Protocol::AuthNextFactor: after the first factor of an account with multi-factor authentication
+// succeeds, the server names the plugin of the next one (MULTI_FACTOR_AUTHENTICATION).
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_packets_protocol_auth_next_factor_request.html
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct AuthNextFactor {
+    pub packet_type: u8,
+    pub plugin_name: String,
+    pub plugin_provided_data: Vec<u8>,
+}
+
+impl AuthNextFactor {
+    pub fn new(plugin_name: &str, plugin_provided_data: &[u8]) -> Self {
+        Self {
+            packet_type: AUTH_NEXT_FACTOR_HEADER,
+            plugin_name: String::from(plugin_name),
+            plugin_provided_data: plugin_provided_data.to_vec(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.packet_type);
+        w.nul_str(self.plugin_name.as_bytes());
+        w.bytes(&self.plugin_provided_data);
+    }
+
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+
+        check_len(pkt, pos, 1)?;
+        let packet_type = pkt[pos];
+        if packet_type != AUTH_NEXT_FACTOR_HEADER {
+            bail!("not auth next factor packet");
+        }
+        pos += 1;
+
+        let plugin_name = decode_nul_string(pkt, &mut pos)?;
+
+        Ok(Self {
+            packet_type,
+            plugin_name,
+            plugin_provided_data: pkt[pos..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(client_flag: u32, auth_response: Vec<u8>) -> HandshakeResponse41 {
+        HandshakeResponse41::new(
+            client_flag,
+            255,
+            "app",
+            auth_response,
+            "test",
+            "mysql_clear_password",
+        )
+    }
+
+    #[test]
+    fn handshake_response_round_trips_long_auth_response() {
+        let auth_response = vec![b'x'; 300];
+        let decoded = HandshakeResponse41::decode(
+            &response(DEFAULT_CLIENT_FLAG, auth_response.clone()).encode(),
+        )
+        .unwrap();
+        assert_eq!(decoded.auth_response, auth_response);
+        assert_eq!(decoded.username, "app");
+        assert_eq!(decoded.database, "test");
+        assert_eq!(decoded.client_plugin_name, "mysql_clear_password");
+    }
+
+    #[test]
+    fn handshake_response_round_trips_long_connect_attrs() {
+        let mut response = response(DEFAULT_CLIENT_FLAG | CLIENT_CONNECT_ATTRS, vec![1; 20]);
+        response
+            .connect_attrs
+            .push((String::from("long"), "v".repeat(300)));
+        let decoded = HandshakeResponse41::decode(&response.encode()).unwrap();
+        assert_eq!(decoded.connect_attrs, response.connect_attrs);
+        assert_eq!(decoded.connect_attrs.last().unwrap().1.len(), 300);
+    }
+
+    #[test]
+    fn handshake_response_without_connect_attrs_capability_omits_them() {
+        let pkt = response(DEFAULT_CLIENT_FLAG & !CLIENT_CONNECT_ATTRS, vec![1; 20]).encode();
+        assert!(pkt.ends_with(b"mysql_clear_password\0"));
+    }
+
+    #[test]
+    fn handshake_response_without_lenenc_client_data_uses_one_byte_length() {
+        let client_flag = DEFAULT_CLIENT_FLAG & !CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA;
+        let pkt = response(client_flag, vec![7; 20]).encode();
+        // client_flag, max_packet_size, character_set, filler, "app\0"
+        assert_eq!(pkt[32 + 4], 20);
+        let decoded = HandshakeResponse41::decode(&pkt).unwrap();
+        assert_eq!(decoded.auth_response, vec![7; 20]);
+    }
+
+    #[test]
+    fn handshake_response_round_trips_mariadb_capabilities() {
+        let mut response = response(DEFAULT_CLIENT_FLAG & !CLIENT_LONG_PASSWORD, vec![1; 20]);
+        response.set_mariadb_capabilities(MARIADB_CLIENT_STMT_BULK_OPERATIONS);
+        let decoded = HandshakeResponse41::decode(&response.encode()).unwrap();
+        assert_eq!(decoded.filler, response.filler);
+        assert_eq!(
+            decoded.filler[19..],
+            MARIADB_CLIENT_STMT_BULK_OPERATIONS.to_le_bytes()
+        );
+    }
+
+    // Greetings as MySQL 8.0.36 and 5.7.44 send them.
+    const GREETING_8_0: &[u8] =
+        b"\x0a8.0.36\x00\x0d\x00\x00\x00\x1b5\x10j@6\x0fo\x00\xff\xff\xff\x02\x00\
+        \xff\xdf\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x1e\x7fbA%n}\x0bPw\x01\x00\
+        caching_sha2_password\x00";
+    const GREETING_5_7: &[u8] =
+        b"\x0a5.7.44-log\x00\x05\x00\x00\x00r\x13Yp+2$\x1c\x00\xff\xf7!\x02\x00\
+        \xff\x81\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00K7a\x14;\x08\x1f7\x0cN\x1d{\x00\
+        mysql_native_password\x00";
+
+    #[test]
+    fn handshake_v10_decodes_mysql_8_0_greeting() {
+        let handshake = HandshakeV10::decode(GREETING_8_0.to_vec()).unwrap();
+        assert_eq!(handshake.server_version(), "8.0.36");
+        assert_eq!(handshake.connection_id(), 13);
+        assert_eq!(handshake.capability_flags(), 0xdfffffff);
+        assert_eq!(handshake.character_set, 255);
+        assert_eq!(handshake.status_flags().0, StatusFlags::AUTOCOMMIT);
+        assert_eq!(
+            handshake.auth_plugin_data(),
+            b"\x1b5\x10j@6\x0fo\x03\x1e\x7fbA%n}\x0bPw\x01"
+        );
+        assert_eq!(handshake.auth_plugin_name(), "caching_sha2_password");
+        assert_eq!(handshake.mariadb_capabilities(), 0);
+    }
+
+    #[test]
+    fn handshake_v10_decodes_mysql_5_7_greeting() {
+        let handshake = HandshakeV10::decode(GREETING_5_7.to_vec()).unwrap();
+        assert_eq!(handshake.server_version(), "5.7.44-log");
+        assert_eq!(handshake.connection_id(), 5);
+        assert_eq!(handshake.capability_flags(), 0x81fff7ff);
+        assert_eq!(
+            handshake.capability_flags() & CLIENT_DEPRECATE_EOF,
+            CLIENT_DEPRECATE_EOF
+        );
+        assert_eq!(handshake.character_set, 33);
+        assert_eq!(
+            handshake.auth_plugin_data(),
+            b"r\x13Yp+2$\x1cK7a\x14;\x08\x1f7\x0cN\x1d{"
+        );
+        assert_eq!(handshake.auth_plugin_name(), "mysql_native_password");
+    }
+
+    #[test]
+    fn handshake_v10_reencodes_captured_greetings() {
+        for greeting in [GREETING_8_0, GREETING_5_7] {
+            let handshake = HandshakeV10::decode(greeting.to_vec()).unwrap();
+            assert_eq!(handshake.encode(), greeting);
+        }
+    }
+
+    #[test]
+    fn handshake_v10_rejects_truncated_greetings() {
+        for len in [0, 1, 10, 30] {
+            assert!(HandshakeV10::decode(GREETING_8_0[..len].to_vec()).is_err());
+        }
+    }
+
+    #[test]
+    fn handshake_v10_takes_as_much_auth_plugin_data_as_announced() {
+        for len in [64, 254] {
+            let challenge = (0..len).map(|i| i as u8 | 1).collect::<Vec<_>>();
+            let pkt = HandshakeV10::new(
+                "8.0.36",
+                1,
+                DEFAULT_CLIENT_FLAG,
+                255,
+                StatusFlags(StatusFlags::AUTOCOMMIT),
+                &challenge,
+                "toy_token",
+            )
+            .encode();
+            let handshake = HandshakeV10::decode(pkt).unwrap();
+            // With the trailing NUL.
+            assert_eq!(handshake.auth_plugin_data_len as usize, len + 1);
+            assert_eq!(handshake.auth_plugin_data(), challenge);
+            assert_eq!(handshake.auth_plugin_name(), "toy_token");
+        }
+    }
+
+    #[test]
+    fn auth_switch_request_round_trips() {
+        let request = AuthSwitchRequest::new("mysql_native_password", b"0123456789abcdefghij");
+        let decoded = AuthSwitchRequest::decode(request.encode()).unwrap();
+        assert_eq!(decoded.plugin_name, "mysql_native_password");
+        assert_eq!(decoded.plugin_provided_data, b"0123456789abcdefghij");
+    }
+
+    #[test]
+    fn auth_switch_request_rejects_truncated_packets() {
+        assert!(AuthSwitchRequest::decode(vec![]).is_err());
+        assert!(AuthSwitchRequest::decode(vec![AUTH_SWITCH_REQUEST_HEADER]).is_err());
+        assert!(AuthSwitchRequest::decode(b"\xfemysql_native".to_vec()).is_err());
+    }
+
+    #[test]
+    fn auth_next_factor_round_trips() {
+        let request = AuthNextFactor::new("authentication_fido", b"\x00nonce\x00");
+        let decoded = AuthNextFactor::decode(&request.encode()).unwrap();
+        assert_eq!(decoded.plugin_name, "authentication_fido");
+        assert_eq!(decoded.plugin_provided_data, b"\x00nonce\x00");
+        assert!(AuthNextFactor::decode(&[]).is_err());
+        assert!(AuthNextFactor::decode(b"\x02authentication_fido").is_err());
+        assert!(AuthNextFactor::decode(b"\xfeauthentication_fido\x00").is_err());
     }
 }
@@ -0,0 +1,69 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use anyhow::{Result, anyhow};
+use log::debug;
+
+use crate::{
+    connection::{Connection, ConnectionOptions},
+    deadline::Deadline,
+};
+
+// The host:port pairs to try, in order: `hosts` when given, `host`/`port` otherwise.
+pub(crate) fn candidates(options: &ConnectionOptions) -> Vec<(String, u16)> {
+    let mut hosts = if options.hosts.is_empty() {
+        vec![(options.host.clone(), options.port)]
+    } else {
+        options.hosts.clone()
+    };
+    if options.randomize_hosts {
+        shuffle(&mut hosts);
+    }
+    hosts
+}
+
+// Fisher-Yates with the randomness std already has (the per-process random hash keys).
+fn shuffle<T>(items: &mut [T]) {
+    let state = RandomState::new();
+    for i in (1..items.len()).rev() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        let j = hasher.finish() as usize % (i + 1);
+        items.swap(i, j);
+    }
+}
+
+impl Connection {
+    // Tries each candidate host until one accepts the connection and the handshake succeeds.
+    pub(crate) fn connect_any(options: ConnectionOptions, deadline: &Deadline) -> Result<Self> {
+        let mut errors = vec![];
+        for (host, port) in candidates(&options) {
+            let options = ConnectionOptions {
+                host: host.clone(),
+                port,
+                ..options.clone()
+            };
+            match Self::connect_following_redirects(options, deadline) {
+                Ok(conn) => return Ok(conn),
+                Err(err) => {
+                    debug!("failed to connect to {}:{}: {}", host, port, err);
+                    errors.push((format!("{}:{}", host, port), err));
+                }
+            }
+        }
+        // A single host keeps its error as is, so callers can still inspect it.
+        if errors.len() == 1 {
+            return Err(errors.remove(0).1);
+        }
+        let errors = errors
+            .iter()
+            .map(|(addr, err)| format!("{}: {}", addr, err))
+            .collect::<Vec<_>>();
+        Err(anyhow!(
+            "could not connect to any host ({})",
+            errors.join("; ")
+        ))
+    }
+}
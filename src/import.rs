@@ -0,0 +1,306 @@
+use std::io::{BufRead, BufReader, Read};
+
+use anyhow::{Result, anyhow, bail};
+use log::debug;
+
+use crate::{
+    command::ErrPacket, connection::Connection, schema::ColumnInfo, utils::quote_identifier,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    // Abort the import on the first bad row.
+    Stop,
+    // Leave bad rows out and carry on.
+    Skip,
+    // Like `Skip`, but also report every bad row in `CsvImportReport::errors`.
+    Collect,
+}
+
+pub struct CsvImportOptions {
+    pub has_header: bool,
+    // Target columns in CSV field order. When empty, the header row is used.
+    pub columns: Vec<String>,
+    pub batch_rows: usize,
+    pub on_error: OnError,
+    // Called with the number of records processed so far, every `progress_every` records.
+    pub progress: Option<Box<dyn FnMut(u64)>>,
+    pub progress_every: u64,
+}
+
+impl Default for CsvImportOptions {
+    fn default() -> Self {
+        Self {
+            has_header: true,
+            columns: vec![],
+            batch_rows: 1000,
+            on_error: OnError::Stop,
+            progress: None,
+            progress_every: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvImportError {
+    // 1-based record number in the file, the header included.
+    pub row: u64,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CsvImportReport {
+    pub rows_inserted: u64,
+    pub rows_skipped: u64,
+    pub errors: Vec<CsvImportError>,
+}
+
+type Record = Vec<Option<String>>;
+
+impl Connection {
+    // Loads CSV (RFC 4180, as written by `dump_table`) into `table` with batched multi-row INSERTs.
+    // An unquoted \N field is NULL. Values are checked against the column types from
+    // `describe_table` so bad rows are reported with their position instead of failing a whole batch.
+    pub fn import_csv(
+        &mut self,
+        table: &str,
+        reader: impl Read,
+        mut options: CsvImportOptions,
+    ) -> Result<CsvImportReport> {
+        debug!("import_csv start: {}", table);
+        let mut reader = BufReader::new(reader);
+        let mut record_no = 0;
+
+        let header = if options.has_header {
+            record_no += 1;
+            read_record(&mut reader)?
+        } else {
+            None
+        };
+        let names = if !options.columns.is_empty() {
+            options.columns.clone()
+        } else if let Some(header) = header {
+            header.into_iter().map(Option::unwrap_or_default).collect()
+        } else {
+            bail!("no column list: set CsvImportOptions::columns or has_header");
+        };
+
+        let described = self.describe_table(table)?;
+        let columns = names
+            .iter()
+            .map(|name| {
+                described
+                    .iter()
+                    .find(|col| col.field.eq_ignore_ascii_case(name))
+                    .cloned()
+                    .ok_or_else(|| anyhow!("table {} has no column {}", table, name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut report = CsvImportReport::default();
+        let mut batch: Vec<(u64, Record)> = vec![];
+        while let Some(record) = read_record(&mut reader)? {
+            record_no += 1;
+            match check_record(&columns, &record) {
+                Ok(()) => batch.push((record_no, record)),
+                Err((column, message)) => {
+                    let err = CsvImportError {
+                        row: record_no,
+                        column,
+                        message,
+                    };
+                    record_error(&mut report, options.on_error, err)?;
+                }
+            }
+            if batch.len() >= options.batch_rows.max(1) {
+                self.insert_batch(table, &columns, &mut batch, options.on_error, &mut report)?;
+            }
+            let processed = record_no - options.has_header as u64;
+            if let Some(progress) = options.progress.as_mut()
+                && options.progress_every > 0
+                && processed.is_multiple_of(options.progress_every)
+            {
+                progress(processed);
+            }
+        }
+        self.insert_batch(table, &columns, &mut batch, options.on_error, &mut report)?;
+        debug!("import_csv done: {:?}", report);
+        Ok(report)
+    }
+
+    fn insert_batch(
+        &mut self,
+        table: &str,
+        columns: &[ColumnInfo],
+        batch: &mut Vec<(u64, Record)>,
+        on_error: OnError,
+        report: &mut CsvImportReport,
+    ) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let records = batch.iter().map(|(_, record)| record);
+        match self.query(&insert_statement(table, columns, records, |s| {
+            self.escape_string(s)
+        })) {
+            Ok(result) => report.rows_inserted += result.affected_rows(),
+            Err(err) if on_error != OnError::Stop && err.is::<ErrPacket>() => {
+                // Find the offending rows by inserting the batch one row at a time.
+                for (record_no, record) in batch.iter() {
+                    match self.query(&insert_statement(table, columns, [record], |s| {
+                        self.escape_string(s)
+                    })) {
+                        Ok(result) => report.rows_inserted += result.affected_rows(),
+                        Err(err) if err.is::<ErrPacket>() => {
+                            let err = CsvImportError {
+                                row: *record_no,
+                                column: None,
+                                message: err.to_string(),
+                            };
+                            record_error(report, on_error, err)?;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        batch.clear();
+        Ok(())
+    }
+}
+
+fn record_error(
+    report: &mut CsvImportReport,
+    on_error: OnError,
+    err: CsvImportError,
+) -> Result<()> {
+    match on_error {
+        OnError::Stop => bail!(
+            "row {}{}: {}",
+            err.row,
+            err.column
+                .map(|col| format!(", column {}", col))
+                .unwrap_or_default(),
+            err.message
+        ),
+        OnError::Skip => report.rows_skipped += 1,
+        OnError::Collect => {
+            report.rows_skipped += 1;
+            report.errors.push(err);
+        }
+    }
+    Ok(())
+}
+
+fn check_record(columns: &[ColumnInfo], record: &Record) -> Result<(), (Option<String>, String)> {
+    if record.len() != columns.len() {
+        return Err((
+            None,
+            format!("expected {} fields, found {}", columns.len(), record.len()),
+        ));
+    }
+    for (col, field) in columns.iter().zip(record) {
+        let fail = |message: String| Err((Some(col.field.clone()), message));
+        match field {
+            None if !col.null => return fail(String::from("NULL in a NOT NULL column")),
+            Some(val) if col.is_integer() && val.trim().parse::<i128>().is_err() => {
+                return fail(format!("{:?} is not a valid {}", val, col.type_));
+            }
+            Some(val) if col.is_numeric() && val.trim().parse::<f64>().is_err() => {
+                return fail(format!("{:?} is not a valid {}", val, col.type_));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// `escape` is `Connection::escape_string`, so the values are escaped for the session's sql_mode.
+fn insert_statement<'a>(
+    table: &str,
+    columns: &[ColumnInfo],
+    records: impl IntoIterator<Item = &'a Record>,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let column_list = columns
+        .iter()
+        .map(|col| quote_identifier(&col.field))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = records
+        .into_iter()
+        .map(|record| {
+            let fields = record
+                .iter()
+                .map(|field| match field {
+                    None => String::from("NULL"),
+                    Some(val) => format!("'{}'", escape(val)),
+                })
+                .collect::<Vec<_>>();
+            format!("({})", fields.join(","))
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        quote_identifier(table),
+        column_list,
+        values.join(",")
+    )
+}
+
+// Reads one CSV record. Quoted fields may contain separators, doubled quotes and line breaks.
+fn read_record(reader: &mut impl BufRead) -> Result<Option<Record>> {
+    let mut line = vec![];
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let mut record = vec![];
+    let mut field = vec![];
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut pos = 0;
+    loop {
+        if pos == line.len() {
+            if !in_quotes {
+                break;
+            }
+            // The line break belongs to a quoted field; continue with the next line.
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                bail!("unterminated quoted field");
+            }
+            continue;
+        }
+        let b = line[pos];
+        pos += 1;
+        match b {
+            b'"' if in_quotes && line.get(pos) == Some(&b'"') => {
+                field.push(b'"');
+                pos += 1;
+            }
+            b'"' if in_quotes => in_quotes = false,
+            b'"' if field.is_empty() && !quoted => {
+                quoted = true;
+                in_quotes = true;
+            }
+            b',' if !in_quotes => {
+                record.push(finish_field(&mut field, quoted)?);
+                quoted = false;
+            }
+            b'\r' | b'\n' if !in_quotes => break,
+            _ => field.push(b),
+        }
+    }
+    record.push(finish_field(&mut field, quoted)?);
+    Ok(Some(record))
+}
+
+fn finish_field(field: &mut Vec<u8>, quoted: bool) -> Result<Option<String>> {
+    let val = String::from_utf8(std::mem::take(field))?;
+    if !quoted && val == "\\N" {
+        return Ok(None);
+    }
+    Ok(Some(val))
+}
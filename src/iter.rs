@@ -0,0 +1,263 @@
+use std::io::{self, Read, Write};
+
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{
+    command::{ColumnDefinition41, ErrPacket, ResultsetRow},
+    connection::{Connection, RowPacket},
+    consts::{EOF_HEADER, ERR_HEADER, LENENC_NULL, LENENC_U16, LENENC_U24, LENENC_U64},
+    decode::PacketKind,
+    error::Error,
+    result::{ProgressHandler, ProgressInterval, ProgressTracker, Terminator},
+    state::ConnectionState,
+};
+
+// Rows of a result set read one by one from the connection instead of being collected up front.
+//
+// The connection stays in `ConnectionState::StreamingResult` until every row has been read.
+// Dropping the iterator early reads and discards the remaining rows (and any further results) so the
+// connection can be used again. An io or protocol error mid-stream leaves the connection `Broken`.
+#[derive(Debug)]
+pub struct ResultSetIter<'a> {
+    conn: &'a mut Connection,
+    columns: Vec<ColumnDefinition41>,
+    terminator: Option<Terminator>,
+    // Set when the server aborted the result set with an error.
+    failed: bool,
+    progress: Option<ProgressTracker>,
+}
+
+impl<'a> ResultSetIter<'a> {
+    pub(crate) fn new(
+        conn: &'a mut Connection,
+        columns: Vec<ColumnDefinition41>,
+        terminator: Option<Terminator>,
+    ) -> Self {
+        Self {
+            conn,
+            columns,
+            terminator,
+            failed: false,
+            progress: None,
+        }
+    }
+
+    // Calls `handler` every `interval` of rows read, as `QueryOptions::on_progress` does for
+    // collected result sets.
+    pub fn with_progress(mut self, handler: ProgressHandler, interval: ProgressInterval) -> Self {
+        self.progress = Some(ProgressTracker::new(handler, interval));
+        self
+    }
+
+    pub fn columns(&self) -> &[ColumnDefinition41] {
+        &self.columns
+    }
+
+    // The packet that ended the result set, once every row has been read.
+    pub fn terminator(&self) -> Option<&Terminator> {
+        self.terminator.as_ref()
+    }
+
+    // Like `next`, but the value of every column with a sink in `sinks` (by column index) is copied
+    // into that sink as it arrives from the socket instead of being collected, so a huge LONGBLOB
+    // can go straight to a file. The other columns are decoded as usual. Returns `None` after the
+    // last row.
+    //
+    // The connection is broken if a sink fails, since the rest of the row can't be skipped reliably.
+    pub fn next_row_streaming(
+        &mut self,
+        sinks: &mut [Option<&mut dyn Write>],
+    ) -> Result<Option<RowSummary>> {
+        if self.terminator.is_some() || self.failed {
+            return Ok(None);
+        }
+        if self.conn.state() == ConnectionState::Broken {
+            bail!(Error::Broken);
+        }
+        match self.conn.read_row_streaming(self.columns.len(), sinks) {
+            Ok(StreamedRow::Row(row)) => {
+                self.conn.count_row();
+                if let Some(progress) = &mut self.progress {
+                    progress.row(row.byte_counts().iter().sum());
+                }
+                Ok(Some(row))
+            }
+            Ok(StreamedRow::End(terminator)) => {
+                self.finish(terminator)?;
+                Ok(None)
+            }
+            Err(err) => self.fail(err),
+        }
+    }
+
+    // A server error ends the result set and leaves the connection usable; anything else breaks it.
+    fn fail<T>(&mut self, err: anyhow::Error) -> Result<T> {
+        self.failed = true;
+        self.conn.end_command(Err(err))
+    }
+
+    fn finish(&mut self, terminator: Terminator) -> Result<()> {
+        let more_results = terminator.status_flags().more_results_exists();
+        self.terminator = Some(terminator);
+        let read = if more_results {
+            self.conn.set_state(ConnectionState::QueryInFlight);
+            self.conn.read_results(|_| {})
+        } else {
+            Ok(())
+        };
+        self.conn.end_command(read)
+    }
+}
+
+impl Iterator for ResultSetIter<'_> {
+    type Item = Result<ResultsetRow>;
+
+    // The iterator ends after an error. A server error (e.g. max_execution_time exceeded) leaves the
+    // connection usable, any other error leaves it broken.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.terminator.is_some() || self.failed || self.conn.state() == ConnectionState::Broken
+        {
+            return None;
+        }
+        match self.conn.read_row() {
+            Ok(RowPacket::Row(row)) => {
+                self.conn.count_row();
+                if let Some(progress) = &mut self.progress {
+                    progress.row(row.0.iter().flatten().map(|v| v.len() as u64).sum());
+                }
+                Some(Ok(row))
+            }
+            Ok(RowPacket::End(terminator)) => self.finish(terminator).err().map(Err),
+            Err(err) => Some(self.fail(err)),
+        }
+    }
+}
+
+impl Drop for ResultSetIter<'_> {
+    fn drop(&mut self) {
+        // Nothing can be drained from a broken connection; whatever is left unread stays unread.
+        if self.terminator.is_some() || self.failed || self.conn.state() == ConnectionState::Broken
+        {
+            return;
+        }
+        let drained = loop {
+            match self.conn.read_row() {
+                Ok(RowPacket::Row(_)) => {}
+                Ok(RowPacket::End(terminator)) => break self.finish(terminator),
+                Err(err) => break self.conn.end_command(Err(err)),
+            }
+        };
+        if let Err(err) = drained {
+            debug!("failed to drain result set: {}", err);
+        }
+    }
+}
+
+// A cell read by `ResultSetIter::next_row_streaming`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamedCell {
+    Null,
+    Value(Vec<u8>),
+    // Copied into the column's sink; holds the number of bytes written.
+    Written(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowSummary {
+    pub cells: Vec<StreamedCell>,
+}
+
+impl RowSummary {
+    // Size of each cell in bytes, 0 for NULL.
+    pub fn byte_counts(&self) -> Vec<u64> {
+        self.cells
+            .iter()
+            .map(|cell| match cell {
+                StreamedCell::Null => 0,
+                StreamedCell::Value(value) => value.len() as u64,
+                StreamedCell::Written(len) => *len,
+            })
+            .collect()
+    }
+}
+
+enum StreamedRow {
+    Row(RowSummary),
+    End(Terminator),
+}
+
+impl Connection {
+    // ProtocolText::ResultsetRow, decoded while it is read rather than from a collected packet.
+    fn read_row_streaming(
+        &mut self,
+        column_count: usize,
+        sinks: &mut [Option<&mut dyn Write>],
+    ) -> Result<StreamedRow> {
+        let mut reader = self.payload_reader()?;
+        let first = read_u8(&mut reader)?;
+        // An OK packet (an EOF packet without CLIENT_DEPRECATE_EOF) ends the result set; a row never
+        // starts with 0xfe unless its first value alone is 16MB or more, which takes several frames.
+        if first == EOF_HEADER && !reader.is_multi_frame() {
+            let mut pkt = vec![first];
+            reader.read_to_end(&mut pkt)?;
+            return Ok(StreamedRow::End(self.decode_terminator(pkt)?));
+        }
+        if first == ERR_HEADER {
+            let mut pkt = vec![first];
+            reader.read_to_end(&mut pkt)?;
+            bail!(self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?);
+        }
+        let mut cells = Vec::with_capacity(column_count);
+        let mut next_byte = Some(first);
+        for i in 0..column_count {
+            let first = match next_byte.take() {
+                Some(b) => b,
+                None => read_u8(&mut reader)?,
+            };
+            let len = match first {
+                LENENC_NULL => {
+                    cells.push(StreamedCell::Null);
+                    continue;
+                }
+                LENENC_U16 => read_uint(&mut reader, 2)?,
+                LENENC_U24 => read_uint(&mut reader, 3)?,
+                LENENC_U64 => read_uint(&mut reader, 8)?,
+                len => len as u64,
+            };
+            let mut value = (&mut reader).take(len);
+            let (cell, read) = match sinks.get_mut(i).and_then(Option::as_mut) {
+                Some(sink) => {
+                    let written = io::copy(&mut value, sink)?;
+                    (StreamedCell::Written(written), written)
+                }
+                None => {
+                    let mut buf = vec![];
+                    value.read_to_end(&mut buf)?;
+                    let read = buf.len() as u64;
+                    (StreamedCell::Value(buf), read)
+                }
+            };
+            if read != len {
+                bail!("row ended after {} of {} bytes of column {}", read, len, i);
+            }
+            cells.push(cell);
+        }
+        if !reader.is_finished() {
+            bail!("row has more values than the {} columns", column_count);
+        }
+        Ok(StreamedRow::Row(RowSummary { cells }))
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_uint(reader: &mut impl Read, len: usize) -> Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf[..len])?;
+    Ok(u64::from_le_bytes(buf))
+}
@@ -1,5 +1,57 @@
+pub mod admin;
+#[cfg(feature = "tokio")]
+pub mod async_connection;
+pub mod auth;
+pub mod bulk_insert;
+pub mod charset;
+pub mod command;
 pub mod connection;
+pub mod consts;
+pub mod deadline;
+#[cfg(feature = "deadpool")]
+pub mod deadpool;
+pub mod decode;
+pub mod dump;
+pub mod error;
+pub mod explain;
+pub mod handshake;
+pub mod hosts;
+pub mod import;
+pub mod iter;
+pub mod packet_writer;
+pub mod packets;
+pub mod pipeline;
+pub mod progress_report;
+pub mod query_builder;
+pub mod query_writer;
+#[cfg(feature = "r2d2")]
+pub mod r2d2;
+pub mod raw;
+pub mod reconnect;
+pub mod redirect;
+pub mod result;
+pub mod retry;
+pub mod routing;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod server_stats;
+pub mod server_version;
+pub mod session;
+pub mod slow_query;
+pub mod sql;
+pub mod sql_mode;
+pub mod state;
+pub mod statement;
+pub mod strict;
+pub mod table;
+pub mod upsert;
+pub mod url;
+pub mod utils;
+pub mod value;
+pub mod warnings;
 
-mod command;
-mod handshake;
-mod utils;
+mod encoding;
+mod rewrite;
+mod transport;
+mod variables;
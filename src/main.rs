@@ -1,18 +1,76 @@
-use std::io::{self, Write};
+use std::{
+    env,
+    io::{self, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
-use anyhow::Result;
-use toy_mysql_client::connection::{Connection, ConnectionOptions};
+use anyhow::{Context, Result, bail};
+use toy_mysql_client::{
+    command::ErrPacket,
+    connection::{Connection, ConnectionOptions},
+    explain::ExplainRow,
+    result::{ProgressHandler, ProgressInterval, QueryOptions, QueryResult},
+    slow_query::SlowQueryHandler,
+    table,
+};
+
+// Keeps an unbounded SELECT from flooding the terminal.
+const DEFAULT_MAX_ROWS: u64 = 10_000;
+
+// Queries still reading rows after this long get a row counter.
+const PROGRESS_AFTER: Duration = Duration::from_secs(2);
 
 fn main() -> Result<()> {
     env_logger::init();
 
+    let mut slow_query_threshold = None;
+    let mut max_width = None;
+    let mut max_rows = Some(DEFAULT_MAX_ROWS);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--slow-ms" => {
+                let ms = args.next().context("--slow-ms needs a value")?;
+                slow_query_threshold = Some(Duration::from_millis(ms.parse()?));
+            }
+            "--max-rows" => {
+                let rows = args.next().context("--max-rows needs a value")?;
+                max_rows = Some(rows.parse()?).filter(|&rows| rows > 0);
+            }
+            "--max-width" => {
+                let width = args.next().context("--max-width needs a value")?;
+                max_width = Some(width.parse()?);
+            }
+            _ => bail!("unknown argument: {}", arg),
+        }
+    }
+
     let mut conn = Connection::new(ConnectionOptions {
         username: String::from("root"),
         password: String::from("root"),
         database: String::from("test"),
         host: String::from("127.0.0.1"),
         port: 3306,
+        slow_query_threshold,
+        slow_query_handler: Some(SlowQueryHandler::new(|slow| {
+            eprintln!(
+                "\x1b[33mslow query ({:?}, {} rows): {}\x1b[0m",
+                slow.duration, slow.rows, slow.sql
+            );
+        })),
+        ..Default::default()
     })?;
+    let progress_shown = Arc::new(AtomicBool::new(false));
+    let query_options = QueryOptions {
+        max_rows,
+        on_progress: Some(progress_counter(progress_shown.clone())),
+        progress_interval: ProgressInterval::Rows(1000),
+        ..Default::default()
+    };
     let mut buf = String::new();
     loop {
         print!("mysql> ");
@@ -22,11 +80,113 @@ fn main() -> Result<()> {
         let sql = buf.trim();
         match sql {
             "exit" | "exit;" => break,
+            "\\s" | "status" => print_status(&conn),
+            _ if sql.starts_with("\\e") => match conn.explain(&sql[2..]) {
+                Ok(plan) => print_plan(&plan, max_width),
+                Err(err) if !conn.is_broken() => println!("{:#}", err),
+                Err(err) => return Err(err),
+            },
             _ => {
-                let result = conn.query(sql)?;
-                println!("{}", result);
+                // As in the mysql client, `\G` in place of `;` prints the rows vertically.
+                let (sql, vertical) = match sql.strip_suffix("\\G") {
+                    Some(sql) => (sql, true),
+                    None => (sql, false),
+                };
+                let results = conn.query_all_with(sql, &query_options);
+                if progress_shown.swap(false, Ordering::Relaxed) {
+                    eprint!("\r\x1b[K");
+                }
+                match results {
+                    Ok(results) => {
+                        for result in results {
+                            print_result(&result, max_width, vertical);
+                        }
+                    }
+                    Err(err) if err.is::<ErrPacket>() => println!("{}", err),
+                    Err(err) => return Err(err),
+                }
             }
         }
     }
     Ok(())
 }
+
+// A spinner and row counter on stderr, once a result set has been coming in for a while.
+fn progress_counter(shown: Arc<AtomicBool>) -> ProgressHandler {
+    const SPINNER: &[char] = &['|', '/', '-', '\\'];
+    ProgressHandler::new(move |progress| {
+        if progress.elapsed < PROGRESS_AFTER {
+            return;
+        }
+        let frame = SPINNER[(progress.rows / 1000) as usize % SPINNER.len()];
+        eprint!("\r{} {} rows", frame, progress.rows);
+        shown.store(true, Ordering::Relaxed);
+    })
+}
+
+fn print_status(conn: &Connection) {
+    println!("Server version:\t\t{:?}", conn.server_version());
+    println!("Connection state:\t{:?}", conn.state());
+    println!(
+        "Current database:\t{}",
+        conn.current_database().unwrap_or_default()
+    );
+    println!("In transaction:\t\t{}", conn.in_transaction());
+}
+
+fn print_plan(plan: &[ExplainRow], max_width: Option<usize>) {
+    let header = [
+        "id",
+        "select_type",
+        "table",
+        "type",
+        "possible_keys",
+        "key",
+        "key_len",
+        "ref",
+        "rows",
+        "filtered",
+        "Extra",
+    ];
+    let rows = plan
+        .iter()
+        .map(|row| {
+            vec![
+                row.id.map(|id| id.to_string()),
+                Some(row.select_type.clone()),
+                row.table.clone(),
+                row.type_.clone(),
+                row.possible_keys.clone(),
+                row.key.clone(),
+                row.key_len.clone(),
+                row.ref_.clone(),
+                row.rows.map(|n| n.to_string()),
+                row.filtered.map(|f| f.to_string()),
+                row.extra.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    // id, rows and filtered are numbers.
+    let right_align = header.map(|name| matches!(name, "id" | "rows" | "filtered"));
+    print!("{}", table::render(&header, &rows, &right_align, max_width));
+}
+
+fn print_result(result: &QueryResult, max_width: Option<usize>, vertical: bool) {
+    // No precision means no limit.
+    let max_width = max_width.unwrap_or(usize::MAX);
+    if vertical {
+        println!("{:#.*}", max_width, result);
+    } else {
+        println!("{:.*}", max_width, result);
+    }
+    if let QueryResult::ResultSet(rs) = result
+        && rs.truncated.is_some()
+    {
+        println!(
+            "(only the first {} rows are shown; run with --max-rows N to change the limit, or 0 for \
+             none)",
+            rs.rows.len()
+        );
+    }
+    println!();
+}
@@ -0,0 +1,88 @@
+use crate::utils::{encode_lenenc_integer, encode_lenenc_string};
+
+// Builds the payload of an outgoing packet. The buffer is kept between packets (see `clear`), so a
+// connection encoding its commands into the same writer stops allocating once it has grown to the
+// size of its usual commands. Every encoder in `command`, `handshake` and `statement` writes
+// through one.
+#[derive(Debug, Default)]
+pub struct PacketWriter {
+    buf: Vec<u8>,
+}
+
+impl PacketWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Runs `write` on a fresh writer and returns what it wrote, for encoding a single packet.
+    pub fn encode(write: impl FnOnce(&mut Self)) -> Vec<u8> {
+        let mut writer = Self::new();
+        write(&mut writer);
+        writer.buf
+    }
+
+    // Empties the buffer for the next packet, keeping its capacity.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    // Protocol::FixedLengthInteger
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_dt_integers.html#sect_protocol_basic_dt_int_fixed
+    pub fn u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    pub fn u16_le(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u32_le(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u64_le(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn lenenc_int(&mut self, val: u64) {
+        encode_lenenc_integer(&mut self.buf, val);
+    }
+
+    pub fn lenenc_str(&mut self, val: &[u8]) {
+        encode_lenenc_string(&mut self.buf, val);
+    }
+
+    // Protocol::NulTerminatedString
+    pub fn nul_str(&mut self, val: &[u8]) {
+        self.buf.extend_from_slice(val);
+        self.buf.push(0);
+    }
+
+    pub fn bytes(&mut self, val: &[u8]) {
+        self.buf.extend_from_slice(val);
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    // Gives the memory back after an unusually large packet (e.g. a multi-row INSERT), rather than
+    // keeping it for the life of the connection.
+    pub(crate) fn release_if_larger_than(&mut self, capacity: usize) {
+        if self.buf.capacity() > capacity {
+            self.buf = Vec::new();
+        }
+    }
+}
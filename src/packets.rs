@@ -0,0 +1,45 @@
+use std::io::Read;
+
+use anyhow::Result;
+
+// Payloads of 16MB - 1 bytes or more are split across frames; a frame shorter than that ends the
+// packet (which may take an empty one).
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_packets.html
+const MAX_FRAME_LEN: usize = 0xffffff;
+
+// MySQL Packets
+// Wraps a payload in frames of a 3-byte length and a sequence id, the first one being `seq`. With
+// this and `deframe` both sides of an exchange can be written without a `Connection`, e.g. a toy
+// server or proxy built from the encode/decode pairs in `handshake` and `command`.
+pub fn frame(payload: &[u8], seq: u8) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(payload.len() + 4);
+    let mut seq = seq;
+    let mut chunks = payload.chunks(MAX_FRAME_LEN);
+    loop {
+        let chunk = chunks.next().unwrap_or_default();
+        buf.extend_from_slice(&(chunk.len() as u32).to_le_bytes()[..3]);
+        buf.push(seq);
+        buf.extend_from_slice(chunk);
+        seq = seq.wrapping_add(1);
+        if chunk.len() < MAX_FRAME_LEN {
+            return buf;
+        }
+    }
+}
+
+// Reads one packet, joining its frames. Returns the payload and the sequence id the next packet of
+// the exchange (e.g. the reply to it) goes out with.
+pub fn deframe(reader: &mut impl Read) -> Result<(Vec<u8>, u8)> {
+    let mut payload = vec![];
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let frame_len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let start = payload.len();
+        payload.resize(start + frame_len, 0);
+        reader.read_exact(&mut payload[start..])?;
+        if frame_len < MAX_FRAME_LEN {
+            return Ok((payload, header[3].wrapping_add(1)));
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use log::debug;
+
+use crate::{command::ErrPacket, connection::Connection, result::QueryResult};
+
+// Sends several COM_QUERY commands back-to-back and reads the responses afterwards, so a batch of
+// independent statements costs one round trip instead of one per statement.
+//
+// Caveats:
+// - The statements are already on the wire when the first response is read, so a failing statement
+//   does not stop the ones queued after it. Each failure is reported in its own slot of the result.
+// - Only use it for statements that don't depend on each other's session side effects (e.g. a `USE`,
+//   `SET` or transaction statement whose outcome a later statement relies on), since there is no
+//   chance to react between them.
+// - Every response is buffered by the server until it is read, so keep batches reasonably small.
+#[derive(Debug)]
+pub struct Pipeline<'a> {
+    conn: &'a mut Connection,
+    queries: Vec<String>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub(crate) fn new(conn: &'a mut Connection) -> Self {
+        Self {
+            conn,
+            queries: vec![],
+        }
+    }
+
+    pub fn query(&mut self, sql: &str) -> &mut Self {
+        self.queries.push(String::from(sql));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    // The outer error means the connection itself failed and is no longer in sync; the inner ones are
+    // the per-statement outcomes, in the order the statements were queued.
+    pub fn send(self) -> Result<Vec<Result<QueryResult>>> {
+        debug!("pipeline start: {} queries", self.queries.len());
+        let commands = self
+            .queries
+            .iter()
+            .map(|sql| {
+                let sql = self.conn.rewrite(sql);
+                self.conn.com_query(&sql)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.conn.begin_command()?;
+        // The sequence id each response starts at: a response continues the sequence of its
+        // command, which takes more than one frame for a statement of 16MB or more.
+        let mut response_sequences = Vec::with_capacity(commands.len());
+        for com_query in &commands {
+            // Each command starts a new sequence.
+            self.conn.set_sequence(0);
+            self.conn.buffer_packet_with(|w| com_query.write_to(w))?;
+            response_sequences.push(self.conn.sequence());
+        }
+        self.conn.flush()?;
+
+        let mut results = vec![];
+        for sequence in response_sequences {
+            self.conn.set_sequence(sequence);
+            let mut first = None;
+            match self.conn.read_results(|result| {
+                first.get_or_insert(result);
+            }) {
+                Ok(()) => results.push(Ok(first.expect("at least one result is read"))),
+                Err(err) if err.is::<ErrPacket>() => results.push(Err(err)),
+                Err(err) => return self.conn.end_command(Err(err)),
+            }
+        }
+        self.conn.end_command(Ok(()))?;
+        debug!("pipeline done");
+        Ok(results)
+    }
+}
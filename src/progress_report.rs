@@ -0,0 +1,156 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{
+    connection::Connection,
+    consts::ERR_HEADER,
+    decode::{PacketKind, check_len},
+    handshake::MARIADB_CLIENT_PROGRESS,
+    utils::decode_lenenc_string,
+};
+
+// The error code that marks an ERR packet as a progress report.
+pub const PROGRESS_REPORT_ERROR_CODE: u16 = 0xffff;
+
+// A progress report MariaDB sends while a long statement runs (ALTER TABLE, LOAD DATA, CREATE
+// INDEX, ...), with `ConnectionOptions::mariadb_progress_reports`. It arrives before the statement's
+// OK or ERR and doesn't end it.
+// https://mariadb.com/kb/en/progress-reporting/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressReport {
+    // Starting at 1; ALTER TABLE e.g. copies the rows in stage 1 and rebuilds the indexes in 2.
+    pub stage: u8,
+    pub max_stage: u8,
+    // Of the current stage, from 0.0 to 1.0.
+    pub progress: f64,
+    // What the server is doing, e.g. "copy to tmp table".
+    pub info: String,
+}
+
+impl ProgressReport {
+    // 0xff, the error code 0xffff, the number of fields that follow (1), stage, max_stage,
+    // progress in thousandths of a percent (int<3>), info (string<lenenc>).
+    pub fn decode(pkt: &[u8]) -> Result<Self> {
+        if !is_progress_report(pkt) {
+            bail!("not progress report packet");
+        }
+        let mut pos = 1 + 2 + 1;
+        check_len(pkt, pos, 5)?;
+        let stage = pkt[pos];
+        let max_stage = pkt[pos + 1];
+        pos += 2;
+        let progress = u32::from_le_bytes([pkt[pos], pkt[pos + 1], pkt[pos + 2], 0]);
+        pos += 3;
+        let (info, _) = decode_lenenc_string(pkt, pos)?;
+        Ok(Self {
+            stage,
+            max_stage,
+            progress: (progress as f64 / 100_000.0).min(1.0),
+            info,
+        })
+    }
+}
+
+pub fn is_progress_report(pkt: &[u8]) -> bool {
+    pkt.len() >= 3 && pkt[0] == ERR_HEADER && pkt[1..3] == PROGRESS_REPORT_ERROR_CODE.to_le_bytes()
+}
+
+// Called with each progress report of a statement run with `QueryOptions::on_progress_report`.
+#[derive(Clone)]
+pub struct ProgressReportHandler(pub Arc<Mutex<dyn FnMut(ProgressReport) + Send>>);
+
+impl ProgressReportHandler {
+    pub fn new(handler: impl FnMut(ProgressReport) + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(handler)))
+    }
+}
+
+impl fmt::Debug for ProgressReportHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressReportHandler")
+    }
+}
+
+impl Connection {
+    // Whether `pkt` is a progress report to pass on rather than a response packet. Only servers
+    // that were asked for them send them, so an ERR with code 0xffff is otherwise left alone.
+    pub(crate) fn take_progress_report(&mut self, pkt: &[u8]) -> Result<bool> {
+        if self.mariadb_capabilities & MARIADB_CLIENT_PROGRESS == 0 || !is_progress_report(pkt) {
+            return Ok(false);
+        }
+        let report = self.decode_packet(PacketKind::ProgressReport, pkt, ProgressReport::decode)?;
+        debug!(
+            "progress report: stage {}/{} {:.1}% {}",
+            report.stage,
+            report.max_stage,
+            report.progress * 100.0,
+            report.info
+        );
+        if let Some(ProgressReportHandler(handler)) = &self.progress_report_handler {
+            // A handler that panicked before is still called; its state is its own business.
+            let mut handler = handler.lock().unwrap_or_else(|err| err.into_inner());
+            handler(report);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Progress reports of a MariaDB 10.11 `ALTER TABLE t ADD INDEX (name)`: copying the rows, then
+    // building the index.
+    const COPYING: &[u8] = b"\xff\xff\xff\x01\x01\x02\xa8\x61\x00\x11copy to tmp table";
+    const COPIED: &[u8] = b"\xff\xff\xff\x01\x01\x02\xa0\x86\x01\x11copy to tmp table";
+    const ENABLING_KEYS: &[u8] = b"\xff\xff\xff\x01\x02\x02\x50\xc3\x00\x0denabling keys";
+
+    #[test]
+    fn decode_reads_stage_progress_and_info() {
+        let report = |stage, progress, info: &str| ProgressReport {
+            stage,
+            max_stage: 2,
+            progress,
+            info: String::from(info),
+        };
+        assert_eq!(
+            ProgressReport::decode(COPYING).unwrap(),
+            report(1, 0.25, "copy to tmp table")
+        );
+        assert_eq!(
+            ProgressReport::decode(COPIED).unwrap(),
+            report(1, 1.0, "copy to tmp table")
+        );
+        assert_eq!(
+            ProgressReport::decode(ENABLING_KEYS).unwrap(),
+            report(2, 0.5, "enabling keys")
+        );
+    }
+
+    #[test]
+    fn progress_past_100_percent_is_capped() {
+        let report = ProgressReport::decode(b"\xff\xff\xff\x01\x01\x01\xff\xff\xff\x00").unwrap();
+        assert_eq!(report.progress, 1.0);
+        assert_eq!(report.info, "");
+    }
+
+    #[test]
+    fn other_err_packets_are_not_progress_reports() {
+        // ER_DUP_ENTRY
+        let err = b"\xff\x26\x04#23000Duplicate entry '1' for key 'PRIMARY'";
+        assert!(!is_progress_report(err));
+        assert!(ProgressReport::decode(err).is_err());
+        assert!(!is_progress_report(b"\xff"));
+    }
+
+    #[test]
+    fn truncated_progress_reports_are_errors() {
+        assert!(ProgressReport::decode(&COPYING[..6]).is_err());
+        assert!(ProgressReport::decode(&COPYING[..12]).is_err());
+    }
+}
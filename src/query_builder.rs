@@ -0,0 +1,365 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    connection::Connection,
+    result::QueryResult,
+    sql::{named_placeholders, placeholders},
+    state::ConnectionState,
+    value::Value,
+};
+
+// A statement with `?` placeholders and the values bound to them, in order. It runs either as a
+// prepared statement (`execute`, the values never become part of the SQL) or as a plain query with
+// the values written in as literals (`query`), for statements that can't be prepared or a server
+// whose prepared statement cache is full.
+//
+//     QueryBuilder::new("SELECT * FROM users WHERE id = ? AND status = ?")
+//         .bind(42)
+//         .bind("active")
+//         .execute(&mut conn)?;
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    sql: String,
+    params: Vec<Value>,
+}
+
+impl QueryBuilder {
+    pub fn new(sql: impl Into<String>) -> Self {
+        Self {
+            sql: sql.into(),
+            params: vec![],
+        }
+    }
+
+    // A statement with `:name` placeholders, bound by name; see `bind_named`.
+    //
+    //     QueryBuilder::named(
+    //         "SELECT * FROM events WHERE owner = :user OR assignee = :user LIMIT :limit",
+    //         &[("user", Value::from(42)), ("limit", Value::from(10))],
+    //     )?
+    //     .execute(&mut conn)?;
+    pub fn named(sql: &str, params: &[(&str, Value)]) -> Result<Self> {
+        let (sql, params) = bind_named(sql, params)?;
+        Ok(Self { sql, params })
+    }
+
+    // Binds the next placeholder.
+    pub fn bind(mut self, value: impl Into<Value>) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    // Binds the next placeholder to a list, e.g. for `WHERE id IN (?)`: the placeholder is expanded
+    // into one per value (`IN (?, ?, ?)`), so the statement still runs as a prepared statement. An
+    // empty list becomes `NULL`, and `IN (NULL)` matches no row; note that `NOT IN (NULL)` matches
+    // none either, so a `NOT IN` with a possibly empty list needs its own branch.
+    pub fn bind_list<T: Into<Value>>(mut self, values: impl IntoIterator<Item = T>) -> Self {
+        let values = values.into_iter().map(Into::into).collect::<Vec<_>>();
+        // Without a placeholder left the values are just appended, and the count check fails later.
+        if let Some(&pos) = placeholders(&self.sql).get(self.params.len()) {
+            let expanded = match values.len() {
+                0 => String::from("NULL"),
+                len => vec!["?"; len].join(", "),
+            };
+            self.sql.replace_range(pos..pos + 1, &expanded);
+        }
+        self.params.extend(values);
+        self
+    }
+
+    pub fn bind_all<T: Into<Value>>(mut self, values: impl IntoIterator<Item = T>) -> Self {
+        self.params.extend(values.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn params(&self) -> &[Value] {
+        &self.params
+    }
+
+    // See `interpolate`.
+    pub fn interpolate(&self, no_backslash_escapes: bool) -> Result<String> {
+        interpolate(&self.sql, &self.params, no_backslash_escapes)
+    }
+
+    // Runs the statement as a prepared statement; see `Connection::query_params`.
+    pub fn execute(&self, conn: &mut Connection) -> Result<QueryResult> {
+        conn.query_params(&self.sql, &self.params)
+    }
+
+    // Runs the statement with the values interpolated, escaped for the session's sql_mode.
+    pub fn query(&self, conn: &mut Connection) -> Result<QueryResult> {
+        let sql = self.interpolate(conn.no_backslash_escapes())?;
+        conn.query(&sql)
+    }
+}
+
+// `sql` with each `?` placeholder replaced by the SQL literal of its value (see
+// `Value::to_sql_literal`). There must be exactly as many values as placeholders.
+pub fn interpolate(sql: &str, params: &[Value], no_backslash_escapes: bool) -> Result<String> {
+    let positions = placeholders(sql);
+    if positions.len() != params.len() {
+        bail!(
+            "the statement has {} placeholders, but {} values were given",
+            positions.len(),
+            params.len()
+        );
+    }
+    let mut out = String::with_capacity(sql.len() + params.len() * 8);
+    let mut copied = 0;
+    for (pos, param) in positions.into_iter().zip(params) {
+        out.push_str(&sql[copied..pos]);
+        out.push_str(&param.to_sql_literal(no_backslash_escapes)?);
+        copied = pos + 1;
+    }
+    out.push_str(&sql[copied..]);
+    Ok(out)
+}
+
+// Rewrites the `:name` placeholders of `sql` (see `sql::named_placeholders`) to `?` and returns the
+// positional values for them: a name used several times gets its value at each place. A name may be
+// bound as `id` or `:id`. A placeholder without a binding, a binding no placeholder uses and a name
+// bound twice are all errors.
+pub fn bind_named(sql: &str, params: &[(&str, Value)]) -> Result<(String, Vec<Value>)> {
+    let names = params
+        .iter()
+        .map(|(name, _)| name.strip_prefix(':').unwrap_or(name))
+        .collect::<Vec<_>>();
+    if let Some((i, name)) = names
+        .iter()
+        .enumerate()
+        .find(|(i, name)| names[..*i].contains(name))
+    {
+        bail!("the parameter :{} is bound twice (binding {})", name, i + 1);
+    }
+    let mut used = vec![false; params.len()];
+    let mut out = String::with_capacity(sql.len());
+    let mut values = vec![];
+    let mut copied = 0;
+    for (range, name) in named_placeholders(sql) {
+        let Some(i) = names.iter().position(|bound| *bound == name) else {
+            bail!("no value is bound for the parameter :{}", name);
+        };
+        used[i] = true;
+        values.push(params[i].1.clone());
+        out.push_str(&sql[copied..range.start]);
+        out.push('?');
+        copied = range.end;
+    }
+    out.push_str(&sql[copied..]);
+    if let Some(i) = used.iter().position(|used| !used) {
+        bail!("the statement has no parameter :{}", names[i]);
+    }
+    Ok((out, values))
+}
+
+impl Connection {
+    // Prepares `sql`, executes it with `params` and closes the statement again, returning the first
+    // result like `query`. For a statement run more than once, keep the `Statement` from `prepare`
+    // instead.
+    pub fn query_params(&mut self, sql: &str, params: &[Value]) -> Result<QueryResult> {
+        let mut stmt = self.prepare(sql)?;
+        let result = self.execute(&mut stmt, params);
+        // Closed even when the execution failed, unless that left the connection unusable.
+        if self.state() == ConnectionState::Idle {
+            self.close_statement(stmt)?;
+        }
+        result
+    }
+
+    // `query_params` for a statement with `:name` placeholders; see `bind_named`.
+    pub fn query_named(&mut self, sql: &str, params: &[(&str, Value)]) -> Result<QueryResult> {
+        let (sql, params) = bind_named(sql, params)?;
+        self.query_params(&sql, &params)
+    }
+
+    // `interpolate` with the escaping of the session's sql_mode.
+    pub fn interpolate(&self, sql: &str, params: &[Value]) -> Result<String> {
+        interpolate(sql, params, self.no_backslash_escapes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Date, DateTime, Time};
+
+    #[test]
+    fn interpolate_rejects_a_count_mismatch() {
+        let builder = QueryBuilder::new("SELECT ?, ?").bind(1);
+        let err = builder.interpolate(false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the statement has 2 placeholders, but 1 values were given"
+        );
+        let builder = QueryBuilder::new("SELECT 1").bind(1);
+        assert!(builder.interpolate(false).is_err());
+    }
+
+    #[test]
+    fn interpolate_writes_each_value_type_as_a_literal() {
+        let date = Date {
+            year: 2024,
+            month: 2,
+            day: 29,
+        };
+        let cases = [
+            (Value::Null, "NULL"),
+            (Value::Int(-42), "-42"),
+            (Value::UInt(u64::MAX), "18446744073709551615"),
+            (Value::Float(1.5), "1.5e0"),
+            (Value::Double(0.001), "1e-3"),
+            (Value::Bytes(vec![0x00, 0xff, b'\'']), "X'00FF27'"),
+            (Value::Text(String::from("it's")), "'it\\'s'"),
+            (Value::Date(date), "'2024-02-29'"),
+            (
+                Value::DateTime(DateTime {
+                    date,
+                    hour: 13,
+                    minute: 5,
+                    second: 9,
+                    micros: 500,
+                }),
+                "'2024-02-29 13:05:09.000500'",
+            ),
+            (
+                Value::Time(Time {
+                    negative: true,
+                    hours: 100,
+                    minutes: 0,
+                    seconds: 1,
+                    micros: 0,
+                }),
+                "'-100:00:01'",
+            ),
+            (Value::Decimal(String::from("12.50")), "'12.50'"),
+            (Value::Bit(vec![0x05]), "X'05'"),
+            (
+                Value::Json(String::from(r#"{"a": "b"}"#)),
+                r#"'{\"a\": \"b\"}'"#,
+            ),
+        ];
+        for (value, literal) in cases {
+            let sql = interpolate("SELECT ?", &[value], false).unwrap();
+            assert_eq!(sql, format!("SELECT {}", literal));
+        }
+    }
+
+    #[test]
+    fn interpolate_rejects_non_finite_floats() {
+        for value in [Value::Double(f64::NAN), Value::Float(f32::INFINITY)] {
+            assert!(interpolate("SELECT ?", &[value], false).is_err());
+        }
+    }
+
+    #[test]
+    fn interpolate_leaves_question_marks_in_literals_and_comments() {
+        let sql = interpolate(
+            "SELECT '?', `?`, ? /* ? */ -- ?\n, ?",
+            &[Value::Int(1), Value::from("x")],
+            false,
+        )
+        .unwrap();
+        assert_eq!(sql, "SELECT '?', `?`, 1 /* ? */ -- ?\n, 'x'");
+    }
+
+    #[test]
+    fn bind_converts_rust_values() {
+        let builder = QueryBuilder::new("SELECT ?, ?, ?, ?")
+            .bind(7u8)
+            .bind("seven")
+            .bind(None::<i32>)
+            .bind(true);
+        assert_eq!(
+            builder.params(),
+            [
+                Value::UInt(7),
+                Value::Text(String::from("seven")),
+                Value::Null,
+                Value::Int(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn bind_named_gives_a_reused_name_its_value_at_each_place() {
+        let (sql, params) = bind_named(
+            "SELECT * FROM t WHERE owner = :user OR assignee = :user LIMIT :limit",
+            &[("user", Value::Int(42)), (":limit", Value::Int(10))],
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM t WHERE owner = ? OR assignee = ? LIMIT ?"
+        );
+        assert_eq!(params, [Value::Int(42), Value::Int(42), Value::Int(10)]);
+    }
+
+    #[test]
+    fn bind_named_rejects_a_missing_binding() {
+        let err = QueryBuilder::named(
+            "SELECT * FROM t WHERE id = :id AND name = :name",
+            &[("id", Value::Int(1))],
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "no value is bound for the parameter :name");
+    }
+
+    #[test]
+    fn bind_named_rejects_unused_and_duplicate_bindings() {
+        let err = bind_named(
+            "SELECT :id",
+            &[("id", Value::Int(1)), ("name", Value::from("x"))],
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "the statement has no parameter :name");
+        let err = bind_named(
+            "SELECT :id",
+            &[("id", Value::Int(1)), (":id", Value::Int(2))],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the parameter :id is bound twice (binding 2)"
+        );
+    }
+
+    #[test]
+    fn bind_list_expands_the_placeholder_into_one_per_value() {
+        let builder = QueryBuilder::new("SELECT * FROM t WHERE id IN (?) AND name = ?")
+            .bind_list([1, 2, 3])
+            .bind("x");
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM t WHERE id IN (?, ?, ?) AND name = ?"
+        );
+        assert_eq!(
+            builder.params(),
+            [
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3),
+                Value::Text(String::from("x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn bind_list_turns_an_empty_list_into_null() {
+        let builder = QueryBuilder::new("SELECT * FROM t WHERE id IN (?) AND name = ?")
+            .bind_list(Vec::<i32>::new())
+            .bind("x");
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM t WHERE id IN (NULL) AND name = ?"
+        );
+        assert_eq!(builder.params(), [Value::Text(String::from("x"))]);
+        assert_eq!(
+            builder.interpolate(false).unwrap(),
+            "SELECT * FROM t WHERE id IN (NULL) AND name = 'x'"
+        );
+    }
+}
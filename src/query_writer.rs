@@ -0,0 +1,99 @@
+use std::io::{self, Write};
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    connection::{Connection, MAX_FRAME_LEN},
+    result::QueryResult,
+    state::ConnectionState,
+};
+
+// A COM_QUERY whose SQL is written piece by piece, e.g. a multi-row INSERT generated from a file,
+// without building the whole statement first. At most one frame (16MB) is held in memory: every
+// full frame goes out as soon as it is filled, and `finish` sends the rest and reads the result.
+//
+// The bytes written go out as they are, so they must already be in the session character set. The
+// query rewriter doesn't see streamed SQL, and a statement larger than the server's
+// max_allowed_packet is still rejected by the server.
+#[derive(Debug)]
+pub struct QueryWriter<'a> {
+    conn: &'a mut Connection,
+    // The frame being filled.
+    buf: Vec<u8>,
+    // Whether a frame has gone out; the command can no longer be abandoned cleanly after that.
+    sent: bool,
+    finished: bool,
+}
+
+impl Connection {
+    // Starts a COM_QUERY and returns a writer for its SQL. Nothing is sent until a frame is full or
+    // `QueryWriter::finish` is called. Dropping the writer without finishing it breaks the
+    // connection if part of the statement was already sent.
+    pub fn query_streaming_writer(&mut self) -> Result<QueryWriter<'_>> {
+        debug!("query_streaming_writer start");
+        self.reconnect_if_closed()?;
+        // The command byte and, with query attributes, the empty parameter fields.
+        let buf = self.com_query("")?.encode();
+        self.begin_command()?;
+        Ok(QueryWriter {
+            conn: self,
+            buf,
+            sent: false,
+            finished: false,
+        })
+    }
+}
+
+impl QueryWriter<'_> {
+    // Sends the end of the statement and returns its first result, like `Connection::query`.
+    pub fn finish(mut self) -> Result<QueryResult> {
+        self.finished = true;
+        let mut first = None;
+        let ran = self
+            .conn
+            .buffer_frame(&self.buf)
+            .and_then(|()| self.conn.flush())
+            .and_then(|()| {
+                self.conn.read_results(|result| {
+                    first.get_or_insert(result);
+                })
+            });
+        self.conn.end_command(ran)?;
+        debug!("query_streaming_writer done");
+        Ok(first.expect("at least one result is read"))
+    }
+}
+
+impl Write for QueryWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let len = data.len().min(MAX_FRAME_LEN - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        if self.buf.len() == MAX_FRAME_LEN {
+            self.sent = true;
+            self.conn
+                .buffer_frame(&self.buf)
+                .map_err(io::Error::other)?;
+            self.buf.clear();
+        }
+        Ok(len)
+    }
+
+    // A frame can't be sent before it is full (or the last one), so there is nothing to flush.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for QueryWriter<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if self.sent {
+            self.conn.set_state(ConnectionState::Broken);
+        } else {
+            self.conn.set_state(ConnectionState::Idle);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use log::debug;
+
+pub use crate::error::ManagerError as Error;
+use crate::{
+    connection::{Connection, ConnectionOptions},
+    state::ConnectionState,
+};
+
+// How a connection is cleaned up before the next user gets it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetStrategy {
+    None,
+    // Roll back any open transaction; session variables and temporary tables survive.
+    Rollback,
+    // COM_RESET_CONNECTION, falling back to a rollback on servers that don't support it.
+    ResetConnection,
+}
+
+// r2d2::ManageConnection for `Connection`
+//
+//   let manager = MysqlConnectionManager::new(options).with_reset(ResetStrategy::ResetConnection);
+//   let pool = r2d2::Pool::builder().max_size(4).build(manager)?;
+#[derive(Debug, Clone)]
+pub struct MysqlConnectionManager {
+    options: ConnectionOptions,
+    reset: ResetStrategy,
+    reset_on_checkin: bool,
+}
+
+impl MysqlConnectionManager {
+    pub fn new(options: ConnectionOptions) -> Self {
+        Self {
+            options,
+            reset: ResetStrategy::None,
+            reset_on_checkin: false,
+        }
+    }
+
+    pub fn with_reset(mut self, reset: ResetStrategy) -> Self {
+        self.reset = reset;
+        self
+    }
+
+    // Reset when the connection is returned to the pool instead of when it is checked out (which
+    // only happens with r2d2's test_on_check_out, the default).
+    pub fn reset_on_checkin(mut self, reset_on_checkin: bool) -> Self {
+        self.reset_on_checkin = reset_on_checkin;
+        self
+    }
+
+    fn reset(&self, conn: &mut Connection) -> Result<()> {
+        match self.reset {
+            ResetStrategy::None => Ok(()),
+            ResetStrategy::ResetConnection if conn.server_version().supports_reset_connection() => {
+                conn.reset_connection()
+            }
+            ResetStrategy::Rollback | ResetStrategy::ResetConnection => {
+                conn.internal(|conn| conn.query("ROLLBACK"))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ::r2d2::ManageConnection for MysqlConnectionManager {
+    type Connection = Connection;
+    type Error = Error;
+
+    fn connect(&self) -> Result<Connection, Error> {
+        Ok(Connection::new(self.options.clone())?)
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> Result<(), Error> {
+        if !self.reset_on_checkin {
+            self.reset(conn)?;
+        }
+        Ok(conn.ping()?)
+    }
+
+    // Only an `Idle` connection goes back to the pool; anything else still has a response in flight
+    // or has failed.
+    fn has_broken(&self, conn: &mut Connection) -> bool {
+        if conn.state() != ConnectionState::Idle {
+            debug!("discarding connection in state {:?}", conn.state());
+            return true;
+        }
+        if self.reset_on_checkin
+            && let Err(err) = self.reset(conn)
+        {
+            debug!("failed to reset connection on checkin: {}", err);
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    command::{ErrPacket, OkPacket},
+    connection::Connection,
+    consts::{CommandByte, EOF_HEADER, ERR_HEADER, OK_HEADER},
+    decode::PacketKind,
+};
+
+// Commands whose response `send_command` can't read without knowing more about the protocol state
+// (prepared statements, binlog streams, re-authentication) or that get no response at all. Sending
+// them leaves the connection out of sync unless the caller handles the response itself.
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_command_phase.html
+const DESYNCHRONIZING_COMMANDS: &[CommandByte] = &[
+    CommandByte::Quit,
+    CommandByte::ChangeUser,
+    CommandByte::BinlogDump,
+    CommandByte::RegisterSlave,
+    CommandByte::StmtPrepare,
+    CommandByte::StmtExecute,
+    CommandByte::StmtSendLongData,
+    CommandByte::StmtClose,
+    CommandByte::StmtFetch,
+    CommandByte::BinlogDumpGtid,
+];
+
+// What kind of response `send_command` should expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseKind {
+    // A single packet, e.g. COM_PING, COM_INIT_DB, COM_STATISTICS.
+    Packet,
+    // A result set (or an OK/ERR instead of one), e.g. COM_QUERY.
+    ResultSet,
+}
+
+#[derive(Debug)]
+pub enum RawResponse {
+    Ok(OkPacket),
+    Err(ErrPacket),
+    // An EOF_Packet, as sent by old servers and by COM_DEBUG.
+    Eof(Vec<u8>),
+    // Any other single packet (e.g. the status string of COM_STATISTICS), or every packet of a
+    // result set: the column count, the column definitions, the rows and the terminator.
+    Packets(Vec<Vec<u8>>),
+}
+
+impl Connection {
+    // Unstable: a low-level escape hatch for commands this crate doesn't wrap, and for exploring the
+    // protocol. Sends `cmd` followed by `payload` as a new command and reads the response as
+    // `kind` describes. Only a single result is read, so don't use it for statements returning
+    // several.
+    //
+    // Commands whose response it can't follow (see DESYNCHRONIZING_COMMANDS) are refused unless
+    // `ConnectionOptions::unsafe_raw` is set; with it, keeping the connection in sync is up to the
+    // caller.
+    pub fn send_command(
+        &mut self,
+        cmd: u8,
+        payload: &[u8],
+        kind: ResponseKind,
+    ) -> Result<RawResponse> {
+        if !self.options.unsafe_raw
+            && let Some(name) = CommandByte::from_u8(cmd)
+                .filter(|c| DESYNCHRONIZING_COMMANDS.contains(c))
+                .map(CommandByte::name)
+        {
+            bail!(
+                "refusing to send {} (0x{:02x}) as a raw command because its response can't be \
+                 read generically; set ConnectionOptions::unsafe_raw to send it anyway",
+                name,
+                cmd
+            );
+        }
+        self.begin_command()?;
+        let mut pkt = vec![cmd];
+        pkt.extend_from_slice(payload);
+        self.write_packet(&pkt)?;
+        let response = self.read_raw_response(kind);
+        self.end_command(response)
+    }
+
+    fn read_raw_response(&mut self, kind: ResponseKind) -> Result<RawResponse> {
+        let pkt = self.read_packet()?;
+        match pkt.first() {
+            Some(&OK_HEADER) => return Ok(RawResponse::Ok(self.decode_ok(pkt)?)),
+            Some(&ERR_HEADER) => {
+                let err = self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?;
+                return Ok(RawResponse::Err(err));
+            }
+            Some(&EOF_HEADER) if pkt.len() < 9 => return Ok(RawResponse::Eof(pkt)),
+            _ => {}
+        }
+        if kind == ResponseKind::Packet {
+            return Ok(RawResponse::Packets(vec![pkt]));
+        }
+        // Everything up to and including the packet that ends the rows; with CLIENT_DEPRECATE_EOF
+        // there is no EOF between the column definitions and the rows.
+        let mut packets = vec![pkt];
+        loop {
+            let pkt = self.read_packet()?;
+            let end = matches!(pkt.first(), Some(&EOF_HEADER) if pkt.len() < 0xffffff)
+                || pkt.first() == Some(&ERR_HEADER);
+            packets.push(pkt);
+            if end {
+                return Ok(RawResponse::Packets(packets));
+            }
+        }
+    }
+}
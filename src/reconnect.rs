@@ -0,0 +1,49 @@
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{connection::Connection, error::Error, state::ConnectionState};
+
+impl Connection {
+    // With `ConnectionOptions::reconnect`, checked before every query: when the previous command
+    // lost the connection, or the server dropped it while it was idle (typically after wait_timeout),
+    // a new connection is opened in its place and the query goes there. Nothing has been sent yet at
+    // that point, so this never runs a statement twice.
+    //
+    // A new connection is a new session: session variables, temporary tables, prepared statements
+    // and user locks are gone. An open transaction would be lost too, so in that case the
+    // connection is not replaced and `Error::ServerGone` is returned instead.
+    //
+    // TCP keepalive doesn't prevent wait_timeout, which counts time without commands; only sending
+    // commands (e.g. a periodic `ping`) does. Keepalive only helps detect dead peers sooner.
+    pub(crate) fn reconnect_if_closed(&mut self) -> Result<()> {
+        if !self.options.reconnect {
+            return Ok(());
+        }
+        let closed = match self.state() {
+            ConnectionState::Broken => true,
+            ConnectionState::Idle => self.is_peer_closed(),
+            _ => false,
+        };
+        if !closed {
+            return Ok(());
+        }
+        if self.in_transaction() {
+            bail!(Error::ServerGone(String::from(
+                "the connection was closed with a transaction open"
+            )));
+        }
+        debug!("connection closed by the server; reconnecting");
+        self.reconnect()
+    }
+
+    // Replaces this connection with a new one to the same server, with the same options (including
+    // the current database and character set). The state change callback and the query rewriter
+    // are kept.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let mut conn = Connection::new(self.options.clone())?;
+        conn.state_listener = self.state_listener.take();
+        conn.query_rewriter = self.query_rewriter.take();
+        *self = conn;
+        Ok(())
+    }
+}
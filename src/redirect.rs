@@ -0,0 +1,103 @@
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{
+    connection::{Connection, ConnectionOptions},
+    deadline::Deadline,
+    url::split_host_port,
+};
+
+// Hops followed before giving up, even without a cycle.
+pub const MAX_REDIRECTS: usize = 8;
+
+// Where the server asked the client to go instead, from the OK packet that ends the handshake. Two
+// forms are in use:
+// - the info string `Location: mysql://host:port/...` (Azure Database for MySQL)
+// - the `redirect_url` system variable reported through session tracking (MariaDB)
+pub(crate) fn redirect_target(
+    info: &str,
+    changed_system_variables: &[(String, String)],
+) -> Option<(String, u16)> {
+    let url = info.strip_prefix("Location: ").map(str::trim).or_else(|| {
+        changed_system_variables
+            .iter()
+            .find(|(name, _)| name == "redirect_url")
+            .map(|(_, value)| value.as_str())
+    })?;
+    parse_url(url)
+}
+
+// mysql://host[:port][/...], with IPv6 hosts in brackets.
+fn parse_url(url: &str) -> Option<(String, u16)> {
+    let rest = url.strip_prefix("mysql://")?;
+    let authority = rest.split(['/', '?']).next()?;
+    split_host_port(authority.rsplit('@').next()?)
+}
+
+impl Connection {
+    // Connects and, when `follow_redirect` is set, keeps reconnecting to wherever the server
+    // redirects to until a server accepts the session.
+    pub(crate) fn connect_following_redirects(
+        options: ConnectionOptions,
+        deadline: &Deadline,
+    ) -> Result<Self> {
+        let mut visited = vec![(options.host.clone(), options.port)];
+        let mut conn = Self::connect(options, deadline)?;
+        while conn.options.follow_redirect {
+            let Some((host, port)) = conn.redirect.take() else {
+                break;
+            };
+            if visited.contains(&(host.clone(), port)) {
+                bail!(
+                    "redirect cycle: {:?} redirected back to {}:{}",
+                    visited,
+                    host,
+                    port
+                );
+            }
+            if visited.len() > MAX_REDIRECTS {
+                bail!("too many redirects: {:?}", visited);
+            }
+            debug!("redirected to {}:{}", host, port);
+            visited.push((host.clone(), port));
+            let options = ConnectionOptions {
+                host,
+                port,
+                ..conn.options.clone()
+            };
+            conn = Self::connect(options, deadline)?;
+        }
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_target_from_the_info_string() {
+        assert_eq!(
+            redirect_target("Location: mysql://db.example.com:3307/?user=app", &[]),
+            Some((String::from("db.example.com"), 3307))
+        );
+        assert_eq!(
+            redirect_target("Location: mysql://app@[::1]/test", &[]),
+            Some((String::from("::1"), 3306))
+        );
+        assert_eq!(redirect_target("Rows matched: 1", &[]), None);
+    }
+
+    #[test]
+    fn redirect_target_from_the_redirect_url_variable() {
+        let changed = [(
+            String::from("redirect_url"),
+            String::from("mysql://10.0.0.2:3306"),
+        )];
+        assert_eq!(
+            redirect_target("", &changed),
+            Some((String::from("10.0.0.2"), 3306))
+        );
+        assert_eq!(redirect_target("", &[]), None);
+    }
+}
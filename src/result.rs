@@ -0,0 +1,795 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    command::{
+        ColumnDefinition41, MYSQL_TYPE_DECIMAL, MYSQL_TYPE_DOUBLE, MYSQL_TYPE_FLOAT,
+        MYSQL_TYPE_INT24, MYSQL_TYPE_LONG, MYSQL_TYPE_LONGLONG, MYSQL_TYPE_NEWDECIMAL,
+        MYSQL_TYPE_SHORT, MYSQL_TYPE_TINY, MYSQL_TYPE_YEAR, OkPacket, ResultsetRow, StatusFlags,
+    },
+    progress_report::ProgressReportHandler,
+    server_stats::ServerStats,
+    table,
+    utils::{FNV_OFFSET_BASIS, fnv1a},
+    value::{FromValue, Value},
+};
+
+// Text Resultset
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query_response_text_resultset.html
+#[derive(Debug)]
+pub struct ResultSet {
+    pub columns: Vec<ColumnDefinition41>,
+    pub rows: Vec<ResultsetRow>,
+    pub terminator: Terminator,
+    // The limit that stopped `Connection::query_with` from collecting more rows; `rows` is then
+    // only the first part of the result set.
+    pub truncated: Option<ResultLimit>,
+    // Session status counter deltas of the whole statement, with
+    // `ConnectionOptions::collect_session_status`.
+    pub server_stats: Option<ServerStats>,
+}
+
+impl ResultSet {
+    // Every row as typed values; rows read with `query` and with `execute` give the same values.
+    pub fn values(&self) -> Result<Vec<Vec<Value>>> {
+        self.rows
+            .iter()
+            .map(|row| row.values(&self.columns))
+            .collect()
+    }
+
+    // Number of rows kept (see `truncated`).
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    // The rows as the mysql client prints them with `\G`: a block of "column: value" lines per row.
+    // Bytes that aren't UTF-8 are shown lossily.
+    pub fn to_vertical(&self) -> String {
+        let header = self
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        table::render_vertical(&header, &self.text_rows(), None)
+    }
+
+    // The rows as a GitHub-flavored Markdown table, for pasting into docs and pull requests.
+    // Numeric columns are right-aligned.
+    pub fn to_markdown(&self) -> String {
+        let header = self
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        table::render_markdown(&header, &self.text_rows(), &self.numeric_columns())
+    }
+
+    // Which columns hold numbers, to right-align them in tables.
+    fn numeric_columns(&self) -> Vec<bool> {
+        self.columns
+            .iter()
+            .map(|column| {
+                matches!(
+                    column.type_,
+                    MYSQL_TYPE_TINY
+                        | MYSQL_TYPE_SHORT
+                        | MYSQL_TYPE_LONG
+                        | MYSQL_TYPE_INT24
+                        | MYSQL_TYPE_LONGLONG
+                        | MYSQL_TYPE_FLOAT
+                        | MYSQL_TYPE_DOUBLE
+                        | MYSQL_TYPE_DECIMAL
+                        | MYSQL_TYPE_NEWDECIMAL
+                        | MYSQL_TYPE_YEAR
+                )
+            })
+            .collect()
+    }
+
+    // Every cell as text, for display.
+    fn text_rows(&self) -> Vec<Vec<Option<String>>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.0
+                    .iter()
+                    .map(|cell| {
+                        cell.as_ref()
+                            .map(|cell| String::from_utf8_lossy(cell).into_owned())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // The rows, borrowed, each with the column definitions; same as `for row in &result_set`.
+    pub fn iter(&self) -> Rows<'_> {
+        Rows {
+            columns: &self.columns,
+            rows: self.rows.iter(),
+        }
+    }
+
+    // The column definitions, in select-list order.
+    pub fn columns(&self) -> &[ColumnDefinition41] {
+        &self.columns
+    }
+
+    // The column names as selected, i.e. the aliases if any.
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect()
+    }
+
+    // The index of the column named `name` (case-sensitively, as selected). When several columns
+    // have the name, e.g. `id` in `SELECT * FROM a JOIN b`, it is the first of them, which is also
+    // the one `Row::get_by_name` reads.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        column_index(&self.columns, name)
+    }
+
+    pub fn contains_column(&self, name: &str) -> bool {
+        self.column_index(name).is_some()
+    }
+
+    // See `schema_fingerprint`.
+    pub fn schema_fingerprint(&self) -> u64 {
+        schema_fingerprint(&self.columns)
+    }
+}
+
+fn column_index(columns: &[ColumnDefinition41], name: &str) -> Option<usize> {
+    columns.iter().position(|column| column.name == name)
+}
+
+// A hash of the names and types of `columns`, in order, for telling when the shape of a result
+// (e.g. of a `SELECT *` after an ALTER TABLE) is no longer the one something was built for, like a
+// cached row mapping. The rows don't change it. It is stable across processes and releases of this
+// crate, so it can be stored.
+pub fn schema_fingerprint(columns: &[ColumnDefinition41]) -> u64 {
+    columns.iter().fold(FNV_OFFSET_BASIS, |hash, column| {
+        fingerprint_column(hash, column.name.as_bytes(), column.type_)
+    })
+}
+
+// Adds a column to a `schema_fingerprint`. The name is length-prefixed, so that no two lists of
+// columns run together into the same bytes.
+pub(crate) fn fingerprint_column(hash: u64, name: &[u8], type_: u8) -> u64 {
+    let hash = fnv1a(hash, &(name.len() as u64).to_le_bytes());
+    fnv1a(fnv1a(hash, name), &[type_])
+}
+
+impl<'a> IntoIterator for &'a ResultSet {
+    type Item = Row<'a>;
+    type IntoIter = Rows<'a>;
+
+    fn into_iter(self) -> Rows<'a> {
+        self.iter()
+    }
+}
+
+// Consumes the result set; the rows come without the column definitions, so take `columns` first
+// if they are needed.
+impl IntoIterator for ResultSet {
+    type Item = ResultsetRow;
+    type IntoIter = std::vec::IntoIter<ResultsetRow>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rows<'a> {
+    columns: &'a [ColumnDefinition41],
+    rows: std::slice::Iter<'a, ResultsetRow>,
+}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = Row<'a>;
+
+    fn next(&mut self) -> Option<Row<'a>> {
+        let row = self.rows.next()?;
+        Some(Row {
+            columns: self.columns,
+            row,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Rows<'_> {}
+
+// A row of a result set along with the result set's column definitions, so its cells can be read
+// by column name.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    pub columns: &'a [ColumnDefinition41],
+    pub row: &'a ResultsetRow,
+}
+
+impl Row<'_> {
+    // The cell at `idx`, converted with `FromValue`.
+    pub fn get<T: FromValue>(&self, idx: usize) -> Result<T> {
+        let (Some(cell), Some(column)) = (self.row.0.get(idx), self.columns.get(idx)) else {
+            bail!("no column {} (the row has {})", idx, self.columns.len());
+        };
+        let value = match cell {
+            Some(bytes) => Value::parse_text(bytes, column)?,
+            None => Value::Null,
+        };
+        T::from_value(value).with_context(|| format!("column {}", column.name))
+    }
+
+    // The cell of the first column named `name` (the name as selected, i.e. the alias if any).
+    pub fn get_by_name<T: FromValue>(&self, name: &str) -> Result<T> {
+        let Some(idx) = column_index(self.columns, name) else {
+            bail!("no column named {}", name);
+        };
+        self.get(idx)
+    }
+}
+
+// The packet that ended a result set: an OK packet with CLIENT_DEPRECATE_EOF, an EOF packet
+// otherwise. Both carry the status flags, e.g. NO_INDEX_USED for a query that scanned a table.
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    Eof { warnings: u16, status: StatusFlags },
+    Ok(OkPacket),
+}
+
+impl Terminator {
+    pub fn status_flags(&self) -> StatusFlags {
+        match self {
+            Self::Eof { status, .. } => *status,
+            Self::Ok(ok) => ok.status_flags,
+        }
+    }
+
+    pub fn warnings(&self) -> u16 {
+        match self {
+            Self::Eof { warnings, .. } => *warnings,
+            Self::Ok(ok) => ok.warnings,
+        }
+    }
+}
+
+// Client-side guards against result sets too large to hold, e.g. an unbounded SELECT typed into a
+// REPL, and progress reporting while the rows come in. Rows past a limit are still read from the
+// server, but not decoded or kept.
+#[derive(Debug, Default, Clone)]
+pub struct QueryOptions {
+    pub max_rows: Option<u64>,
+    // Counted over the cell values of the kept rows.
+    pub max_result_bytes: Option<u64>,
+    // Called while the rows are read, every `progress_interval`, so a long export can show it is
+    // moving. Costs a counter check per row when set, nothing when not.
+    pub on_progress: Option<ProgressHandler>,
+    pub progress_interval: ProgressInterval,
+    // Called with the progress reports MariaDB sends while the statement runs, with
+    // `ConnectionOptions::mariadb_progress_reports`.
+    pub on_progress_report: Option<ProgressReportHandler>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    // Rows read so far from the current result set, including any past a limit.
+    pub rows: u64,
+    // Size of the cell values of those rows; rows past a limit aren't decoded and don't count.
+    pub bytes: u64,
+    // Since the first row.
+    pub elapsed: Duration,
+}
+
+#[derive(Clone)]
+pub struct ProgressHandler(pub Arc<Mutex<dyn FnMut(Progress) + Send>>);
+
+impl ProgressHandler {
+    pub fn new(handler: impl FnMut(Progress) + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(handler)))
+    }
+}
+
+impl fmt::Debug for ProgressHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHandler")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressInterval {
+    Rows(u64),
+    Bytes(u64),
+}
+
+impl Default for ProgressInterval {
+    fn default() -> Self {
+        Self::Rows(10_000)
+    }
+}
+
+// Counts the rows of a result set and calls the progress handler each time another interval is
+// done.
+#[derive(Debug)]
+pub(crate) struct ProgressTracker {
+    handler: ProgressHandler,
+    interval: ProgressInterval,
+    started: Instant,
+    rows: u64,
+    bytes: u64,
+    next_report: u64,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(handler: ProgressHandler, interval: ProgressInterval) -> Self {
+        let next_report = match interval {
+            ProgressInterval::Rows(step) | ProgressInterval::Bytes(step) => step.max(1),
+        };
+        Self {
+            handler,
+            interval,
+            started: Instant::now(),
+            rows: 0,
+            bytes: 0,
+            next_report,
+        }
+    }
+
+    pub(crate) fn from_options(options: &QueryOptions) -> Option<Self> {
+        let handler = options.on_progress.clone()?;
+        Some(Self::new(handler, options.progress_interval))
+    }
+
+    pub(crate) fn row(&mut self, bytes: u64) {
+        self.rows += 1;
+        self.bytes += bytes;
+        let (done, step) = match self.interval {
+            ProgressInterval::Rows(step) => (self.rows, step),
+            ProgressInterval::Bytes(step) => (self.bytes, step),
+        };
+        if done < self.next_report {
+            return;
+        }
+        self.next_report = done + step.max(1);
+        let progress = Progress {
+            rows: self.rows,
+            bytes: self.bytes,
+            elapsed: self.started.elapsed(),
+        };
+        // A handler that panicked before is still called; its state is its own business.
+        let mut handler = self.handler.0.lock().unwrap_or_else(|err| err.into_inner());
+        handler(progress);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultLimit {
+    MaxRows,
+    MaxResultBytes,
+}
+
+impl QueryOptions {
+    // The limit that keeping a row of `row_bytes` would exceed, given what was kept so far.
+    pub(crate) fn exceeded(&self, rows: u64, bytes: u64, row_bytes: u64) -> Option<ResultLimit> {
+        if self.max_rows.is_some_and(|max| rows + 1 > max) {
+            return Some(ResultLimit::MaxRows);
+        }
+        if self
+            .max_result_bytes
+            .is_some_and(|max| bytes + row_bytes > max)
+        {
+            return Some(ResultLimit::MaxResultBytes);
+        }
+        None
+    }
+}
+
+// COM_QUERY Response
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query_response.html
+#[derive(Debug)]
+pub enum QueryResult {
+    ResultSet(ResultSet),
+    Ok(OkPacket),
+}
+
+impl QueryResult {
+    pub fn result_set(&self) -> Option<&ResultSet> {
+        match self {
+            Self::ResultSet(rs) => Some(rs),
+            Self::Ok(_) => None,
+        }
+    }
+
+    // Rows changed by an UPDATE, or matched with `ConnectionOptions::found_rows`; 0 for a result
+    // set.
+    pub fn affected_rows(&self) -> u64 {
+        match self {
+            Self::ResultSet(_) => 0,
+            Self::Ok(ok) => ok.affected_rows,
+        }
+    }
+
+    pub fn last_insert_id(&self) -> u64 {
+        match self {
+            Self::ResultSet(_) => 0,
+            Self::Ok(ok) => ok.last_insert_id,
+        }
+    }
+
+    // How the result ended; for a statement without a result set, its OK packet.
+    pub fn terminator(&self) -> Terminator {
+        match self {
+            Self::ResultSet(rs) => rs.terminator.clone(),
+            Self::Ok(ok) => Terminator::Ok(ok.clone()),
+        }
+    }
+
+    pub fn status_flags(&self) -> StatusFlags {
+        match self {
+            Self::ResultSet(rs) => rs.terminator.status_flags(),
+            Self::Ok(ok) => ok.status_flags,
+        }
+    }
+
+    pub fn warnings(&self) -> u16 {
+        match self {
+            Self::ResultSet(rs) => rs.terminator.warnings(),
+            Self::Ok(ok) => ok.warnings,
+        }
+    }
+
+    // The column helpers below are those of `ResultSet`; a statement without a result set has no
+    // columns.
+    pub fn columns(&self) -> &[ColumnDefinition41] {
+        match self {
+            Self::ResultSet(rs) => rs.columns(),
+            Self::Ok(_) => &[],
+        }
+    }
+
+    pub fn column_names(&self) -> Vec<&str> {
+        self.result_set()
+            .map_or_else(Vec::new, ResultSet::column_names)
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        column_index(self.columns(), name)
+    }
+
+    pub fn contains_column(&self, name: &str) -> bool {
+        self.column_index(name).is_some()
+    }
+
+    pub fn schema_fingerprint(&self) -> u64 {
+        schema_fingerprint(self.columns())
+    }
+
+    // See `ResultSet::server_stats`; for a statement without a result set, use
+    // `Connection::server_stats`.
+    pub fn server_stats(&self) -> Option<&ServerStats> {
+        match self {
+            Self::ResultSet(rs) => rs.server_stats.as_ref(),
+            Self::Ok(_) => None,
+        }
+    }
+}
+
+// What the mysql client prints for a result, without the timing: the table and "N rows in set"
+// (or "Empty set"), or "Query OK, N rows affected" followed by the OK packet's info, with the
+// warning count when there are warnings. `{:#}` prints the rows vertically as with `\G`, and a
+// precision (`{:.40}`) cuts longer cells to that many columns.
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ResultSet(rs) => fmt::Display::fmt(rs, f),
+            Self::Ok(ok) => {
+                write!(f, "Query OK, {} affected", plural(ok.affected_rows, "row"))?;
+                write_warnings(f, ok.warnings)?;
+                if !ok.info.is_empty() {
+                    write!(f, "\n{}", ok.info)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ResultSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rows.is_empty() {
+            f.write_str("Empty set")?;
+        } else {
+            let header = self
+                .columns
+                .iter()
+                .map(|column| column.name.as_str())
+                .collect::<Vec<_>>();
+            let rows = self.text_rows();
+            if f.alternate() {
+                f.write_str(&table::render_vertical(&header, &rows, f.precision()))?;
+            } else {
+                let right_align = self.numeric_columns();
+                f.write_str(&table::render(&header, &rows, &right_align, f.precision()))?;
+            }
+            write!(f, "{} in set", plural(self.rows.len() as u64, "row"))?;
+        }
+        write_warnings(f, self.terminator.warnings())
+    }
+}
+
+fn write_warnings(f: &mut fmt::Formatter<'_>, warnings: u16) -> fmt::Result {
+    if warnings > 0 {
+        write!(f, ", {}", plural(warnings.into(), "warning"))?;
+    }
+    Ok(())
+}
+
+fn plural(n: u64, word: &str) -> String {
+    match n {
+        1 => format!("1 {}", word),
+        n => format!("{} {}s", n, word),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MYSQL_TYPE_VAR_STRING;
+
+    fn column(name: &str, type_: u8) -> ColumnDefinition41 {
+        ColumnDefinition41 {
+            catalog: String::from("def"),
+            schema: String::from("test"),
+            table: String::from("t"),
+            org_table: String::from("t"),
+            name: String::from(name),
+            org_name: String::from(name),
+            length_of_fixed_length_fields: 0x0c,
+            character_set: 255,
+            column_length: 0,
+            type_,
+            flags: 0,
+            decimals: 0,
+        }
+    }
+
+    fn result_set(columns: Vec<ColumnDefinition41>, rows: &[&[Option<&str>]]) -> ResultSet {
+        ResultSet {
+            columns,
+            rows: rows
+                .iter()
+                .map(|row| {
+                    ResultsetRow(
+                        row.iter()
+                            .map(|cell| cell.map(|cell| cell.as_bytes().to_vec()))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            terminator: Terminator::Eof {
+                warnings: 0,
+                status: StatusFlags(StatusFlags::AUTOCOMMIT),
+            },
+            truncated: None,
+            server_stats: None,
+        }
+    }
+
+    // SELECT name, qty FROM orders
+    fn result_set_of(rows: &[&[Option<&str>]]) -> ResultSet {
+        result_set(
+            vec![
+                column("name", MYSQL_TYPE_VAR_STRING),
+                column("qty", MYSQL_TYPE_LONG),
+            ],
+            rows,
+        )
+    }
+
+    fn orders() -> ResultSet {
+        result_set_of(&[
+            &[Some("apple"), Some("3")],
+            &[Some("pear"), Some("4")],
+            &[Some("fig"), None],
+            &[Some("plum"), Some("5")],
+        ])
+    }
+
+    #[test]
+    fn iterating_by_reference_sums_a_column() {
+        let result_set = orders();
+        let mut total = 0;
+        for row in &result_set {
+            total += row.get_by_name::<Option<i64>>("qty").unwrap().unwrap_or(0);
+        }
+        assert_eq!(total, 12);
+        // The rows are borrowed, not cloned.
+        let first = result_set.iter().next().unwrap();
+        assert!(std::ptr::eq(first.row, &result_set.rows[0]));
+        assert_eq!(result_set.iter().len(), 4);
+    }
+
+    #[test]
+    fn iterating_by_value_yields_the_rows() {
+        let result_set = orders();
+        let columns = result_set.columns.clone();
+        let total = result_set
+            .into_iter()
+            .map(|row| match &row.values(&columns).unwrap()[1] {
+                Value::Int(qty) => *qty,
+                _ => 0,
+            })
+            .sum::<i64>();
+        assert_eq!(total, 12);
+    }
+
+    #[test]
+    fn len_counts_rows_and_cells() {
+        let result_set = orders();
+        assert_eq!(result_set.len(), 4);
+        assert!(!result_set.is_empty());
+        assert_eq!(result_set.rows[0].len(), 2);
+        assert!(!result_set.rows[0].is_empty());
+
+        let empty = result_set_of(&[]);
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+        assert_eq!(empty.columns().len(), 2);
+        assert!(ResultsetRow(vec![]).is_empty());
+    }
+
+    #[test]
+    fn to_vertical_names_each_cell() {
+        let result_set = result_set_of(&[&[Some("apple"), Some("3")]]);
+        assert_eq!(
+            result_set.to_vertical(),
+            "\
+*************************** 1. row ***************************
+name: apple
+ qty: 3
+"
+        );
+        assert_eq!(result_set_of(&[]).to_vertical(), "");
+    }
+
+    // Laid out as the mysql client prints them, but without the timings.
+    #[test]
+    fn display_renders_like_the_mysql_client() {
+        assert_eq!(
+            orders().to_string(),
+            "\
++-------+------+
+| name  | qty  |
++-------+------+
+| apple |    3 |
+| pear  |    4 |
+| fig   | NULL |
+| plum  |    5 |
++-------+------+
+4 rows in set"
+        );
+        let one_row = result_set_of(&[&[Some("apple"), Some("3")]]);
+        assert!(one_row.to_string().ends_with("+\n1 row in set"));
+        assert_eq!(
+            format!("{:#}", one_row),
+            "\
+*************************** 1. row ***************************
+name: apple
+ qty: 3
+1 row in set"
+        );
+        assert_eq!(
+            QueryResult::ResultSet(result_set_of(&[])).to_string(),
+            "Empty set"
+        );
+    }
+
+    #[test]
+    fn display_appends_warnings() {
+        let mut result_set = result_set_of(&[]);
+        result_set.terminator = Terminator::Eof {
+            warnings: 2,
+            status: StatusFlags(StatusFlags::AUTOCOMMIT),
+        };
+        assert_eq!(result_set.to_string(), "Empty set, 2 warnings");
+
+        let mut ok = OkPacket::new(1, 0, StatusFlags(StatusFlags::AUTOCOMMIT));
+        ok.warnings = 1;
+        assert_eq!(
+            QueryResult::Ok(ok).to_string(),
+            "Query OK, 1 row affected, 1 warning"
+        );
+    }
+
+    #[test]
+    fn display_of_ok_shows_affected_rows_and_info() {
+        let status = StatusFlags(StatusFlags::AUTOCOMMIT);
+        assert_eq!(
+            QueryResult::Ok(OkPacket::new(0, 0, status)).to_string(),
+            "Query OK, 0 rows affected"
+        );
+        let mut ok = OkPacket::new(3, 0, status);
+        ok.info = String::from("Rows matched: 3  Changed: 3  Warnings: 0");
+        assert_eq!(
+            QueryResult::Ok(ok).to_string(),
+            "Query OK, 3 rows affected\nRows matched: 3  Changed: 3  Warnings: 0"
+        );
+    }
+
+    #[test]
+    fn to_markdown_right_aligns_numbers_in_the_separator_row() {
+        let result_set = result_set_of(&[&[Some("a|b"), Some("3")], &[Some("fig"), None]]);
+        let markdown = result_set.to_markdown();
+        assert_eq!(
+            markdown,
+            "\
+| name | qty |
+| --- | ---: |
+| a\\|b | 3 |
+| fig | NULL |
+"
+        );
+        assert_eq!(markdown.lines().nth(1), Some("| --- | ---: |"));
+    }
+
+    #[test]
+    fn schema_fingerprint_follows_column_types_not_rows() {
+        let fingerprint = orders().schema_fingerprint();
+        assert_eq!(result_set_of(&[]).schema_fingerprint(), fingerprint);
+
+        let mut retyped = orders();
+        retyped.columns[1].type_ = MYSQL_TYPE_LONGLONG;
+        assert_ne!(retyped.schema_fingerprint(), fingerprint);
+
+        let mut renamed = orders();
+        renamed.columns[1].name = String::from("quantity");
+        assert_ne!(renamed.schema_fingerprint(), fingerprint);
+
+        let mut reordered = orders();
+        reordered.columns.reverse();
+        assert_ne!(reordered.schema_fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn schema_fingerprint_keeps_column_names_apart() {
+        let ab_c = [column("ab", MYSQL_TYPE_LONG), column("c", MYSQL_TYPE_LONG)];
+        let a_bc = [column("a", MYSQL_TYPE_LONG), column("bc", MYSQL_TYPE_LONG)];
+        assert_ne!(schema_fingerprint(&ab_c), schema_fingerprint(&a_bc));
+    }
+
+    // Stored fingerprints stay valid, so the hash must not change between releases.
+    #[test]
+    fn schema_fingerprint_is_stable() {
+        assert_eq!(orders().schema_fingerprint(), 6156110179584442183);
+    }
+
+    // Prepared statements fingerprint the column definitions from their names and types alone.
+    #[test]
+    fn schema_fingerprint_matches_the_one_of_undecoded_definitions() {
+        let result_set = orders();
+        let fingerprint = result_set
+            .columns
+            .iter()
+            .fold(FNV_OFFSET_BASIS, |hash, column| {
+                let pkt = column.encode();
+                let (name, type_) = ColumnDefinition41::decode_name_and_type(&pkt).unwrap();
+                fingerprint_column(hash, name, type_)
+            });
+        assert_eq!(fingerprint, result_set.schema_fingerprint());
+    }
+}
@@ -0,0 +1,68 @@
+use std::{thread, time::Duration};
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{command::ErrPacket, connection::Connection, result::QueryResult};
+
+// ER_LOCK_WAIT_TIMEOUT
+pub const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+// ER_LOCK_DEADLOCK
+pub const ER_LOCK_DEADLOCK: u16 = 1213;
+
+// Which failures `query_with_retry` retries. Only server errors whose code is listed are retried;
+// anything else (syntax errors, constraint violations, io errors) is returned immediately, since
+// running the statement again could apply a write twice or can't succeed anyway.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub retriable_codes: Vec<u16>,
+    // Attempts after the first one.
+    pub max_retries: u32,
+    // Wait before the first retry, doubled for every further one.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retriable_codes: vec![ER_LOCK_WAIT_TIMEOUT, ER_LOCK_DEADLOCK],
+            max_retries: 3,
+            backoff: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retriable(&self, err: &anyhow::Error) -> bool {
+        err.downcast_ref::<ErrPacket>()
+            .is_some_and(|err| self.retriable_codes.contains(&err.error_code))
+    }
+}
+
+impl Connection {
+    // `query`, retried on the server errors `policy` allows. The connection stays usable after a
+    // server error, so the statement is simply sent again on the same connection.
+    //
+    // A statement inside an explicit transaction is never retried: a deadlock rolls back the whole
+    // transaction, so the statement would have to be retried together with the rest of it.
+    pub fn query_with_retry(&mut self, sql: &str, policy: &RetryPolicy) -> Result<QueryResult> {
+        let in_transaction = self.in_transaction();
+        let mut backoff = policy.backoff;
+        let mut attempt = 0;
+        loop {
+            match self.query(sql) {
+                Err(err)
+                    if attempt < policy.max_retries
+                        && !in_transaction
+                        && policy.is_retriable(&err) =>
+                {
+                    attempt += 1;
+                    debug!("retrying ({}/{}): {}", attempt, policy.max_retries, err);
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use crate::connection::Connection;
+
+type RewriteFn = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct QueryRewriter(Arc<RewriteFn>);
+
+impl fmt::Debug for QueryRewriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QueryRewriter")
+    }
+}
+
+impl Connection {
+    // Called with the SQL of every `query`, `query_all`, `query_with`, `query_drop`, `query_iter`,
+    // pipelined query and `prepare` before it is sent; what it returns is sent instead, and is also
+    // what the slow query handler sees. Statements the crate issues for its own purposes (session
+    // setup, SET NAMES, SHOW WARNINGS/VARIABLES/STATUS, SHOW COLUMNS, the snapshot transaction and
+    // SELECT of `dump_table`, the pool's ROLLBACK) bypass it. Only one rewriter is kept; setting
+    // another one replaces it. It is kept across `reconnect`.
+    pub fn set_query_rewriter(
+        &mut self,
+        rewriter: impl Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    ) {
+        self.query_rewriter = Some(QueryRewriter(Arc::new(rewriter)));
+    }
+
+    pub fn clear_query_rewriter(&mut self) {
+        self.query_rewriter = None;
+    }
+
+    pub(crate) fn rewrite<'a>(&self, sql: &'a str) -> Cow<'a, str> {
+        match &self.query_rewriter {
+            Some(QueryRewriter(rewriter)) if !self.internal => rewriter(sql),
+            _ => Cow::Borrowed(sql),
+        }
+    }
+
+    // Runs `f` with the query rewriter bypassed, for statements the crate issues itself.
+    pub(crate) fn internal<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.internal, true);
+        let result = f(self);
+        self.internal = outer;
+        result
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use log::debug;
+
+use crate::{
+    connection::{Connection, ConnectionOptions},
+    result::QueryResult,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    Read,
+    Write,
+}
+
+// Guesses whether a statement only reads, from its first keyword: SELECT and SHOW are reads, except
+// locking reads (FOR UPDATE, FOR SHARE, LOCK IN SHARE MODE) and SELECT ... INTO. Everything else,
+// including anything this heuristic doesn't understand, is treated as a write.
+//
+// It does not parse SQL: a SELECT calling a function with side effects is still classified as a read.
+pub fn classify(sql: &str) -> StatementKind {
+    let sql = skip_comments(sql);
+    let keyword = sql
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_ascii_uppercase();
+    let upper = sql.to_ascii_uppercase();
+    let locking = ["FOR UPDATE", "FOR SHARE", "LOCK IN SHARE MODE", " INTO "]
+        .iter()
+        .any(|clause| upper.contains(clause));
+    match keyword.as_str() {
+        "SELECT" if !locking => StatementKind::Read,
+        "SHOW" => StatementKind::Read,
+        _ => StatementKind::Write,
+    }
+}
+
+pub(crate) fn skip_comments(mut sql: &str) -> &str {
+    loop {
+        sql = sql.trim_start();
+        if let Some(rest) = sql.strip_prefix("/*") {
+            // `/*! ... */` and `/*+ ... */` are executed by the server; stop there.
+            if rest.starts_with(['!', '+']) {
+                return sql;
+            }
+            sql = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else if sql.starts_with("--") || sql.starts_with('#') {
+            sql = sql.split_once('\n').map_or("", |(_, rest)| rest);
+        } else {
+            return sql;
+        }
+    }
+}
+
+// Sends reads to a replica and everything else to the primary. While a transaction is open on the
+// primary every statement goes there, so reads see the transaction's own writes.
+#[derive(Debug)]
+pub struct RoutingConnection {
+    primary: Connection,
+    replica: Connection,
+}
+
+impl RoutingConnection {
+    // `replica` may list several candidates in `hosts`; the first that connects is used.
+    pub fn new(primary: ConnectionOptions, replica: ConnectionOptions) -> Result<Self> {
+        Ok(Self {
+            primary: Connection::new(primary)?,
+            replica: Connection::new(replica)?,
+        })
+    }
+
+    pub fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        self.route(sql).query(sql)
+    }
+
+    pub fn route(&mut self, sql: &str) -> &mut Connection {
+        let kind = classify(sql);
+        if kind == StatementKind::Read && !self.primary.in_transaction() {
+            debug!("route to replica: {}", sql);
+            &mut self.replica
+        } else {
+            debug!("route to primary: {}", sql);
+            &mut self.primary
+        }
+    }
+
+    pub fn primary(&mut self) -> &mut Connection {
+        &mut self.primary
+    }
+
+    pub fn replica(&mut self) -> &mut Connection {
+        &mut self.replica
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_reads() {
+        for sql in [
+            "SELECT 1",
+            "  select * from t",
+            "SHOW TABLES",
+            "/* app */ SELECT 1",
+            "-- note\nSELECT 1",
+            "# note\nSELECT 1",
+        ] {
+            assert_eq!(classify(sql), StatementKind::Read, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn classify_writes() {
+        for sql in [
+            "INSERT INTO t VALUES (1)",
+            "UPDATE t SET a = 1",
+            "SELECT * FROM t FOR UPDATE",
+            "SELECT * FROM t FOR SHARE",
+            "SELECT * FROM t LOCK IN SHARE MODE",
+            "SELECT 1 INTO @x",
+            "/*!40101 SET NAMES utf8mb4 */",
+            "CALL refresh()",
+            "",
+        ] {
+            assert_eq!(classify(sql), StatementKind::Write, "{}", sql);
+        }
+    }
+}
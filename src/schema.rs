@@ -0,0 +1,66 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::{connection::Connection, result::QueryResult, utils::quote_identifier};
+
+// A row of DESCRIBE / SHOW COLUMNS
+// https://dev.mysql.com/doc/refman/8.4/en/show-columns.html
+#[derive(Debug, Clone)]
+pub struct ColumnInfo {
+    pub field: String,
+    pub type_: String,
+    pub null: bool,
+    pub key: String,
+    pub default: Option<String>,
+    pub extra: String,
+}
+
+impl ColumnInfo {
+    pub fn is_integer(&self) -> bool {
+        ["tinyint", "smallint", "mediumint", "int", "bigint"]
+            .iter()
+            .any(|t| self.type_.split(['(', ' ']).next() == Some(t))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer()
+            || ["decimal", "float", "double"]
+                .iter()
+                .any(|t| self.type_.split(['(', ' ']).next() == Some(t))
+    }
+}
+
+impl Connection {
+    pub fn describe_table(&mut self, table: &str) -> Result<Vec<ColumnInfo>> {
+        let sql = format!("SHOW COLUMNS FROM {}", quote_identifier(table));
+        let QueryResult::ResultSet(rs) = self.internal(|conn| conn.query(&sql))? else {
+            bail!("SHOW COLUMNS did not return a result set");
+        };
+        rs.rows
+            .into_iter()
+            .map(|row| {
+                let [
+                    Some(field),
+                    Some(type_),
+                    Some(null),
+                    Some(key),
+                    default,
+                    Some(extra),
+                ]: [Option<String>; 6] = row
+                    .into_strings()?
+                    .try_into()
+                    .map_err(|row: Vec<_>| anyhow!("unexpected SHOW COLUMNS row: {:?}", row))?
+                else {
+                    bail!("unexpected NULL in SHOW COLUMNS row");
+                };
+                Ok(ColumnInfo {
+                    field,
+                    type_,
+                    null: null == "YES",
+                    key,
+                    default,
+                    extra,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+
+use ::serde::{
+    Deserializer, Serialize, Serializer,
+    de::{
+        self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+        value::SeqDeserializer,
+    },
+    forward_to_deserialize_any,
+    ser::{Error, SerializeMap, SerializeSeq},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{
+    command::{ColumnDefinition41, ResultsetRow},
+    result::ResultSet,
+    value::{FromValue, Value},
+};
+
+// Numbers as JSON numbers, DECIMAL (to keep its digits), text, JSON documents and temporal values
+// as strings, and bytes as base64.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Null => serializer.serialize_none(),
+            Self::Int(val) => serializer.serialize_i64(*val),
+            Self::UInt(val) => serializer.serialize_u64(*val),
+            Self::Float(val) => serializer.serialize_f32(*val),
+            Self::Double(val) => serializer.serialize_f64(*val),
+            Self::Bytes(val) | Self::Bit(val) => serializer.serialize_str(&STANDARD.encode(val)),
+            Self::Text(val) | Self::Decimal(val) | Self::Json(val) => serializer.serialize_str(val),
+            Self::Date(date) => serializer.collect_str(date),
+            Self::Time(time) => serializer.collect_str(time),
+            Self::DateTime(datetime) => serializer.collect_str(datetime),
+        }
+    }
+}
+
+// An array of the cells as sent, without the column types: text, base64 for bytes that aren't
+// UTF-8, or null.
+impl Serialize for ResultsetRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for cell in &self.0 {
+            match cell.as_deref().map(std::str::from_utf8) {
+                None => seq.serialize_element(&None::<&str>)?,
+                Some(Ok(text)) => seq.serialize_element(text)?,
+                Some(Err(_)) => seq.serialize_element(&STANDARD.encode(cell.as_ref().unwrap()))?,
+            }
+        }
+        seq.end()
+    }
+}
+
+// An array of objects keyed by column name, with the values typed as `Value`.
+impl Serialize for ResultSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.rows.len()))?;
+        for row in &self.rows {
+            seq.serialize_element(&RowObject {
+                columns: &self.columns,
+                row,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+struct RowObject<'a> {
+    columns: &'a [ColumnDefinition41],
+    row: &'a ResultsetRow,
+}
+
+impl Serialize for RowObject<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let values = self.row.values(self.columns).map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(values.len()))?;
+        for (column, value) in self.columns.iter().zip(&values) {
+            map.serialize_entry(&column.name, value)?;
+        }
+        map.end()
+    }
+}
+
+// Deserializes a row into `T` (a struct deriving `Deserialize`, a map, or a tuple), matching
+// struct fields to columns by name. Cells convert as with `FromValue`: numbers when they fit, text
+// and DECIMAL parsed as numbers, NULL only into `Option`.
+pub fn from_row<T: DeserializeOwned>(
+    row: &ResultsetRow,
+    columns: &[ColumnDefinition41],
+) -> anyhow::Result<T> {
+    let values = row.values(columns)?;
+    let row = RowDeserializer {
+        columns,
+        values: values.into_iter(),
+    };
+    T::deserialize(row).map_err(|DeError(msg)| anyhow::anyhow!(msg))
+}
+
+impl ResultSet {
+    // Every row deserialized with `from_row`.
+    pub fn rows_as<T: DeserializeOwned>(&self) -> anyhow::Result<Vec<T>> {
+        self.rows
+            .iter()
+            .map(|row| from_row(row, &self.columns))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct DeError(String);
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+struct RowDeserializer<'a> {
+    columns: &'a [ColumnDefinition41],
+    values: std::vec::IntoIter<Value>,
+}
+
+impl<'de> Deserializer<'de> for RowDeserializer<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_map(RowAccess {
+            columns: self.columns.iter(),
+            values: self.values,
+            column: None,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_seq(RowAccess {
+            columns: self.columns.iter(),
+            values: self.values,
+            column: None,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct map struct enum identifier ignored_any
+    }
+}
+
+// The cells of a row, as a map by column name or as a sequence.
+struct RowAccess<'a> {
+    columns: std::slice::Iter<'a, ColumnDefinition41>,
+    values: std::vec::IntoIter<Value>,
+    // The column whose value is next, once its name has been read.
+    column: Option<&'a ColumnDefinition41>,
+}
+
+impl<'de> MapAccess<'de> for RowAccess<'_> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        let Some(column) = self.columns.next() else {
+            return Ok(None);
+        };
+        self.column = Some(column);
+        seed.deserialize(column.name.as_str().into_deserializer())
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let (Some(column), Some(value)) = (self.column.take(), self.values.next()) else {
+            return Err(DeError(String::from("no value for the column")));
+        };
+        seed.deserialize(ValueDeserializer(value))
+            .map_err(|DeError(msg)| DeError(format!("column {}: {}", column.name, msg)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+impl<'de> SeqAccess<'de> for RowAccess<'_> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        let (Some(column), Some(value)) = (self.columns.next(), self.values.next()) else {
+            return Ok(None);
+        };
+        seed.deserialize(ValueDeserializer(value))
+            .map(Some)
+            .map_err(|DeError(msg)| DeError(format!("column {}: {}", column.name, msg)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+// A single cell. Types with a `FromValue` conversion go through it; anything else sees the value
+// as it is (temporal values as their text).
+struct ValueDeserializer(Value);
+
+impl ValueDeserializer {
+    fn convert<T: FromValue>(self) -> Result<T, DeError> {
+        T::from_value(self.0).map_err(|err| DeError(err.to_string()))
+    }
+}
+
+macro_rules! deserialize_with_from_value {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.convert()?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            Value::Int(val) => visitor.visit_i64(val),
+            Value::UInt(val) => visitor.visit_u64(val),
+            Value::Float(val) => visitor.visit_f32(val),
+            Value::Double(val) => visitor.visit_f64(val),
+            Value::Bytes(val) | Value::Bit(val) => visitor.visit_byte_buf(val),
+            Value::Text(val) | Value::Decimal(val) | Value::Json(val) => visitor.visit_string(val),
+            value => visitor.visit_string(value.to_string()),
+        }
+    }
+
+    deserialize_with_from_value! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_string => visit_string,
+        deserialize_byte_buf => visit_byte_buf
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    // `Vec<u8>` asks for a sequence.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.0 {
+            Value::Bytes(val) | Value::Bit(val) => {
+                visitor.visit_seq(SeqDeserializer::new(val.into_iter()))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // ENUM and SET columns into unit variants, by name.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let name: String = self.convert()?;
+        visitor.visit_enum(name.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
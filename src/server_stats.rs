@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{connection::Connection, result::QueryResult};
+
+// Counters snapshotted by default: how the handler read rows (index lookups, index scans, table
+// scans) plus the scans, sorts and temporary tables of the statement.
+// https://dev.mysql.com/doc/refman/8.4/en/server-status-variables.html
+pub const DEFAULT_SESSION_STATUS_COUNTERS: &[&str] = &[
+    "Handler_read_first",
+    "Handler_read_key",
+    "Handler_read_last",
+    "Handler_read_next",
+    "Handler_read_prev",
+    "Handler_read_rnd",
+    "Handler_read_rnd_next",
+    "Select_full_join",
+    "Select_scan",
+    "Sort_rows",
+    "Created_tmp_tables",
+    "Created_tmp_disk_tables",
+];
+
+// How much each counter went up while a statement ran, by counter name. Counters the server doesn't
+// have are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    pub deltas: HashMap<String, u64>,
+}
+
+impl ServerStats {
+    pub fn get(&self, counter: &str) -> Option<u64> {
+        self.deltas.get(counter).copied()
+    }
+
+    // Rows read by the handler, summed over the Handler_read_* counters.
+    pub fn rows_read(&self) -> u64 {
+        self.deltas
+            .iter()
+            .filter(|(name, _)| name.starts_with("Handler_read_"))
+            .map(|(_, delta)| delta)
+            .sum()
+    }
+
+    fn between(before: &HashMap<String, u64>, after: HashMap<String, u64>) -> Self {
+        let deltas = after
+            .into_iter()
+            .filter_map(|(name, val)| {
+                let delta = val.saturating_sub(*before.get(&name)?);
+                Some((name, delta))
+            })
+            .collect();
+        Self { deltas }
+    }
+}
+
+impl Connection {
+    // The counter deltas of the last `query`/`query_all`/`query_with`, when they were collected
+    // (see `ConnectionOptions::collect_session_status`). Also available for statements without a
+    // result set, which `QueryResult::server_stats` doesn't cover.
+    pub fn server_stats(&self) -> Option<&ServerStats> {
+        self.server_stats.as_ref()
+    }
+
+    // Inside an explicit transaction the extra statements would show up in what the transaction has
+    // done (e.g. `transaction_state().result_set_sent`), so collection is skipped there unless
+    // `collect_session_status_in_transaction` is set.
+    pub(crate) fn collects_session_status(&self) -> bool {
+        if !self.options.collect_session_status || self.options.session_status_counters.is_empty() {
+            return false;
+        }
+        let explicit = match self.transaction_state() {
+            Some(state) => state.explicit,
+            None => self.in_transaction(),
+        };
+        !explicit || self.options.collect_session_status_in_transaction
+    }
+
+    // One round trip: SHOW SESSION STATUS for the configured counters. What the statement before it
+    // reported (warning count, changed system variables) is kept.
+    pub(crate) fn session_status_snapshot(&mut self) -> Result<HashMap<String, u64>> {
+        let names = self
+            .options
+            .session_status_counters
+            .iter()
+            .map(|name| format!("'{}'", self.escape_string(name)))
+            .collect::<Vec<_>>();
+        let sql = format!(
+            "SHOW SESSION STATUS WHERE Variable_name IN ({})",
+            names.join(", ")
+        );
+        let warning_count = self.warning_count;
+        let changed_system_variables = std::mem::take(&mut self.changed_system_variables);
+        let mut results = vec![];
+        let com_query = self.com_query(&sql)?;
+        self.begin_command()?;
+        let ran = self
+            .write_packet_with(|w| com_query.write_to(w))
+            .and_then(|()| self.read_results(|result| results.push(result)));
+        self.end_command(ran)?;
+        self.warning_count = warning_count;
+        self.changed_system_variables = changed_system_variables;
+
+        let mut counters = HashMap::new();
+        let rows = results.into_iter().flat_map(|result| match result {
+            QueryResult::ResultSet(rs) => rs.rows,
+            QueryResult::Ok(_) => vec![],
+        });
+        for row in rows {
+            let mut cells = row.into_strings()?.into_iter();
+            if let (Some(Some(name)), Some(Some(value))) = (cells.next(), cells.next()) {
+                // Non-numeric status variables can't be diffed.
+                if let Ok(value) = value.parse() {
+                    counters.insert(name, value);
+                }
+            }
+        }
+        Ok(counters)
+    }
+
+    pub(crate) fn server_stats_since(
+        &mut self,
+        before: &HashMap<String, u64>,
+    ) -> Result<ServerStats> {
+        let after = self.session_status_snapshot()?;
+        Ok(ServerStats::between(before, after))
+    }
+}
@@ -0,0 +1,102 @@
+use std::cmp::Ordering;
+
+// Server version as reported in Protocol::HandshakeV10 (e.g. "8.0.36", "8.0.36-log",
+// "5.5.5-10.11.6-MariaDB-log").
+//
+// Parsing is best-effort because proxies report all kinds of strings. When the leading
+// "major.minor" can't be read the version is marked as unknown, and comparing an unknown version
+// with a tuple yields no ordering, so both `>=` and `<` are false and version-gated features stay
+// disabled.
+//
+// MariaDB numbers are kept as reported (10.x, 11.x), so they can't be compared with MySQL releases
+// meaningfully; check `is_mariadb` first when that matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub is_mariadb: bool,
+    pub is_log: bool,
+    pub is_known: bool,
+}
+
+impl ServerVersion {
+    pub fn parse(s: &str) -> Self {
+        let is_mariadb = s.to_ascii_lowercase().contains("mariadb");
+        let is_log = s.split('-').skip(1).any(|part| part == "log");
+
+        // MariaDB prefixes the version with "5.5.5-" to keep old replication clients happy.
+        let s = match s.strip_prefix("5.5.5-") {
+            Some(rest) if is_mariadb => rest,
+            _ => s,
+        };
+
+        let mut numbers = s
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|n| n.parse::<u16>().ok());
+        let major = numbers.next().flatten();
+        let minor = numbers.next().flatten();
+        let patch = numbers.next().flatten();
+
+        Self {
+            major: major.unwrap_or(0),
+            minor: minor.unwrap_or(0),
+            patch: patch.unwrap_or(0),
+            is_mariadb,
+            is_log,
+            is_known: major.is_some() && minor.is_some(),
+        }
+    }
+
+    fn is_mysql_at_least(&self, version: (u16, u16, u16)) -> bool {
+        !self.is_mariadb && *self >= version
+    }
+
+    // Query attributes in COM_QUERY
+    // https://dev.mysql.com/doc/refman/8.4/en/query-attributes.html
+    pub fn supports_query_attributes(&self) -> bool {
+        self.is_mysql_at_least((8, 0, 23))
+    }
+
+    // COM_RESET_CONNECTION
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_reset_connection.html
+    pub fn supports_reset_connection(&self) -> bool {
+        self.is_mysql_at_least((5, 7, 3)) || (self.is_mariadb && *self >= (10, 2, 4))
+    }
+
+    pub fn default_auth_plugin(&self) -> &'static str {
+        if self.is_mysql_at_least((8, 0, 4)) {
+            "caching_sha2_password"
+        } else {
+            "mysql_native_password"
+        }
+    }
+
+    // utf8mb4_0900_ai_ci (255) only exists since MySQL 8.0.1; older servers and MariaDB get
+    // utf8mb4_general_ci (45).
+    pub fn utf8mb4_collation_id(&self) -> u8 {
+        if self.is_mysql_at_least((8, 0, 1)) {
+            255
+        } else {
+            45
+        }
+    }
+}
+
+impl PartialEq<(u16, u16, u16)> for ServerVersion {
+    fn eq(&self, other: &(u16, u16, u16)) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd<(u16, u16, u16)> for ServerVersion {
+    fn partial_cmp(&self, other: &(u16, u16, u16)) -> Option<Ordering> {
+        if !self.is_known {
+            return None;
+        }
+        Some((self.major, self.minor, self.patch).cmp(other))
+    }
+}
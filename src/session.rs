@@ -0,0 +1,218 @@
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{
+    command::{ErrPacket, OkPacket},
+    connection::Connection,
+    handshake::CLIENT_SESSION_TRACK,
+    sql_mode::SqlMode,
+    utils::{decode_lenenc_string, escape_string, escape_string_no_backslash},
+};
+
+// Session state change types
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/mysql__com_8h.html
+pub const SESSION_TRACK_SYSTEM_VARIABLES: u8 = 0x00;
+pub const SESSION_TRACK_SCHEMA: u8 = 0x01;
+pub const SESSION_TRACK_STATE_CHANGE: u8 = 0x02;
+pub const SESSION_TRACK_GTIDS: u8 = 0x03;
+pub const SESSION_TRACK_TRANSACTION_CHARACTERISTICS: u8 = 0x04;
+pub const SESSION_TRACK_TRANSACTION_STATE: u8 = 0x05;
+
+// Transaction state reported with SESSION_TRACK_TRANSACTION_STATE
+// https://dev.mysql.com/doc/refman/8.4/en/session-state-tracking.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TransactionState {
+    // Started with START TRANSACTION / BEGIN
+    pub explicit: bool,
+    // Started implicitly (autocommit = 0)
+    pub implicit: bool,
+    pub non_transactional_read: bool,
+    pub transactional_read: bool,
+    pub non_transactional_write: bool,
+    pub transactional_write: bool,
+    pub unsafe_statement: bool,
+    pub result_set_sent: bool,
+    pub locked_tables: bool,
+}
+
+impl TransactionState {
+    // The state is 8 characters, one per position, with `_` for "not set".
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.as_bytes();
+        if s.len() != 8 {
+            bail!(
+                "invalid transaction state: {:?}",
+                String::from_utf8_lossy(s)
+            );
+        }
+        Ok(Self {
+            explicit: s[0] == b'T',
+            implicit: s[0] == b'I',
+            non_transactional_read: s[1] == b'r',
+            transactional_read: s[2] == b'R',
+            non_transactional_write: s[3] == b'w',
+            transactional_write: s[4] == b'W',
+            unsafe_statement: s[5] == b's',
+            result_set_sent: s[6] == b'S',
+            locked_tables: s[7] == b'L',
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.explicit || self.implicit
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        !self.non_transactional_write && !self.transactional_write && !self.unsafe_statement
+    }
+}
+
+impl Connection {
+    // The default database of the session, kept in sync with `USE` through session tracking.
+    pub fn current_database(&self) -> Option<&str> {
+        if self.options.database.is_empty() {
+            None
+        } else {
+            Some(&self.options.database)
+        }
+    }
+
+    // System variables the server reported as changed by the last command, as (name, value) pairs.
+    // Which variables are reported is controlled by the session_track_system_variables variable;
+    // by default it covers autocommit, time_zone and the character_set_* variables.
+    pub fn changed_system_variables(&self) -> &[(String, String)] {
+        &self.changed_system_variables
+    }
+
+    // GTID of the last transaction committed by this session, as reported through session tracking
+    // (session_track_gtids = OWN_GTID). Stays `None` when the server has gtid_mode off.
+    pub fn last_gtid(&self) -> Option<&str> {
+        self.last_gtid.as_deref()
+    }
+
+    // Whether the session's sql_mode has NO_BACKSLASH_ESCAPES. The server reports it in the status
+    // flags of every response, so a `SET sql_mode` is picked up as soon as it ran.
+    pub fn no_backslash_escapes(&self) -> bool {
+        self.status_flags.no_backslash_escapes()
+    }
+
+    // Escapes a string for a quoted literal in the session's current sql_mode (see
+    // `utils::escape_string` and `utils::escape_string_no_backslash`).
+    pub fn escape_string(&self, s: &str) -> String {
+        if self.no_backslash_escapes() {
+            escape_string_no_backslash(s)
+        } else {
+            escape_string(s)
+        }
+    }
+
+    // Whether a transaction is open, from the IN_TRANS status flag of the last OK packet.
+    pub fn in_transaction(&self) -> bool {
+        self.status_flags.in_trans()
+    }
+
+    // What the open transaction has done so far, as reported through session tracking
+    // (session_track_transaction_info = STATE). `None` until the server reported it.
+    pub fn transaction_state(&self) -> Option<TransactionState> {
+        self.transaction_state
+    }
+
+    // Makes sure the server reports the state this connection keeps track of, whatever the server
+    // defaults are.
+    pub(crate) fn init_session_tracking(&mut self) -> Result<()> {
+        if self.capabilities & CLIENT_SESSION_TRACK == 0 {
+            return Ok(());
+        }
+        let sql = match self.server_version().is_mariadb {
+            true => "SET SESSION session_track_schema = ON",
+            false => {
+                "SET SESSION session_track_schema = ON, session_track_gtids = OWN_GTID, \
+                 session_track_transaction_info = STATE"
+            }
+        };
+        // Proxies advertise CLIENT_SESSION_TRACK without always accepting these variables (Vitess
+        // rejects some of them); the session then just goes without the tracking.
+        match self.internal(|conn| conn.query_drop(sql)) {
+            Err(err) if err.downcast_ref::<ErrPacket>().is_some() => {
+                debug!("session tracking not enabled: {}", err);
+                Ok(())
+            }
+            set => set,
+        }
+    }
+
+    pub(crate) fn track_session_state(&mut self, ok: &OkPacket) -> Result<()> {
+        self.status_flags = ok.status_flags;
+        for change in &ok.session_state_changes {
+            match change.type_ {
+                SESSION_TRACK_SYSTEM_VARIABLES => {
+                    let (name, consumed) = decode_lenenc_string(&change.data, 0)?;
+                    let (value, _) = decode_lenenc_string(&change.data, consumed)?;
+                    debug!("session system variable changed: {} = {}", name, value);
+                    // A `SET NAMES` run as a statement; outgoing text is encoded in this charset.
+                    if name == "character_set_client" {
+                        self.track_charset(&value);
+                    }
+                    if name == "sql_mode" {
+                        self.sql_mode = Some(SqlMode::parse(&value));
+                    }
+                    self.changed_system_variables.push((name, value));
+                }
+                SESSION_TRACK_SCHEMA => {
+                    let (schema, _) = decode_lenenc_string(&change.data, 0)?;
+                    debug!("session schema changed: {}", schema);
+                    self.options.database = schema;
+                    self.metadata_generation += 1;
+                }
+                SESSION_TRACK_GTIDS => {
+                    // The payload starts with the encoding specification, 0 being the only one defined.
+                    let (gtid, _) = decode_lenenc_string(&change.data, 1)?;
+                    debug!("session gtid: {}", gtid);
+                    self.last_gtid = Some(gtid);
+                }
+                SESSION_TRACK_TRANSACTION_STATE => {
+                    let (state, _) = decode_lenenc_string(&change.data, 0)?;
+                    debug!("session transaction state: {}", state);
+                    self.transaction_state = Some(TransactionState::parse(&state)?);
+                }
+                _ => debug!("untracked session state change: {:?}", change),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_state_of_an_explicit_read_write_transaction() {
+        let state = TransactionState::parse("T_R_W_S_").unwrap();
+        assert!(state.explicit && !state.implicit);
+        assert!(state.transactional_read && state.transactional_write);
+        assert!(state.result_set_sent);
+        assert!(state.is_active());
+        assert!(!state.is_read_only());
+    }
+
+    #[test]
+    fn transaction_state_of_an_implicit_read_only_transaction() {
+        let state = TransactionState::parse("I_R_____").unwrap();
+        assert!(state.implicit && state.is_active());
+        assert!(state.is_read_only());
+    }
+
+    #[test]
+    fn transaction_state_outside_a_transaction() {
+        let state = TransactionState::parse("________").unwrap();
+        assert_eq!(state, TransactionState::default());
+        assert!(!state.is_active());
+    }
+
+    #[test]
+    fn transaction_state_must_be_eight_characters() {
+        assert!(TransactionState::parse("T_R_W_S").is_err());
+        assert!(TransactionState::parse("").is_err());
+    }
+}
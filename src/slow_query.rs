@@ -0,0 +1,65 @@
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::error;
+
+use crate::{connection::Connection, sql::digest, utils::truncate};
+
+// `SlowQuery::sql` is cut to this many bytes, so a huge INSERT doesn't end up in the log as a whole.
+pub const SLOW_QUERY_SQL_MAX_LEN: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    pub sql: String,
+    // `sql::digest` of the full statement.
+    pub digest: String,
+    pub duration: Duration,
+    // Rows returned, or affected for statements that don't return rows, summed over every result.
+    pub rows: u64,
+    pub connection_id: u32,
+}
+
+// Called with every statement slower than `ConnectionOptions::slow_query_threshold`, failed ones
+// included. A panic in the handler is caught and logged; it doesn't affect the connection.
+#[derive(Clone)]
+pub struct SlowQueryHandler(pub Arc<dyn Fn(SlowQuery) + Send + Sync>);
+
+impl SlowQueryHandler {
+    pub fn new(handler: impl Fn(SlowQuery) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+}
+
+impl fmt::Debug for SlowQueryHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SlowQueryHandler")
+    }
+}
+
+impl Connection {
+    pub(crate) fn report_slow_query(&self, sql: &str, duration: Duration, rows: u64) {
+        let (Some(threshold), Some(SlowQueryHandler(handler))) = (
+            self.options.slow_query_threshold,
+            &self.options.slow_query_handler,
+        ) else {
+            return;
+        };
+        if duration < threshold {
+            return;
+        }
+        let slow_query = SlowQuery {
+            sql: String::from(truncate(sql, SLOW_QUERY_SQL_MAX_LEN)),
+            digest: digest(sql),
+            duration,
+            rows,
+            connection_id: self.connection_id(),
+        };
+        if panic::catch_unwind(AssertUnwindSafe(|| handler(slow_query))).is_err() {
+            error!("slow query handler panicked");
+        }
+    }
+}
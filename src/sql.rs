@@ -0,0 +1,472 @@
+use std::ops::Range;
+
+use crate::utils::{FNV_OFFSET_BASIS, fnv1a};
+
+// Statement digests: the text of a statement with its literals replaced by `?`, so statements that
+// only differ in their values group together (e.g. when aggregating a slow query log).
+//
+// The normalization is close to, but not the same as, the server's statement digests in
+// performance_schema; use the digest text and hash to correlate client-side, not to look up server
+// digests by exact equality.
+//
+// - string, numeric (decimal, float, 0x/X'' hex, 0b/b'' bit) literals become `?`
+// - comments are dropped, including `/*! ... */` version comments
+// - keywords are lowercased; identifiers, backticked or not, are kept as written
+// - whitespace is collapsed
+//
+// Digesting a digest returns it unchanged.
+pub fn digest(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut prev: Option<Token> = None;
+    for token in tokenize(sql) {
+        let text = match token {
+            Token::Literal => "?",
+            Token::Word(word) if is_keyword(word) => {
+                push_separated(&mut out, prev, token);
+                out.extend(word.chars().map(|c| c.to_ascii_lowercase()));
+                prev = Some(token);
+                continue;
+            }
+            Token::Word(text) | Token::Quoted(text) | Token::Punct(text) => text,
+        };
+        push_separated(&mut out, prev, token);
+        out.push_str(text);
+        prev = Some(token);
+    }
+    out
+}
+
+// 64-bit FNV-1a hash of `digest(sql)`. It is stable across processes and releases of this crate
+// (unlike std's `DefaultHasher`), so it can be stored and compared later.
+pub fn digest_hash(sql: &str) -> u64 {
+    fnv1a(FNV_OFFSET_BASIS, digest(sql).as_bytes())
+}
+
+// Byte offsets of the `?` placeholders in `sql`. A `?` in a string, a quoted identifier or a
+// comment isn't one.
+pub fn placeholders(sql: &str) -> Vec<usize> {
+    let mut found = vec![];
+    scan_code(sql, |pos, rest| {
+        if rest.starts_with('?') {
+            found.push(pos);
+        }
+        1
+    });
+    found
+}
+
+// The `:name` placeholders in `sql`, as the byte range of each (colon included) and the name. A name
+// is a letter or `_` followed by letters, digits and `_`, so `:=` and times in strings aren't
+// taken for one.
+pub fn named_placeholders(sql: &str) -> Vec<(Range<usize>, &str)> {
+    let mut found = vec![];
+    scan_code(sql, |pos, rest| {
+        let Some(name) = rest.strip_prefix(':') else {
+            return 1;
+        };
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            return 1;
+        }
+        let len = name
+            .bytes()
+            .position(|b| !is_word_byte(b))
+            .unwrap_or(name.len());
+        found.push((pos..pos + 1 + len, &name[..len]));
+        1 + len
+    });
+    found
+}
+
+// Calls `on_code` at every character of `sql` outside strings, quoted identifiers and comments,
+// with its offset and the rest of the statement; it returns how many bytes it consumed (at least
+// the one it was called at).
+fn scan_code<'a>(sql: &'a str, mut on_code: impl FnMut(usize, &'a str) -> usize) {
+    let bytes = sql.as_bytes();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &sql[pos..];
+        match bytes[pos] {
+            quote @ (b'\'' | b'"' | b'`') => pos += quoted_len(rest, quote),
+            b'#' => pos += rest.find('\n').unwrap_or(rest.len()),
+            _ if rest.starts_with("/*") => {
+                pos += rest.find("*/").map_or(rest.len(), |end| end + 2);
+            }
+            _ if is_dash_comment(rest) => pos += rest.find('\n').unwrap_or(rest.len()),
+            c if c.is_ascii() => pos += on_code(pos, rest),
+            _ => pos += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    // A keyword or an unquoted identifier.
+    Word(&'a str),
+    // A backticked identifier, quotes included.
+    Quoted(&'a str),
+    Literal,
+    Punct(&'a str),
+}
+
+// No space after an opening parenthesis or a dot, and none before a closing parenthesis, a comma, a
+// dot or a semicolon; a single space everywhere else.
+fn push_separated(out: &mut String, prev: Option<Token>, next: Token) {
+    let Some(prev) = prev else {
+        return;
+    };
+    let glued_after = matches!(prev, Token::Punct("(" | "." | "@" | "@@"));
+    let glued_before = matches!(next, Token::Punct(")" | "," | "." | ";"));
+    if !glued_after && !glued_before {
+        out.push(' ');
+    }
+}
+
+// Multi-character operators, longest first.
+const OPERATORS: &[&str] = &[
+    "<=>", "->>", "<=", ">=", "<>", "!=", ":=", "||", "&&", "<<", ">>", "->", "@@",
+];
+
+fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &sql[pos..];
+        let c = bytes[pos];
+        if c.is_ascii_whitespace() {
+            pos += 1;
+        } else if rest.starts_with("/*") {
+            pos += rest.find("*/").map_or(rest.len(), |end| end + 2);
+        } else if c == b'#' || is_dash_comment(rest) {
+            pos += rest.find('\n').unwrap_or(rest.len());
+        } else if c == b'\'' || c == b'"' {
+            pos += quoted_len(rest, c);
+            tokens.push(Token::Literal);
+        } else if c == b'`' {
+            let len = quoted_len(rest, c);
+            tokens.push(Token::Quoted(&rest[..len]));
+            pos += len;
+        } else if let Some(len) = prefixed_literal_len(rest) {
+            pos += len;
+            tokens.push(Token::Literal);
+        } else if let Some(len) = number_len(rest) {
+            pos += len;
+            tokens.push(Token::Literal);
+        } else if is_word_byte(c) {
+            let len = rest
+                .bytes()
+                .position(|b| !is_word_byte(b))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..len]));
+            pos += len;
+        } else {
+            let len = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .map_or_else(
+                    || rest.chars().next().map_or(1, char::len_utf8),
+                    |op| op.len(),
+                );
+            tokens.push(Token::Punct(&rest[..len]));
+            pos += len;
+        }
+    }
+    tokens
+}
+
+// `--` only starts a comment when followed by whitespace (or the end), so `1--1` is arithmetic.
+fn is_dash_comment(rest: &str) -> bool {
+    rest.starts_with("--")
+        && rest[2..]
+            .bytes()
+            .next()
+            .is_none_or(|b| b.is_ascii_whitespace())
+}
+
+// Length of a quoted string or identifier, including the quotes. A doubled quote and (except in
+// backticks) a backslash escape don't end it; an unterminated one runs to the end.
+fn quoted_len(rest: &str, quote: u8) -> usize {
+    let bytes = rest.as_bytes();
+    let mut pos = 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' if quote != b'`' => pos += 2,
+            b if b == quote => {
+                if bytes.get(pos + 1) == Some(&quote) {
+                    pos += 2;
+                } else {
+                    return pos + 1;
+                }
+            }
+            _ => pos += 1,
+        }
+    }
+    bytes.len()
+}
+
+// X'..', B'..' and charset introducers such as _utf8mb4'..' / N'..'.
+fn prefixed_literal_len(rest: &str) -> Option<usize> {
+    let bytes = rest.as_bytes();
+    let prefix_len = match bytes[0] {
+        b'x' | b'X' | b'b' | b'B' | b'n' | b'N' => 1,
+        b'_' => bytes.iter().position(|b| !is_word_byte(*b))?,
+        _ => return None,
+    };
+    if bytes.get(prefix_len) != Some(&b'\'') {
+        return None;
+    }
+    Some(prefix_len + quoted_len(&rest[prefix_len..], b'\''))
+}
+
+// Decimal integers, fixed-point and floating-point numbers (`1`, `.5`, `1.5e-3`) and 0x/0b
+// literals. A run of digits followed by letters is an identifier (`1abc`), not a number.
+fn number_len(rest: &str) -> Option<usize> {
+    let bytes = rest.as_bytes();
+    let digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .map_or(bytes.len(), |n| from + n)
+    };
+    let len = if let Some(prefixed) = rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0b")) {
+        let n = prefixed
+            .bytes()
+            .position(|b| !b.is_ascii_hexdigit())
+            .unwrap_or(prefixed.len());
+        if n == 0 {
+            return None;
+        }
+        2 + n
+    } else {
+        let mut len = digits(0);
+        if bytes.get(len) == Some(&b'.') {
+            len = digits(len + 1);
+        }
+        if len == 0 || &rest[..len] == "." {
+            return None;
+        }
+        if matches!(bytes.get(len), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+            let exponent_end = digits(len + 1 + sign);
+            if exponent_end > len + 1 + sign {
+                len = exponent_end;
+            }
+        }
+        len
+    };
+    match bytes.get(len) {
+        Some(b) if is_word_byte(*b) => None,
+        _ => Some(len),
+    }
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80
+}
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS
+        .binary_search(&word.to_ascii_uppercase().as_str())
+        .is_ok()
+}
+
+// Reserved words and the common non-reserved ones, sorted for binary search.
+const KEYWORDS: &[&str] = &[
+    "ADD",
+    "ALL",
+    "ALTER",
+    "AND",
+    "AS",
+    "ASC",
+    "AUTO_INCREMENT",
+    "BEGIN",
+    "BETWEEN",
+    "BIGINT",
+    "BINARY",
+    "BLOB",
+    "BY",
+    "CALL",
+    "CASE",
+    "CHAR",
+    "CHARACTER",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT_DATE",
+    "CURRENT_TIMESTAMP",
+    "DATABASE",
+    "DATETIME",
+    "DECIMAL",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DESCRIBE",
+    "DISTINCT",
+    "DIV",
+    "DOUBLE",
+    "DROP",
+    "DUPLICATE",
+    "ELSE",
+    "END",
+    "ENGINE",
+    "ESCAPE",
+    "EXISTS",
+    "EXPLAIN",
+    "FALSE",
+    "FLOAT",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INT",
+    "INTEGER",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOCK",
+    "MOD",
+    "NAMES",
+    "NATURAL",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "PRIMARY",
+    "PROCEDURE",
+    "REFERENCES",
+    "REGEXP",
+    "RENAME",
+    "REPLACE",
+    "RIGHT",
+    "ROLLBACK",
+    "SELECT",
+    "SESSION",
+    "SET",
+    "SHARE",
+    "SHOW",
+    "START",
+    "STATUS",
+    "TABLE",
+    "TEXT",
+    "THEN",
+    "TIMESTAMP",
+    "TINYINT",
+    "TO",
+    "TRANSACTION",
+    "TRUE",
+    "TRUNCATE",
+    "UNION",
+    "UNIQUE",
+    "UNSIGNED",
+    "UPDATE",
+    "USE",
+    "USING",
+    "VALUES",
+    "VARCHAR",
+    "VARIABLES",
+    "WHEN",
+    "WHERE",
+    "WITH",
+    "XOR",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATEMENTS: &[&str] = &[
+        "SELECT * FROM users WHERE id = 42",
+        "select  name,email from `my table` where name = 'O\\'Brien' and x in (1, 2.5e3, -7)",
+        "INSERT INTO t (a, b) VALUES (0x1F, X'1f'), (0b101, b'101') -- trailing comment",
+        "SELECT /* hint */ `col``umn`, \"it\"\"s\" # comment\nFROM db.t WHERE a<=>NULL",
+        "SELECT /*!40001 SQL_NO_CACHE */ @@session.sql_mode, @var := .5",
+        "UPDATE t SET note = 'a -- b /* c */ # d' WHERE id = 1;",
+    ];
+
+    #[test]
+    fn digest_is_idempotent() {
+        for sql in STATEMENTS {
+            let digest_once = digest(sql);
+            assert_eq!(digest(&digest_once), digest_once, "{}", sql);
+            assert_eq!(digest_hash(&digest_once), digest_hash(sql), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn digest_replaces_literals() {
+        assert_eq!(
+            digest("SELECT * FROM users WHERE id = 42 AND name = 'O\\'Brien'"),
+            "select * from users where id = ? and name = ?"
+        );
+        assert_eq!(
+            digest("SELECT 2.5e3, .5, 0x1F, X'1f', 0b101, b'101'"),
+            "select ?, ?, ?, ?, ?, ?"
+        );
+    }
+
+    #[test]
+    fn digest_drops_comments() {
+        assert_eq!(
+            digest("SELECT /* hint */ 1 -- one\n, 2 # two\n/*!40001 SQL_NO_CACHE */"),
+            "select ?, ?"
+        );
+        // Not a comment without the space after `--`.
+        assert_eq!(digest("SELECT 1--1"), digest("SELECT 1 - -1"));
+    }
+
+    #[test]
+    fn digest_keeps_quoted_identifiers() {
+        assert_eq!(
+            digest("SELECT `Select`, `a``b` FROM MyTable WHERE `x` = \"s\""),
+            "select `Select`, `a``b` from MyTable where `x` = ?"
+        );
+        // Comment markers in strings are part of the literal.
+        assert_eq!(
+            digest("UPDATE t SET note = 'a -- b /* c */ # d' WHERE id = 1"),
+            "update t set note = ? where id = ?"
+        );
+    }
+
+    #[test]
+    fn digest_hash_groups_statements_by_shape() {
+        assert_eq!(
+            digest_hash("SELECT * FROM t WHERE id = 1"),
+            digest_hash("select *  from t\nwhere id = 'x'")
+        );
+        assert_ne!(
+            digest_hash("SELECT * FROM t WHERE id = 1"),
+            digest_hash("SELECT * FROM u WHERE id = 1")
+        );
+    }
+
+    #[test]
+    fn placeholders_skip_strings_and_comments() {
+        assert_eq!(
+            placeholders("SELECT ?, '?', `?` /* ? */ FROM t WHERE a = ? -- ?"),
+            vec![7, 44]
+        );
+        let found = named_placeholders("SELECT :id, ':no', @a := 1, '12:30' WHERE x = :name_2");
+        let names = found.iter().map(|(_, name)| *name).collect::<Vec<_>>();
+        assert_eq!(names, ["id", "name_2"]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::connection::Connection;
+
+// The session's sql_mode, as its individual modes. Combination modes such as ANSI and TRADITIONAL
+// are already expanded by the server into the modes they stand for (ANSI stays in the list too).
+// https://dev.mysql.com/doc/refman/8.4/en/sql-mode.html
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SqlMode {
+    modes: Vec<String>,
+}
+
+impl SqlMode {
+    // The value of @@sql_mode: a comma-separated list, empty for no modes.
+    pub fn parse(s: &str) -> Self {
+        let modes = s
+            .split(',')
+            .map(str::trim)
+            .filter(|mode| !mode.is_empty())
+            .map(str::to_ascii_uppercase)
+            .collect();
+        Self { modes }
+    }
+
+    // Whether `mode` (any case) is set.
+    pub fn contains(&self, mode: &str) -> bool {
+        self.modes.iter().any(|m| m.eq_ignore_ascii_case(mode))
+    }
+
+    // The modes, uppercased, in the server's order.
+    pub fn modes(&self) -> &[String] {
+        &self.modes
+    }
+
+    // `"` quotes identifiers, not strings.
+    pub fn ansi_quotes(&self) -> bool {
+        self.contains("ANSI_QUOTES")
+    }
+
+    // A backslash is an ordinary character in string literals; see `Connection::escape_string`.
+    pub fn no_backslash_escapes(&self) -> bool {
+        self.contains("NO_BACKSLASH_ESCAPES")
+    }
+
+    // `||` concatenates strings instead of being OR.
+    pub fn pipes_as_concat(&self) -> bool {
+        self.contains("PIPES_AS_CONCAT")
+    }
+
+    // Invalid or out-of-range values are rejected rather than adjusted with a warning, for
+    // transactional tables at least.
+    pub fn is_strict(&self) -> bool {
+        self.contains("STRICT_TRANS_TABLES") || self.contains("STRICT_ALL_TABLES")
+    }
+}
+
+// The modes as @@sql_mode shows them, comma-separated.
+impl fmt::Display for SqlMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.modes.join(","))
+    }
+}
+
+impl Connection {
+    // The session's sql_mode. It is fetched once and cached; the cache is updated when session
+    // tracking reports a new value (with sql_mode in session_track_system_variables), and dropped
+    // when a `SET` mentioning sql_mode is sent through `query` and friends or the session is reset.
+    // A mode changed another way, e.g. inside a stored procedure, is only seen after that.
+    pub fn sql_mode(&mut self) -> Result<SqlMode> {
+        if let Some(mode) = &self.sql_mode {
+            return Ok(mode.clone());
+        }
+        let mode = SqlMode::parse(&self.server_variable("sql_mode")?);
+        self.sql_mode = Some(mode.clone());
+        Ok(mode)
+    }
+
+    // Drops the cached sql_mode before a statement that may change it.
+    pub(crate) fn forget_sql_mode_if_changed_by(&mut self, sql: &str) {
+        if self.sql_mode.is_some() && sets_sql_mode(sql) {
+            self.sql_mode = None;
+        }
+    }
+}
+
+// `SET sql_mode = ...`, `SET SESSION sql_mode`, `SET @@sql_mode`, ...; a statement setting several
+// variables only has to mention sql_mode somewhere.
+fn sets_sql_mode(sql: &str) -> bool {
+    let sql = sql.trim_start();
+    sql.get(..3)
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("SET"))
+        && sql.to_ascii_lowercase().contains("sql_mode")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_splits_the_modes() {
+        let mode = SqlMode::parse(
+            "ONLY_FULL_GROUP_BY,STRICT_TRANS_TABLES,NO_ZERO_IN_DATE,NO_ZERO_DATE,\
+             ERROR_FOR_DIVISION_BY_ZERO,NO_ENGINE_SUBSTITUTION",
+        );
+        assert_eq!(
+            mode.modes(),
+            [
+                "ONLY_FULL_GROUP_BY",
+                "STRICT_TRANS_TABLES",
+                "NO_ZERO_IN_DATE",
+                "NO_ZERO_DATE",
+                "ERROR_FOR_DIVISION_BY_ZERO",
+                "NO_ENGINE_SUBSTITUTION",
+            ]
+        );
+        assert!(mode.is_strict());
+        assert!(mode.contains("only_full_group_by"));
+        assert!(!mode.ansi_quotes());
+        assert!(!mode.no_backslash_escapes());
+        assert!(!mode.pipes_as_concat());
+    }
+
+    #[test]
+    fn parse_of_ansi_and_lowercase_modes() {
+        let mode = SqlMode::parse(
+            "real_as_float,PIPES_AS_CONCAT,ANSI_QUOTES,IGNORE_SPACE,ONLY_FULL_GROUP_BY,ANSI,\
+             NO_BACKSLASH_ESCAPES",
+        );
+        assert_eq!(mode.modes()[0], "REAL_AS_FLOAT");
+        assert!(mode.ansi_quotes());
+        assert!(mode.pipes_as_concat());
+        assert!(mode.no_backslash_escapes());
+        assert!(!mode.is_strict());
+        assert!(SqlMode::parse("STRICT_ALL_TABLES").is_strict());
+    }
+
+    #[test]
+    fn parse_of_an_empty_mode() {
+        let mode = SqlMode::parse("");
+        assert!(mode.modes().is_empty());
+        assert_eq!(mode, SqlMode::default());
+        assert_eq!(mode.to_string(), "");
+    }
+
+    #[test]
+    fn display_joins_the_modes() {
+        let mode = SqlMode::parse("strict_trans_tables, ansi_quotes");
+        assert_eq!(mode.to_string(), "STRICT_TRANS_TABLES,ANSI_QUOTES");
+    }
+
+    #[test]
+    fn sets_sql_mode_spots_set_statements() {
+        assert!(sets_sql_mode("SET sql_mode = ''"));
+        assert!(sets_sql_mode("  set SESSION SQL_MODE='ANSI'"));
+        assert!(sets_sql_mode(
+            "SET @@session.sql_mode = 'ANSI', autocommit = 0"
+        ));
+        assert!(!sets_sql_mode("SELECT @@sql_mode"));
+        assert!(!sets_sql_mode("SET autocommit = 0"));
+        assert!(!sets_sql_mode("SE"));
+    }
+}
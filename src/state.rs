@@ -0,0 +1,55 @@
+use std::fmt;
+
+use crate::connection::Connection;
+
+// What the connection is doing on the wire. Commands can only be sent while `Idle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    // Connected, waiting for the server greeting.
+    Connecting,
+    // The handshake response is sent and the server is checking it (possibly switching plugins).
+    Authenticating,
+    Idle,
+    // A command was sent and its response hasn't been read completely.
+    QueryInFlight,
+    // A `ResultSetIter` is reading rows; `rows_read` counts the ones read so far.
+    StreamingResult { rows_read: u64 },
+    // An io or protocol failure left the stream in an unknown position. This state is final.
+    Broken,
+}
+
+pub(crate) struct StateListener(Box<dyn FnMut(ConnectionState) + Send>);
+
+impl fmt::Debug for StateListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StateListener")
+    }
+}
+
+impl Connection {
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    // Called with the new state on every transition. Only one callback is kept; setting another one
+    // replaces it. `rows_read` of `StreamingResult` is updated without a notification per row.
+    pub fn on_state_change(&mut self, callback: impl FnMut(ConnectionState) + Send + 'static) {
+        self.state_listener = Some(StateListener(Box::new(callback)));
+    }
+
+    pub(crate) fn set_state(&mut self, state: ConnectionState) {
+        if self.state == state || self.state == ConnectionState::Broken {
+            return;
+        }
+        self.state = state;
+        if let Some(StateListener(callback)) = &mut self.state_listener {
+            callback(state);
+        }
+    }
+
+    pub(crate) fn count_row(&mut self) {
+        if let ConnectionState::StreamingResult { rows_read } = &mut self.state {
+            *rows_read += 1;
+        }
+    }
+}
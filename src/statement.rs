@@ -0,0 +1,736 @@
+use std::{fmt, time::Instant};
+
+use anyhow::{Result, bail};
+use log::debug;
+
+use crate::{
+    command::{ColumnDefinition41, ErrPacket, ResultsetRow},
+    connection::Connection,
+    consts::{
+        COM_STMT_CLOSE, COM_STMT_EXECUTE, COM_STMT_PREPARE, COM_STMT_RESET, EOF_HEADER, ERR_HEADER,
+        OK_HEADER,
+    },
+    decode::{PacketKind, check_len, packet_header},
+    encoding::{encode_params, encode_str},
+    handshake::{
+        CLIENT_OPTIONAL_RESULTSET_METADATA, CLIENT_QUERY_ATTRIBUTES,
+        MARIADB_CLIENT_STMT_BULK_OPERATIONS,
+    },
+    packet_writer::PacketWriter,
+    result::{QueryResult, ResultSet, fingerprint_column, schema_fingerprint},
+    utils::{FNV_OFFSET_BASIS, truncate},
+    value::Value,
+};
+
+// ER_NEED_REPREPARE: the tables behind a statement changed since it was prepared. The server
+// re-prepares transparently and only reports this after giving up, so the cached metadata is stale.
+const ER_NEED_REPREPARE: u16 = 1615;
+
+const STATEMENT_DEBUG_SQL_MAX_LEN: usize = 64;
+
+// MariaDB's COM_STMT_BULK_EXECUTE, its flag for sending the parameter types and the parameter
+// indicators.
+const COM_STMT_BULK_EXECUTE: u8 = 0xfa;
+const STMT_BULK_FLAG_SEND_TYPES_TO_SERVER: u16 = 128;
+const STMT_INDICATOR_NONE: u8 = 0;
+const STMT_INDICATOR_NULL: u8 = 1;
+
+// COM_STMT_PREPARE
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_stmt_prepare.html
+#[derive(Debug)]
+pub struct ComStmtPrepare {
+    pub command: u8,
+    // The statement in the session character set.
+    pub query: Vec<u8>,
+}
+
+impl ComStmtPrepare {
+    pub fn new(query: impl AsRef<[u8]>) -> Self {
+        Self {
+            command: COM_STMT_PREPARE,
+            query: query.as_ref().to_vec(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.command);
+        w.bytes(&self.query);
+    }
+}
+
+// COM_STMT_PREPARE_OK
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_stmt_prepare.html#sect_protocol_com_stmt_prepare_response_ok
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct StmtPrepareOk {
+    pub status: u8,
+    pub statement_id: u32,
+    pub num_columns: u16,
+    pub num_params: u16,
+    pub warning_count: u16,
+    pub metadata_follows: bool,
+}
+
+impl StmtPrepareOk {
+    pub fn decode(pkt: &[u8], capabilities: u32) -> Result<Self> {
+        let mut pos = 0;
+
+        check_len(pkt, pos, 10)?;
+        let status = pkt[pos];
+        if status != 0x00 {
+            bail!("not prepare ok packet");
+        }
+        pos += 1;
+
+        let statement_id = u32::from_le_bytes([pkt[pos], pkt[pos + 1], pkt[pos + 2], pkt[pos + 3]]);
+        pos += 4;
+
+        let num_columns = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
+        pos += 2;
+
+        let num_params = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
+        pos += 2;
+
+        // reserved_1
+        pos += 1;
+
+        let mut warning_count = 0;
+        if pkt.len() >= pos + 2 {
+            warning_count = u16::from_le_bytes([pkt[pos], pkt[pos + 1]]);
+            pos += 2;
+        }
+
+        let mut metadata_follows = true;
+        if capabilities & CLIENT_OPTIONAL_RESULTSET_METADATA != 0 && pkt.len() > pos {
+            metadata_follows = pkt[pos] != 0;
+        }
+
+        Ok(Self {
+            status,
+            statement_id,
+            num_columns,
+            num_params,
+            warning_count,
+            metadata_follows,
+        })
+    }
+}
+
+// COM_STMT_EXECUTE
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_stmt_execute.html
+#[derive(Debug)]
+pub struct ComStmtExecute<'a> {
+    pub command: u8,
+    pub statement_id: u32,
+    pub flags: u8,
+    pub iteration_count: u32,
+    pub query_attributes: bool,
+    // new_params_bind_flag: whether the parameter types are sent. Without them the server uses the
+    // ones of the statement's previous execution.
+    pub new_params_bound: bool,
+    pub params: &'a [Value],
+}
+
+impl<'a> ComStmtExecute<'a> {
+    pub fn new(statement_id: u32, params: &'a [Value], query_attributes: bool) -> Self {
+        Self {
+            command: COM_STMT_EXECUTE,
+            statement_id,
+            flags: 0, // CURSOR_TYPE_NO_CURSOR
+            iteration_count: 1,
+            query_attributes,
+            new_params_bound: true,
+            params,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.command);
+        w.u32_le(self.statement_id);
+        w.u8(self.flags);
+        w.u32_le(self.iteration_count);
+        if self.params.is_empty() {
+            return;
+        }
+
+        if self.query_attributes {
+            w.lenenc_int(self.params.len() as u64);
+        }
+        for params in self.params.chunks(8) {
+            let null_bitmap = params
+                .iter()
+                .enumerate()
+                .filter(|(_, param)| param.is_null())
+                .fold(0, |bitmap, (i, _)| bitmap | 1 << i);
+            w.u8(null_bitmap);
+        }
+        w.u8(self.new_params_bound as u8);
+        if !self.new_params_bound {
+            self.params.iter().for_each(|param| param.write_binary(w));
+            return;
+        }
+        for param in self.params {
+            let (type_, flags) = param.binary_type();
+            w.u8(type_);
+            w.u8(flags);
+            // Parameters of a prepared statement are unnamed.
+            if self.query_attributes {
+                w.u8(0);
+            }
+        }
+        for param in self.params {
+            param.write_binary(w);
+        }
+    }
+}
+
+// COM_STMT_BULK_EXECUTE (MariaDB 10.2+, with MARIADB_CLIENT_STMT_BULK_OPERATIONS): one execution
+// of a statement with several parameter rows. Every row is sent with the same parameter types, so
+// the values of a column must all be of one type (or NULL).
+// https://mariadb.com/kb/en/com_stmt_bulk_execute/
+#[derive(Debug)]
+pub struct ComStmtBulkExecute<'a> {
+    pub command: u8,
+    pub statement_id: u32,
+    pub flags: u16,
+    pub param_types: Vec<(u8, u8)>,
+    pub rows: &'a [Vec<Value>],
+}
+
+impl<'a> ComStmtBulkExecute<'a> {
+    pub fn new(statement_id: u32, param_types: Vec<(u8, u8)>, rows: &'a [Vec<Value>]) -> Self {
+        Self {
+            command: COM_STMT_BULK_EXECUTE,
+            statement_id,
+            flags: STMT_BULK_FLAG_SEND_TYPES_TO_SERVER,
+            param_types,
+            rows,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.command);
+        w.u32_le(self.statement_id);
+        w.u16_le(self.flags);
+        if self.flags & STMT_BULK_FLAG_SEND_TYPES_TO_SERVER != 0 {
+            for (type_, flags) in &self.param_types {
+                w.u8(*type_);
+                w.u8(*flags);
+            }
+        }
+        for row in self.rows {
+            for param in row {
+                // The indicator: a value follows, or NULL.
+                match param.is_null() {
+                    true => w.u8(STMT_INDICATOR_NULL),
+                    false => {
+                        w.u8(STMT_INDICATOR_NONE);
+                        param.write_binary(w);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The type of each parameter column for COM_STMT_BULK_EXECUTE, from its first non-NULL value.
+// `None` when a column mixes types.
+fn bulk_param_types(rows: &[Vec<Value>], param_count: usize) -> Option<Vec<(u8, u8)>> {
+    let mut types = vec![Value::Null.binary_type(); param_count];
+    for row in rows {
+        for (type_, param) in types.iter_mut().zip(row) {
+            if param.is_null() {
+                continue;
+            }
+            if *type_ == Value::Null.binary_type() {
+                *type_ = param.binary_type();
+            } else if *type_ != param.binary_type() {
+                return None;
+            }
+        }
+    }
+    Some(types)
+}
+
+// COM_STMT_CLOSE
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_stmt_close.html
+#[derive(Debug)]
+pub struct ComStmtClose {
+    pub command: u8,
+    pub statement_id: u32,
+}
+
+impl ComStmtClose {
+    pub fn new(statement_id: u32) -> Self {
+        Self {
+            command: COM_STMT_CLOSE,
+            statement_id,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        PacketWriter::encode(|w| self.write_to(w))
+    }
+
+    pub fn write_to(&self, w: &mut PacketWriter) {
+        w.u8(self.command);
+        w.u32_le(self.statement_id);
+    }
+}
+
+// A server-side prepared statement. It belongs to the connection that prepared it and stays open
+// until `Connection::close_statement`.
+//
+// The result set metadata is cached: the column definitions are only decoded on the first
+// execution, and when the server skips sending them (a session with `resultset_metadata = NONE`,
+// which requires CLIENT_OPTIONAL_RESULTSET_METADATA) the cached ones are used instead. The cache is
+// dropped on ER_NEED_REPREPARE and when the session's default schema changes, since unqualified
+// table names may then resolve to other tables. It is keyed by the `schema_fingerprint` of the
+// columns too: when the server sends definitions whose names or types differ from the cached ones
+// (e.g. for a `SELECT *` after an ALTER TABLE), they are decoded again.
+pub struct Statement {
+    pub(crate) id: u32,
+    pub(crate) sql: String,
+    pub(crate) connection_id: u32,
+    pub(crate) param_count: u16,
+    // The parameter definitions sent with COM_STMT_PREPARE_OK, when the server sends metadata.
+    pub(crate) params: Vec<ColumnDefinition41>,
+    pub(crate) warnings: u16,
+    pub(crate) columns: Option<Vec<ColumnDefinition41>>,
+    pub(crate) metadata_generation: u64,
+    // The `schema_fingerprint` of `columns`.
+    pub(crate) fingerprint: u64,
+    // The parameter types the server has from the last execution; `None` when they must be sent.
+    pub(crate) param_types: Option<Vec<(u8, u8)>>,
+}
+
+impl Statement {
+    // The id the server gave the statement, as in performance_schema.prepared_statements_instances
+    // (STATEMENT_ID).
+    pub fn statement_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn param_count(&self) -> u16 {
+        self.param_count
+    }
+
+    // The parameter definitions from the prepare response. Servers can't tell a parameter's type
+    // from the statement, so these are mostly MYSQL_TYPE_VAR_STRING; empty when the server sent no
+    // metadata (CLIENT_OPTIONAL_RESULTSET_METADATA).
+    pub fn param_types(&self) -> &[ColumnDefinition41] {
+        &self.params
+    }
+
+    // The result set columns, from the prepare response or from the latest execution that changed
+    // them. Empty for a statement without a result set, and when the metadata is unknown (not sent,
+    // or dropped after ER_NEED_REPREPARE).
+    pub fn columns(&self) -> &[ColumnDefinition41] {
+        self.columns.as_deref().unwrap_or_default()
+    }
+
+    // The warning count of COM_STMT_PREPARE_OK; SHOW WARNINGS right after `prepare` lists them.
+    pub fn warnings_at_prepare(&self) -> u16 {
+        self.warnings
+    }
+
+    fn cached_columns(&self, generation: u64, count: u64) -> Option<&Vec<ColumnDefinition41>> {
+        self.columns.as_ref().filter(|columns| {
+            self.metadata_generation == generation && columns.len() as u64 == count
+        })
+    }
+
+    // Whether the types of `params` must be sent: on the first execution, and when a parameter's
+    // type differs from the one the server has. A NULL parameter matches any type, since its value
+    // is only in the NULL bitmap, but a parameter that was NULL before needs its type now.
+    fn needs_param_types(&self, params: &[Value]) -> bool {
+        let Some(types) = &self.param_types else {
+            return true;
+        };
+        params
+            .iter()
+            .zip(types)
+            .any(|(param, type_)| !param.is_null() && param.binary_type() != *type_)
+    }
+
+    fn cache_columns(&mut self, columns: Vec<ColumnDefinition41>, generation: u64) {
+        self.fingerprint = schema_fingerprint(&columns);
+        self.columns = Some(columns);
+        self.metadata_generation = generation;
+    }
+}
+
+// Statement { id: 1, sql: "SELECT id, name FROM users WHERE ...", params: 1, columns: 2 }
+impl fmt::Debug for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sql = truncate(&self.sql, STATEMENT_DEBUG_SQL_MAX_LEN);
+        let sql = match sql.len() < self.sql.len() {
+            true => format!("{}...", sql),
+            false => String::from(sql),
+        };
+        f.debug_struct("Statement")
+            .field("id", &self.id)
+            .field("sql", &sql)
+            .field("params", &self.param_count)
+            .field("columns", &self.columns().len())
+            .finish()
+    }
+}
+
+impl Connection {
+    pub fn prepare(&mut self, sql: &str) -> Result<Statement> {
+        self.reconnect_if_closed()?;
+        let sql = &self.rewrite(sql);
+        let com_stmt_prepare = ComStmtPrepare::new(encode_str(sql, &self.options.charset)?);
+        self.begin_command()?;
+        let prepared = self
+            .write_packet_with(|w| com_stmt_prepare.write_to(w))
+            .and_then(|()| self.read_prepare_response(sql));
+        self.end_command(prepared)
+    }
+
+    // Returns the first result, like `query`.
+    pub fn execute(&mut self, stmt: &mut Statement, params: &[Value]) -> Result<QueryResult> {
+        if stmt.connection_id != self.connection_id() {
+            bail!("the statement was prepared on another connection");
+        }
+        if params.len() != stmt.param_count as usize {
+            bail!(
+                "the statement takes {} parameters, but {} were given",
+                stmt.param_count,
+                params.len()
+            );
+        }
+        let params = encode_params(params, &self.options.charset)?;
+        let started = Instant::now();
+        let mut first = None;
+        let mut rows = 0;
+        let new_params_bound =
+            self.options.always_send_param_types || stmt.needs_param_types(&params);
+        // Cleared until the execution succeeds, so a failure never leaves types the server may
+        // not have.
+        let param_types = match new_params_bound {
+            true => Some(params.iter().map(Value::binary_type).collect()),
+            false => stmt.param_types.take(),
+        };
+        let ran = self.begin_command().and_then(|()| {
+            let query_attributes = self.capabilities & CLIENT_QUERY_ATTRIBUTES != 0;
+            self.write_packet_with(|w| {
+                let mut execute = ComStmtExecute::new(stmt.id, &params, query_attributes);
+                execute.new_params_bound = new_params_bound;
+                execute.write_to(w)
+            })?;
+            let read = self.read_binary_results(stmt, |result| {
+                rows += match &result {
+                    QueryResult::ResultSet(rs) => rs.rows.len() as u64,
+                    QueryResult::Ok(ok) => ok.affected_rows,
+                };
+                first.get_or_insert(result);
+            });
+            self.end_command(read)
+        });
+        if let Err(err) = &ran
+            && err
+                .downcast_ref::<ErrPacket>()
+                .is_some_and(|err| err.error_code == ER_NEED_REPREPARE)
+        {
+            stmt.columns = None;
+        }
+        if ran.is_ok() {
+            stmt.param_types = param_types;
+        }
+        let sql = stmt.sql.clone();
+        self.report_slow_query(&sql, started.elapsed(), rows);
+        ran?;
+        Ok(first.expect("at least one result is read"))
+    }
+
+    // Executes the statement once for each row of parameters and returns the affected rows of all
+    // executions. With MariaDB the rows go out in one COM_STMT_BULK_EXECUTE, a single round trip;
+    // other servers, statements with a result set and rows mixing types in a column get one
+    // execution per row. MySQL has no way to send several parameter rows at once (COM_QUERY's
+    // parameter_set_count must be 1).
+    //
+    // Per-row executions stop at the first failing row; a bulk execution fails as a whole, and its
+    // error doesn't say which row failed.
+    pub fn execute_batch(&mut self, stmt: &mut Statement, rows: &[Vec<Value>]) -> Result<u64> {
+        if stmt.connection_id != self.connection_id() {
+            bail!("the statement was prepared on another connection");
+        }
+        if let Some((i, row)) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.len() != stmt.param_count as usize)
+        {
+            bail!(
+                "the statement takes {} parameters, but row {} has {}",
+                stmt.param_count,
+                i,
+                row.len()
+            );
+        }
+        let param_types = (self.mariadb_capabilities & MARIADB_CLIENT_STMT_BULK_OPERATIONS != 0
+            && stmt.param_count > 0
+            && stmt.columns().is_empty())
+        .then(|| bulk_param_types(rows, stmt.param_count as usize))
+        .flatten();
+        let Some(param_types) = param_types.filter(|_| rows.len() > 1) else {
+            let mut affected_rows = 0;
+            for row in rows {
+                affected_rows += self.execute(stmt, row)?.affected_rows();
+            }
+            return Ok(affected_rows);
+        };
+        let rows = rows
+            .iter()
+            .map(|row| Ok(encode_params(row, &self.options.charset)?.into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+
+        debug!(
+            "bulk executing statement {} with {} rows",
+            stmt.id,
+            rows.len()
+        );
+        let started = Instant::now();
+        // The server takes the types sent here for later executions too.
+        stmt.param_types = None;
+        self.begin_command()?;
+        let ran = self
+            .write_packet_with(|w| ComStmtBulkExecute::new(stmt.id, param_types, &rows).write_to(w))
+            .and_then(|()| self.read_packet())
+            .and_then(|pkt| {
+                self.check_server_error(&pkt)?;
+                self.decode_ok(pkt)
+            });
+        let ok = self.end_command(ran)?;
+        let sql = stmt.sql.clone();
+        self.report_slow_query(&sql, started.elapsed(), ok.affected_rows);
+        Ok(ok.affected_rows)
+    }
+
+    // COM_STMT_RESET: discards the long data sent for the statement and closes its cursor, if any.
+    // The parameter types are sent again on the next execution.
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_stmt_reset.html
+    pub fn reset_statement(&mut self, stmt: &mut Statement) -> Result<()> {
+        if stmt.connection_id != self.connection_id() {
+            bail!("the statement was prepared on another connection");
+        }
+        stmt.param_types = None;
+        let mut payload = vec![COM_STMT_RESET];
+        payload.extend_from_slice(&stmt.id.to_le_bytes());
+        self.simple_command(&payload)?;
+        Ok(())
+    }
+
+    // The server sends no response to COM_STMT_CLOSE.
+    pub fn close_statement(&mut self, stmt: Statement) -> Result<()> {
+        if stmt.connection_id != self.connection_id() {
+            return Ok(());
+        }
+        self.begin_command()?;
+        let closed = self.write_packet_with(|w| ComStmtClose::new(stmt.id).write_to(w));
+        self.end_command(closed)
+    }
+
+    // COM_STMT_PREPARE_OK is followed by the parameter definitions and then the column definitions,
+    // each only when there are any (and the server sends metadata), and each ended by an EOF packet
+    // when CLIENT_DEPRECATE_EOF isn't negotiated. A statement without either, like `SET @x = 1` or
+    // DDL, gets nothing after COM_STMT_PREPARE_OK.
+    fn read_prepare_response(&mut self, sql: &str) -> Result<Statement> {
+        let pkt = self.read_packet()?;
+        if pkt.first() == Some(&ERR_HEADER) {
+            bail!(self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?);
+        }
+        let capabilities = self.capabilities;
+        let prepare_ok = self.decode_packet(PacketKind::StmtPrepareOk, &pkt, |pkt| {
+            StmtPrepareOk::decode(pkt, capabilities)
+        })?;
+        debug!("prepared statement {:?}", prepare_ok);
+        let mut params = vec![];
+        let mut columns = vec![];
+        if prepare_ok.metadata_follows {
+            if prepare_ok.num_params > 0 {
+                for _ in 0..prepare_ok.num_params {
+                    params.push(self.read_column_definition()?);
+                }
+                self.read_eof()?;
+            }
+            if prepare_ok.num_columns > 0 {
+                for _ in 0..prepare_ok.num_columns {
+                    columns.push(self.read_column_definition()?);
+                }
+                self.read_eof()?;
+            }
+        }
+        self.warning_count = prepare_ok.warning_count;
+        Ok(Statement {
+            id: prepare_ok.statement_id,
+            sql: String::from(sql),
+            connection_id: self.connection_id(),
+            param_count: prepare_ok.num_params,
+            params,
+            warnings: prepare_ok.warning_count,
+            fingerprint: schema_fingerprint(&columns),
+            param_types: None,
+            columns: (prepare_ok.metadata_follows && prepare_ok.num_columns > 0).then_some(columns),
+            metadata_generation: self.metadata_generation,
+        })
+    }
+
+    fn read_binary_results(
+        &mut self,
+        stmt: &mut Statement,
+        mut on_result: impl FnMut(QueryResult),
+    ) -> Result<()> {
+        loop {
+            let result = self.read_binary_result(stmt)?;
+            self.warning_count = result.warnings();
+            let more_results = result.status_flags().more_results_exists();
+            on_result(result);
+            if !more_results {
+                return Ok(());
+            }
+        }
+    }
+
+    // Binary Protocol Resultset
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_binary_resultset.html
+    fn read_binary_result(&mut self, stmt: &mut Statement) -> Result<QueryResult> {
+        let pkt = self.read_packet()?;
+        match packet_header(&pkt)? {
+            ERR_HEADER => bail!(self.decode_packet(PacketKind::Err, &pkt, ErrPacket::decode)?),
+            OK_HEADER => return Ok(QueryResult::Ok(self.decode_ok(pkt)?)),
+            _ => {}
+        }
+        let (col_count, metadata_follows) = self.decode_column_count(&pkt)?;
+        let generation = self.metadata_generation;
+        let columns = match (metadata_follows, stmt.cached_columns(generation, col_count)) {
+            // The definitions are usually the same on every execution: only their names and types
+            // are read, to compare their fingerprint with the cached one, and they are decoded
+            // again only when they changed.
+            (true, Some(columns)) => {
+                let mut pkts = Vec::with_capacity(columns.len());
+                let mut fingerprint = FNV_OFFSET_BASIS;
+                for _ in 0..col_count {
+                    let pkt = self.read_packet()?;
+                    self.check_server_error(&pkt)?;
+                    fingerprint =
+                        self.decode_packet(PacketKind::ColumnDefinition, &pkt, |pkt| {
+                            let (name, type_) = ColumnDefinition41::decode_name_and_type(pkt)?;
+                            Ok(fingerprint_column(fingerprint, name, type_))
+                        })?;
+                    pkts.push(pkt);
+                }
+                self.read_eof()?;
+                if fingerprint == stmt.fingerprint {
+                    columns.clone()
+                } else {
+                    debug!("the result set columns of statement {} changed", stmt.id);
+                    let columns = pkts
+                        .iter()
+                        .map(|pkt| {
+                            self.decode_packet(
+                                PacketKind::ColumnDefinition,
+                                pkt,
+                                ColumnDefinition41::decode,
+                            )
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    stmt.cache_columns(columns.clone(), generation);
+                    columns
+                }
+            }
+            (true, None) => {
+                let mut columns = vec![];
+                for _ in 0..col_count {
+                    columns.push(self.read_column_definition()?);
+                }
+                self.read_eof()?;
+                stmt.cache_columns(columns.clone(), generation);
+                columns
+            }
+            (false, Some(columns)) => columns.clone(),
+            (false, None) => {
+                bail!("the server sent no result set metadata and none is cached for the statement")
+            }
+        };
+
+        let mut rows = vec![];
+        let terminator = loop {
+            let pkt = self.read_packet()?;
+            self.check_server_error(&pkt)?;
+            if packet_header(&pkt)? == EOF_HEADER && pkt.len() < 0xffffff {
+                break self.decode_terminator(pkt)?;
+            }
+            rows.push(self.decode_packet(PacketKind::Row, &pkt, |pkt| {
+                decode_binary_row(pkt, &columns)
+            })?);
+        };
+        Ok(QueryResult::ResultSet(ResultSet {
+            columns,
+            rows,
+            terminator,
+            truncated: None,
+            server_stats: None,
+        }))
+    }
+
+    fn read_column_definition(&mut self) -> Result<ColumnDefinition41> {
+        let pkt = self.read_packet()?;
+        self.check_server_error(&pkt)?;
+        self.decode_packet(
+            PacketKind::ColumnDefinition,
+            &pkt,
+            ColumnDefinition41::decode,
+        )
+    }
+}
+
+// ProtocolBinary::ResultsetRow
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_binary_resultset.html#sect_protocol_binary_resultset_row
+// Values are converted to the text the text protocol would have sent for them, so rows of both
+// protocols look the same.
+pub fn decode_binary_row(pkt: &[u8], columns: &[ColumnDefinition41]) -> Result<ResultsetRow> {
+    let mut pos = 0;
+
+    check_len(pkt, pos, 1)?;
+    if pkt[pos] != 0x00 {
+        bail!("not binary resultset row packet");
+    }
+    pos += 1;
+
+    // The NULL bitmap of a row starts at bit 2.
+    let bitmap_len = (columns.len() + 7 + 2) / 8;
+    check_len(pkt, pos, bitmap_len)?;
+    let null_bitmap = &pkt[pos..(pos + bitmap_len)];
+    pos += bitmap_len;
+
+    let mut cells = Vec::with_capacity(columns.len());
+    for (i, column) in columns.iter().enumerate() {
+        let bit = i + 2;
+        if null_bitmap[bit / 8] & (1 << (bit % 8)) != 0 {
+            cells.push(None);
+            continue;
+        }
+        let (value, consumed) = Value::decode_binary(pkt, pos, column)?;
+        cells.push(value.encode_text(column));
+        pos += consumed;
+    }
+    Ok(ResultsetRow(cells))
+}
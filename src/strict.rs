@@ -0,0 +1,179 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    decode::PacketKind,
+    error::Error,
+    handshake::{CLIENT_LONG_PASSWORD, CLIENT_OPTIONAL_RESULTSET_METADATA},
+    utils::decode_lenenc_integer,
+};
+
+// The rules `ConnectionOptions::strict_protocol` checks, as found in `Error::ProtocolViolation`.
+// The decoders accept every packet breaking them; strict mode is for testing a server against the
+// protocol documentation, not for talking to one.
+//
+// Sequence ids aren't checked for wrapping: MySQL itself wraps them from 255 to 0 in any response
+// longer than 256 packets (e.g. a result set of a few hundred rows).
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_packets.html#sect_protocol_basic_packets_sequence_id
+
+// The filler byte after auth-plugin-data-part-1 of Protocol::HandshakeV10 isn't 0x00.
+pub const HANDSHAKE_FILLER: &str = "handshake.filler";
+// The 10 reserved bytes of Protocol::HandshakeV10 aren't all 0x00. MariaDB sends its extended
+// capabilities in the last 4 (announced by leaving CLIENT_LONG_PASSWORD unset), so those are only
+// checked for servers setting CLIENT_LONG_PASSWORD.
+pub const HANDSHAKE_RESERVED: &str = "handshake.reserved";
+// length_of_fixed_length_fields of a ColumnDefinition41 isn't 0x0c.
+pub const COLUMN_DEFINITION_FIXED_LENGTH: &str = "column_definition.fixed_length";
+// The 2 bytes ending the fixed-length fields of a ColumnDefinition41 are missing or not 0x00.
+pub const COLUMN_DEFINITION_FILLER: &str = "column_definition.filler";
+// Bytes after the last field of a ColumnDefinition41.
+pub const COLUMN_DEFINITION_TRAILING_BYTES: &str = "column_definition.trailing_bytes";
+// The reserved byte of COM_STMT_PREPARE_OK isn't 0x00.
+pub const STMT_PREPARE_OK_RESERVED: &str = "stmt_prepare_ok.reserved";
+// Bytes after the last field of COM_STMT_PREPARE_OK (warning_count, or metadata_follows with
+// CLIENT_OPTIONAL_RESULTSET_METADATA).
+pub const STMT_PREPARE_OK_TRAILING_BYTES: &str = "stmt_prepare_ok.trailing_bytes";
+// An EOF_Packet that isn't 5 bytes long.
+pub const EOF_LENGTH: &str = "eof.length";
+
+fn violation(rule: &'static str, detail: String) -> Result<()> {
+    bail!(Error::ProtocolViolation { rule, detail })
+}
+
+// Checks a packet that was decoded successfully; `capabilities` are those of the connection.
+pub(crate) fn check(kind: PacketKind, pkt: &[u8], capabilities: u32) -> Result<()> {
+    match kind {
+        PacketKind::Handshake => check_handshake(pkt),
+        PacketKind::ColumnDefinition => check_column_definition(pkt),
+        PacketKind::StmtPrepareOk => check_stmt_prepare_ok(pkt, capabilities),
+        PacketKind::Eof => check_eof(pkt),
+        _ => Ok(()),
+    }
+}
+
+// Protocol::HandshakeV10
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_connection_phase_packets_protocol_handshake_v10.html
+fn check_handshake(pkt: &[u8]) -> Result<()> {
+    // The decoder takes a server version without a NUL as running to the end of the packet.
+    let Some(version_end) = pkt.iter().skip(1).position(|&b| b == 0) else {
+        return Ok(());
+    };
+    // protocol_version, server_version and its NUL, thread_id, auth-plugin-data-part-1
+    let filler_pos = 1 + version_end + 1 + 4 + 8;
+    let Some(&filler) = pkt.get(filler_pos) else {
+        return Ok(());
+    };
+    if filler != 0 {
+        return violation(
+            HANDSHAKE_FILLER,
+            format!("filler at offset {} is 0x{:02x}", filler_pos, filler),
+        );
+    }
+    let capability_flags_1 = match pkt.get(filler_pos + 1..filler_pos + 3) {
+        Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+        None => return Ok(()),
+    };
+    // filler, capability_flags_1, character_set, status_flags, capability_flags_2,
+    // auth_plugin_data_len
+    let reserved_pos = filler_pos + 1 + 2 + 1 + 2 + 2 + 1;
+    let reserved_len = if capability_flags_1 & CLIENT_LONG_PASSWORD != 0 {
+        10
+    } else {
+        6
+    };
+    if let Some(reserved) = pkt.get(reserved_pos..reserved_pos + reserved_len)
+        && let Some(i) = reserved.iter().position(|&b| b != 0)
+    {
+        return violation(
+            HANDSHAKE_RESERVED,
+            format!(
+                "reserved byte at offset {} is 0x{:02x}",
+                reserved_pos + i,
+                reserved[i]
+            ),
+        );
+    }
+    Ok(())
+}
+
+// Protocol::ColumnDefinition41
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query_response_text_resultset_column_definition.html
+fn check_column_definition(pkt: &[u8]) -> Result<()> {
+    let mut pos = 0;
+    // catalog, schema, table, org_table, name, org_name
+    for _ in 0..6 {
+        let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+        pos += consumed + len as usize;
+    }
+    let (fixed_len, consumed) = decode_lenenc_integer(pkt, pos)?;
+    pos += consumed;
+    if fixed_len != 0x0c {
+        return violation(
+            COLUMN_DEFINITION_FIXED_LENGTH,
+            format!("length_of_fixed_length_fields is 0x{:02x}", fixed_len),
+        );
+    }
+    // character_set, column_length, type, flags, decimals
+    pos += 2 + 4 + 1 + 2 + 1;
+    match pkt.get(pos..pos + 2) {
+        None => {
+            return violation(
+                COLUMN_DEFINITION_FILLER,
+                format!("the packet ends at offset {}, before the filler", pkt.len()),
+            );
+        }
+        Some([0, 0]) => {}
+        Some(filler) => {
+            return violation(
+                COLUMN_DEFINITION_FILLER,
+                format!("filler at offset {} is {:02x?}", pos, filler),
+            );
+        }
+    }
+    pos += 2;
+    if pkt.len() > pos {
+        return violation(
+            COLUMN_DEFINITION_TRAILING_BYTES,
+            format!("{} bytes after offset {}", pkt.len() - pos, pos),
+        );
+    }
+    Ok(())
+}
+
+// COM_STMT_PREPARE_OK
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_stmt_prepare.html#sect_protocol_com_stmt_prepare_response_ok
+fn check_stmt_prepare_ok(pkt: &[u8], capabilities: u32) -> Result<()> {
+    // status, statement_id, num_columns, num_params
+    let reserved_pos = 1 + 4 + 2 + 2;
+    if let Some(&reserved) = pkt.get(reserved_pos)
+        && reserved != 0
+    {
+        return violation(
+            STMT_PREPARE_OK_RESERVED,
+            format!(
+                "reserved byte at offset {} is 0x{:02x}",
+                reserved_pos, reserved
+            ),
+        );
+    }
+    let mut len = reserved_pos + 1 + 2;
+    if capabilities & CLIENT_OPTIONAL_RESULTSET_METADATA != 0 {
+        len += 1;
+    }
+    if pkt.len() > len {
+        return violation(
+            STMT_PREPARE_OK_TRAILING_BYTES,
+            format!("{} bytes after offset {}", pkt.len() - len, len),
+        );
+    }
+    Ok(())
+}
+
+// Protocol::EOF_Packet
+// https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_eof_packet.html
+fn check_eof(pkt: &[u8]) -> Result<()> {
+    // header, warnings, status_flags
+    if pkt.len() != 5 {
+        return violation(EOF_LENGTH, format!("{} bytes instead of 5", pkt.len()));
+    }
+    Ok(())
+}
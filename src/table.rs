@@ -0,0 +1,295 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Renders rows as an ASCII table like the mysql client does. Widths are measured in terminal
+// columns, so wide (CJK, emoji) and zero-width (combining) characters line up; control characters
+// are escaped so a cell always stays on one line. With `max_width`, longer cells are cut at a
+// grapheme boundary and end with "...". The cells of columns with `right_align` set (numbers) are
+// right-aligned; headers are always left-aligned, as in the mysql client.
+pub fn render(
+    header: &[&str],
+    rows: &[Vec<Option<String>>],
+    right_align: &[bool],
+    max_width: Option<usize>,
+) -> String {
+    let cell = |value: &str| truncate(&escape_control(value), max_width);
+    let header = header.iter().map(|name| cell(name)).collect::<Vec<_>>();
+    let rows = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| cell(value.as_deref().unwrap_or("NULL")))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let widths = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .chain([&header[i]])
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let separator = format!(
+        "+{}+\n",
+        widths
+            .iter()
+            .map(|width| "-".repeat(width + 2))
+            .collect::<Vec<_>>()
+            .join("+")
+    );
+    let line = |cells: &[String], right_align: &[bool]| {
+        let cells = cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                let padding = " ".repeat(width - cell.width());
+                match right_align.get(i) {
+                    Some(true) => format!(" {}{} ", padding, cell),
+                    _ => format!(" {}{} ", cell, padding),
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("|{}|\n", cells.join("|"))
+    };
+
+    let mut out = separator.clone();
+    out.push_str(&line(&header, &[]));
+    out.push_str(&separator);
+    for row in &rows {
+        out.push_str(&line(row, right_align));
+    }
+    out.push_str(&separator);
+    out
+}
+
+// Renders rows one after another as "name: value" lines under a numbered banner, like the mysql
+// client's `\G`. Names are right-aligned so the values line up; cells are escaped and cut as in
+// `render`.
+pub fn render_vertical(
+    header: &[&str],
+    rows: &[Vec<Option<String>>],
+    max_width: Option<usize>,
+) -> String {
+    const STARS: &str = "***************************";
+
+    let header = header
+        .iter()
+        .map(|name| escape_control(name))
+        .collect::<Vec<_>>();
+    let name_width = header.iter().map(|name| name.width()).max().unwrap_or(0);
+    let mut out = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        out.push_str(&format!("{} {}. row {}\n", STARS, i + 1, STARS));
+        for (name, value) in header.iter().zip(row) {
+            let value = truncate(
+                &escape_control(value.as_deref().unwrap_or("NULL")),
+                max_width,
+            );
+            out.push_str(&format!(
+                "{}{}: {}\n",
+                " ".repeat(name_width - name.width()),
+                name,
+                value
+            ));
+        }
+    }
+    out
+}
+
+// Renders rows as a GitHub-flavored Markdown table. Columns with `right_align` set (numbers) are
+// right-aligned. Pipes are escaped and line breaks become `<br>` so a cell stays in its column.
+pub fn render_markdown(
+    header: &[&str],
+    rows: &[Vec<Option<String>>],
+    right_align: &[bool],
+) -> String {
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut out = line(header.iter().map(|name| escape_markdown(name)).collect());
+    out.push_str(&line(
+        (0..header.len())
+            .map(|i| match right_align.get(i) {
+                Some(true) => String::from("---:"),
+                _ => String::from("---"),
+            })
+            .collect(),
+    ));
+    for row in rows {
+        out.push_str(&line(
+            row.iter()
+                .map(|value| escape_markdown(value.as_deref().unwrap_or("NULL")))
+                .collect(),
+        ));
+    }
+    out
+}
+
+fn escape_markdown(value: &str) -> String {
+    let value = value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>");
+    escape_control(&value)
+}
+
+// Replaces control characters with their escaped forms (`\n`, `\t`, `\x1b`, ...).
+pub fn escape_control(value: &str) -> String {
+    let mut buf = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\0' => buf.push_str("\\0"),
+            c if c.is_control() => buf.push_str(&format!("\\x{:02x}", c as u32)),
+            c => buf.push(c),
+        }
+    }
+    buf
+}
+
+// Cuts `value` to at most `max_width` columns without splitting a grapheme cluster.
+pub fn truncate(value: &str, max_width: Option<usize>) -> String {
+    const ELLIPSIS: &str = "...";
+
+    let Some(max_width) = max_width else {
+        return String::from(value);
+    };
+    if value.width() <= max_width {
+        return String::from(value);
+    }
+    let budget = max_width.saturating_sub(ELLIPSIS.len());
+    let mut buf = String::new();
+    let mut width = 0;
+    for grapheme in value.graphemes(true) {
+        width += grapheme.width();
+        if width > budget {
+            break;
+        }
+        buf.push_str(grapheme);
+    }
+    buf.push_str(ELLIPSIS);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[Option<&str>]]) -> Vec<Vec<Option<String>>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.map(String::from)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn render_shows_null() {
+        let rows = rows(&[&[Some("1"), Some("Alice")], &[Some("2"), None]]);
+        assert_eq!(
+            render(&["id", "name"], &rows, &[true, false], None),
+            "\
++----+-------+
+| id | name  |
++----+-------+
+|  1 | Alice |
+|  2 | NULL  |
++----+-------+
+"
+        );
+    }
+
+    #[test]
+    fn render_lines_up_wide_and_multibyte_cells() {
+        let rows = rows(&[
+            &[Some("日本語"), Some("café")],
+            &[Some("🍣"), Some("e\u{301}")],
+        ]);
+        assert_eq!(
+            render(&["名前", "note"], &rows, &[], None),
+            "\
++--------+------+
+| 名前   | note |
++--------+------+
+| 日本語 | café |
+| 🍣     | e\u{301}    |
++--------+------+
+"
+        );
+    }
+
+    #[test]
+    fn render_escapes_control_characters() {
+        let rows = rows(&[&[Some("line 1\nline 2\ttab\x1b[31m")]]);
+        assert_eq!(
+            render(&["note"], &rows, &[], None),
+            "\
++-----------------------------+
+| note                        |
++-----------------------------+
+| line 1\\nline 2\\ttab\\x1b[31m |
++-----------------------------+
+"
+        );
+    }
+
+    #[test]
+    fn render_cuts_cells_to_max_width() {
+        let rows = rows(&[&[Some("abcdefghij")], &[Some("日本語テキスト")]]);
+        assert_eq!(
+            render(&["text"], &rows, &[], Some(8)),
+            "\
++----------+
+| text     |
++----------+
+| abcde... |
+| 日本...  |
++----------+
+"
+        );
+    }
+
+    #[test]
+    fn render_vertical_puts_each_row_in_a_block() {
+        let rows = rows(&[&[Some("1"), Some("Alice")], &[Some("2"), None]]);
+        assert_eq!(
+            render_vertical(&["id", "name"], &rows, None),
+            "\
+*************************** 1. row ***************************
+  id: 1
+name: Alice
+*************************** 2. row ***************************
+  id: 2
+name: NULL
+"
+        );
+    }
+
+    #[test]
+    fn render_markdown_escapes_pipes_and_line_breaks() {
+        let rows = rows(&[&[Some("1"), Some("a|b\\c\nd")]]);
+        assert_eq!(
+            render_markdown(&["id", "note"], &rows, &[true, false]),
+            "\
+| id | note |
+| ---: | --- |
+| 1 | a\\|b\\\\c<br>d |
+"
+        );
+    }
+
+    #[test]
+    fn truncate_keeps_grapheme_clusters_whole() {
+        assert_eq!(
+            truncate("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", Some(4)),
+            "e\u{301}..."
+        );
+        assert_eq!(truncate("short", Some(8)), "short");
+        assert_eq!(truncate("anything", None), "anything");
+    }
+}
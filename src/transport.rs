@@ -0,0 +1,123 @@
+#[cfg(windows)]
+use std::fs::{File, OpenOptions};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+#[cfg(windows)]
+use anyhow::{Context, Result};
+
+// The byte stream a `Connection` runs over. The handshake and the commands only read and write it;
+// the rest is what differs between transports.
+#[derive(Debug)]
+pub(crate) enum Transport {
+    Tcp(TcpStream),
+    // A named pipe such as \\.\pipe\MySQL, for servers started with --named-pipe.
+    #[cfg(windows)]
+    Pipe(File),
+}
+
+impl Transport {
+    // `name` is the pipe's name (MySQL by default) or its full path.
+    #[cfg(windows)]
+    pub(crate) fn open_pipe(name: &str) -> Result<Self> {
+        let path = match name.starts_with(r"\\") {
+            true => String::from(name),
+            false => format!(r"\\.\pipe\{}", name),
+        };
+        let pipe = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open named pipe {}", path))?;
+        Ok(Self::Pipe(pipe))
+    }
+
+    pub(crate) fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => stream.try_clone().map(Self::Tcp),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.try_clone().map(Self::Pipe),
+        }
+    }
+
+    // Named pipes are opened for synchronous io, which can't time out; deadlines and timeouts then
+    // only apply between packets.
+    pub(crate) fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)
+            }
+            #[cfg(windows)]
+            Self::Pipe(_) => Ok(()),
+        }
+    }
+
+    // Whether there is something to read (or the peer is gone), checked without blocking. Always
+    // false for a named pipe.
+    pub(crate) fn is_readable(&self) -> bool {
+        match self {
+            Self::Tcp(stream) => {
+                if stream.set_nonblocking(true).is_err() {
+                    return false;
+                }
+                let peeked = stream.peek(&mut [0; 1]);
+                let _ = stream.set_nonblocking(false);
+                match peeked {
+                    Ok(_) => true,
+                    Err(err) => err.kind() != io::ErrorKind::WouldBlock,
+                }
+            }
+            #[cfg(windows)]
+            Self::Pipe(_) => false,
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(stream) => stream.local_addr().ok(),
+            #[cfg(windows)]
+            Self::Pipe(_) => None,
+        }
+    }
+
+    // A named pipe never leaves the machine, so it protects the password like a Unix socket.
+    pub(crate) fn is_secure(&self) -> bool {
+        match self {
+            Self::Tcp(_) => false,
+            #[cfg(windows)]
+            Self::Pipe(_) => true,
+        }
+    }
+}
+
+impl Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.read(buf),
+        }
+    }
+}
+
+impl Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(windows)]
+            Self::Pipe(pipe) => pipe.flush(),
+        }
+    }
+}
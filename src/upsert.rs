@@ -0,0 +1,84 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    connection::Connection, query_builder::interpolate, utils::quote_identifier, value::Value,
+};
+
+// What `Connection::upsert` did, from the affected rows of INSERT ... ON DUPLICATE KEY UPDATE: 1 for
+// an inserted row, 2 for an updated one and 0 for an existing row that already had the values.
+// https://dev.mysql.com/doc/refman/8.4/en/insert-on-duplicate.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    Inserted,
+    Updated,
+    Unchanged,
+    // With `ConnectionOptions::found_rows` (CLIENT_FOUND_ROWS) an unchanged row counts as affected
+    // too, so 1 no longer tells it apart from an inserted one.
+    InsertedOrUnchanged,
+}
+
+impl Connection {
+    // Inserts a row, or updates the row it collides with on a PRIMARY KEY or UNIQUE index:
+    //
+    //     INSERT INTO `t` (`id`, `name`) VALUES (1, 'alice') ON DUPLICATE KEY UPDATE `name` = 'alice'
+    //
+    // `values` are the columns and values of the row; those named in `key_columns` identify it and
+    // are left alone by the update, the others are all set. Which index collides is up to the
+    // table's keys, not `key_columns`. The values are written in as literals escaped for the
+    // session's sql_mode, so the statement is a single round trip.
+    pub fn upsert(
+        &mut self,
+        table: &str,
+        key_columns: &[&str],
+        values: &[(&str, Value)],
+    ) -> Result<UpsertOutcome> {
+        if values.is_empty() {
+            bail!("no columns to upsert");
+        }
+        if let Some(key) = key_columns
+            .iter()
+            .find(|key| !values.iter().any(|(col, _)| col == *key))
+        {
+            bail!("key column {} has no value", key);
+        }
+        let columns = values
+            .iter()
+            .map(|(col, _)| quote_identifier(col))
+            .collect::<Vec<_>>();
+        let mut params = values
+            .iter()
+            .map(|(_, val)| val.clone())
+            .collect::<Vec<_>>();
+        let mut assignments = vec![];
+        for (col, val) in values {
+            if !key_columns.contains(col) {
+                assignments.push(format!("{} = ?", quote_identifier(col)));
+                params.push(val.clone());
+            }
+        }
+        // With nothing but keys, a no-op update keeps the existing row (and reports it unchanged).
+        if assignments.is_empty() {
+            assignments.push(format!("{0} = {0}", columns[0]));
+        }
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON DUPLICATE KEY UPDATE {}",
+            quote_identifier(table),
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", "),
+            assignments.join(", ")
+        );
+        let sql = interpolate(&sql, &params, self.no_backslash_escapes())?;
+        let affected_rows = self.query(&sql)?.affected_rows();
+        Ok(match (affected_rows, self.found_rows()) {
+            (0, false) => UpsertOutcome::Unchanged,
+            (1, false) => UpsertOutcome::Inserted,
+            (1, true) => UpsertOutcome::InsertedOrUnchanged,
+            (2, _) => UpsertOutcome::Updated,
+            _ => bail!(
+                "unexpected affected rows for an upsert into {}: {}",
+                table,
+                affected_rows
+            ),
+        })
+    }
+}
@@ -1,16 +1,21 @@
 use anyhow::{Result, bail};
 
+use crate::{
+    consts::{LENENC_U16, LENENC_U24, LENENC_U64},
+    decode::check_len,
+};
+
 // Protocol::LengthEncodedString
 // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_basic_dt_strings.html#sect_protocol_basic_dt_string_le
 pub fn decode_lenenc_string(pkt: &[u8], pos: usize) -> Result<(String, usize)> {
     let mut pos = pos;
 
-    let head = pkt[pos];
-    pos += 1;
+    let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+    pos += consumed;
 
-    let val = String::from_utf8(pkt[pos..(pos + head as usize)].to_vec())?;
-    let len = val.len();
-    Ok((val, len + 1))
+    check_len(pkt, pos, len as usize)?;
+    let val = String::from_utf8(pkt[pos..(pos + len as usize)].to_vec())?;
+    Ok((val, consumed + len as usize))
 }
 
 // Protocol::LengthEncodedInteger
@@ -18,21 +23,26 @@ pub fn decode_lenenc_string(pkt: &[u8], pos: usize) -> Result<(String, usize)> {
 pub fn decode_lenenc_integer(pkt: &[u8], pos: usize) -> Result<(u64, usize)> {
     let mut pos = pos;
 
+    check_len(pkt, pos, 1)?;
     let head = pkt[pos];
     pos += 1;
 
+    let int_len = match head {
+        LENENC_U16 => 2,
+        LENENC_U24 => 3,
+        LENENC_U64 => 8,
+        _ => 0,
+    };
+    check_len(pkt, pos, int_len)?;
+
     Ok(match head {
-        // 1-byte integer
         0x00..=0xfa => (head as u64, 1),
-        // 0xfc + 2-byte integer
-        0xfc => (u16::from_le_bytes([pkt[pos], pkt[pos + 1]]) as u64, 3),
-        // 0xfd + 3-byte integer
-        0xfd => (
+        LENENC_U16 => (u16::from_le_bytes([pkt[pos], pkt[pos + 1]]) as u64, 3),
+        LENENC_U24 => (
             u32::from_le_bytes([pkt[pos], pkt[pos + 1], pkt[pos + 2], 0]) as u64,
             4,
         ),
-        // 0xfe + 8-byte integer
-        0xfe => (
+        LENENC_U64 => (
             u64::from_le_bytes([
                 pkt[pos],
                 pkt[pos + 1],
@@ -48,3 +58,113 @@ pub fn decode_lenenc_integer(pkt: &[u8], pos: usize) -> Result<(u64, usize)> {
         _ => bail!("unknown byte: {}", head),
     })
 }
+
+pub fn encode_lenenc_integer(buf: &mut Vec<u8>, val: u64) {
+    match val {
+        0..0xfb => buf.push(val as u8),
+        0xfb..0x1_0000 => {
+            buf.push(LENENC_U16);
+            buf.extend_from_slice(&(val as u16).to_le_bytes());
+        }
+        0x1_0000..0x100_0000 => {
+            buf.push(LENENC_U24);
+            buf.extend_from_slice(&(val as u32).to_le_bytes()[..3]);
+        }
+        _ => {
+            buf.push(LENENC_U64);
+            buf.extend_from_slice(&val.to_le_bytes());
+        }
+    }
+}
+
+pub fn encode_lenenc_string(buf: &mut Vec<u8>, val: &[u8]) {
+    encode_lenenc_integer(buf, val.len() as u64);
+    buf.extend_from_slice(val);
+}
+
+// Escapes a string for use inside a quoted SQL string literal, like mysql_real_escape_string().
+// https://dev.mysql.com/doc/c-api/8.4/en/mysql-real-escape-string.html
+pub fn escape_string(s: &str) -> String {
+    let mut buf = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\0' => buf.push_str("\\0"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\\' => buf.push_str("\\\\"),
+            '\'' => buf.push_str("\\'"),
+            '"' => buf.push_str("\\\""),
+            '\x1a' => buf.push_str("\\Z"),
+            _ => buf.push(c),
+        }
+    }
+    buf
+}
+
+// `escape_string` for a session whose sql_mode has NO_BACKSLASH_ESCAPES. A backslash is an ordinary
+// character there, so only quotes are escaped, by doubling them; with backslash escapes, a `\'` in
+// the value would end the literal and let the rest of it run as SQL.
+pub fn escape_string_no_backslash(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+// Quotes an identifier with backticks. A dotted name (`db.table`) is quoted part by part.
+// https://dev.mysql.com/doc/refman/8.4/en/identifiers.html
+pub fn quote_identifier(name: &str) -> String {
+    name.split('.')
+        .map(|part| format!("`{}`", part.replace('`', "``")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// The longest prefix of `s` of at most `max_len` bytes that ends on a character boundary.
+pub(crate) fn truncate(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+// 64-bit FNV-1a, continuing from `hash` (start from `FNV_OFFSET_BASIS`). Unlike std's
+// `DefaultHasher`, it gives the same hash in every process and release, so hashes can be stored.
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_string_uses_backslashes() {
+        assert_eq!(escape_string("it's"), "it\\'s");
+        assert_eq!(escape_string("a\\b"), "a\\\\b");
+        assert_eq!(escape_string("\"x\""), "\\\"x\\\"");
+        assert_eq!(escape_string("\0\n\r\x1a"), "\\0\\n\\r\\Z");
+        assert_eq!(escape_string("héllo"), "héllo");
+    }
+
+    #[test]
+    fn escape_string_no_backslash_doubles_quotes() {
+        assert_eq!(escape_string_no_backslash("it's"), "it''s");
+        // A backslash is an ordinary character, so it stays as it is.
+        assert_eq!(escape_string_no_backslash("a\\b"), "a\\b");
+        assert_eq!(escape_string_no_backslash("\"x\"\n"), "\"x\"\n");
+    }
+
+    // The value `\' OR 1=1 -- ` must stay inside the literal in both modes.
+    #[test]
+    fn each_escape_keeps_an_injection_inside_the_literal() {
+        let value = "\\' OR 1=1 -- ";
+        assert_eq!(escape_string(value), "\\\\\\' OR 1=1 -- ");
+        assert_eq!(escape_string_no_backslash(value), "\\'' OR 1=1 -- ");
+    }
+}
@@ -0,0 +1,965 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    command::{
+        ColumnDefinition41, MYSQL_TYPE_BIT, MYSQL_TYPE_DATE, MYSQL_TYPE_DATETIME,
+        MYSQL_TYPE_DECIMAL, MYSQL_TYPE_DOUBLE, MYSQL_TYPE_FLOAT, MYSQL_TYPE_INT24, MYSQL_TYPE_JSON,
+        MYSQL_TYPE_LONG, MYSQL_TYPE_LONGLONG, MYSQL_TYPE_NEWDECIMAL, MYSQL_TYPE_NULL,
+        MYSQL_TYPE_SHORT, MYSQL_TYPE_STRING, MYSQL_TYPE_TIME, MYSQL_TYPE_TIMESTAMP,
+        MYSQL_TYPE_TINY, MYSQL_TYPE_YEAR, UNSIGNED_FLAG,
+    },
+    decode::check_len,
+    encoding::decode_text,
+    packet_writer::PacketWriter,
+    table::escape_control,
+    utils::{decode_lenenc_integer, escape_string, escape_string_no_backslash},
+};
+
+// The binary collation, which marks BINARY/VARBINARY/BLOB columns (and BIT, DECIMAL, ...).
+const BINARY_COLLATION: u16 = 63;
+
+// DATE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+// DATETIME and TIMESTAMP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DateTime {
+    pub date: Date,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub micros: u32,
+}
+
+// TIME, which is a duration of up to 838 hours either way rather than a time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Time {
+    pub negative: bool,
+    pub hours: u32,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub micros: u32,
+}
+
+// A cell of a result set or a parameter of a prepared statement. Rows of both the text and the
+// binary protocol decode to the same values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+    // Binary strings (BINARY, VARBINARY, BLOB) and anything else without a character set.
+    Bytes(Vec<u8>),
+    Text(String),
+    Date(Date),
+    Time(Time),
+    DateTime(DateTime),
+    // DECIMAL, kept as its exact digits.
+    Decimal(String),
+    Bit(Vec<u8>),
+    Json(String),
+}
+
+impl Value {
+    // A value of the text protocol, as sent for `column`.
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_com_query_response_text_resultset_row.html
+    pub fn parse_text(bytes: &[u8], column: &ColumnDefinition41) -> Result<Self> {
+        let text = || -> Result<&str> {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => Ok(text),
+                Err(_) => bail!("invalid value {:?} for column {}", bytes, column.name),
+            }
+        };
+        let invalid = || {
+            anyhow::anyhow!(
+                "invalid value {:?} for column {} of type 0x{:02x}",
+                String::from_utf8_lossy(bytes),
+                column.name,
+                column.type_
+            )
+        };
+        Ok(match column.type_ {
+            MYSQL_TYPE_TINY | MYSQL_TYPE_SHORT | MYSQL_TYPE_LONG | MYSQL_TYPE_INT24
+            | MYSQL_TYPE_LONGLONG | MYSQL_TYPE_YEAR => match is_unsigned(column) {
+                true => Self::UInt(text()?.parse().map_err(|_| invalid())?),
+                false => Self::Int(text()?.parse().map_err(|_| invalid())?),
+            },
+            MYSQL_TYPE_FLOAT => Self::Float(text()?.parse().map_err(|_| invalid())?),
+            MYSQL_TYPE_DOUBLE => Self::Double(text()?.parse().map_err(|_| invalid())?),
+            MYSQL_TYPE_DATE => Self::Date(parse_date(text()?).ok_or_else(invalid)?),
+            MYSQL_TYPE_DATETIME | MYSQL_TYPE_TIMESTAMP => {
+                Self::DateTime(parse_datetime(text()?).ok_or_else(invalid)?)
+            }
+            MYSQL_TYPE_TIME => Self::Time(parse_time(text()?).ok_or_else(invalid)?),
+            _ => Self::from_string_bytes(bytes.to_vec(), column)?,
+        })
+    }
+
+    // A value of the binary protocol at `pos` of a row, and the number of bytes it took.
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_binary_resultset.html#sect_protocol_binary_resultset_row_value
+    pub fn decode_binary(
+        pkt: &[u8],
+        pos: usize,
+        column: &ColumnDefinition41,
+    ) -> Result<(Self, usize)> {
+        let unsigned = is_unsigned(column);
+        let fixed = |len: usize| -> Result<&[u8]> {
+            check_len(pkt, pos, len)?;
+            Ok(&pkt[pos..(pos + len)])
+        };
+        Ok(match column.type_ {
+            MYSQL_TYPE_TINY => {
+                let b = fixed(1)?[0];
+                match unsigned {
+                    true => (Self::UInt(b as u64), 1),
+                    false => (Self::Int(b as i8 as i64), 1),
+                }
+            }
+            MYSQL_TYPE_SHORT | MYSQL_TYPE_YEAR => {
+                let val = u16::from_le_bytes(fixed(2)?.try_into()?);
+                match unsigned {
+                    true => (Self::UInt(val as u64), 2),
+                    false => (Self::Int(val as i16 as i64), 2),
+                }
+            }
+            MYSQL_TYPE_LONG | MYSQL_TYPE_INT24 => {
+                let val = u32::from_le_bytes(fixed(4)?.try_into()?);
+                match unsigned {
+                    true => (Self::UInt(val as u64), 4),
+                    false => (Self::Int(val as i32 as i64), 4),
+                }
+            }
+            MYSQL_TYPE_LONGLONG => {
+                let val = u64::from_le_bytes(fixed(8)?.try_into()?);
+                match unsigned {
+                    true => (Self::UInt(val), 8),
+                    false => (Self::Int(val as i64), 8),
+                }
+            }
+            MYSQL_TYPE_FLOAT => (Self::Float(f32::from_le_bytes(fixed(4)?.try_into()?)), 4),
+            MYSQL_TYPE_DOUBLE => (Self::Double(f64::from_le_bytes(fixed(8)?.try_into()?)), 8),
+            MYSQL_TYPE_DATE | MYSQL_TYPE_DATETIME | MYSQL_TYPE_TIMESTAMP => {
+                let len = fixed(1)?[0] as usize;
+                let b = fixed(1 + len)?;
+                let mut datetime = DateTime::default();
+                if len >= 4 {
+                    datetime.date = Date {
+                        year: u16::from_le_bytes([b[1], b[2]]),
+                        month: b[3],
+                        day: b[4],
+                    };
+                }
+                if len >= 7 {
+                    (datetime.hour, datetime.minute, datetime.second) = (b[5], b[6], b[7]);
+                }
+                if len >= 11 {
+                    datetime.micros = u32::from_le_bytes([b[8], b[9], b[10], b[11]]);
+                }
+                match column.type_ {
+                    MYSQL_TYPE_DATE => (Self::Date(datetime.date), 1 + len),
+                    _ => (Self::DateTime(datetime), 1 + len),
+                }
+            }
+            MYSQL_TYPE_TIME => {
+                let len = fixed(1)?[0] as usize;
+                let b = fixed(1 + len)?;
+                let mut time = Time::default();
+                if len >= 8 {
+                    time.negative = b[1] == 1;
+                    time.hours = u32::from_le_bytes([b[2], b[3], b[4], b[5]]) * 24 + b[6] as u32;
+                    (time.minutes, time.seconds) = (b[7], b[8]);
+                }
+                if len >= 12 {
+                    time.micros = u32::from_le_bytes([b[9], b[10], b[11], b[12]]);
+                }
+                (Self::Time(time), 1 + len)
+            }
+            // Everything else (strings, DECIMAL, BIT, JSON, ...) is a length-encoded string.
+            _ => {
+                let (len, consumed) = decode_lenenc_integer(pkt, pos)?;
+                let start = pos + consumed;
+                check_len(pkt, start, len as usize)?;
+                let bytes = pkt[start..(start + len as usize)].to_vec();
+                (
+                    Self::from_string_bytes(bytes, column)?,
+                    consumed + len as usize,
+                )
+            }
+        })
+    }
+
+    // The types both protocols send as strings.
+    fn from_string_bytes(bytes: Vec<u8>, column: &ColumnDefinition41) -> Result<Self> {
+        Ok(match column.type_ {
+            MYSQL_TYPE_NULL => Self::Null,
+            MYSQL_TYPE_DECIMAL | MYSQL_TYPE_NEWDECIMAL => Self::Decimal(String::from_utf8(bytes)?),
+            MYSQL_TYPE_BIT => Self::Bit(bytes),
+            MYSQL_TYPE_JSON => Self::Json(String::from_utf8(bytes)?),
+            _ if column.character_set == BINARY_COLLATION => Self::Bytes(bytes),
+            // Transcoded from the column's character set (e.g. latin1) with the encoding_rs
+            // feature; bytes that can't be read as text are kept as they are.
+            _ => match decode_text(bytes, column.character_set) {
+                Ok(text) => Self::Text(text),
+                Err(bytes) => Self::Bytes(bytes),
+            },
+        })
+    }
+
+    // The value as the text protocol would have sent it for `column`; `None` for NULL.
+    pub fn encode_text(&self, column: &ColumnDefinition41) -> Option<Vec<u8>> {
+        let text = match self {
+            Self::Null => return None,
+            Self::Int(val) => val.to_string(),
+            Self::UInt(val) => val.to_string(),
+            Self::Float(val) => val.to_string(),
+            Self::Double(val) => val.to_string(),
+            Self::Bytes(val) | Self::Bit(val) => return Some(val.clone()),
+            Self::Text(val) | Self::Decimal(val) | Self::Json(val) => val.clone(),
+            Self::Date(date) => date.to_string(),
+            Self::DateTime(datetime) => format!(
+                "{} {:02}:{:02}:{:02}{}",
+                datetime.date,
+                datetime.hour,
+                datetime.minute,
+                datetime.second,
+                fraction(datetime.micros, column.decimals)
+            ),
+            Self::Time(time) => format!(
+                "{}{:02}:{:02}:{:02}{}",
+                if time.negative { "-" } else { "" },
+                time.hours,
+                time.minutes,
+                time.seconds,
+                fraction(time.micros, column.decimals)
+            ),
+        };
+        Some(text.into_bytes())
+    }
+
+    // The parameter type, its flags (0x80 for unsigned) and the value in the binary protocol.
+    // https://dev.mysql.com/doc/dev/mysql-server/8.4.3/page_protocol_binary_resultset.html#sect_protocol_binary_resultset_row_value
+    pub fn encode_binary(&self) -> (u8, u8, Vec<u8>) {
+        let (type_, flags) = self.binary_type();
+        (type_, flags, PacketWriter::encode(|w| self.write_binary(w)))
+    }
+
+    pub fn binary_type(&self) -> (u8, u8) {
+        match self {
+            Self::Null => (MYSQL_TYPE_NULL, 0),
+            Self::Int(_) => (MYSQL_TYPE_LONGLONG, 0),
+            Self::UInt(_) => (MYSQL_TYPE_LONGLONG, 0x80),
+            Self::Float(_) => (MYSQL_TYPE_FLOAT, 0),
+            Self::Double(_) => (MYSQL_TYPE_DOUBLE, 0),
+            Self::Date(_) => (MYSQL_TYPE_DATE, 0),
+            Self::DateTime(_) => (MYSQL_TYPE_DATETIME, 0),
+            Self::Time(_) => (MYSQL_TYPE_TIME, 0),
+            // The server converts strings to the parameter's type as needed.
+            Self::Bytes(_) | Self::Bit(_) | Self::Text(_) | Self::Decimal(_) | Self::Json(_) => {
+                (MYSQL_TYPE_STRING, 0)
+            }
+        }
+    }
+
+    pub fn write_binary(&self, w: &mut PacketWriter) {
+        match self {
+            Self::Null => {}
+            Self::Int(val) => w.u64_le(*val as u64),
+            Self::UInt(val) => w.u64_le(*val),
+            Self::Float(val) => w.bytes(&val.to_le_bytes()),
+            Self::Double(val) => w.bytes(&val.to_le_bytes()),
+            Self::Date(date) => {
+                w.u8(4);
+                w.u16_le(date.year);
+                w.u8(date.month);
+                w.u8(date.day);
+            }
+            Self::DateTime(datetime) => {
+                w.u8(11);
+                w.u16_le(datetime.date.year);
+                w.bytes(&[
+                    datetime.date.month,
+                    datetime.date.day,
+                    datetime.hour,
+                    datetime.minute,
+                    datetime.second,
+                ]);
+                w.u32_le(datetime.micros);
+            }
+            Self::Time(time) => {
+                w.u8(12);
+                w.u8(time.negative as u8);
+                w.u32_le(time.hours / 24);
+                w.bytes(&[(time.hours % 24) as u8, time.minutes, time.seconds]);
+                w.u32_le(time.micros);
+            }
+            Self::Bytes(val) | Self::Bit(val) => w.lenenc_str(val),
+            Self::Text(val) | Self::Decimal(val) | Self::Json(val) => w.lenenc_str(val.as_bytes()),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == Self::Null
+    }
+
+    // The value as an SQL literal, for writing it into the text of a statement: strings quoted and
+    // escaped (see `utils::escape_string`; `no_backslash_escapes` for a session with that sql_mode),
+    // bytes in hex (X'...'), and floating-point numbers in exponent notation, so the server reads
+    // them as DOUBLE rather than DECIMAL. NaN and infinities have no literal.
+    pub fn to_sql_literal(&self, no_backslash_escapes: bool) -> Result<String> {
+        let quote = |text: &str| match no_backslash_escapes {
+            true => format!("'{}'", escape_string_no_backslash(text)),
+            false => format!("'{}'", escape_string(text)),
+        };
+        Ok(match self {
+            Self::Null => String::from("NULL"),
+            Self::Int(val) => val.to_string(),
+            Self::UInt(val) => val.to_string(),
+            Self::Float(val) if val.is_finite() => format!("{:e}", val),
+            Self::Double(val) if val.is_finite() => format!("{:e}", val),
+            Self::Float(_) | Self::Double(_) => {
+                bail!("{} can't be written as an SQL literal", self)
+            }
+            Self::Bytes(val) | Self::Bit(val) => {
+                let mut literal = String::with_capacity(3 + val.len() * 2);
+                literal.push_str("X'");
+                for b in val {
+                    literal.push_str(&format!("{:02X}", b));
+                }
+                literal.push('\'');
+                literal
+            }
+            // DECIMAL digits are quoted too, so whatever the string holds can't end up as SQL.
+            Self::Text(val) | Self::Decimal(val) | Self::Json(val) => quote(val),
+            Self::Date(date) => quote(&date.to_string()),
+            Self::Time(time) => quote(&time.to_string()),
+            Self::DateTime(datetime) => quote(&datetime.to_string()),
+        })
+    }
+}
+
+fn is_unsigned(column: &ColumnDefinition41) -> bool {
+    column.flags & UNSIGNED_FLAG != 0 || column.type_ == MYSQL_TYPE_YEAR
+}
+
+// The fractional seconds part, with as many digits as the column declares (`decimals` is 0x1f for
+// values without a fixed scale, which get all six when there is a fraction).
+fn fraction(micros: u32, decimals: u8) -> String {
+    match decimals {
+        1..=6 => format!(".{:06}", micros)[..(1 + decimals as usize)].to_string(),
+        0 => String::new(),
+        _ if micros == 0 => String::new(),
+        _ => format!(".{:06}", micros),
+    }
+}
+
+// YYYY-MM-DD
+fn parse_date(text: &str) -> Option<Date> {
+    let mut parts = text.splitn(3, '-');
+    let date = Date {
+        year: parts.next()?.parse().ok()?,
+        month: parts.next()?.parse().ok()?,
+        day: parts.next()?.parse().ok()?,
+    };
+    Some(date)
+}
+
+// YYYY-MM-DD hh:mm:ss[.ffffff]
+fn parse_datetime(text: &str) -> Option<DateTime> {
+    let (date, time) = text.split_once(' ').unwrap_or((text, "00:00:00"));
+    let time = parse_time(time).filter(|time| !time.negative && time.hours < 24)?;
+    Some(DateTime {
+        date: parse_date(date)?,
+        hour: time.hours as u8,
+        minute: time.minutes,
+        second: time.seconds,
+        micros: time.micros,
+    })
+}
+
+// [-]hhh:mm:ss[.ffffff]
+fn parse_time(text: &str) -> Option<Time> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let (text, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let mut parts = text.splitn(3, ':');
+    let mut time = Time {
+        negative,
+        hours: parts.next()?.parse().ok()?,
+        minutes: parts.next()?.parse().ok()?,
+        seconds: parts.next()?.parse().ok()?,
+        micros: 0,
+    };
+    if !fraction.is_empty() {
+        if fraction.len() > 6 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        time.micros = format!("{:0<6}", fraction).parse().ok()?;
+    }
+    Some(time)
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}{}",
+            self.date,
+            self.hour,
+            self.minute,
+            self.second,
+            fraction(self.micros, 0x1f)
+        )
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}{}",
+            if self.negative { "-" } else { "" },
+            self.hours,
+            self.minutes,
+            self.seconds,
+            fraction(self.micros, 0x1f)
+        )
+    }
+}
+
+// The value as a cell shows it: NULL, numbers as written in SQL, text with control characters
+// escaped, and bytes that aren't UTF-8 (and BIT values) in hex (0x...), like
+// `mysql --binary-as-hex`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |f: &mut fmt::Formatter<'_>, val: &[u8]| {
+            f.write_str("0x")?;
+            val.iter().try_for_each(|b| write!(f, "{:02X}", b))
+        };
+        match self {
+            Self::Null => f.write_str("NULL"),
+            Self::Int(val) => write!(f, "{}", val),
+            Self::UInt(val) => write!(f, "{}", val),
+            Self::Float(val) => write!(f, "{}", val),
+            // Large and tiny magnitudes in exponent notation, as the server prints them (1e300).
+            Self::Double(val) if *val != 0.0 && !(1e-5..1e15).contains(&val.abs()) => {
+                write!(f, "{:e}", val)
+            }
+            Self::Double(val) => write!(f, "{}", val),
+            Self::Bytes(val) => match std::str::from_utf8(val) {
+                Ok(text) => f.write_str(&escape_control(text)),
+                Err(_) => hex(f, val),
+            },
+            Self::Text(val) => f.write_str(&escape_control(val)),
+            Self::Date(date) => write!(f, "{}", date),
+            Self::Time(time) => write!(f, "{}", time),
+            Self::DateTime(datetime) => write!(f, "{}", datetime),
+            Self::Decimal(val) | Self::Json(val) => f.write_str(val),
+            Self::Bit(val) => hex(f, val),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident: $($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(val: $ty) -> Self {
+                    Self::$variant(val.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from!(Int: i8, i16, i32, i64);
+impl_from!(UInt: u8, u16, u32, u64);
+impl_from!(Float: f32);
+impl_from!(Double: f64);
+impl_from!(Bytes: Vec<u8>, &[u8]);
+impl_from!(Text: String, &str);
+impl_from!(Date: Date);
+impl_from!(Time: Time);
+impl_from!(DateTime: DateTime);
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Self {
+        Self::Int(val as i64)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Self {
+        val.map_or(Self::Null, Into::into)
+    }
+}
+
+// Conversion of a `Value` to a Rust type, the other way from `Into<Value>`. Numbers convert when
+// they fit, and are parsed from text and DECIMAL (BIT converts to integers); NULL only converts to
+// `Option`.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+fn mismatch<T>(value: &Value) -> anyhow::Error {
+    anyhow::anyhow!(
+        "cannot convert {:?} to {}",
+        value,
+        std::any::type_name::<T>()
+    )
+}
+
+macro_rules! impl_from_value_int {
+    ($($ty:ty),*) => {
+        $(
+            impl FromValue for $ty {
+                fn from_value(value: Value) -> Result<Self> {
+                    let converted = match &value {
+                        Value::Int(val) => (*val).try_into().ok(),
+                        Value::UInt(val) => (*val).try_into().ok(),
+                        Value::Text(val) | Value::Decimal(val) => val.parse().ok(),
+                        // BIT(M) is big-endian, at most 8 bytes.
+                        Value::Bit(val) if val.len() <= 8 => val
+                            .iter()
+                            .fold(0u64, |acc, byte| acc << 8 | u64::from(*byte))
+                            .try_into()
+                            .ok(),
+                        _ => None,
+                    };
+                    converted.ok_or_else(|| mismatch::<Self>(&value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_value_int!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self> {
+        match &value {
+            Value::Float(val) => Ok(*val as f64),
+            Value::Double(val) => Ok(*val),
+            Value::Int(val) => Ok(*val as f64),
+            Value::UInt(val) => Ok(*val as f64),
+            Value::Text(val) | Value::Decimal(val) => {
+                val.parse().map_err(|_| mismatch::<Self>(&value))
+            }
+            _ => Err(mismatch::<Self>(&value)),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Float(val) => Ok(val),
+            value => f64::from_value(value).map(|val| val as f32),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self> {
+        match &value {
+            Value::Int(0) | Value::UInt(0) => Ok(false),
+            Value::Int(1) | Value::UInt(1) => Ok(true),
+            Value::Bit(val) if val.len() == 1 && val[0] <= 1 => Ok(val[0] == 1),
+            _ => Err(mismatch::<Self>(&value)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Text(val) | Value::Decimal(val) | Value::Json(val) => Ok(val),
+            Value::Bytes(val) => {
+                String::from_utf8(val).map_err(|err| mismatch::<Self>(&err.into_bytes().into()))
+            }
+            Value::Null | Value::Bit(_) => Err(mismatch::<Self>(&value)),
+            value => Ok(value.to_string()),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Bytes(val) | Value::Bit(val) => Ok(val),
+            Value::Text(val) | Value::Decimal(val) | Value::Json(val) => Ok(val.into_bytes()),
+            value => Err(mismatch::<Self>(&value)),
+        }
+    }
+}
+
+macro_rules! impl_from_value_variant {
+    ($($variant:ident),*) => {
+        $(
+            impl FromValue for $variant {
+                fn from_value(value: Value) -> Result<Self> {
+                    match value {
+                        Value::$variant(val) => Ok(val),
+                        value => Err(mismatch::<Self>(&value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_value_variant!(Date, Time, DateTime);
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{MYSQL_TYPE_BLOB, MYSQL_TYPE_VAR_STRING};
+
+    const DATE: Date = Date {
+        year: 2024,
+        month: 2,
+        day: 29,
+    };
+
+    fn column(type_: u8, flags: u16, character_set: u16, decimals: u8) -> ColumnDefinition41 {
+        ColumnDefinition41 {
+            catalog: String::from("def"),
+            schema: String::from("test"),
+            table: String::from("t"),
+            org_table: String::from("t"),
+            name: String::from("c"),
+            org_name: String::from("c"),
+            length_of_fixed_length_fields: 0x0c,
+            character_set,
+            column_length: 0,
+            type_,
+            flags,
+            decimals,
+        }
+    }
+
+    fn lenenc(bytes: &[u8]) -> Vec<u8> {
+        [&[bytes.len() as u8], bytes].concat()
+    }
+
+    // One row as the server sends it for each column type: the column, the cell in a text row, the
+    // cell in a binary row, and the value both decode to.
+    fn cells() -> Vec<(ColumnDefinition41, Vec<u8>, Vec<u8>, Value)> {
+        let number = |type_, flags| column(type_, flags, BINARY_COLLATION, 0);
+        let unsigned = UNSIGNED_FLAG;
+        vec![
+            (
+                number(MYSQL_TYPE_TINY, 0),
+                b"-1".to_vec(),
+                vec![0xff],
+                Value::Int(-1),
+            ),
+            (
+                number(MYSQL_TYPE_TINY, unsigned),
+                b"255".to_vec(),
+                vec![0xff],
+                Value::UInt(255),
+            ),
+            (
+                number(MYSQL_TYPE_SHORT, 0),
+                b"-2".to_vec(),
+                (-2i16).to_le_bytes().to_vec(),
+                Value::Int(-2),
+            ),
+            (
+                number(MYSQL_TYPE_YEAR, unsigned),
+                b"2024".to_vec(),
+                2024u16.to_le_bytes().to_vec(),
+                Value::UInt(2024),
+            ),
+            (
+                number(MYSQL_TYPE_INT24, 0),
+                b"-8388608".to_vec(),
+                (-8388608i32).to_le_bytes().to_vec(),
+                Value::Int(-8388608),
+            ),
+            (
+                number(MYSQL_TYPE_LONG, unsigned),
+                b"4294967295".to_vec(),
+                u32::MAX.to_le_bytes().to_vec(),
+                Value::UInt(u32::MAX as u64),
+            ),
+            (
+                number(MYSQL_TYPE_LONGLONG, 0),
+                b"-9223372036854775808".to_vec(),
+                i64::MIN.to_le_bytes().to_vec(),
+                Value::Int(i64::MIN),
+            ),
+            (
+                number(MYSQL_TYPE_LONGLONG, unsigned),
+                b"18446744073709551615".to_vec(),
+                u64::MAX.to_le_bytes().to_vec(),
+                Value::UInt(u64::MAX),
+            ),
+            (
+                number(MYSQL_TYPE_FLOAT, 0),
+                b"1.5".to_vec(),
+                1.5f32.to_le_bytes().to_vec(),
+                Value::Float(1.5),
+            ),
+            (
+                number(MYSQL_TYPE_DOUBLE, 0),
+                b"0.1".to_vec(),
+                0.1f64.to_le_bytes().to_vec(),
+                Value::Double(0.1),
+            ),
+            (
+                column(MYSQL_TYPE_NEWDECIMAL, 0, BINARY_COLLATION, 2),
+                b"-12.50".to_vec(),
+                lenenc(b"-12.50"),
+                Value::Decimal(String::from("-12.50")),
+            ),
+            (
+                number(MYSQL_TYPE_DATE, 0),
+                b"2024-02-29".to_vec(),
+                vec![4, 0xe8, 0x07, 2, 29],
+                Value::Date(DATE),
+            ),
+            (
+                number(MYSQL_TYPE_DATETIME, 0),
+                b"2024-02-29 13:05:09".to_vec(),
+                vec![7, 0xe8, 0x07, 2, 29, 13, 5, 9],
+                Value::DateTime(DateTime {
+                    date: DATE,
+                    hour: 13,
+                    minute: 5,
+                    second: 9,
+                    micros: 0,
+                }),
+            ),
+            (
+                column(MYSQL_TYPE_TIMESTAMP, 0, BINARY_COLLATION, 6),
+                b"2024-02-29 00:00:00.120000".to_vec(),
+                vec![11, 0xe8, 0x07, 2, 29, 0, 0, 0, 0xc0, 0xd4, 0x01, 0x00],
+                Value::DateTime(DateTime {
+                    date: DATE,
+                    micros: 120_000,
+                    ..DateTime::default()
+                }),
+            ),
+            (
+                number(MYSQL_TYPE_TIME, 0),
+                b"-838:59:59".to_vec(),
+                // 34 days and 22 hours
+                vec![8, 1, 34, 0, 0, 0, 22, 59, 59],
+                Value::Time(Time {
+                    negative: true,
+                    hours: 838,
+                    minutes: 59,
+                    seconds: 59,
+                    micros: 0,
+                }),
+            ),
+            (
+                column(MYSQL_TYPE_TIME, 0, BINARY_COLLATION, 6),
+                b"01:02:03.000004".to_vec(),
+                vec![12, 0, 0, 0, 0, 0, 1, 2, 3, 4, 0, 0, 0],
+                Value::Time(Time {
+                    negative: false,
+                    hours: 1,
+                    minutes: 2,
+                    seconds: 3,
+                    micros: 4,
+                }),
+            ),
+            (
+                column(MYSQL_TYPE_VAR_STRING, 0, 255, 0),
+                "héllo".as_bytes().to_vec(),
+                lenenc("héllo".as_bytes()),
+                Value::Text(String::from("héllo")),
+            ),
+            (
+                column(MYSQL_TYPE_BLOB, 0, BINARY_COLLATION, 0),
+                vec![0xff, 0x00],
+                lenenc(&[0xff, 0x00]),
+                Value::Bytes(vec![0xff, 0x00]),
+            ),
+            (
+                column(MYSQL_TYPE_BIT, unsigned, BINARY_COLLATION, 0),
+                vec![0x01, 0x02],
+                lenenc(&[0x01, 0x02]),
+                Value::Bit(vec![0x01, 0x02]),
+            ),
+            (
+                column(MYSQL_TYPE_JSON, 0, BINARY_COLLATION, 0),
+                br#"{"a": 1}"#.to_vec(),
+                lenenc(br#"{"a": 1}"#),
+                Value::Json(String::from(r#"{"a": 1}"#)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn text_and_binary_rows_decode_to_the_same_values() {
+        for (column, text, binary, expected) in cells() {
+            assert_eq!(Value::parse_text(&text, &column).unwrap(), expected);
+            // After a cell of another column, as in a row.
+            let pkt = [&[0xaa][..], &binary].concat();
+            let (value, len) = Value::decode_binary(&pkt, 1, &column).unwrap();
+            assert_eq!(value, expected);
+            assert_eq!(len, binary.len(), "{:?}", expected);
+        }
+    }
+
+    #[test]
+    fn encode_text_gives_the_text_protocol_cell() {
+        for (column, text, _, value) in cells() {
+            assert_eq!(value.encode_text(&column).unwrap(), text, "{:?}", value);
+        }
+        assert_eq!(
+            Value::Null.encode_text(&column(MYSQL_TYPE_LONG, 0, 63, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn encode_binary_round_trips_through_decode_binary() {
+        let values = [
+            Value::Int(i64::MIN),
+            Value::UInt(u64::MAX),
+            Value::Float(-0.25),
+            Value::Double(1e300),
+            Value::Date(DATE),
+            Value::DateTime(DateTime {
+                date: DATE,
+                hour: 23,
+                minute: 59,
+                second: 58,
+                micros: 999_999,
+            }),
+            Value::Time(Time {
+                negative: true,
+                hours: 838,
+                minutes: 59,
+                seconds: 59,
+                micros: 1,
+            }),
+            Value::Text(String::from("héllo")),
+        ];
+        for value in values {
+            let (type_, flags, bytes) = value.encode_binary();
+            let flags = if flags & 0x80 != 0 { UNSIGNED_FLAG } else { 0 };
+            let column = column(type_, flags, 255, 0x1f);
+            let (decoded, len) = Value::decode_binary(&bytes, 0, &column).unwrap();
+            assert_eq!(decoded, value);
+            assert_eq!(len, bytes.len());
+        }
+        let (type_, _, bytes) = Value::Bytes(vec![0, 0xff]).encode_binary();
+        let column = column(type_, 0, BINARY_COLLATION, 0);
+        assert_eq!(
+            Value::decode_binary(&bytes, 0, &column).unwrap().0,
+            Value::Bytes(vec![0, 0xff])
+        );
+    }
+
+    #[test]
+    fn truncated_binary_cells_are_errors() {
+        for (column, _, binary, _) in cells() {
+            assert!(Value::decode_binary(&binary[..binary.len() - 1], 0, &column).is_err());
+        }
+    }
+
+    #[test]
+    fn display_renders_each_variant() {
+        let cases = [
+            (Value::Null, "NULL"),
+            (Value::Int(-42), "-42"),
+            (Value::UInt(u64::MAX), "18446744073709551615"),
+            (Value::Float(1.5), "1.5"),
+            (Value::Double(0.1), "0.1"),
+            (Value::Double(0.0), "0"),
+            (Value::Double(1e300), "1e300"),
+            (Value::Double(-1.5e-7), "-1.5e-7"),
+            (Value::Bytes(b"abc".to_vec()), "abc"),
+            (Value::Bytes(vec![0xff, 0x00, 0x1b]), "0xFF001B"),
+            (Value::Text(String::from("a\tb\nc")), "a\\tb\\nc"),
+            (Value::Text(String::from("héllo")), "héllo"),
+            (Value::Date(DATE), "2024-02-29"),
+            (
+                Value::DateTime(DateTime {
+                    date: DATE,
+                    hour: 13,
+                    minute: 5,
+                    second: 9,
+                    micros: 0,
+                }),
+                "2024-02-29 13:05:09",
+            ),
+            (
+                Value::DateTime(DateTime {
+                    date: DATE,
+                    hour: 0,
+                    minute: 0,
+                    second: 0,
+                    micros: 120_000,
+                }),
+                "2024-02-29 00:00:00.120000",
+            ),
+            (
+                Value::Time(Time {
+                    negative: true,
+                    hours: 838,
+                    minutes: 59,
+                    seconds: 59,
+                    micros: 0,
+                }),
+                "-838:59:59",
+            ),
+            (Value::Decimal(String::from("12.50")), "12.50"),
+            (
+                Value::Json(String::from(r#"{"a": [1, 2]}"#)),
+                r#"{"a": [1, 2]}"#,
+            ),
+            (Value::Bit(vec![0x01, 0x02]), "0x0102"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_string(), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn debug_names_the_variant() {
+        assert_eq!(format!("{:?}", Value::Null), "Null");
+        assert_eq!(
+            format!("{:?}", Value::Text(String::from("1"))),
+            r#"Text("1")"#
+        );
+        assert_eq!(format!("{:?}", Value::Int(1)), "Int(1)");
+    }
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{connection::Connection, result::QueryResult};
+
+impl Connection {
+    // SHOW VARIABLES [LIKE 'pattern']
+    // https://dev.mysql.com/doc/refman/8.4/en/show-variables.html
+    //
+    // `like` is a LIKE pattern, so `%` and `_` keep their wildcard meaning; it is escaped as a string
+    // literal and is safe to take from user input.
+    pub fn server_variables(&mut self, like: Option<&str>) -> Result<HashMap<String, String>> {
+        self.show_name_value("SHOW VARIABLES", like)
+    }
+
+    // SHOW STATUS [LIKE 'pattern']
+    // https://dev.mysql.com/doc/refman/8.4/en/show-status.html
+    pub fn server_status(&mut self, like: Option<&str>) -> Result<HashMap<String, String>> {
+        self.show_name_value("SHOW STATUS", like)
+    }
+
+    // Cached after the first fetch; the value can only change for new sessions.
+    pub fn max_allowed_packet(&mut self) -> Result<u64> {
+        if let Some(val) = self.max_allowed_packet {
+            return Ok(val);
+        }
+        let val = self.server_variable("max_allowed_packet")?.parse()?;
+        self.max_allowed_packet = Some(val);
+        Ok(val)
+    }
+
+    // Not cached: a session can change it with SET.
+    pub fn auto_increment_increment(&mut self) -> Result<u64> {
+        Ok(self.server_variable("auto_increment_increment")?.parse()?)
+    }
+
+    pub fn wait_timeout(&mut self) -> Result<Duration> {
+        let secs = self.server_variable("wait_timeout")?.parse()?;
+        Ok(Duration::from_secs(secs))
+    }
+
+    pub fn version_comment(&mut self) -> Result<String> {
+        self.server_variable("version_comment")
+    }
+
+    pub fn character_set_server(&mut self) -> Result<String> {
+        self.server_variable("character_set_server")
+    }
+
+    // net_read_timeout and net_write_timeout accept 1 second to a year.
+    // https://dev.mysql.com/doc/refman/8.4/en/server-system-variables.html
+    pub(crate) fn set_net_timeouts(&mut self) -> Result<()> {
+        const MAX_SECS: u64 = 31_536_000;
+
+        let assignments = [
+            ("net_read_timeout", self.options.net_read_timeout),
+            ("net_write_timeout", self.options.net_write_timeout),
+        ]
+        .into_iter()
+        .filter_map(|(name, timeout)| {
+            // Round up, so a sub-second timeout doesn't become 0.
+            let secs = timeout?.as_millis().div_ceil(1000) as u64;
+            Some(format!("{} = {}", name, secs.clamp(1, MAX_SECS)))
+        })
+        .collect::<Vec<_>>();
+        if !assignments.is_empty() {
+            let sql = format!("SET SESSION {}", assignments.join(", "));
+            self.internal(|conn| conn.query_drop(&sql))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn server_variable(&mut self, name: &str) -> Result<String> {
+        self.server_variables(Some(name))?
+            .remove(name)
+            .with_context(|| format!("unknown server variable: {}", name))
+    }
+
+    fn show_name_value(
+        &mut self,
+        statement: &str,
+        like: Option<&str>,
+    ) -> Result<HashMap<String, String>> {
+        let sql = match like {
+            Some(pattern) => format!("{} LIKE '{}'", statement, self.escape_string(pattern)),
+            None => String::from(statement),
+        };
+        let QueryResult::ResultSet(rs) = self.internal(|conn| conn.query(&sql))? else {
+            bail!("{} did not return a result set", statement);
+        };
+        let mut vars = HashMap::new();
+        for row in rs.rows {
+            let mut cells = row.into_strings()?.into_iter();
+            if let (Some(Some(name)), Some(value)) = (cells.next(), cells.next()) {
+                vars.insert(name, value.unwrap_or_default());
+            }
+        }
+        Ok(vars)
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{Result, anyhow, bail};
+
+use crate::{connection::Connection, result::QueryResult};
+
+// A row of SHOW WARNINGS
+// https://dev.mysql.com/doc/refman/8.4/en/show-warnings.html
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub level: String,
+    pub code: u16,
+    pub message: String,
+}
+
+impl Connection {
+    // Fetches the diagnostics of the last statement. SHOW WARNINGS doesn't reset them itself, but any
+    // other statement does, so call it right after the statement that reported `warning_count() > 0`.
+    pub fn show_warnings(&mut self) -> Result<Vec<Warning>> {
+        let QueryResult::ResultSet(rs) = self.internal(|conn| conn.query("SHOW WARNINGS"))? else {
+            bail!("SHOW WARNINGS did not return a result set");
+        };
+        rs.rows
+            .into_iter()
+            .map(|row| {
+                let [Some(level), Some(code), Some(message)]: [Option<String>; 3] = row
+                    .into_strings()?
+                    .try_into()
+                    .map_err(|row: Vec<_>| anyhow!("unexpected SHOW WARNINGS row: {:?}", row))?
+                else {
+                    bail!("unexpected NULL in SHOW WARNINGS row");
+                };
+                Ok(Warning {
+                    level,
+                    code: code.parse()?,
+                    message,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,23 @@
+// Runs each benchmark body once, so a benchmark that no longer builds or panics fails the test
+// suite:
+//
+//   cargo test --features bench-smoke --test bench_smoke
+//
+// Under `cargo test --benches` criterion runs every benchmark a single time instead of measuring
+// it. The end-to-end ones in benches/query.rs return early without TOY_MYSQL_BENCH.
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn benches_run_once() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A target directory of its own, as the one of this test is locked by the cargo running it.
+    let status = Command::new(env!("CARGO"))
+        .args(["test", "--benches", "--target-dir"])
+        .arg(manifest_dir.join("target").join("bench-smoke"))
+        .current_dir(manifest_dir)
+        .env_remove("TOY_MYSQL_BENCH")
+        .status()
+        .unwrap();
+    assert!(status.success(), "cargo test --benches: {}", status);
+}
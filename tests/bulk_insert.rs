@@ -0,0 +1,141 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc,
+};
+
+use common::{STATUS, Server, column, options, sql, string_column};
+use toy_mysql_client::{
+    bulk_insert::BulkInsert,
+    command::{MYSQL_TYPE_VAR_STRING, OkPacket},
+    connection::Connection,
+    consts::{COM_STMT_CLOSE, COM_STMT_EXECUTE, COM_STMT_PREPARE},
+    value::Value,
+};
+
+// Small enough for a few hundred rows per statement.
+const MAX_ALLOWED_PACKET: usize = 8192;
+const AUTO_INCREMENT_INCREMENT: u64 = 2;
+
+// A server with a small max_allowed_packet and an auto_increment_increment of 2, answering each
+// INSERT with an OK packet counting its rows (one for every parenthesized tuple after VALUES) and
+// the AUTO_INCREMENT value of its first row, and passing the statement on.
+fn serve_inserts(tx: mpsc::Sender<String>) -> u16 {
+    let next_id = AtomicU64::new(1);
+    Server::new().serve(move |session, pkt| {
+        let sql = sql(&pkt).unwrap_or_default();
+        let variable = |name: &str, value: String| {
+            (sql == format!("SHOW VARIABLES LIKE '{}'", name)).then(|| (String::from(name), value))
+        };
+        if let Some((name, value)) = variable("max_allowed_packet", MAX_ALLOWED_PACKET.to_string())
+            .or_else(|| {
+                variable(
+                    "auto_increment_increment",
+                    AUTO_INCREMENT_INCREMENT.to_string(),
+                )
+            })
+        {
+            session.result_set(
+                &[string_column("Variable_name"), string_column("Value")],
+                &[vec![Some(&name), Some(&value)]],
+            );
+            return true;
+        }
+        let rows = match sql.split_once(" VALUES ") {
+            Some((_, values)) => values.matches("), (").count() as u64 + 1,
+            None => 0,
+        };
+        let mut first_id = 0;
+        if rows > 0 {
+            first_id = next_id.fetch_add(rows * AUTO_INCREMENT_INCREMENT, Ordering::SeqCst);
+            tx.send(sql).unwrap();
+        }
+        session.send_ok(OkPacket::new(rows, first_id, STATUS));
+        true
+    })
+}
+
+#[test]
+fn bulk_insert_of_1000_rows_is_split_to_fit_max_allowed_packet() {
+    let (tx, rx) = mpsc::channel();
+    let mut conn = Connection::new(options(serve_inserts(tx))).unwrap();
+    let inserted = BulkInsert::new("users", ["id", "name"])
+        .rows((0..1000).map(|id| [Value::from(id), Value::from(format!("user {}", id))]))
+        .execute(&mut conn)
+        .unwrap();
+    assert_eq!(inserted, 1000);
+
+    let statements = rx.try_iter().collect::<Vec<_>>();
+    assert!(statements.len() > 1, "{} statements", statements.len());
+    assert!(statements.iter().all(|sql| sql.len() <= MAX_ALLOWED_PACKET));
+    assert!(statements[0].starts_with("INSERT INTO `users` (`id`, `name`) VALUES (0, 'user 0'), "));
+    assert!(statements.last().unwrap().ends_with(", (999, 'user 999')"));
+}
+
+#[test]
+fn bulk_insert_returns_the_generated_id_of_each_row() {
+    let (tx, rx) = mpsc::channel();
+    let mut conn = Connection::new(options(serve_inserts(tx))).unwrap();
+    let ids = BulkInsert::new("users", ["name"])
+        .rows((0..1000).map(|id| [format!("user {}", id)]))
+        .execute_returning_ids(&mut conn)
+        .unwrap();
+    // Over several statements, each reporting the id of its first row only.
+    assert!(rx.try_iter().count() > 1);
+    let expected = (0..1000)
+        .map(|i| 1 + i * AUTO_INCREMENT_INCREMENT)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, expected);
+}
+
+#[test]
+fn prepared_insert_returns_the_id_of_each_row() {
+    let (tx, rx) = mpsc::channel();
+    let next_id = AtomicU64::new(100);
+    let port = Server::new().serve(move |session, pkt| {
+        let param = || column("?", MYSQL_TYPE_VAR_STRING);
+        match pkt[0] {
+            COM_STMT_PREPARE => {
+                tx.send(String::from_utf8_lossy(&pkt[1..]).into_owned())
+                    .unwrap();
+                session.prepare_ok(1, &[param(), param()], &[]);
+            }
+            COM_STMT_EXECUTE => {
+                // new_params_bound, after the NULL bitmap of the two parameters
+                tx.send(format!("execute {}", pkt[11])).unwrap();
+                let id = next_id.fetch_add(1, Ordering::SeqCst);
+                session.send_ok(OkPacket::new(1, id, STATUS));
+            }
+            COM_STMT_CLOSE => tx.send(String::from("close")).unwrap(),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let mut insert = conn.prepare_insert("users", ["name", "email"]).unwrap();
+    assert_eq!(insert.last_insert_id(), 0);
+    let ids = ["alice", "bob", "carol"].map(|name| {
+        let email = format!("{}@example.com", name);
+        insert
+            .insert_row(&[Value::from(name), Value::from(email)])
+            .unwrap()
+    });
+    assert_eq!(ids, [100, 101, 102]);
+    assert_eq!(insert.last_insert_id(), 102);
+    assert_eq!(insert.rows_inserted(), 3);
+    insert.close().unwrap();
+    conn.ping().unwrap();
+
+    // Prepared once, with the parameter types sent on the first execution only.
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [
+            "INSERT INTO `users` (`name`, `email`) VALUES (?, ?)",
+            "execute 1",
+            "execute 0",
+            "execute 0",
+            "close",
+        ]
+    );
+}
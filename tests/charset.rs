@@ -0,0 +1,89 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{STATUS, Server, options, string_column};
+use toy_mysql_client::{
+    command::ResultsetRow,
+    connection::{Connection, ConnectionOptions},
+    consts::COM_QUERY,
+};
+
+// A latin1 table t with a name column holding "café". The statements the server gets are kept as
+// the bytes it got.
+fn serve_latin1(statements: Arc<Mutex<Vec<Vec<u8>>>>) -> u16 {
+    Server::new().serve(move |session, pkt| {
+        let Some((&COM_QUERY, sql)) = pkt.split_first() else {
+            session.ok();
+            return true;
+        };
+        statements.lock().unwrap().push(sql.to_vec());
+        if sql == b"SELECT name FROM t" {
+            let mut name = string_column("name");
+            // latin1_swedish_ci
+            name.character_set = 8;
+            session.column_count(1);
+            session.send(&name.encode());
+            session.eof();
+            session.send(&ResultsetRow(vec![Some(b"caf\xe9".to_vec())]).encode());
+            session.end_rows(STATUS);
+        } else {
+            session.ok();
+        }
+        true
+    })
+}
+
+fn connect_latin1(port: u16) -> Connection {
+    Connection::new(ConnectionOptions {
+        charset: String::from("latin1"),
+        ..options(port)
+    })
+    .unwrap()
+}
+
+#[cfg(feature = "encoding_rs")]
+#[test]
+fn accented_text_round_trips_through_a_latin1_session() {
+    use toy_mysql_client::value::Value;
+
+    let statements = Arc::new(Mutex::new(vec![]));
+    let mut conn = connect_latin1(serve_latin1(statements.clone()));
+    conn.query("INSERT INTO t (name) VALUES ('café')").unwrap();
+    assert!(
+        statements
+            .lock()
+            .unwrap()
+            .contains(&b"INSERT INTO t (name) VALUES ('caf\xe9')".to_vec())
+    );
+    let result = conn.query("SELECT name FROM t").unwrap();
+    let values = result.result_set().unwrap().values().unwrap();
+    assert_eq!(values, [[Value::Text(String::from("café"))]]);
+}
+
+#[cfg(feature = "encoding_rs")]
+#[test]
+fn text_latin1_cannot_represent_is_an_error() {
+    let statements = Arc::new(Mutex::new(vec![]));
+    let mut conn = connect_latin1(serve_latin1(statements));
+    let err = conn
+        .query("INSERT INTO t (name) VALUES ('日本')")
+        .unwrap_err();
+    assert!(err.to_string().contains("latin1"), "{:#}", err);
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+#[test]
+fn non_ascii_text_in_a_latin1_session_needs_encoding_rs() {
+    let statements = Arc::new(Mutex::new(vec![]));
+    let mut conn = connect_latin1(serve_latin1(statements.clone()));
+    let err = conn
+        .query("INSERT INTO t (name) VALUES ('café')")
+        .unwrap_err();
+    assert!(err.to_string().contains("encoding_rs"), "{:#}", err);
+    // ASCII still goes through, and is the only INSERT the server got.
+    conn.query("INSERT INTO t (name) VALUES ('cafe')").unwrap();
+    let statements = statements.lock().unwrap();
+    let inserts = statements.iter().filter(|sql| sql.starts_with(b"INSERT"));
+    assert_eq!(inserts.count(), 1);
+}
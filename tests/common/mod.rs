@@ -0,0 +1,370 @@
+// A scripted MySQL server for the integration tests, built from the crate's own packet types like
+// examples/toy_server.rs. Each test binary uses a different part of it.
+#![allow(dead_code)]
+
+use std::{
+    io::Write,
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+};
+
+use toy_mysql_client::{
+    command::{
+        ColumnDefinition41, EofPacket, ErrPacket, MYSQL_TYPE_VAR_STRING, OkPacket, ResultsetRow,
+        SessionStateChange, StatusFlags,
+    },
+    connection::ConnectionOptions,
+    consts::{COM_QUERY, COM_QUIT},
+    handshake::{
+        CLIENT_DEPRECATE_EOF, CLIENT_OPTIONAL_RESULTSET_METADATA, CLIENT_QUERY_ATTRIBUTES,
+        DEFAULT_CLIENT_FLAG, HandshakeResponse41, HandshakeV10,
+    },
+    packets::{deframe, frame},
+    utils::{encode_lenenc_integer, encode_lenenc_string},
+    value::Value,
+};
+
+// The server leaves out the features that change the shape of COM_QUERY and of result sets.
+pub const CAPABILITIES: u32 =
+    DEFAULT_CLIENT_FLAG & !(CLIENT_QUERY_ATTRIBUTES | CLIENT_OPTIONAL_RESULTSET_METADATA);
+
+pub const STATUS: StatusFlags = StatusFlags(StatusFlags::AUTOCOMMIT);
+
+pub const SCRAMBLE: &[u8; 20] = b"01234567890123456789";
+
+// One client connection, from the server's side.
+pub struct Session {
+    stream: TcpStream,
+    seq: u8,
+    // As the client sent them in its handshake response.
+    pub capabilities: u32,
+    // Counting from 0 in the order the server accepted them.
+    pub number: usize,
+}
+
+impl Session {
+    pub fn send(&mut self, payload: &[u8]) {
+        self.send_raw(&frame(payload, self.seq));
+        self.seq = self.seq.wrapping_add(1);
+    }
+
+    // Bytes as they are, e.g. a truncated frame.
+    pub fn send_raw(&mut self, bytes: &[u8]) {
+        // The client may already have given up on the connection.
+        let _ = self.stream.write_all(bytes);
+    }
+
+    pub fn recv(&mut self) -> Option<Vec<u8>> {
+        let (pkt, seq) = deframe(&mut self.stream).ok()?;
+        self.seq = seq;
+        Some(pkt)
+    }
+
+    pub fn ok(&mut self) {
+        self.send_ok(OkPacket::new(0, 0, STATUS));
+    }
+
+    pub fn send_ok(&mut self, ok: OkPacket) {
+        self.send(&ok.encode(self.capabilities));
+    }
+
+    // An OK packet reporting session state changes, as the server sends them with
+    // CLIENT_SESSION_TRACK.
+    pub fn ok_with_changes(&mut self, status: StatusFlags, changes: Vec<SessionStateChange>) {
+        let mut ok = OkPacket::new(
+            0,
+            0,
+            StatusFlags(status.0 | StatusFlags::SESSION_STATE_CHANGED),
+        );
+        ok.session_state_changes = changes;
+        self.send_ok(ok);
+    }
+
+    pub fn err(&mut self, code: u16, sql_state: &str, message: &str) {
+        self.send(&ErrPacket::new(code, sql_state, message).encode());
+    }
+
+    // The EOF packet after column definitions, sent only without CLIENT_DEPRECATE_EOF.
+    pub fn eof(&mut self) {
+        if self.capabilities & CLIENT_DEPRECATE_EOF == 0 {
+            self.send(&EofPacket::new(0, STATUS).encode());
+        }
+    }
+
+    // The EOF packet, or OK packet with CLIENT_DEPRECATE_EOF, ending the rows of a result set.
+    pub fn end_rows(&mut self, status: StatusFlags) {
+        if self.capabilities & CLIENT_DEPRECATE_EOF == 0 {
+            self.send(&EofPacket::new(0, status).encode());
+        } else {
+            let mut ok = OkPacket::new(0, 0, status);
+            ok.header = 0xfe;
+            self.send_ok(ok);
+        }
+    }
+
+    pub fn column_count(&mut self, count: usize) {
+        let mut pkt = vec![];
+        encode_lenenc_integer(&mut pkt, count as u64);
+        self.send(&pkt);
+    }
+
+    // A text result set; the columns are strings unless `columns` says otherwise.
+    pub fn result_set(&mut self, columns: &[ColumnDefinition41], rows: &[Vec<Option<&str>>]) {
+        self.result_set_with_status(columns, rows, STATUS);
+    }
+
+    pub fn result_set_with_status(
+        &mut self,
+        columns: &[ColumnDefinition41],
+        rows: &[Vec<Option<&str>>],
+        status: StatusFlags,
+    ) {
+        self.column_count(columns.len());
+        for column in columns {
+            self.send(&column.encode());
+        }
+        self.eof();
+        for row in rows {
+            let row = row
+                .iter()
+                .map(|cell| cell.map(|cell| cell.as_bytes().to_vec()))
+                .collect();
+            self.send(&ResultsetRow(row).encode());
+        }
+        self.end_rows(status);
+    }
+
+    // COM_STMT_PREPARE_OK followed by the parameter and column definitions.
+    pub fn prepare_ok(
+        &mut self,
+        statement_id: u32,
+        params: &[ColumnDefinition41],
+        columns: &[ColumnDefinition41],
+    ) {
+        let mut pkt = vec![0];
+        pkt.extend_from_slice(&statement_id.to_le_bytes());
+        pkt.extend_from_slice(&(columns.len() as u16).to_le_bytes());
+        pkt.extend_from_slice(&(params.len() as u16).to_le_bytes());
+        // reserved, warning_count
+        pkt.extend_from_slice(&[0, 0, 0]);
+        self.send(&pkt);
+        for definitions in [params, columns] {
+            if definitions.is_empty() {
+                continue;
+            }
+            for definition in definitions {
+                self.send(&definition.encode());
+            }
+            self.eof();
+        }
+    }
+
+    // A binary result set, the response to COM_STMT_EXECUTE.
+    pub fn binary_result_set(&mut self, columns: &[ColumnDefinition41], rows: &[Vec<Value>]) {
+        self.column_count(columns.len());
+        for column in columns {
+            self.send(&column.encode());
+        }
+        self.eof();
+        for row in rows {
+            self.send(&binary_row(row));
+        }
+        self.end_rows(STATUS);
+    }
+
+    // Closes the connection without a word, as a crashed server or a dropped link would.
+    pub fn close(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+pub fn column(name: &str, type_: u8) -> ColumnDefinition41 {
+    ColumnDefinition41 {
+        catalog: String::from("def"),
+        schema: String::from("test"),
+        table: String::from("t"),
+        org_table: String::from("t"),
+        name: String::from(name),
+        org_name: String::from(name),
+        length_of_fixed_length_fields: 0x0c,
+        character_set: 255,
+        column_length: 1024,
+        type_,
+        flags: 0,
+        decimals: 0,
+    }
+}
+
+pub fn string_column(name: &str) -> ColumnDefinition41 {
+    column(name, MYSQL_TYPE_VAR_STRING)
+}
+
+// A session state change of `type_` whose data is `fields` as length-encoded strings, preceded by
+// `prefix` (e.g. the encoding specification of SESSION_TRACK_GTIDS).
+pub fn state_change(type_: u8, prefix: &[u8], fields: &[&str]) -> SessionStateChange {
+    let mut data = prefix.to_vec();
+    for field in fields {
+        encode_lenenc_string(&mut data, field.as_bytes());
+    }
+    SessionStateChange { type_, data }
+}
+
+// ProtocolBinary::ResultsetRow
+pub fn binary_row(values: &[Value]) -> Vec<u8> {
+    let mut pkt = vec![0];
+    // The NULL bitmap of a row starts at bit 2.
+    let mut null_bitmap = vec![0; (values.len() + 7 + 2) / 8];
+    let mut cells = vec![];
+    for (i, value) in values.iter().enumerate() {
+        match value {
+            Value::Null => null_bitmap[(i + 2) / 8] |= 1 << ((i + 2) % 8),
+            value => cells.extend(value.encode_binary().2),
+        }
+    }
+    pkt.extend(null_bitmap);
+    pkt.extend(cells);
+    pkt
+}
+
+// The statement of a COM_QUERY packet.
+pub fn sql(pkt: &[u8]) -> Option<String> {
+    match pkt.split_first() {
+        Some((&COM_QUERY, sql)) => Some(String::from_utf8_lossy(sql).into_owned()),
+        _ => None,
+    }
+}
+
+type Login = dyn Fn(&mut Session, HandshakeResponse41) -> bool + Send + Sync;
+type Handler = dyn Fn(&mut Session, Vec<u8>) -> bool + Send + Sync;
+
+pub struct Server {
+    greeting: Vec<u8>,
+    login: Arc<Login>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            greeting: greeting(CAPABILITIES).encode(),
+            // Anyone may log in.
+            login: Arc::new(|session, _| {
+                session.ok();
+                true
+            }),
+        }
+    }
+
+    pub fn greeting(mut self, greeting: HandshakeV10) -> Self {
+        self.greeting = greeting.encode();
+        self
+    }
+
+    // A greeting as another server sent it, e.g. with bytes `HandshakeV10::encode` wouldn't write.
+    pub fn raw_greeting(mut self, greeting: &[u8]) -> Self {
+        self.greeting = greeting.to_vec();
+        self
+    }
+
+    // Runs after the handshake response is read; the session is closed when it returns false.
+    pub fn login(
+        mut self,
+        login: impl Fn(&mut Session, HandshakeResponse41) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.login = Arc::new(login);
+        self
+    }
+
+    // Accepts connections until the test ends, each served on its own thread. `handler` gets every
+    // command but COM_QUIT and must answer it; the session is closed when it returns false.
+    pub fn serve(
+        self,
+        handler: impl Fn(&mut Session, Vec<u8>) -> bool + Send + Sync + 'static,
+    ) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let greeting = Arc::new(self.greeting);
+        let login = self.login;
+        let handler: Arc<Handler> = Arc::new(handler);
+        thread::spawn(move || {
+            for (number, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { continue };
+                let (greeting, login, handler) = (greeting.clone(), login.clone(), handler.clone());
+                thread::spawn(move || {
+                    let mut session = Session {
+                        stream,
+                        seq: 0,
+                        capabilities: 0,
+                        number,
+                    };
+                    session.send(&greeting);
+                    let Some(pkt) = session.recv() else { return };
+                    let response = HandshakeResponse41::decode(&pkt).unwrap();
+                    session.capabilities = response.client_flag;
+                    if !login(&mut session, response) {
+                        session.close();
+                        return;
+                    }
+                    while let Some(pkt) = session.recv() {
+                        if pkt.first() == Some(&COM_QUIT) || !handler(&mut session, pkt) {
+                            break;
+                        }
+                    }
+                    session.close();
+                });
+            }
+        });
+        port
+    }
+}
+
+// The greeting of a MySQL 8.0 server offering `capabilities`.
+pub fn greeting(capabilities: u32) -> HandshakeV10 {
+    HandshakeV10::new(
+        "8.0.36-test",
+        1,
+        capabilities,
+        255,
+        STATUS,
+        SCRAMBLE,
+        "mysql_native_password",
+    )
+}
+
+// The greeting of a MariaDB 10.11 server offering `mariadb_capabilities` besides `CAPABILITIES`.
+pub fn mariadb_greeting(mariadb_capabilities: u32) -> HandshakeV10 {
+    let mut greeting = HandshakeV10::new(
+        "5.5.5-10.11.6-MariaDB",
+        1,
+        CAPABILITIES,
+        45,
+        STATUS,
+        SCRAMBLE,
+        "mysql_native_password",
+    );
+    greeting.set_mariadb_capabilities(mariadb_capabilities);
+    greeting
+}
+
+// A server answering every command with OK.
+pub fn serve_ok() -> u16 {
+    Server::new().serve(|session, _| {
+        session.ok();
+        true
+    })
+}
+
+pub fn options(port: u16) -> ConnectionOptions {
+    ConnectionOptions {
+        host: String::from("127.0.0.1"),
+        port,
+        username: String::from("test"),
+        ..Default::default()
+    }
+}
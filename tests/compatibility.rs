@@ -0,0 +1,116 @@
+mod common;
+
+use std::sync::mpsc;
+
+use common::{Server, options, sql};
+use toy_mysql_client::{
+    auth::{scramble_caching_sha2_password, scramble_native_password},
+    connection::{Connection, ConnectionOptions},
+    handshake::HandshakeResponse41,
+};
+
+// Greetings of MySQL-compatible servers and proxies, each with the quirks it shows next to MySQL's.
+
+// ProxySQL: a filler byte that isn't 0x00, auth_plugin_data_len 0 despite CLIENT_PLUGIN_AUTH, and
+// an empty plugin name.
+const PROXYSQL: &[u8] =
+    b"\x0a8.0.11\x00\x07\x00\x00\x00Gx2;Rk]z\x01\x0f\xa2!\x02\x00\x0f\x00\x00\x00\
+    \x00\x00\x00\x00\x00\x00\x00\x00\x009q@L#m*P$wUe\x00\x00";
+const PROXYSQL_SCRAMBLE: &[u8] = b"Gx2;Rk]z9q@L#m*P$wUe";
+
+// MariaDB 10.11: the "5.5.5-" version prefix, CLIENT_LONG_PASSWORD cleared, and MariaDB's own
+// capabilities in the last 4 reserved bytes.
+const MARIADB: &[u8] = b"\x0a5.5.5-10.11.6-MariaDB-log\x00\x1f\x00\x00\x00a[3OZ!@k\x00\xfe\xf7-\x02\x00\
+    \xff\x81\x15\x00\x00\x00\x00\x00\x00\x1d\x00\x00\x00o4`ZyQ8\x22~Xt7\x00mysql_native_password\x00";
+const MARIADB_SCRAMBLE: &[u8] = b"a[3OZ!@ko4`ZyQ8\"~Xt7";
+
+// Percona Server 8.0: as MySQL 8.0, caching_sha2_password included.
+const PERCONA: &[u8] = b"\x0a8.0.35-27\x00\x0c\x00\x00\x00\x1b5\x10j@6\x0fo\x00\xff\xff\xff\x02\x00\xff\
+    \xdf\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00P;p\x13Mrl\x7f}`Q\x01\x00caching_sha2_password\x00";
+const PERCONA_SCRAMBLE: &[u8] = b"\x1b5\x10j@6\x0foP;p\x13Mrl\x7f}`Q\x01";
+
+// Vitess (vtgate): a smaller set of capabilities, CLIENT_SESSION_TRACK among them, though it
+// rejects some of the session tracking variables.
+const VITESS: &[u8] = b"\x0a8.0.30-Vitess\x00\x03\x00\x00\x00\x06N3wtcZ\x11\x00\x0f\xa2\xff\x02\x00\
+    \xab\x01\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00oA\x16k,Tk}%7q.\x00mysql_native_password\x00";
+const VITESS_SCRAMBLE: &[u8] = b"\x06N3wtcZ\x11oA\x16k,Tk}%7q.";
+
+// Connects to a server greeting with `greeting`, and returns the connection after a ping along
+// with the handshake response the server got.
+fn connect(greeting: &'static [u8]) -> (Connection, HandshakeResponse41) {
+    let (tx, rx) = mpsc::channel();
+    let port = Server::new()
+        .raw_greeting(greeting)
+        .login(move |session, response| {
+            if response.client_plugin_name == "caching_sha2_password" {
+                // fast_auth_success
+                session.send(&[0x01, 0x03]);
+            }
+            tx.send(response).unwrap();
+            session.ok();
+            true
+        })
+        .serve(|session, pkt| {
+            match sql(&pkt) {
+                Some(sql) if sql.starts_with("SET session_track") => {
+                    session.err(1231, "42000", "Variable can't be set to the value")
+                }
+                _ => session.ok(),
+            }
+            true
+        });
+    let mut conn = Connection::new(ConnectionOptions {
+        password: String::from("secret"),
+        ..options(port)
+    })
+    .unwrap();
+    conn.ping().unwrap();
+    (conn, rx.recv().unwrap())
+}
+
+#[test]
+fn connects_through_proxysql() {
+    let (conn, response) = connect(PROXYSQL);
+    assert_eq!(conn.connection_id(), 7);
+    assert_eq!(response.client_plugin_name, "mysql_native_password");
+    assert_eq!(
+        response.auth_response,
+        scramble_native_password("secret", PROXYSQL_SCRAMBLE)
+    );
+}
+
+#[test]
+fn connects_to_mariadb() {
+    let (conn, response) = connect(MARIADB);
+    assert_eq!(conn.connection_id(), 31);
+    let version = conn.server_version();
+    assert!(version.is_mariadb);
+    assert_eq!((version.major, version.minor, version.patch), (10, 11, 6));
+    assert_eq!(
+        response.auth_response,
+        scramble_native_password("secret", MARIADB_SCRAMBLE)
+    );
+}
+
+#[test]
+fn connects_to_percona_server() {
+    let (conn, response) = connect(PERCONA);
+    assert_eq!(conn.connection_id(), 12);
+    assert_eq!(response.client_plugin_name, "caching_sha2_password");
+    assert_eq!(
+        response.auth_response,
+        scramble_caching_sha2_password("secret", PERCONA_SCRAMBLE)
+    );
+}
+
+#[test]
+fn connects_through_vitess() {
+    let (mut conn, response) = connect(VITESS);
+    assert_eq!(conn.connection_id(), 3);
+    assert_eq!(
+        response.auth_response,
+        scramble_native_password("secret", VITESS_SCRAMBLE)
+    );
+    // Rejected session tracking leaves the session usable.
+    conn.query("SELECT 1").unwrap();
+}
@@ -0,0 +1,260 @@
+mod common;
+
+use std::{
+    net::{Ipv4Addr, TcpListener},
+    time::{Duration, Instant},
+};
+
+use common::{CAPABILITIES, STATUS, Server, greeting, options, serve_ok, sql, string_column};
+use toy_mysql_client::{
+    command::OkPacket,
+    connection::{Connection, ConnectionOptions},
+    consts::COM_PING,
+    error::Error,
+    handshake::{CLIENT_DEPRECATE_EOF, CLIENT_FOUND_ROWS, CLIENT_SESSION_TRACK},
+    result::Terminator,
+    state::ConnectionState,
+    upsert::UpsertOutcome,
+    value::Value,
+};
+
+#[test]
+fn empty_response_is_an_error_and_breaks_the_connection() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT 1") => session.send(&[]),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let err = conn.query("SELECT 1").unwrap_err();
+    assert!(err.to_string().contains("empty packet"), "{:#}", err);
+    assert!(conn.is_broken());
+}
+
+#[test]
+fn query_while_a_leaked_iterator_holds_a_result_set_is_refused() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT name FROM users") => session.result_set(
+                &[string_column("name")],
+                &[vec![Some("alice")], vec![Some("bob")]],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let iter = conn.query_iter("SELECT name FROM users").unwrap();
+    std::mem::forget(iter);
+
+    let err = conn.query("SELECT 1").unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidState {
+                expected: ConnectionState::Idle,
+                actual: ConnectionState::StreamingResult { .. },
+            })
+        ),
+        "{:#}",
+        err
+    );
+    assert!(conn.is_broken());
+}
+
+#[test]
+fn empty_response_to_query_drop_is_an_error() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("DO 1") => session.send(&[]),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let err = conn.query_drop("DO 1").unwrap_err();
+    assert!(err.to_string().contains("empty packet"), "{:#}", err);
+    assert!(conn.is_broken());
+}
+
+#[test]
+fn result_sets_and_ok_packets_with_each_eof_and_session_track_combination() {
+    for server_flags in [
+        0,
+        CLIENT_DEPRECATE_EOF,
+        CLIENT_SESSION_TRACK,
+        CLIENT_DEPRECATE_EOF | CLIENT_SESSION_TRACK,
+    ] {
+        let capabilities =
+            CAPABILITIES & !(CLIENT_DEPRECATE_EOF | CLIENT_SESSION_TRACK) | server_flags;
+        let port = Server::new()
+            .greeting(greeting(capabilities))
+            .serve(|session, pkt| {
+                match sql(&pkt).as_deref() {
+                    Some("SELECT name FROM users") => session
+                        .result_set(&[string_column("name")], &[vec![Some("alice")], vec![None]]),
+                    Some("UPDATE users SET name = 'bob'") => {
+                        let mut ok = OkPacket::new(2, 0, STATUS);
+                        ok.info = String::from("Rows matched: 2  Changed: 2  Warnings: 0");
+                        session.send_ok(ok);
+                    }
+                    _ => session.ok(),
+                }
+                true
+            });
+        let mut conn = Connection::new(options(port)).unwrap();
+
+        let result = conn.query("SELECT name FROM users").unwrap();
+        let rs = result.result_set().unwrap();
+        assert_eq!(rs.rows.len(), 2, "{:#x}", server_flags);
+        assert_eq!(rs.rows[1].0[0], None);
+        match &rs.terminator {
+            Terminator::Ok(_) => assert_ne!(server_flags & CLIENT_DEPRECATE_EOF, 0),
+            Terminator::Eof { .. } => assert_eq!(server_flags & CLIENT_DEPRECATE_EOF, 0),
+        }
+
+        let result = conn.query("UPDATE users SET name = 'bob'").unwrap();
+        assert_eq!(result.affected_rows(), 2);
+        let Terminator::Ok(ok) = result.terminator() else {
+            panic!("an UPDATE ends with an OK packet");
+        };
+        assert_eq!(ok.info, "Rows matched: 2  Changed: 2  Warnings: 0");
+    }
+}
+
+#[test]
+fn dropping_a_partly_read_iterator_drains_it() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT name FROM users") => session.result_set(
+                &[string_column("name")],
+                &[vec![Some("alice")], vec![Some("bob")], vec![Some("carol")]],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let mut iter = conn.query_iter("SELECT name FROM users").unwrap();
+    iter.next().unwrap().unwrap();
+    drop(iter);
+    assert_eq!(conn.state(), ConnectionState::Idle);
+    let result = conn.query("SELECT name FROM users").unwrap();
+    assert_eq!(result.result_set().unwrap().rows.len(), 3);
+}
+
+#[test]
+fn ping_against_a_silent_server_times_out() {
+    // Never answers a ping.
+    let port = Server::new().serve(|session, pkt| {
+        if pkt.first() != Some(&COM_PING) {
+            session.ok();
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let started = Instant::now();
+    let err = conn
+        .ping_with_timeout(Duration::from_millis(100))
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(
+        matches!(err.downcast_ref::<Error>(), Some(Error::Timeout { .. })),
+        "{:#}",
+        err
+    );
+    // The response may still come, so the connection can't be used any more.
+    assert!(conn.is_broken());
+}
+
+#[test]
+fn binding_to_an_address_not_on_this_host_is_a_descriptive_error() {
+    let port = serve_ok();
+    // TEST-NET-1, assigned to no host.
+    let err = Connection::new(ConnectionOptions {
+        local_address: Some(Ipv4Addr::new(192, 0, 2, 1).into()),
+        ..options(port)
+    })
+    .unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(
+        err.contains("failed to bind to local address 192.0.2.1:0"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn binding_to_a_taken_port_is_a_descriptive_error() {
+    let port = serve_ok();
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let local_port = taken.local_addr().unwrap().port();
+    let err = Connection::new(ConnectionOptions {
+        local_address: Some(Ipv4Addr::LOCALHOST.into()),
+        local_port: Some(local_port),
+        ..options(port)
+    })
+    .unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(
+        err.contains(&format!(
+            "failed to bind to local address 127.0.0.1:{}",
+            local_port
+        )),
+        "{}",
+        err
+    );
+}
+
+// A server where user 1 is already named alice: an UPDATE or upsert setting that name matches the
+// row without changing it, which counts as affected only with CLIENT_FOUND_ROWS.
+fn serve_unchanged_row() -> u16 {
+    Server::new()
+        .greeting(greeting(CAPABILITIES | CLIENT_FOUND_ROWS))
+        .serve(|session, pkt| {
+            let found_rows = session.capabilities & CLIENT_FOUND_ROWS != 0;
+            match sql(&pkt).as_deref() {
+                Some(sql) if sql.starts_with("UPDATE") || sql.starts_with("INSERT") => {
+                    let mut ok = OkPacket::new(found_rows as u64, 0, STATUS);
+                    ok.info = String::from("Rows matched: 1  Changed: 0  Warnings: 0");
+                    session.send_ok(ok);
+                }
+                _ => session.ok(),
+            }
+            true
+        })
+}
+
+// The affected rows of an UPDATE matching a row it leaves unchanged, and the outcome of an upsert
+// of that row.
+fn unchanged_row_counts(found_rows: bool) -> (u64, UpsertOutcome) {
+    let mut conn = Connection::new(ConnectionOptions {
+        found_rows,
+        ..options(serve_unchanged_row())
+    })
+    .unwrap();
+    assert_eq!(conn.found_rows(), found_rows);
+    let updated = conn
+        .query("UPDATE users SET name = 'alice' WHERE id = 1")
+        .unwrap()
+        .affected_rows();
+    let upserted = conn
+        .upsert(
+            "users",
+            &["id"],
+            &[("id", Value::from(1)), ("name", Value::from("alice"))],
+        )
+        .unwrap();
+    (updated, upserted)
+}
+
+#[test]
+fn found_rows_counts_matched_rather_than_changed_rows() {
+    assert_eq!(unchanged_row_counts(false), (0, UpsertOutcome::Unchanged));
+    assert_eq!(
+        unchanged_row_counts(true),
+        (1, UpsertOutcome::InsertedOrUnchanged)
+    );
+}
@@ -0,0 +1,473 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use common::{CAPABILITIES, SCRAMBLE, STATUS, Server, greeting, options};
+use toy_mysql_client::{
+    auth::{AuthContext, Authenticator, scramble_caching_sha2_password, scramble_native_password},
+    command::ErrPacket,
+    connection::{Connection, ConnectionOptions},
+    handshake::{
+        AuthNextFactor, AuthSwitchRequest, CLIENT_CONNECT_ATTRS, HandshakeResponse41, HandshakeV10,
+    },
+};
+
+// Connects with `options` to a server that does or doesn't offer CLIENT_CONNECT_ATTRS and returns
+// the handshake response it got.
+fn handshake_response(
+    server_connect_attrs: bool,
+    options: impl FnOnce(u16) -> ConnectionOptions,
+) -> HandshakeResponse41 {
+    let mut capabilities = CAPABILITIES & !CLIENT_CONNECT_ATTRS;
+    if server_connect_attrs {
+        capabilities |= CLIENT_CONNECT_ATTRS;
+    }
+    let (tx, rx) = mpsc::channel();
+    let port = Server::new()
+        .greeting(greeting(capabilities))
+        .login(move |session, response| {
+            tx.send(response).unwrap();
+            session.ok();
+            true
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    Connection::new(options(port)).unwrap();
+    rx.recv().unwrap()
+}
+
+#[test]
+fn connect_attrs_are_sent_when_enabled_and_offered() {
+    let response = handshake_response(true, options);
+    assert_ne!(response.client_flag & CLIENT_CONNECT_ATTRS, 0);
+    assert!(!response.connect_attrs.is_empty());
+}
+
+#[test]
+fn connect_attrs_are_left_out_when_disabled() {
+    let response = handshake_response(true, |port| ConnectionOptions {
+        send_connect_attrs: false,
+        ..options(port)
+    });
+    assert_eq!(response.client_flag & CLIENT_CONNECT_ATTRS, 0);
+    assert!(response.connect_attrs.is_empty());
+}
+
+#[test]
+fn connect_attrs_are_left_out_when_the_server_does_not_offer_them() {
+    let response = handshake_response(false, options);
+    assert_eq!(response.client_flag & CLIENT_CONNECT_ATTRS, 0);
+    assert!(response.connect_attrs.is_empty());
+}
+
+#[test]
+fn connect_attrs_are_left_out_when_disabled_and_not_offered() {
+    let response = handshake_response(false, |port| ConnectionOptions {
+        send_connect_attrs: false,
+        ..options(port)
+    });
+    assert_eq!(response.client_flag & CLIENT_CONNECT_ATTRS, 0);
+    assert!(response.connect_attrs.is_empty());
+}
+
+#[test]
+fn two_auth_switches_in_a_row() {
+    let (tx, rx) = mpsc::channel();
+    let port = Server::new()
+        .login(move |session, _| {
+            session.send(
+                &AuthSwitchRequest::new("caching_sha2_password", b"abcdefghijabcdefghij").encode(),
+            );
+            let first = session.recv().unwrap();
+            session.send(
+                &AuthSwitchRequest::new("mysql_native_password", b"ABCDEFGHIJABCDEFGHIJ").encode(),
+            );
+            let second = session.recv().unwrap();
+            tx.send((first, second)).unwrap();
+            session.ok();
+            true
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    let mut conn = Connection::new(ConnectionOptions {
+        password: String::from("secret"),
+        ..options(port)
+    })
+    .unwrap();
+    let (first, second) = rx.recv().unwrap();
+    assert_eq!(
+        first,
+        scramble_caching_sha2_password("secret", b"abcdefghijabcdefghij")
+    );
+    assert_eq!(
+        second,
+        scramble_native_password("secret", b"ABCDEFGHIJABCDEFGHIJ")
+    );
+    conn.ping().unwrap();
+}
+
+#[test]
+fn endless_auth_switches_are_an_error() {
+    let port = Server::new()
+        .login(|session, _| {
+            loop {
+                session.send(
+                    &AuthSwitchRequest::new("mysql_native_password", b"ABCDEFGHIJABCDEFGHIJ")
+                        .encode(),
+                );
+                if session.recv().is_none() {
+                    return false;
+                }
+            }
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    let err = Connection::new(options(port)).unwrap_err();
+    assert!(err.to_string().contains("auth switches"), "{:#}", err);
+}
+
+#[test]
+fn secure_transport_required_error_says_to_enable_tls() {
+    let port = Server::new()
+        .login(|session, _| {
+            session.err(
+                3159,
+                "HY000",
+                "Connections using insecure transport are prohibited while \
+                 --require_secure_transport=ON.",
+            );
+            false
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    let err = Connection::new(options(port)).unwrap_err();
+    assert!(err.to_string().contains("requires TLS"), "{:#}", err);
+    // The server's error is still there to inspect.
+    assert_eq!(err.downcast_ref::<ErrPacket>().unwrap().error_code, 3159);
+}
+
+#[test]
+fn other_handshake_errors_are_returned_as_they_are() {
+    let port = Server::new()
+        .login(|session, _| {
+            session.err(1045, "28000", "Access denied for user 'test'@'localhost'");
+            false
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    let err = Connection::new(options(port)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        ErrPacket::new(1045, "28000", "Access denied for user 'test'@'localhost'").to_string()
+    );
+}
+
+#[test]
+fn chosen_auth_plugin_is_sent_in_the_handshake_response() {
+    let response = handshake_response(true, |port| ConnectionOptions {
+        password: String::from("secret"),
+        auth_plugin: Some(String::from("caching_sha2_password")),
+        ..options(port)
+    });
+    // The server's default is mysql_native_password.
+    assert_eq!(response.client_plugin_name, "caching_sha2_password");
+    assert_eq!(
+        response.auth_response,
+        scramble_caching_sha2_password("secret", SCRAMBLE)
+    );
+}
+
+#[test]
+fn server_default_auth_plugin_is_used_without_a_choice() {
+    let response = handshake_response(true, options);
+    assert_eq!(response.client_plugin_name, "mysql_native_password");
+}
+
+#[test]
+fn chosen_auth_plugin_without_an_authenticator_is_an_error() {
+    let port = Server::new().serve(|session, _| {
+        session.ok();
+        true
+    });
+    let err = Connection::new(ConnectionOptions {
+        auth_plugin: Some(String::from("no_such_plugin")),
+        ..options(port)
+    })
+    .unwrap_err();
+    assert!(err.to_string().contains("no_such_plugin"), "{:#}", err);
+}
+
+// A server accepting only `accepted`, recording the plugin of every handshake response.
+fn serve_plugin(accepted: &'static str, tried: Arc<Mutex<Vec<String>>>) -> u16 {
+    Server::new()
+        .login(move |session, response| {
+            tried
+                .lock()
+                .unwrap()
+                .push(response.client_plugin_name.clone());
+            if response.client_plugin_name != accepted {
+                session.err(1045, "28000", "Access denied for user 'test'");
+                return false;
+            }
+            session.ok();
+            true
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        })
+}
+
+#[test]
+fn falls_back_to_the_next_auth_plugin_when_rejected() {
+    let tried = Arc::new(Mutex::new(vec![]));
+    let port = serve_plugin("mysql_native_password", tried.clone());
+    let mut conn = Connection::new(ConnectionOptions {
+        auth_plugin: Some(String::from("caching_sha2_password")),
+        auth_plugin_fallbacks: vec![String::from("mysql_native_password")],
+        ..options(port)
+    })
+    .unwrap();
+    conn.ping().unwrap();
+    assert_eq!(
+        *tried.lock().unwrap(),
+        ["caching_sha2_password", "mysql_native_password"]
+    );
+}
+
+#[test]
+fn last_server_error_is_returned_when_every_auth_plugin_is_rejected() {
+    let tried = Arc::new(Mutex::new(vec![]));
+    let port = serve_plugin("sha256_password", tried.clone());
+    let err = Connection::new(ConnectionOptions {
+        auth_plugin: Some(String::from("caching_sha2_password")),
+        auth_plugin_fallbacks: vec![String::from("mysql_native_password")],
+        ..options(port)
+    })
+    .unwrap_err();
+    assert_eq!(err.downcast_ref::<ErrPacket>().unwrap().error_code, 1045);
+    assert_eq!(tried.lock().unwrap().len(), 2);
+}
+
+// Answers the challenge with the password and the challenge, and AuthMoreData with its data reversed.
+struct Token;
+
+impl Authenticator for Token {
+    fn auth_response(&self, ctx: &AuthContext, challenge: &[u8]) -> Result<Vec<u8>> {
+        Ok([ctx.password.as_bytes(), b":", challenge].concat())
+    }
+
+    fn more_data(
+        &self,
+        _ctx: &AuthContext,
+        _challenge: &[u8],
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        Ok(Some(data.iter().rev().copied().collect()))
+    }
+}
+
+#[test]
+fn custom_authenticator_drives_the_handshake() {
+    let (tx, rx) = mpsc::channel();
+    let port = Server::new()
+        .greeting(HandshakeV10::new(
+            "8.0.36-test",
+            1,
+            CAPABILITIES,
+            255,
+            STATUS,
+            SCRAMBLE,
+            "toy_token",
+        ))
+        .login(move |session, response| {
+            // Protocol::AuthMoreData
+            session.send(b"\x01nonce");
+            let more = session.recv().unwrap();
+            tx.send((response, more)).unwrap();
+            session.ok();
+            true
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    let mut conn = Connection::new(
+        ConnectionOptions {
+            password: String::from("secret"),
+            ..options(port)
+        }
+        .with_auth_plugin("toy_token", Token),
+    )
+    .unwrap();
+    let (response, more) = rx.recv().unwrap();
+    assert_eq!(response.client_plugin_name, "toy_token");
+    assert_eq!(response.auth_response, [b"secret:", &SCRAMBLE[..]].concat());
+    assert_eq!(more, b"ecnon");
+    conn.ping().unwrap();
+}
+
+// A listener that isn't a MySQL server: it sends `bytes` (maybe nothing) to each connection and
+// holds it open until the client hangs up.
+fn serve_bytes(bytes: &'static [u8]) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            thread::spawn(move || {
+                let _ = stream.write_all(bytes);
+                let _ = stream.read_to_end(&mut vec![]);
+            });
+        }
+    });
+    port
+}
+
+fn connect_err(port: u16) -> String {
+    let err = Connection::new(ConnectionOptions {
+        connect_timeout: Some(Duration::from_millis(200)),
+        ..options(port)
+    })
+    .unwrap_err();
+    format!("{:#}", err)
+}
+
+#[test]
+fn tls_alert_is_reported_as_a_tls_only_server() {
+    // A TLS 1.2 alert record: handshake_failure.
+    let err = connect_err(serve_bytes(b"\x15\x03\x03\x00\x02\x02\x28"));
+    assert!(err.contains("try enabling tls"), "{}", err);
+}
+
+#[test]
+fn tls_handshake_is_reported_as_a_tls_only_server() {
+    let err = connect_err(serve_bytes(b"\x16\x03\x01\x00\x05\x02\x00\x00\x01\x00"));
+    assert!(err.contains("try enabling tls"), "{}", err);
+}
+
+#[test]
+fn http_response_is_reported_as_an_http_server() {
+    let err = connect_err(serve_bytes(
+        b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n",
+    ));
+    assert!(err.contains("HTTP server"), "{}", err);
+}
+
+#[test]
+fn silent_server_is_reported_as_such() {
+    let err = connect_err(serve_bytes(b""));
+    assert!(err.contains("sent nothing"), "{}", err);
+}
+
+// A server that switches every login to `plugin` and sends back what the client answered.
+fn serve_auth_switch(plugin: &'static str) -> (u16, mpsc::Receiver<Vec<u8>>) {
+    let (tx, rx) = mpsc::channel();
+    let port = Server::new()
+        .login(move |session, _| {
+            session.send(&AuthSwitchRequest::new(plugin, SCRAMBLE).encode());
+            let Some(response) = session.recv() else {
+                return false;
+            };
+            tx.send(response).unwrap();
+            session.ok();
+            true
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    (port, rx)
+}
+
+#[test]
+fn registered_authenticator_answers_an_auth_switch_to_its_plugin() {
+    let (port, rx) = serve_auth_switch("toy_token");
+    let mut conn = Connection::new(
+        ConnectionOptions {
+            password: String::from("secret"),
+            ..options(port)
+        }
+        .with_auth_plugin("toy_token", Token),
+    )
+    .unwrap();
+    assert_eq!(rx.recv().unwrap(), [b"secret:", &SCRAMBLE[..]].concat());
+    conn.ping().unwrap();
+}
+
+#[test]
+fn auth_switch_to_an_unregistered_plugin_is_an_error() {
+    let (port, _rx) = serve_auth_switch("toy_token");
+    let err = Connection::new(options(port)).unwrap_err();
+    let err = err.to_string();
+    assert!(err.contains("toy_token"), "{}", err);
+    assert!(err.contains("with_auth_plugin"), "{}", err);
+}
+
+// A server whose account has a second authentication factor with `plugin`: after the password it
+// asks for the next factor and passes the answer on.
+fn serve_next_factor(plugin: &'static str) -> (u16, mpsc::Receiver<Vec<u8>>) {
+    let (tx, rx) = mpsc::channel();
+    let port = Server::new()
+        .login(move |session, response| {
+            if response.auth_response != scramble_native_password("secret", SCRAMBLE) {
+                session.err(1045, "28000", "Access denied for user 'test'");
+                return false;
+            }
+            session.send(&AuthNextFactor::new(plugin, b"nonce").encode());
+            let Some(response) = session.recv() else {
+                return false;
+            };
+            tx.send(response).unwrap();
+            session.ok();
+            true
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    (port, rx)
+}
+
+#[test]
+fn next_authentication_factor_is_answered_by_its_plugin() {
+    let (port, rx) = serve_next_factor("toy_token");
+    let mut conn = Connection::new(
+        ConnectionOptions {
+            password: String::from("secret"),
+            ..options(port)
+        }
+        .with_auth_plugin("toy_token", Token),
+    )
+    .unwrap();
+    assert_eq!(rx.recv().unwrap(), b"secret:nonce");
+    conn.ping().unwrap();
+}
+
+#[test]
+fn next_authentication_factor_with_an_unregistered_plugin_is_an_error() {
+    let (port, _rx) = serve_next_factor("authentication_fido");
+    let err = Connection::new(ConnectionOptions {
+        password: String::from("secret"),
+        ..options(port)
+    })
+    .unwrap_err();
+    let err = err.to_string();
+    assert!(err.contains("authentication_fido"), "{}", err);
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use std::{
+    net::TcpListener,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use common::{STATUS, Server, options, sql, string_column};
+use toy_mysql_client::{
+    command::{OkPacket, StatusFlags},
+    connection::{Connection, ConnectionOptions},
+    routing::RoutingConnection,
+};
+
+// A server answering `SELECT @@hostname` with `name`, to tell which one a connection reached.
+fn serve_named(server: Server, name: &'static str) -> u16 {
+    server.serve(move |session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT @@hostname") => {
+                session.result_set(&[string_column("@@hostname")], &[vec![Some(name)]])
+            }
+            _ => session.ok(),
+        }
+        true
+    })
+}
+
+fn hostname(conn: &mut Connection) -> String {
+    let result = conn.query("SELECT @@hostname").unwrap();
+    let row = result.result_set().unwrap().iter().next().unwrap();
+    row.get(0).unwrap()
+}
+
+// A server whose handshake OK packet redirects to the port in `target`, which may be set after the
+// server started (e.g. to its own port).
+fn redirecting(target: Arc<OnceLock<u16>>) -> Server {
+    Server::new().login(move |session, _| {
+        let mut ok = OkPacket::new(0, 0, STATUS);
+        ok.info = format!("Location: mysql://127.0.0.1:{}/test", target.get().unwrap());
+        session.send_ok(ok);
+        true
+    })
+}
+
+#[test]
+fn follows_one_redirect() {
+    let target = serve_named(Server::new(), "target");
+    let router = serve_named(redirecting(Arc::new(OnceLock::from(target))), "router");
+    let mut conn = Connection::new(ConnectionOptions {
+        follow_redirect: true,
+        ..options(router)
+    })
+    .unwrap();
+    assert_eq!(hostname(&mut conn), "target");
+
+    // Without the option the redirect is ignored.
+    let mut conn = Connection::new(options(router)).unwrap();
+    assert_eq!(hostname(&mut conn), "router");
+}
+
+#[test]
+fn redirect_cycle_is_an_error() {
+    let (a_target, b_target) = (Arc::new(OnceLock::new()), Arc::new(OnceLock::new()));
+    let a = serve_named(redirecting(a_target.clone()), "a");
+    let b = serve_named(redirecting(b_target.clone()), "b");
+    a_target.set(b).unwrap();
+    b_target.set(a).unwrap();
+    let err = Connection::new(ConnectionOptions {
+        follow_redirect: true,
+        ..options(a)
+    })
+    .unwrap_err();
+    assert!(err.to_string().contains("redirect cycle"), "{:#}", err);
+}
+
+// A port nothing listens on.
+fn closed_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn connects_to_the_first_host_that_accepts() {
+    let refused = closed_port();
+    let denying = Server::new()
+        .login(|session, _| {
+            session.err(1045, "28000", "Access denied for user 'test'");
+            false
+        })
+        .serve(|session, _| {
+            session.ok();
+            true
+        });
+    let healthy = serve_named(Server::new(), "healthy");
+    let mut conn = Connection::new(ConnectionOptions {
+        hosts: [refused, denying, healthy]
+            .into_iter()
+            .map(|port| (String::from("127.0.0.1"), port))
+            .collect(),
+        ..options(refused)
+    })
+    .unwrap();
+    assert_eq!(hostname(&mut conn), "healthy");
+}
+
+#[test]
+fn every_host_failing_is_one_error_naming_each() {
+    let (first, second) = (closed_port(), closed_port());
+    let err = Connection::new(ConnectionOptions {
+        hosts: vec![
+            (String::from("127.0.0.1"), first),
+            (String::from("127.0.0.1"), second),
+        ],
+        ..options(first)
+    })
+    .unwrap_err();
+    let message = err.to_string();
+    assert!(
+        message.contains("could not connect to any host"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains(&format!("127.0.0.1:{}", first)),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains(&format!("127.0.0.1:{}", second)),
+        "{}",
+        message
+    );
+}
+
+// A server recording the statements it gets as (`name`, statement); it opens a transaction on BEGIN
+// and ends it on COMMIT.
+fn serve_recording(name: &'static str, log: Arc<Mutex<Vec<(&'static str, String)>>>) -> u16 {
+    Server::new().serve(move |session, pkt| {
+        let Some(sql) = sql(&pkt) else {
+            session.ok();
+            return true;
+        };
+        // Before answering, so the client never sees a response the log doesn't have yet.
+        log.lock().unwrap().push((name, sql.clone()));
+        let status = match sql.as_str() {
+            "BEGIN" => StatusFlags(StatusFlags::AUTOCOMMIT | StatusFlags::IN_TRANS),
+            _ => STATUS,
+        };
+        if sql.starts_with("SELECT") {
+            session.result_set_with_status(&[string_column("1")], &[vec![Some("1")]], status);
+        } else {
+            session.send_ok(OkPacket::new(0, 0, status));
+        }
+        true
+    })
+}
+
+#[test]
+fn reads_go_to_the_replica_and_writes_to_the_primary() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let primary = serve_recording("primary", log.clone());
+    let replica = serve_recording("replica", log.clone());
+    let mut conn = RoutingConnection::new(options(primary), options(replica)).unwrap();
+    // Whatever the connections ran while connecting isn't of interest.
+    log.lock().unwrap().clear();
+
+    conn.query("SELECT * FROM users").unwrap();
+    conn.query("INSERT INTO users (name) VALUES ('alice')")
+        .unwrap();
+    conn.query("SELECT * FROM users FOR UPDATE").unwrap();
+    // Inside a transaction the reads stay on the primary.
+    conn.query("BEGIN").unwrap();
+    conn.query("SELECT * FROM users").unwrap();
+    conn.query("COMMIT").unwrap();
+    conn.query("SELECT * FROM users").unwrap();
+
+    let log = log.lock().unwrap().clone();
+    let expected = [
+        ("replica", "SELECT * FROM users"),
+        ("primary", "INSERT INTO users (name) VALUES ('alice')"),
+        ("primary", "SELECT * FROM users FOR UPDATE"),
+        ("primary", "BEGIN"),
+        ("primary", "SELECT * FROM users"),
+        ("primary", "COMMIT"),
+        ("replica", "SELECT * FROM users"),
+    ];
+    let log = log
+        .iter()
+        .map(|(name, sql)| (*name, sql.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(log, expected);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{Server, mariadb_greeting, options, sql};
+use toy_mysql_client::{
+    command::ErrPacket,
+    connection::{Connection, ConnectionOptions},
+    handshake::MARIADB_CLIENT_PROGRESS,
+    progress_report::{ProgressReport, ProgressReportHandler},
+    result::QueryOptions,
+};
+
+// The progress reports of a MariaDB 10.11 `ALTER TABLE t ADD INDEX (name)`, as captured.
+const REPORTS: [&[u8]; 3] = [
+    b"\xff\xff\xff\x01\x01\x02\xa8\x61\x00\x11copy to tmp table",
+    b"\xff\xff\xff\x01\x01\x02\xa0\x86\x01\x11copy to tmp table",
+    b"\xff\xff\xff\x01\x02\x02\x50\xc3\x00\x0denabling keys",
+];
+
+// A MariaDB server sending the progress reports of an ALTER TABLE before its OK, or before the
+// ERR of one adding a unique index over duplicates.
+fn serve_alter_table() -> u16 {
+    Server::new()
+        .greeting(mariadb_greeting(MARIADB_CLIENT_PROGRESS))
+        .serve(|session, pkt| {
+            let sql = sql(&pkt).unwrap_or_default();
+            if sql.starts_with("ALTER TABLE") {
+                for report in REPORTS {
+                    session.send(report);
+                }
+            }
+            match sql.as_str() {
+                "ALTER TABLE t ADD UNIQUE (name)" => {
+                    session.err(1062, "23000", "Duplicate entry 'x' for key 'name'")
+                }
+                _ => session.ok(),
+            }
+            true
+        })
+}
+
+// Runs `sql` with the progress reports collected.
+fn query_with_reports(
+    conn: &mut Connection,
+    sql: &str,
+) -> (anyhow::Result<()>, Vec<ProgressReport>) {
+    let reports = Arc::new(Mutex::new(vec![]));
+    let options = QueryOptions {
+        on_progress_report: Some(ProgressReportHandler::new({
+            let reports = reports.clone();
+            move |report| reports.lock().unwrap().push(report)
+        })),
+        ..QueryOptions::default()
+    };
+    let result = conn.query_with(sql, &options).map(|_| ());
+    let reports = reports.lock().unwrap().clone();
+    (result, reports)
+}
+
+fn connect() -> Connection {
+    Connection::new(ConnectionOptions {
+        mariadb_progress_reports: true,
+        ..options(serve_alter_table())
+    })
+    .unwrap()
+}
+
+#[test]
+fn progress_reports_are_passed_on_until_the_ok() {
+    let mut conn = connect();
+    let (result, reports) = query_with_reports(&mut conn, "ALTER TABLE t ADD INDEX (name)");
+    result.unwrap();
+    let progress = reports
+        .iter()
+        .map(|report| {
+            (
+                report.stage,
+                report.max_stage,
+                report.progress,
+                report.info.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        progress,
+        [
+            (1, 2, 0.25, "copy to tmp table"),
+            (1, 2, 1.0, "copy to tmp table"),
+            (2, 2, 0.5, "enabling keys"),
+        ]
+    );
+    conn.ping().unwrap();
+}
+
+#[test]
+fn progress_reports_are_passed_on_until_the_err() {
+    let mut conn = connect();
+    let (result, reports) = query_with_reports(&mut conn, "ALTER TABLE t ADD UNIQUE (name)");
+    let err = result.unwrap_err();
+    assert_eq!(err.downcast_ref::<ErrPacket>().unwrap().error_code, 1062);
+    assert_eq!(reports.len(), 3);
+    conn.ping().unwrap();
+}
+
+#[test]
+fn progress_reports_are_skipped_without_a_handler() {
+    let mut conn = connect();
+    conn.query("ALTER TABLE t ADD INDEX (name)").unwrap();
+    conn.ping().unwrap();
+}
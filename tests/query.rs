@@ -0,0 +1,243 @@
+mod common;
+
+use std::{io::Write, sync::mpsc};
+
+use common::{STATUS, Server, column, options, sql, string_column};
+use toy_mysql_client::{
+    command::{ErrPacket, MYSQL_TYPE_NEWDECIMAL, OkPacket, ResultsetRow, StatusFlags},
+    connection::Connection,
+    state::ConnectionState,
+};
+
+const MORE_RESULTS: StatusFlags =
+    StatusFlags(StatusFlags::AUTOCOMMIT | StatusFlags::MORE_RESULTS_EXISTS);
+
+// CALL of a procedure doing two SELECTs and an UPDATE: a result set for each SELECT, then the OK
+// packet of the CALL itself, which carries the UPDATE's affected rows.
+fn serve_procedure() -> u16 {
+    Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("CALL two_selects_and_an_update()") => {
+                session.result_set_with_status(
+                    &[string_column("name")],
+                    &[vec![Some("alice")], vec![Some("bob")]],
+                    MORE_RESULTS,
+                );
+                session.result_set_with_status(
+                    &[string_column("total")],
+                    &[vec![Some("2")]],
+                    MORE_RESULTS,
+                );
+                session.send_ok(OkPacket::new(1, 0, STATUS));
+            }
+            Some("SELECT 1") => session.result_set(&[string_column("1")], &[vec![Some("1")]]),
+            _ => session.ok(),
+        }
+        true
+    })
+}
+
+#[test]
+fn query_all_returns_every_result_of_a_procedure() {
+    let mut conn = Connection::new(options(serve_procedure())).unwrap();
+    let results = conn.query_all("CALL two_selects_and_an_update()").unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].result_set().unwrap().rows.len(), 2);
+    assert_eq!(results[1].column_names(), ["total"]);
+    assert!(results[2].result_set().is_none());
+    assert_eq!(results[2].affected_rows(), 1);
+}
+
+#[test]
+fn next_query_works_after_a_multi_result_response() {
+    let mut conn = Connection::new(options(serve_procedure())).unwrap();
+    // `query` keeps the first result and reads the others off the socket.
+    let first = conn.query("CALL two_selects_and_an_update()").unwrap();
+    assert_eq!(first.column_names(), ["name"]);
+    let next = conn.query("SELECT 1").unwrap();
+    assert_eq!(
+        next.result_set().unwrap().rows[0].0[0].as_deref(),
+        Some(&b"1"[..])
+    );
+
+    conn.query_drop("CALL two_selects_and_an_update()").unwrap();
+    conn.query_all("CALL two_selects_and_an_update()").unwrap();
+    assert_eq!(conn.query("SELECT 1").unwrap().column_names(), ["1"]);
+}
+
+#[test]
+fn show_warnings_after_an_out_of_range_insert() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("INSERT INTO t (tiny) VALUES (1000)") => {
+                let mut ok = OkPacket::new(1, 0, STATUS);
+                ok.warnings = 1;
+                session.send_ok(ok);
+            }
+            Some("SHOW WARNINGS") => session.result_set(
+                &[
+                    string_column("Level"),
+                    string_column("Code"),
+                    string_column("Message"),
+                ],
+                &[vec![
+                    Some("Warning"),
+                    Some("1264"),
+                    Some("Out of range value for column 'tiny' at row 1"),
+                ]],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    conn.query("INSERT INTO t (tiny) VALUES (1000)").unwrap();
+    assert_eq!(conn.warning_count(), 1);
+    let warnings = conn.show_warnings().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].level, "Warning");
+    assert_eq!(warnings[0].code, 1264);
+    assert_eq!(
+        warnings[0].message,
+        "Out of range value for column 'tiny' at row 1"
+    );
+}
+
+// SELECT 1/0 FROM big_table under strict mode with ERROR_FOR_DIVISION_BY_ZERO: the server sends the
+// rows it has, then an ERR packet where the next row would be. With the column definitions cut
+// short instead, the ERR comes after the first of them.
+fn serve_division_by_zero() -> u16 {
+    Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT 1/0 FROM big_table") => {
+                session.column_count(1);
+                session.send(&column("1/0", MYSQL_TYPE_NEWDECIMAL).encode());
+                session.eof();
+                for _ in 0..2 {
+                    session.send(&ResultsetRow(vec![None]).encode());
+                }
+                session.err(1365, "22012", "Division by 0");
+            }
+            Some("SELECT a, b FROM t") => {
+                session.column_count(2);
+                session.send(&string_column("a").encode());
+                session.err(1317, "70100", "Query execution was interrupted");
+            }
+            Some("SELECT 1") => session.result_set(&[string_column("1")], &[vec![Some("1")]]),
+            _ => session.ok(),
+        }
+        true
+    })
+}
+
+fn error_code(err: anyhow::Error) -> u16 {
+    err.downcast_ref::<ErrPacket>().unwrap().error_code
+}
+
+#[test]
+fn err_between_rows_is_returned_and_leaves_the_connection_usable() {
+    let mut conn = Connection::new(options(serve_division_by_zero())).unwrap();
+    let err = conn.query("SELECT 1/0 FROM big_table").unwrap_err();
+    assert_eq!(error_code(err), 1365);
+    assert_eq!(conn.state(), ConnectionState::Idle);
+    assert_eq!(conn.query("SELECT 1").unwrap().column_names(), ["1"]);
+}
+
+#[test]
+fn err_between_rows_ends_a_row_iterator() {
+    let mut conn = Connection::new(options(serve_division_by_zero())).unwrap();
+    let mut rows = conn.query_iter("SELECT 1/0 FROM big_table").unwrap();
+    assert!(rows.next().unwrap().is_ok());
+    assert!(rows.next().unwrap().is_ok());
+    assert_eq!(error_code(rows.next().unwrap().unwrap_err()), 1365);
+    assert!(rows.next().is_none());
+    drop(rows);
+    assert_eq!(conn.state(), ConnectionState::Idle);
+    assert_eq!(conn.query("SELECT 1").unwrap().column_names(), ["1"]);
+}
+
+#[test]
+fn err_between_column_definitions_is_returned_and_leaves_the_connection_usable() {
+    let mut conn = Connection::new(options(serve_division_by_zero())).unwrap();
+    let err = conn.query("SELECT a, b FROM t").unwrap_err();
+    assert_eq!(error_code(err), 1317);
+    assert_eq!(conn.state(), ConnectionState::Idle);
+    assert_eq!(conn.query("SELECT 1").unwrap().column_names(), ["1"]);
+}
+
+// A server answering each statement with an OK packet counting its rows, one for every
+// parenthesized tuple after VALUES, and passing the statement on.
+fn serve_inserts(tx: mpsc::Sender<String>) -> u16 {
+    Server::new().serve(move |session, pkt| {
+        let sql = sql(&pkt).unwrap_or_default();
+        let rows = match sql.split_once(" VALUES ") {
+            Some((_, values)) => values.matches("), (").count() as u64 + 1,
+            None => 0,
+        };
+        tx.send(sql).unwrap();
+        session.send_ok(OkPacket::new(rows, 0, STATUS));
+        true
+    })
+}
+
+#[test]
+fn large_insert_is_streamed_over_several_frames() {
+    const ROWS: usize = 200_000;
+    let (tx, rx) = mpsc::channel();
+    let mut conn = Connection::new(options(serve_inserts(tx))).unwrap();
+    let name = "x".repeat(90);
+    let mut writer = conn.query_streaming_writer().unwrap();
+    write!(writer, "INSERT INTO t (id, name) VALUES ").unwrap();
+    let mut len = "INSERT INTO t (id, name) VALUES ".len();
+    for id in 0..ROWS {
+        let tuple = match id {
+            0 => format!("({}, '{}')", id, name),
+            _ => format!(", ({}, '{}')", id, name),
+        };
+        len += tuple.len();
+        writer.write_all(tuple.as_bytes()).unwrap();
+    }
+    // More than one 16MB frame.
+    assert!(len > 0xffffff);
+    let result = writer.finish().unwrap();
+    assert_eq!(result.affected_rows(), ROWS as u64);
+    // After the statements setting up the session.
+    let sql = rx.iter().find(|sql| sql.starts_with("INSERT")).unwrap();
+    assert_eq!(sql.len(), len);
+    assert!(sql.ends_with(&format!("({}, '{}')", ROWS - 1, name)));
+    assert_eq!(conn.query("SELECT 1").unwrap().affected_rows(), 0);
+}
+
+#[test]
+fn statement_filling_a_frame_exactly_is_followed_by_an_empty_one() {
+    let (tx, rx) = mpsc::channel();
+    let mut conn = Connection::new(options(serve_inserts(tx))).unwrap();
+    let mut writer = conn.query_streaming_writer().unwrap();
+    // With the command byte, the payload is exactly one full frame.
+    let sql = format!("DO 1{}", " ".repeat(0xffffff - 1 - 4));
+    writer.write_all(sql.as_bytes()).unwrap();
+    writer.finish().unwrap();
+    assert!(rx.iter().any(|received| received == sql));
+    conn.ping().unwrap();
+}
+
+#[test]
+fn pipelined_statement_over_16mb_is_followed_by_a_small_one() {
+    let (tx, rx) = mpsc::channel();
+    let mut conn = Connection::new(options(serve_inserts(tx))).unwrap();
+    let name = "x".repeat(0x1000000);
+    let large = format!("INSERT INTO t (id, name) VALUES (1, '{}'), (2, 'y')", name);
+    let mut pipeline = conn.pipeline();
+    pipeline
+        .query(&large)
+        .query("INSERT INTO t (id, name) VALUES (3, 'z')")
+        .query("DO 1");
+    let results = pipeline.send().unwrap();
+    let affected_rows = results
+        .into_iter()
+        .map(|result| result.unwrap().affected_rows())
+        .collect::<Vec<_>>();
+    assert_eq!(affected_rows, [2, 1, 0]);
+    assert!(rx.iter().any(|sql| sql.len() == large.len()));
+    conn.ping().unwrap();
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use common::{Server, options, sql, string_column};
+use toy_mysql_client::r2d2::{MysqlConnectionManager, ResetStrategy};
+
+#[test]
+fn pool_of_two_shared_by_four_threads() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let rollbacks = Arc::new(AtomicUsize::new(0));
+    let port = Server::new()
+        .login({
+            let connections = connections.clone();
+            move |session, _| {
+                connections.fetch_add(1, Ordering::SeqCst);
+                session.ok();
+                true
+            }
+        })
+        .serve({
+            let rollbacks = rollbacks.clone();
+            move |session, pkt| {
+                match sql(&pkt).as_deref() {
+                    Some("SELECT CONNECTION_ID()") => {
+                        let id = session.number.to_string();
+                        session.result_set(&[string_column("CONNECTION_ID()")], &[vec![Some(&id)]]);
+                    }
+                    Some("ROLLBACK") => {
+                        rollbacks.fetch_add(1, Ordering::SeqCst);
+                        session.ok();
+                    }
+                    _ => session.ok(),
+                }
+                true
+            }
+        });
+    let manager = MysqlConnectionManager::new(options(port)).with_reset(ResetStrategy::Rollback);
+    let pool = r2d2::Pool::builder().max_size(2).build(manager).unwrap();
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let handles = (0..4)
+        .map(|_| {
+            let (pool, seen) = (pool.clone(), seen.clone());
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    let mut conn = pool.get().unwrap();
+                    let result = conn.query("SELECT CONNECTION_ID()").unwrap();
+                    let rs = result.result_set().unwrap();
+                    let id = rs.iter().next().unwrap().get::<String>(0).unwrap();
+                    seen.lock().unwrap().push(id);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let mut seen = seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 100);
+    seen.sort();
+    seen.dedup();
+    assert!(seen.len() <= 2, "{:?}", seen);
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    // Every checkout resets the connection it gets.
+    assert!(rollbacks.load(Ordering::SeqCst) >= 100);
+}
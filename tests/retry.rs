@@ -0,0 +1,82 @@
+mod common;
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use common::{Server, options, sql};
+use toy_mysql_client::{
+    command::ErrPacket,
+    connection::Connection,
+    retry::{ER_LOCK_DEADLOCK, RetryPolicy},
+};
+
+const ER_DUP_ENTRY: u16 = 1062;
+
+// A server failing an UPDATE with `code` the first `failures` times, counting the attempts.
+fn serve_failing(code: u16, failures: usize, attempts: Arc<AtomicUsize>) -> u16 {
+    Server::new().serve(move |session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("UPDATE t SET a = a + 1") => {
+                if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                    session.err(code, "40001", "Deadlock found when trying to get lock");
+                } else {
+                    session.ok();
+                }
+            }
+            _ => session.ok(),
+        }
+        true
+    })
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy {
+        backoff: Duration::from_millis(1),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn deadlock_is_retried() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let port = serve_failing(ER_LOCK_DEADLOCK, 2, attempts.clone());
+    let mut conn = Connection::new(options(port)).unwrap();
+    conn.query_with_retry("UPDATE t SET a = a + 1", &policy())
+        .unwrap();
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn deadlock_is_returned_after_the_last_retry() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let port = serve_failing(ER_LOCK_DEADLOCK, usize::MAX, attempts.clone());
+    let mut conn = Connection::new(options(port)).unwrap();
+    let err = conn
+        .query_with_retry("UPDATE t SET a = a + 1", &policy())
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ErrPacket>().unwrap().error_code,
+        ER_LOCK_DEADLOCK
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn duplicate_key_is_not_retried() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let port = serve_failing(ER_DUP_ENTRY, 1, attempts.clone());
+    let mut conn = Connection::new(options(port)).unwrap();
+    let err = conn
+        .query_with_retry("UPDATE t SET a = a + 1", &policy())
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ErrPacket>().unwrap().error_code,
+        ER_DUP_ENTRY
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
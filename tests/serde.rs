@@ -0,0 +1,150 @@
+mod common;
+
+use common::{Server, column, options, sql, string_column};
+use serde::Deserialize;
+use serde_json::json;
+use toy_mysql_client::{
+    command::{MYSQL_TYPE_BLOB, MYSQL_TYPE_DATETIME, MYSQL_TYPE_LONGLONG, MYSQL_TYPE_NEWDECIMAL},
+    connection::Connection,
+    result::{QueryResult, ResultSet},
+    serde::from_row,
+};
+
+fn select(conn: &mut Connection) -> ResultSet {
+    match conn.query("SELECT * FROM t").unwrap() {
+        QueryResult::ResultSet(result_set) => result_set,
+        QueryResult::Ok(_) => panic!("no result set"),
+    }
+}
+
+// A table t of id, name, score, data and created, with two rows.
+fn serve_table() -> u16 {
+    Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT * FROM t") => {
+                let mut data = column("data", MYSQL_TYPE_BLOB);
+                data.character_set = 63;
+                session.result_set(
+                    &[
+                        column("id", MYSQL_TYPE_LONGLONG),
+                        string_column("name"),
+                        column("score", MYSQL_TYPE_NEWDECIMAL),
+                        data,
+                        column("created", MYSQL_TYPE_DATETIME),
+                    ],
+                    &[
+                        vec![
+                            Some("1"),
+                            Some("alice"),
+                            Some("12.50"),
+                            Some("abc"),
+                            Some("2024-02-29 13:05:09"),
+                        ],
+                        vec![Some("2"), None, Some("-0.25"), None, None],
+                    ],
+                );
+            }
+            _ => session.ok(),
+        }
+        true
+    })
+}
+
+#[test]
+fn result_set_serializes_to_an_array_of_objects() {
+    let mut conn = Connection::new(options(serve_table())).unwrap();
+    let result_set = select(&mut conn);
+    assert_eq!(
+        serde_json::to_value(&result_set).unwrap(),
+        json!([
+            {
+                "id": 1,
+                "name": "alice",
+                "score": "12.50",
+                "data": "YWJj",
+                "created": "2024-02-29 13:05:09",
+            },
+            {
+                "id": 2,
+                "name": null,
+                "score": "-0.25",
+                "data": null,
+                "created": null,
+            },
+        ])
+    );
+    // A row alone is an array of its cells as sent.
+    assert_eq!(
+        serde_json::to_value(&result_set.rows[1]).unwrap(),
+        json!(["2", null, "-0.25", null, null])
+    );
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Player {
+    id: u32,
+    name: Option<String>,
+    // DECIMAL parsed as a number.
+    score: f64,
+    data: Option<Vec<u8>>,
+    created: Option<String>,
+}
+
+#[test]
+fn rows_deserialize_into_structs() {
+    let mut conn = Connection::new(options(serve_table())).unwrap();
+    let result_set = select(&mut conn);
+    assert_eq!(
+        result_set.rows_as::<Player>().unwrap(),
+        [
+            Player {
+                id: 1,
+                name: Some(String::from("alice")),
+                score: 12.5,
+                data: Some(b"abc".to_vec()),
+                created: Some(String::from("2024-02-29 13:05:09")),
+            },
+            Player {
+                id: 2,
+                name: None,
+                score: -0.25,
+                data: None,
+                created: None,
+            },
+        ]
+    );
+    // Columns the struct doesn't have are ignored.
+    #[derive(Debug, Deserialize)]
+    struct Id {
+        id: i64,
+    }
+    let id: Id = from_row(&result_set.rows[1], &result_set.columns).unwrap();
+    assert_eq!(id.id, 2);
+}
+
+#[test]
+fn null_into_a_field_that_is_not_an_option_names_the_column() {
+    #[derive(Debug, Deserialize)]
+    struct NotNull {
+        #[allow(dead_code)]
+        name: String,
+    }
+    let mut conn = Connection::new(options(serve_table())).unwrap();
+    let result_set = select(&mut conn);
+    let err = from_row::<NotNull>(&result_set.rows[1], &result_set.columns).unwrap_err();
+    assert!(err.to_string().contains("column name"), "{}", err);
+}
+
+#[test]
+fn type_mismatch_names_the_column() {
+    #[derive(Debug, Deserialize)]
+    struct WrongType {
+        #[allow(dead_code)]
+        name: u32,
+    }
+    let mut conn = Connection::new(options(serve_table())).unwrap();
+    let result_set = select(&mut conn);
+    let err = from_row::<WrongType>(&result_set.rows[0], &result_set.columns).unwrap_err();
+    assert!(err.to_string().contains("column name"), "{}", err);
+    assert!(err.to_string().contains("u32"), "{}", err);
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{Server, options, sql, string_column};
+use toy_mysql_client::{
+    connection::{Connection, ConnectionOptions},
+    error::Error,
+};
+
+#[test]
+fn socket_closed_mid_query_breaks_the_connection() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SELECT name FROM users") => {
+                session.column_count(1);
+                return false;
+            }
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    // Closed after the column count, between two packets of the response.
+    let err = conn.query("SELECT name FROM users").unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ServerClosedConnection { error: None })
+        ),
+        "{:#}",
+        err
+    );
+    assert!(conn.is_broken());
+
+    // The next command fails right away instead of writing to the dead socket.
+    let err = conn.query("SELECT 1").unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<Error>(), Some(Error::Broken)),
+        "{:#}",
+        err
+    );
+}
+
+// A server that answers one `SELECT CONNECTION_ID()` per connection and then drops it, as
+// wait_timeout would.
+fn serve_once_per_connection() -> u16 {
+    Server::new().serve(|session, pkt| match sql(&pkt).as_deref() {
+        Some("SELECT CONNECTION_ID()") => {
+            let id = session.number.to_string();
+            session.result_set(&[string_column("CONNECTION_ID()")], &[vec![Some(&id)]]);
+            false
+        }
+        _ => {
+            session.ok();
+            true
+        }
+    })
+}
+
+fn connection_id(conn: &mut Connection) -> anyhow::Result<String> {
+    let result = conn.query("SELECT CONNECTION_ID()")?;
+    result.result_set().unwrap().iter().next().unwrap().get(0)
+}
+
+#[test]
+fn closed_idle_connection_is_replaced_on_the_next_query() {
+    let port = serve_once_per_connection();
+    let mut conn = Connection::new(ConnectionOptions {
+        reconnect: true,
+        ..options(port)
+    })
+    .unwrap();
+    assert_eq!(connection_id(&mut conn).unwrap(), "0");
+    // Let the close reach the client.
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(connection_id(&mut conn).unwrap(), "1");
+    assert!(!conn.is_broken());
+}
+
+#[test]
+fn closed_idle_connection_is_an_error_without_reconnect() {
+    let port = serve_once_per_connection();
+    let mut conn = Connection::new(options(port)).unwrap();
+    connection_id(&mut conn).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let err = connection_id(&mut conn).unwrap_err();
+    // Depending on whether sending the query or reading its response notices first.
+    assert!(
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ServerClosedConnection { .. } | Error::ServerGone(_))
+        ),
+        "{:#}",
+        err
+    );
+}
+
+// A server answering `SELECT 1` with `bytes` and then closing the connection.
+fn serve_truncated(bytes: &'static [u8]) -> u16 {
+    Server::new().serve(move |session, pkt| match sql(&pkt).as_deref() {
+        Some("SELECT 1") => {
+            session.send_raw(bytes);
+            false
+        }
+        _ => {
+            session.ok();
+            true
+        }
+    })
+}
+
+fn server_gone_reason(bytes: &'static [u8]) -> String {
+    let mut conn = Connection::new(options(serve_truncated(bytes))).unwrap();
+    let err = conn.query("SELECT 1").unwrap_err();
+    assert!(conn.is_broken());
+    match err.downcast_ref::<Error>() {
+        Some(Error::ServerGone(reason)) => reason.clone(),
+        _ => panic!("{:#}", err),
+    }
+}
+
+#[test]
+fn stream_closed_after_the_header_says_how_much_arrived() {
+    // A 10-byte packet with sequence id 1, and none of its body.
+    assert_eq!(
+        server_gone_reason(&[10, 0, 0, 1]),
+        "connection closed after 0 of 10 bytes of a packet body"
+    );
+    assert_eq!(
+        server_gone_reason(&[10, 0, 0, 1, 1, 2, 3]),
+        "connection closed after 3 of 10 bytes of a packet body"
+    );
+}
+
+#[test]
+fn stream_closed_inside_the_header_says_how_much_arrived() {
+    assert_eq!(
+        server_gone_reason(&[10, 0]),
+        "connection closed after 2 of 4 bytes of a packet header"
+    );
+}
+
+#[test]
+fn reset_between_columns_and_rows_breaks_the_connection() {
+    let port = Server::new().serve(|session, pkt| match sql(&pkt).as_deref() {
+        Some("SELECT id, name FROM users") => {
+            session.column_count(2);
+            session.send(&string_column("id").encode());
+            session.send(&string_column("name").encode());
+            session.eof();
+            false
+        }
+        _ => {
+            session.ok();
+            true
+        }
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let err = conn.query("SELECT id, name FROM users").unwrap_err();
+    assert!(
+        matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ServerClosedConnection { error: None })
+        ),
+        "{:#}",
+        err
+    );
+    assert!(conn.is_broken());
+    let err = conn.query("SELECT 1").unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<Error>(), Some(Error::Broken)),
+        "{:#}",
+        err
+    );
+
+    // The same through the streaming iterator: the error comes with the first row.
+    let mut conn = Connection::new(options(port)).unwrap();
+    let mut rows = conn.query_iter("SELECT id, name FROM users").unwrap();
+    assert!(rows.next().unwrap().is_err());
+    drop(rows);
+    assert!(conn.is_broken());
+}
@@ -0,0 +1,265 @@
+mod common;
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use common::{STATUS, Server, options, sql, state_change, string_column};
+use toy_mysql_client::{
+    command::{OkPacket, StatusFlags},
+    connection::{Connection, ConnectionOptions},
+    session::{
+        SESSION_TRACK_GTIDS, SESSION_TRACK_SCHEMA, SESSION_TRACK_SYSTEM_VARIABLES,
+        SESSION_TRACK_TRANSACTION_STATE,
+    },
+    value::Value,
+};
+
+#[test]
+fn use_updates_the_current_database() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("USE other_db") => session.ok_with_changes(
+                STATUS,
+                vec![state_change(SESSION_TRACK_SCHEMA, &[], &["other_db"])],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(ConnectionOptions {
+        database: String::from("test"),
+        ..options(port)
+    })
+    .unwrap();
+    assert_eq!(conn.current_database(), Some("test"));
+    conn.query("USE other_db").unwrap();
+    assert_eq!(conn.current_database(), Some("other_db"));
+}
+
+#[test]
+fn set_autocommit_reports_the_changed_variable() {
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            Some("SET autocommit=0") => session.ok_with_changes(
+                StatusFlags(0),
+                vec![state_change(
+                    SESSION_TRACK_SYSTEM_VARIABLES,
+                    &[],
+                    &["autocommit", "OFF"],
+                )],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    conn.query("SET autocommit=0").unwrap();
+    assert_eq!(
+        conn.changed_system_variables(),
+        [(String::from("autocommit"), String::from("OFF"))]
+    );
+    // Only the changes of the last command are kept.
+    conn.query("DO 1").unwrap();
+    assert!(conn.changed_system_variables().is_empty());
+}
+
+#[test]
+fn commit_reports_the_gtid_of_the_transaction() {
+    const GTID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562:23";
+    let port = Server::new().serve(|session, pkt| {
+        match sql(&pkt).as_deref() {
+            // The data starts with the encoding specification, 0.
+            Some("COMMIT") => session.ok_with_changes(
+                STATUS,
+                vec![state_change(SESSION_TRACK_GTIDS, &[0], &[GTID])],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    assert_eq!(conn.last_gtid(), None);
+    conn.query("COMMIT").unwrap();
+    assert_eq!(conn.last_gtid(), Some(GTID));
+}
+
+#[test]
+fn transaction_state_follows_the_tracked_state() {
+    let port = Server::new().serve(|session, pkt| {
+        let in_trans = StatusFlags(StatusFlags::AUTOCOMMIT | StatusFlags::IN_TRANS);
+        match sql(&pkt).as_deref() {
+            Some("START TRANSACTION") => session.ok_with_changes(
+                in_trans,
+                vec![state_change(
+                    SESSION_TRACK_TRANSACTION_STATE,
+                    &[],
+                    &["T_______"],
+                )],
+            ),
+            Some("UPDATE t SET a = 1") => session.ok_with_changes(
+                in_trans,
+                vec![state_change(
+                    SESSION_TRACK_TRANSACTION_STATE,
+                    &[],
+                    &["T___W___"],
+                )],
+            ),
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    assert_eq!(conn.transaction_state(), None);
+    conn.query("START TRANSACTION").unwrap();
+    let state = conn.transaction_state().unwrap();
+    assert!(conn.in_transaction() && state.explicit && state.is_read_only());
+    conn.query("UPDATE t SET a = 1").unwrap();
+    assert!(conn.transaction_state().unwrap().transactional_write);
+}
+
+// The SET statements a connection with `options` runs while connecting.
+fn set_statements(options: impl FnOnce(u16) -> ConnectionOptions) -> Vec<String> {
+    let statements = Arc::new(Mutex::new(vec![]));
+    let port = Server::new().serve({
+        let statements = statements.clone();
+        move |session, pkt| {
+            if let Some(sql) = sql(&pkt).filter(|sql| sql.starts_with("SET ")) {
+                statements.lock().unwrap().push(sql);
+            }
+            session.ok();
+            true
+        }
+    });
+    Connection::new(options(port)).unwrap();
+    statements.lock().unwrap().clone()
+}
+
+#[test]
+fn net_timeouts_are_set_on_the_session() {
+    let statements = set_statements(|port| ConnectionOptions {
+        net_read_timeout: Some(Duration::from_secs(30)),
+        net_write_timeout: Some(Duration::from_secs(60)),
+        ..options(port)
+    });
+    assert!(
+        statements.contains(&String::from(
+            "SET SESSION net_read_timeout = 30, net_write_timeout = 60"
+        )),
+        "{:?}",
+        statements
+    );
+}
+
+#[test]
+fn net_timeouts_are_rounded_up_and_clamped() {
+    let statements = set_statements(|port| ConnectionOptions {
+        net_read_timeout: Some(Duration::from_millis(200)),
+        net_write_timeout: Some(Duration::from_secs(100_000_000)),
+        ..options(port)
+    });
+    assert!(
+        statements.contains(&String::from(
+            "SET SESSION net_read_timeout = 1, net_write_timeout = 31536000"
+        )),
+        "{:?}",
+        statements
+    );
+}
+
+#[test]
+fn net_timeouts_are_left_alone_by_default() {
+    let statements = set_statements(options);
+    assert!(
+        !statements.iter().any(|sql| sql.contains("net_")),
+        "{:?}",
+        statements
+    );
+}
+
+// The server reports NO_BACKSLASH_ESCAPES in the status flags of every response once the sql_mode
+// has it.
+#[test]
+fn escaping_follows_no_backslash_escapes() {
+    let no_backslash_escapes = AtomicBool::new(false);
+    let port = Server::new().serve(move |session, pkt| {
+        if sql(&pkt).as_deref() == Some("SET sql_mode = 'NO_BACKSLASH_ESCAPES'") {
+            no_backslash_escapes.store(true, Ordering::SeqCst);
+        }
+        let mut status = STATUS;
+        if no_backslash_escapes.load(Ordering::SeqCst) {
+            status.0 |= StatusFlags::NO_BACKSLASH_ESCAPES;
+        }
+        session.send_ok(OkPacket::new(0, 0, status));
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let value = [Value::Text(String::from("it\\'s"))];
+    assert!(!conn.no_backslash_escapes());
+    assert_eq!(conn.escape_string("it\\'s"), "it\\\\\\'s");
+    assert_eq!(
+        conn.interpolate("SELECT ?", &value).unwrap(),
+        "SELECT 'it\\\\\\'s'"
+    );
+
+    conn.query("SET sql_mode = 'NO_BACKSLASH_ESCAPES'").unwrap();
+    assert!(conn.no_backslash_escapes());
+    assert_eq!(conn.escape_string("it\\'s"), "it\\''s");
+    assert_eq!(
+        conn.interpolate("SELECT ?", &value).unwrap(),
+        "SELECT 'it\\''s'"
+    );
+}
+
+// A session whose sql_mode is what the last `SET sql_mode = '...'` made it. The number of times
+// it was fetched is counted.
+fn serve_sql_mode(fetches: Arc<Mutex<usize>>) -> u16 {
+    let mode = Mutex::new(String::from("STRICT_TRANS_TABLES,NO_ENGINE_SUBSTITUTION"));
+    Server::new().serve(move |session, pkt| {
+        let sql = sql(&pkt).unwrap_or_default();
+        if let Some(value) = sql
+            .strip_prefix("SET sql_mode = '")
+            .and_then(|rest| rest.strip_suffix('\''))
+        {
+            *mode.lock().unwrap() = String::from(value);
+            session.ok();
+        } else if sql == "SHOW VARIABLES LIKE 'sql_mode'" {
+            *fetches.lock().unwrap() += 1;
+            let mode = mode.lock().unwrap().clone();
+            session.result_set(
+                &[string_column("Variable_name"), string_column("Value")],
+                &[vec![Some("sql_mode"), Some(&mode)]],
+            );
+        } else {
+            session.ok();
+        }
+        true
+    })
+}
+
+#[test]
+fn sql_mode_is_parsed_cached_and_refreshed_after_a_set() {
+    let fetches = Arc::new(Mutex::new(0));
+    let mut conn = Connection::new(options(serve_sql_mode(fetches.clone()))).unwrap();
+    let mode = conn.sql_mode().unwrap();
+    assert_eq!(
+        mode.modes(),
+        ["STRICT_TRANS_TABLES", "NO_ENGINE_SUBSTITUTION"]
+    );
+    assert!(mode.is_strict());
+    assert!(!mode.ansi_quotes());
+    conn.sql_mode().unwrap();
+    assert_eq!(*fetches.lock().unwrap(), 1);
+
+    conn.query("SET sql_mode = 'ANSI_QUOTES,NO_BACKSLASH_ESCAPES'")
+        .unwrap();
+    let mode = conn.sql_mode().unwrap();
+    assert!(mode.ansi_quotes());
+    assert!(mode.no_backslash_escapes());
+    assert!(!mode.is_strict());
+    assert_eq!(*fetches.lock().unwrap(), 2);
+}
@@ -0,0 +1,350 @@
+mod common;
+
+use std::sync::mpsc;
+
+use common::{
+    CAPABILITIES, SCRAMBLE, STATUS, Server, column, greeting, mariadb_greeting, options,
+    string_column,
+};
+use toy_mysql_client::{
+    command::{
+        ColumnDefinition41, MYSQL_TYPE_DATETIME, MYSQL_TYPE_DOUBLE, MYSQL_TYPE_LONG,
+        MYSQL_TYPE_LONGLONG, MYSQL_TYPE_NULL, MYSQL_TYPE_STRING, MYSQL_TYPE_VAR_STRING, OkPacket,
+    },
+    connection::{Connection, ConnectionOptions},
+    consts::{COM_STMT_CLOSE, COM_STMT_EXECUTE, COM_STMT_PREPARE},
+    handshake::{CLIENT_DEPRECATE_EOF, HandshakeV10, MARIADB_CLIENT_STMT_BULK_OPERATIONS},
+    value::Value,
+};
+
+#[test]
+fn empty_response_to_execute_is_an_error() {
+    let port = Server::new().serve(|session, pkt| {
+        match pkt[0] {
+            COM_STMT_PREPARE => session.prepare_ok(1, &[], &[string_column("name")]),
+            COM_STMT_EXECUTE => session.send(&[]),
+            COM_STMT_CLOSE => {}
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let mut stmt = conn.prepare("SELECT name FROM users").unwrap();
+    let err = conn.execute(&mut stmt, &[]).unwrap_err();
+    assert!(err.to_string().contains("empty packet"), "{:#}", err);
+    assert!(conn.is_broken());
+}
+
+// The parameter count, and the names and types of the parameters and columns of a statement.
+type Metadata = (u16, Vec<(String, u8)>, Vec<(String, u8)>);
+
+// Prepares a DDL statement, a SELECT without parameters and an INSERT with three against a server
+// greeting with `greeting`, and returns their metadata.
+fn prepared_metadata(greeting: HandshakeV10) -> Vec<Metadata> {
+    let port = Server::new().greeting(greeting).serve(|session, pkt| {
+        let param = || column("?", MYSQL_TYPE_VAR_STRING);
+        match (pkt[0], &pkt[1..]) {
+            (COM_STMT_PREPARE, b"CREATE TABLE t (id INT)") => session.prepare_ok(1, &[], &[]),
+            (COM_STMT_PREPARE, b"SELECT id, name FROM users") => session.prepare_ok(
+                2,
+                &[],
+                &[column("id", MYSQL_TYPE_LONG), string_column("name")],
+            ),
+            (COM_STMT_PREPARE, b"INSERT INTO users VALUES (?, ?, ?)") => {
+                session.prepare_ok(3, &[param(), param(), param()], &[])
+            }
+            (COM_STMT_CLOSE, _) => {}
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let definitions = |columns: &[ColumnDefinition41]| {
+        columns
+            .iter()
+            .map(|column| (column.name.clone(), column.type_))
+            .collect::<Vec<_>>()
+    };
+    let metadata = [
+        "CREATE TABLE t (id INT)",
+        "SELECT id, name FROM users",
+        "INSERT INTO users VALUES (?, ?, ?)",
+    ]
+    .into_iter()
+    .map(|sql| {
+        let stmt = conn.prepare(sql).unwrap();
+        (
+            stmt.param_count(),
+            definitions(stmt.param_types()),
+            definitions(stmt.columns()),
+        )
+    })
+    .collect();
+    // Nothing of the responses is left unread.
+    conn.ping().unwrap();
+    metadata
+}
+
+#[test]
+fn prepare_metadata_is_the_same_with_and_without_eof_packets() {
+    // 5.7 as clients without CLIENT_DEPRECATE_EOF see it: an EOF packet after each definition group.
+    let mysql_5_7 = HandshakeV10::new(
+        "5.7.44-log",
+        5,
+        CAPABILITIES & !CLIENT_DEPRECATE_EOF,
+        33,
+        STATUS,
+        SCRAMBLE,
+        "mysql_native_password",
+    );
+    let with_eof = prepared_metadata(mysql_5_7);
+    let without_eof = prepared_metadata(greeting(CAPABILITIES));
+    assert_eq!(with_eof, without_eof);
+
+    let param = (String::from("?"), MYSQL_TYPE_VAR_STRING);
+    assert_eq!(with_eof[0], (0, vec![], vec![]));
+    assert_eq!(
+        with_eof[1],
+        (
+            0,
+            vec![],
+            vec![
+                (String::from("id"), MYSQL_TYPE_LONG),
+                (String::from("name"), MYSQL_TYPE_VAR_STRING)
+            ]
+        )
+    );
+    assert_eq!(
+        with_eof[2],
+        (3, vec![param.clone(), param.clone(), param], vec![])
+    );
+}
+
+#[test]
+fn prepared_statements_expose_their_metadata() {
+    const INSERT: &str = "INSERT INTO users (id, name, email) VALUES (?, ?, ?)";
+    const SELECT: &str = "SELECT id, name, email, score, created_at FROM users WHERE name LIKE ?";
+    let port = Server::new().serve(|session, pkt| {
+        let param = || column("?", MYSQL_TYPE_VAR_STRING);
+        match pkt[0] {
+            COM_STMT_PREPARE if pkt[1..] == *INSERT.as_bytes() => {
+                session.prepare_ok(7, &[param(), param(), param()], &[])
+            }
+            COM_STMT_PREPARE if pkt[1..] == *SELECT.as_bytes() => session.prepare_ok(
+                8,
+                &[param()],
+                &[
+                    column("id", MYSQL_TYPE_LONG),
+                    string_column("name"),
+                    string_column("email"),
+                    column("score", MYSQL_TYPE_DOUBLE),
+                    column("created_at", MYSQL_TYPE_DATETIME),
+                ],
+            ),
+            COM_STMT_CLOSE => {}
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+
+    let insert = conn.prepare(INSERT).unwrap();
+    assert_eq!(insert.statement_id(), 7);
+    assert_eq!(insert.sql(), INSERT);
+    assert_eq!(insert.param_count(), 3);
+    let param_types = insert
+        .param_types()
+        .iter()
+        .map(|param| param.type_)
+        .collect::<Vec<_>>();
+    assert_eq!(param_types, [MYSQL_TYPE_VAR_STRING; 3]);
+    assert!(insert.columns().is_empty());
+    assert_eq!(insert.warnings_at_prepare(), 0);
+
+    let select = conn.prepare(SELECT).unwrap();
+    assert_eq!(select.statement_id(), 8);
+    assert_eq!(select.param_count(), 1);
+    let columns = select
+        .columns()
+        .iter()
+        .map(|column| (column.name.as_str(), column.type_))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        columns,
+        [
+            ("id", MYSQL_TYPE_LONG),
+            ("name", MYSQL_TYPE_VAR_STRING),
+            ("email", MYSQL_TYPE_VAR_STRING),
+            ("score", MYSQL_TYPE_DOUBLE),
+            ("created_at", MYSQL_TYPE_DATETIME),
+        ]
+    );
+    // The SQL is cut short.
+    assert_eq!(
+        format!("{:?}", select),
+        "Statement { id: 8, sql: \"SELECT id, name, email, score, created_at FROM users WHERE name ...\", params: 1, columns: 5 }"
+    );
+    assert_eq!(
+        format!("{:?}", insert),
+        format!(
+            "Statement {{ id: 7, sql: {:?}, params: 3, columns: 0 }}",
+            INSERT
+        )
+    );
+}
+
+// Executes a two-parameter INSERT with each of `executions` (resetting the statement before those
+// marked so) and returns the new_params_bound flag and the type bytes of each COM_STMT_EXECUTE.
+fn sent_param_types(
+    options: ConnectionOptions,
+    executions: &[(bool, [Value; 2])],
+) -> Vec<(u8, Vec<u8>)> {
+    let (tx, rx) = mpsc::channel();
+    let param = || column("?", MYSQL_TYPE_VAR_STRING);
+    let port = Server::new().serve(move |session, pkt| {
+        match pkt[0] {
+            COM_STMT_PREPARE => session.prepare_ok(1, &[param(), param()], &[]),
+            COM_STMT_EXECUTE => {
+                // command, statement_id, flags, iteration_count, null_bitmap
+                let (flag, types) = pkt[11..].split_first().unwrap();
+                let types = match flag {
+                    1 => types[..4].to_vec(),
+                    _ => vec![],
+                };
+                tx.send((*flag, types)).unwrap();
+                session.ok();
+            }
+            COM_STMT_CLOSE => {}
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(ConnectionOptions { port, ..options }).unwrap();
+    let mut stmt = conn.prepare("INSERT INTO t VALUES (?, ?)").unwrap();
+    for (reset, params) in executions {
+        if *reset {
+            conn.reset_statement(&mut stmt).unwrap();
+        }
+        conn.execute(&mut stmt, params).unwrap();
+    }
+    rx.try_iter().collect()
+}
+
+#[test]
+fn param_types_are_sent_only_when_they_change() {
+    let text = |s: &str| Value::from(s);
+    let sent = sent_param_types(
+        options(0),
+        &[
+            (false, [Value::Null, text("a")]),
+            // The first parameter was NULL, so the server has no type for it yet.
+            (false, [Value::Int(1), text("b")]),
+            (false, [Value::Int(2), text("c")]),
+            // A NULL goes in the NULL bitmap and fits whatever type the server has.
+            (false, [Value::Int(3), Value::Null]),
+            (false, [text("4"), text("d")]),
+            (false, [text("5"), text("e")]),
+            (true, [text("6"), text("f")]),
+        ],
+    );
+    let (null, longlong, string) = (MYSQL_TYPE_NULL, MYSQL_TYPE_LONGLONG, MYSQL_TYPE_STRING);
+    assert_eq!(
+        sent,
+        [
+            (1, vec![null, 0, string, 0]),
+            (1, vec![longlong, 0, string, 0]),
+            (0, vec![]),
+            (0, vec![]),
+            (1, vec![string, 0, string, 0]),
+            (0, vec![]),
+            (1, vec![string, 0, string, 0]),
+        ]
+    );
+}
+
+#[test]
+fn always_send_param_types_sends_them_on_every_execution() {
+    let params = [Value::Int(1), Value::from("a")];
+    let sent = sent_param_types(
+        ConnectionOptions {
+            always_send_param_types: true,
+            ..options(0)
+        },
+        &[(false, params.clone()), (false, params)],
+    );
+    let types = vec![MYSQL_TYPE_LONGLONG, 0, MYSQL_TYPE_STRING, 0];
+    assert_eq!(sent, [(1, types.clone()), (1, types)]);
+}
+
+// MariaDB's COM_STMT_BULK_EXECUTE.
+const COM_STMT_BULK_EXECUTE: u8 = 0xfa;
+
+// Executes a two-parameter INSERT with three parameter sets against a server greeting with
+// `greeting`, and returns the affected rows and the packets of the executions.
+fn executed_batch(greeting: HandshakeV10) -> (u64, Vec<Vec<u8>>) {
+    let (tx, rx) = mpsc::channel();
+    let param = || column("?", MYSQL_TYPE_VAR_STRING);
+    let port = Server::new().greeting(greeting).serve(move |session, pkt| {
+        match pkt[0] {
+            COM_STMT_PREPARE => session.prepare_ok(1, &[param(), param()], &[]),
+            COM_STMT_EXECUTE => {
+                tx.send(pkt).unwrap();
+                session.send_ok(OkPacket::new(1, 0, STATUS));
+            }
+            COM_STMT_BULK_EXECUTE => {
+                tx.send(pkt).unwrap();
+                session.send_ok(OkPacket::new(3, 0, STATUS));
+            }
+            COM_STMT_CLOSE => {}
+            _ => session.ok(),
+        }
+        true
+    });
+    let mut conn = Connection::new(options(port)).unwrap();
+    let mut stmt = conn.prepare("INSERT INTO t VALUES (?, ?)").unwrap();
+    let rows =
+        [(1, "a"), (2, "b"), (3, "c")].map(|(id, name)| vec![Value::Int(id), Value::from(name)]);
+    let affected_rows = conn.execute_batch(&mut stmt, &rows).unwrap();
+    (affected_rows, rx.try_iter().collect())
+}
+
+#[test]
+fn execute_batch_sends_three_parameter_sets_in_one_bulk_execution_to_mariadb() {
+    let (affected_rows, packets) =
+        executed_batch(mariadb_greeting(MARIADB_CLIENT_STMT_BULK_OPERATIONS));
+    assert_eq!(affected_rows, 3);
+    assert_eq!(packets.len(), 1);
+    let pkt = &packets[0];
+    // command, statement_id, flags (STMT_BULK_FLAG_SEND_TYPES_TO_SERVER), the parameter types
+    assert_eq!(
+        pkt[..11],
+        [
+            COM_STMT_BULK_EXECUTE,
+            1,
+            0,
+            0,
+            0,
+            128,
+            0,
+            MYSQL_TYPE_LONGLONG,
+            0,
+            MYSQL_TYPE_STRING,
+            0
+        ]
+    );
+    // Each row: an indicator and the value for both parameters.
+    let mut row = vec![0];
+    row.extend(2i64.to_le_bytes());
+    row.extend([0, 1, b'b']);
+    assert!(pkt.windows(row.len()).any(|window| window == row));
+    assert_eq!(pkt.len(), 11 + 3 * (1 + 8 + 1 + 2));
+}
+
+#[test]
+fn execute_batch_executes_once_per_parameter_set_without_bulk_operations() {
+    let (affected_rows, packets) = executed_batch(greeting(CAPABILITIES));
+    assert_eq!(affected_rows, 3);
+    assert_eq!(packets.len(), 3);
+    assert!(packets.iter().all(|pkt| pkt[0] == COM_STMT_EXECUTE));
+    assert!(packets[2].ends_with(b"\x01c"));
+}